@@ -0,0 +1,461 @@
+//! Who may run which goblin commands, and a record of everything they've run.
+//!
+//! Every admin has a `Role`, which grants them a few `Capability`s, and each goblin
+//! command needs one of those. Commands that can't be taken back also have to be
+//! confirmed by a second admin who could have run them. Everything that's asked for,
+//! refused, confirmed and run goes into the audit log.
+use crate::chat::mention;
+use crate::records::{self, Record};
+use crate::store::Store;
+use log::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+/// How long a command that needs a second admin waits for one.
+pub const CONFIRM_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+use std::env::var;
+lazy_static::lazy_static! {
+    /// These users are always operators, so that there's someone to grant the first roles.
+    pub static ref ADMIN_OPERATORS: Vec<String> = var("ADMIN_OPERATORS")
+        .map(|ops| ops.split(',').map(|id| id.trim().to_string()).collect())
+        .unwrap_or_default();
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Viewer,
+    EconomyAdmin,
+    GameMaster,
+    Operator,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    /// Looking into how things work, without changing anything.
+    Inspect,
+    /// Making HN and items out of thin air.
+    Economy,
+    /// Changing or wiping out things in everyone's hacksteads.
+    World,
+    /// Restarting and deploying the server.
+    Server,
+    /// Handing out and taking away roles.
+    ManageRoles,
+}
+
+impl Role {
+    pub const ALL: &'static [Role] = &[
+        Role::Viewer,
+        Role::EconomyAdmin,
+        Role::GameMaster,
+        Role::Operator,
+    ];
+
+    pub fn capabilities(self) -> &'static [Capability] {
+        use Capability::*;
+        match self {
+            Role::Viewer => &[Inspect],
+            Role::EconomyAdmin => &[Inspect, Economy],
+            Role::GameMaster => &[Inspect, Economy, World],
+            Role::Operator => &[Inspect, Economy, World, Server, ManageRoles],
+        }
+    }
+
+    pub fn can(self, capability: Capability) -> bool {
+        self.capabilities().contains(&capability)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::EconomyAdmin => "economy_admin",
+            Role::GameMaster => "game_master",
+            Role::Operator => "operator",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Role> {
+        Role::ALL.iter().copied().find(|r| r.name() == name)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RoleGrant {
+    pub user_id: String,
+    pub role: Role,
+    pub granted_by: String,
+    pub granted: SystemTime,
+}
+impl Record for RoleGrant {
+    const KIND: &'static str = "admin_role";
+
+    fn id(&self) -> String {
+        self.user_id.clone()
+    }
+}
+
+/// What someone is allowed to do, if they're an admin at all.
+pub async fn role_of(db: &dyn Store, user_id: &str) -> Result<Option<Role>, String> {
+    if ADMIN_OPERATORS.iter().any(|op| op == user_id) {
+        return Ok(Some(Role::Operator));
+    }
+    Ok(records::get::<RoleGrant>(db, user_id)
+        .await?
+        .map(|g| g.role))
+}
+
+/// Gives someone a role, replacing whatever role they had before.
+pub async fn grant(
+    db: &dyn Store,
+    user_id: &str,
+    role: Role,
+    granted_by: &str,
+) -> Result<(), String> {
+    records::put(
+        db,
+        &RoleGrant {
+            user_id: user_id.to_string(),
+            role,
+            granted_by: granted_by.to_string(),
+            granted: SystemTime::now(),
+        },
+    )
+    .await
+}
+
+/// Takes away someone's role, returning what it was if they had one.
+pub async fn revoke(db: &dyn Store, user_id: &str) -> Result<Option<RoleGrant>, String> {
+    records::remove(db, user_id).await
+}
+
+/// Everyone who's been granted a role.
+pub async fn grants(db: &dyn Store) -> Result<Vec<RoleGrant>, String> {
+    records::all(db).await
+}
+
+/// Makes sure someone can run goblin commands. The first time the game starts with nobody
+/// in `ADMIN_OPERATORS` and no roles granted, the config's special users are made operators;
+/// if there aren't any of those either, it can't start.
+pub async fn seed_operators(db: &dyn Store, special_users: &[String]) -> Result<(), String> {
+    if !ADMIN_OPERATORS.is_empty() || !grants(db).await?.is_empty() {
+        return Ok(());
+    }
+    if special_users.is_empty() {
+        return Err(
+            "nobody can run goblin commands: set ADMIN_OPERATORS or add special_users to the config"
+                .to_string(),
+        );
+    }
+
+    for user_id in special_users {
+        info!("making special user {} an operator", user_id);
+        grant(db, user_id, Role::Operator, "special_users").await?;
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuditEntry {
+    pub id: uuid::Uuid,
+    pub at: SystemTime,
+    pub user_id: String,
+    /// The name of the command that was run.
+    pub command: String,
+    /// The whole message the command was given in, arguments and all.
+    pub text: String,
+    pub outcome: String,
+}
+impl Record for AuditEntry {
+    const KIND: &'static str = "admin_audit";
+
+    fn id(&self) -> String {
+        self.id.to_simple().to_string()
+    }
+}
+
+/// Notes down that someone ran (or tried to run) a command, and how it went.
+pub async fn audit(
+    db: &dyn Store,
+    user_id: &str,
+    command: &str,
+    text: &str,
+    outcome: impl ToString,
+) -> Result<(), String> {
+    let entry = AuditEntry {
+        id: uuid::Uuid::new_v4(),
+        at: SystemTime::now(),
+        user_id: user_id.to_string(),
+        command: command.to_string(),
+        text: text.to_string(),
+        outcome: outcome.to_string(),
+    };
+    info!(
+        "audit: {} ran {}: {}",
+        entry.user_id, entry.command, entry.outcome
+    );
+    records::put(db, &entry).await
+}
+
+/// The most recent entries in the audit log, newest first.
+pub async fn audit_log(db: &dyn Store, limit: usize) -> Result<Vec<AuditEntry>, String> {
+    let mut entries: Vec<AuditEntry> = records::all(db).await?;
+    entries.sort_by(|a, b| b.at.cmp(&a.at));
+    entries.truncate(limit);
+    Ok(entries)
+}
+
+/// A command that's waiting on a second admin to confirm it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PendingCommand {
+    /// Short, so that it's easy to type back.
+    pub id: String,
+    pub command: String,
+    pub text: String,
+    pub channel: String,
+    pub requested_by: String,
+    pub requested: SystemTime,
+}
+impl Record for PendingCommand {
+    const KIND: &'static str = "admin_pending";
+
+    fn id(&self) -> String {
+        self.id.clone()
+    }
+}
+impl PendingCommand {
+    pub fn expired(&self) -> bool {
+        SystemTime::now()
+            .duration_since(self.requested)
+            .map_or(false, |waited| waited > CONFIRM_WINDOW)
+    }
+}
+
+/// Holds onto a command until someone else confirms it, returning the id to confirm it with.
+pub async fn await_confirmation(
+    db: &dyn Store,
+    command: &str,
+    text: &str,
+    channel: &str,
+    requested_by: &str,
+) -> Result<String, String> {
+    let pending = PendingCommand {
+        id: hex::encode(rand::thread_rng().gen::<[u8; 3]>()),
+        command: command.to_string(),
+        text: text.to_string(),
+        channel: channel.to_string(),
+        requested_by: requested_by.to_string(),
+        requested: SystemTime::now(),
+    };
+    if !records::insert(db, &pending).await? {
+        return Err(format!(
+            "command {} is already awaiting confirmation",
+            pending.id
+        ));
+    }
+    Ok(pending.id)
+}
+
+pub async fn pending(db: &dyn Store, id: &str) -> Result<Option<PendingCommand>, String> {
+    records::get(db, id).await
+}
+
+/// Clears a pending command to run now that `confirmer` has confirmed it, as long as it hasn't
+/// expired, it's someone else confirming it, and both of them can still do what it `needs`.
+/// Whatever happens, an expired or confirmed command is forgotten about.
+pub async fn confirm(
+    db: &dyn Store,
+    pending: &PendingCommand,
+    confirmer: &str,
+    needs: Capability,
+) -> Result<(), String> {
+    if pending.expired() {
+        settle(db, &pending.id).await?;
+        return Err(format!(
+            "`{}` waited too long to be confirmed; it'll have to be asked for again",
+            pending.command
+        ));
+    }
+    if pending.requested_by == confirmer {
+        return Err(format!(
+            "someone other than {} has to confirm `{}`",
+            mention(confirmer),
+            pending.command
+        ));
+    }
+    if !role_of(db, confirmer)
+        .await?
+        .map_or(false, |r| r.can(needs))
+    {
+        return Err(format!(
+            "{} can't confirm `{}`, because they couldn't run it themselves",
+            mention(confirmer),
+            pending.command
+        ));
+    }
+    if !role_of(db, &pending.requested_by)
+        .await?
+        .map_or(false, |r| r.can(needs))
+    {
+        settle(db, &pending.id).await?;
+        return Err(format!(
+            "{} can't `{}` anymore, so it won't be run",
+            mention(&pending.requested_by),
+            pending.command
+        ));
+    }
+    settle(db, &pending.id).await
+}
+
+/// Forgets about a pending command, whether it's been confirmed or not.
+pub async fn settle(db: &dyn Store, id: &str) -> Result<(), String> {
+    records::remove::<PendingCommand>(db, id).await.map(|_| ())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::store::MemoryStore;
+
+    const REQUESTER: &str = "U1";
+    const CONFIRMER: &str = "U2";
+
+    /// A command `REQUESTER` asked for, with both of them made game masters.
+    async fn requested(db: &dyn Store) -> PendingCommand {
+        std::env::set_var("CHAT", "recording");
+        grant(db, REQUESTER, Role::GameMaster, "U0").await.unwrap();
+        grant(db, CONFIRMER, Role::GameMaster, "U0").await.unwrap();
+        let id = await_confirmation(db, "stomp", "stomp all", "C1", REQUESTER)
+            .await
+            .unwrap();
+        pending(db, &id).await.unwrap().unwrap()
+    }
+
+    #[test]
+    fn roles_only_grant_their_capabilities() {
+        assert!(Role::Viewer.can(Capability::Inspect));
+        assert!(!Role::Viewer.can(Capability::Economy));
+        assert!(Role::EconomyAdmin.can(Capability::Economy));
+        assert!(!Role::EconomyAdmin.can(Capability::World));
+        assert!(Role::GameMaster.can(Capability::World));
+        assert!(!Role::GameMaster.can(Capability::Server));
+        assert!(!Role::GameMaster.can(Capability::ManageRoles));
+        assert!(Role::Operator.can(Capability::ManageRoles));
+        for role in Role::ALL {
+            assert_eq!(Role::from_name(role.name()), Some(*role));
+        }
+    }
+
+    #[rocket::async_test]
+    async fn roles_last_until_theyre_revoked() {
+        let db = MemoryStore::default();
+        assert_eq!(role_of(&db, REQUESTER).await.unwrap(), None);
+
+        grant(&db, REQUESTER, Role::Viewer, "U0").await.unwrap();
+        assert_eq!(role_of(&db, REQUESTER).await.unwrap(), Some(Role::Viewer));
+
+        let revoked = revoke(&db, REQUESTER).await.unwrap().unwrap();
+        assert_eq!(revoked.role, Role::Viewer);
+        assert_eq!(role_of(&db, REQUESTER).await.unwrap(), None);
+    }
+
+    #[rocket::async_test]
+    async fn commands_are_run_once_someone_else_confirms_them() {
+        let db = MemoryStore::default();
+        let cmd = requested(&db).await;
+
+        confirm(&db, &cmd, CONFIRMER, Capability::World)
+            .await
+            .unwrap();
+        assert!(pending(&db, &cmd.id).await.unwrap().is_none());
+    }
+
+    #[rocket::async_test]
+    async fn nobody_confirms_their_own_command() {
+        let db = MemoryStore::default();
+        let cmd = requested(&db).await;
+
+        assert!(confirm(&db, &cmd, REQUESTER, Capability::World)
+            .await
+            .is_err());
+        // it's still there for someone else to confirm
+        assert!(pending(&db, &cmd.id).await.unwrap().is_some());
+    }
+
+    #[rocket::async_test]
+    async fn confirming_needs_both_admins_to_be_able_to_run_it() {
+        let db = MemoryStore::default();
+        let cmd = requested(&db).await;
+
+        grant(&db, CONFIRMER, Role::Viewer, "U0").await.unwrap();
+        assert!(confirm(&db, &cmd, CONFIRMER, Capability::World)
+            .await
+            .is_err());
+        assert!(pending(&db, &cmd.id).await.unwrap().is_some());
+
+        grant(&db, CONFIRMER, Role::GameMaster, "U0").await.unwrap();
+        revoke(&db, REQUESTER).await.unwrap();
+        assert!(confirm(&db, &cmd, CONFIRMER, Capability::World)
+            .await
+            .is_err());
+        assert!(pending(&db, &cmd.id).await.unwrap().is_none());
+    }
+
+    #[rocket::async_test]
+    async fn commands_left_too_long_expire() {
+        let db = MemoryStore::default();
+        let mut cmd = requested(&db).await;
+        cmd.requested = SystemTime::now() - CONFIRM_WINDOW - Duration::from_secs(1);
+        records::put(&db, &cmd).await.unwrap();
+
+        assert!(cmd.expired());
+        assert!(confirm(&db, &cmd, CONFIRMER, Capability::World)
+            .await
+            .is_err());
+        assert!(pending(&db, &cmd.id).await.unwrap().is_none());
+    }
+
+    #[rocket::async_test]
+    async fn the_audit_log_shows_the_newest_first() {
+        let db = MemoryStore::default();
+        audit(&db, REQUESTER, "spawn", "spawn 1", "ok")
+            .await
+            .unwrap();
+        let now = SystemTime::now();
+        for (secs_ago, outcome) in &[(30, "first"), (20, "second"), (10, "third")] {
+            let entry = AuditEntry {
+                id: uuid::Uuid::new_v4(),
+                at: now - Duration::from_secs(*secs_ago),
+                user_id: CONFIRMER.to_string(),
+                command: "stomp".to_string(),
+                text: "stomp all".to_string(),
+                outcome: outcome.to_string(),
+            };
+            records::put(&db, &entry).await.unwrap();
+        }
+
+        let log = audit_log(&db, 3).await.unwrap();
+        let outcomes: Vec<&str> = log.iter().map(|e| e.outcome.as_str()).collect();
+        assert_eq!(outcomes, vec!["ok", "third", "second"]);
+        assert_eq!(log[0].user_id, REQUESTER);
+    }
+
+    #[rocket::async_test]
+    async fn special_users_are_made_operators_when_nobody_else_is() {
+        let db = MemoryStore::default();
+        assert!(seed_operators(&db, &[]).await.is_err());
+
+        seed_operators(&db, &[REQUESTER.to_string()]).await.unwrap();
+        assert_eq!(role_of(&db, REQUESTER).await.unwrap(), Some(Role::Operator));
+
+        // once there's someone to hand out roles, they're left to it
+        revoke(&db, REQUESTER).await.unwrap();
+        grant(&db, CONFIRMER, Role::Operator, REQUESTER)
+            .await
+            .unwrap();
+        seed_operators(&db, &[REQUESTER.to_string()]).await.unwrap();
+        assert_eq!(role_of(&db, REQUESTER).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,548 @@
+//! A JSON API under `/gotchi/api/v1`, for dashboards and bots.
+//!
+//! Anyone can read from it, save for what happens to each steader as it happens,
+//! which only they get to follow. Everything here is turned into the structs below before
+//! it goes out, so that what the API returns only changes when they do, not whenever
+//! the items in the database or hcor's types happen to change shape.
+//!
+//! Changing anything takes one of the tokens in `tokens`; see `write`.
+//! What's happening in the game can be followed live through `stream`.
+use crate::hacksteader::{Hacksteader, Plant, Tile};
+use crate::store::{store, Store};
+use config::{ArchetypeHandle, CONFIG};
+use hcor::{config, market::Sale, possess, Category, Possession};
+use rocket::get;
+use rocket_contrib::json::Json;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+mod stream;
+pub mod tokens;
+mod write;
+
+/// Every route the API has, to be mounted at `/gotchi/api/v1`.
+pub fn routes() -> Vec<rocket::Route> {
+    let mut routes = rocket::routes![
+        hacksteader,
+        market,
+        plants,
+        stateofsteading,
+        stream::steader_events,
+        stream::market_events
+    ];
+    routes.append(&mut write::routes());
+    routes
+}
+
+fn timestamp(time: SystemTime) -> String {
+    humantime::format_rfc3339(time).to_string()
+}
+
+fn archetype_name(ah: ArchetypeHandle) -> String {
+    CONFIG
+        .possession_archetypes
+        .get(ah)
+        .map(|a| a.name.clone())
+        .unwrap_or_default()
+}
+
+#[derive(Serialize)]
+pub struct ProfileDto {
+    pub user_id: String,
+    pub xp: u64,
+    pub level: usize,
+    pub title: String,
+    pub joined: String,
+    pub last_active: String,
+}
+
+#[derive(Serialize)]
+pub struct HacksteaderDto {
+    pub profile: ProfileDto,
+    pub tiles: Vec<TileDto>,
+    pub inventory: Vec<PossessionDto>,
+    pub gotchis: Vec<GotchiDto>,
+}
+impl From<Hacksteader> for HacksteaderDto {
+    fn from(hs: Hacksteader) -> Self {
+        let neighbor_bonuses = hs.neighbor_bonuses();
+        let profile = &hs.profile;
+
+        Self {
+            profile: ProfileDto {
+                user_id: hs.user_id.clone(),
+                xp: profile.xp,
+                level: profile.advancements.current_position(profile.xp),
+                title: profile.current_advancement().achiever_title.clone(),
+                joined: timestamp(profile.joined),
+                last_active: timestamp(profile.last_active),
+            },
+            tiles: hs
+                .land
+                .iter()
+                .map(|tile| TileDto::new(tile, neighbor_bonuses.clone()))
+                .collect(),
+            inventory: hs.inventory.iter().map(PossessionDto::from).collect(),
+            gotchis: hs.gotchis.iter().map(GotchiDto::from).collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct TileDto {
+    pub id: uuid::Uuid,
+    pub acquired: String,
+    pub plant: Option<PlantDto>,
+}
+impl TileDto {
+    fn new(tile: &Tile, neighbor_bonuses: crate::hacksteader::NeighborBonuses) -> Self {
+        Self {
+            id: tile.id,
+            acquired: timestamp(tile.acquired),
+            plant: tile.plant.as_ref().map(|plant| {
+                PlantDto::new(
+                    plant,
+                    &neighbor_bonuses.bonuses_for_plant(tile.id, plant.archetype_handle),
+                )
+            }),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PlantDto {
+    pub archetype: String,
+    pub xp: u64,
+    pub level: usize,
+    /// Farm cycles left until the plant yields, if it yields at all.
+    pub until_yield: f32,
+    pub craft: Option<CraftDto>,
+    pub effects: Vec<EffectDto>,
+    /// What the plant's advancements, effects and neighbors add up to.
+    pub advancements: AdvancementSumDto,
+}
+impl PlantDto {
+    fn new(plant: &Plant, neighbor_bonuses: &[config::PlantAdvancement]) -> Self {
+        Self {
+            archetype: plant.name.clone(),
+            xp: plant.xp,
+            level: plant.advancements.current_position(plant.xp),
+            until_yield: plant.until_yield,
+            craft: plant.craft.as_ref().and_then(|craft| {
+                Some(CraftDto {
+                    recipe: plant.current_recipe()?.title(),
+                    until_finish: craft.until_finish,
+                })
+            }),
+            effects: plant
+                .effects
+                .iter()
+                .map(|e| EffectDto {
+                    item: e.name.clone(),
+                    until_finish: e.until_finish,
+                })
+                .collect(),
+            advancements: plant.advancements_sum(neighbor_bonuses.iter()).into(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct CraftDto {
+    pub recipe: String,
+    pub until_finish: f32,
+}
+
+#[derive(Serialize)]
+pub struct EffectDto {
+    /// The name of the item that was used to cause this effect.
+    pub item: String,
+    /// Farm cycles left until the effect wears off, if it ever does.
+    pub until_finish: Option<f32>,
+}
+
+#[derive(Serialize)]
+pub struct AdvancementSumDto {
+    pub xp_multiplier: f32,
+    pub yield_speed_multiplier: f32,
+    pub yield_size_multiplier: f32,
+    pub crafting_speed_multiplier: f32,
+    pub double_craft_yield_chance: f32,
+    pub recipes: usize,
+}
+impl From<config::PlantAdvancementSum> for AdvancementSumDto {
+    fn from(sum: config::PlantAdvancementSum) -> Self {
+        Self {
+            xp_multiplier: sum.xp_multiplier,
+            yield_speed_multiplier: sum.yield_speed_multiplier,
+            yield_size_multiplier: sum.yield_size_multiplier,
+            crafting_speed_multiplier: sum.crafting_speed_multiplier,
+            double_craft_yield_chance: sum.double_craft_yield_chance,
+            recipes: sum.recipes.len(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct SaleDto {
+    pub price: u64,
+    pub market_name: String,
+}
+impl From<&Sale> for SaleDto {
+    fn from(sale: &Sale) -> Self {
+        Self {
+            price: sale.price,
+            market_name: sale.market_name.clone(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PossessionDto {
+    pub id: uuid::Uuid,
+    pub archetype: String,
+    pub nickname: String,
+    pub steader: String,
+    pub sale: Option<SaleDto>,
+}
+impl From<&Possession> for PossessionDto {
+    fn from(p: &Possession) -> Self {
+        Self {
+            id: p.id,
+            archetype: archetype_name(p.archetype_handle),
+            nickname: p.nickname().to_string(),
+            steader: p.steader.clone(),
+            sale: p.sale.as_ref().map(SaleDto::from),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct GotchiDto {
+    pub id: uuid::Uuid,
+    pub archetype: String,
+    pub nickname: String,
+    pub base_happiness: u64,
+    /// How much HN this gotchi has made for everyone who's owned it.
+    pub harvested: u64,
+}
+impl From<&possess::Possessed<possess::Gotchi>> for GotchiDto {
+    fn from(g: &possess::Possessed<possess::Gotchi>) -> Self {
+        Self {
+            id: g.id,
+            archetype: g.name.clone(),
+            nickname: g.inner.nickname.clone(),
+            base_happiness: g.inner.base_happiness,
+            harvested: g.inner.harvest_log.iter().map(|h| h.harvested).sum(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ListingDto {
+    pub sale: SaleDto,
+    pub possession: PossessionDto,
+}
+
+#[derive(Serialize)]
+pub struct CountedArchetypeDto {
+    pub count: usize,
+    pub archetype: String,
+}
+
+#[derive(Serialize)]
+pub struct RecipeDto {
+    pub title: String,
+    pub needs: Vec<CountedArchetypeDto>,
+    pub makes: Vec<CountedArchetypeDto>,
+    /// Whether crafting this makes only one of `makes`, rather than all of them.
+    pub makes_one_of: bool,
+    /// How many farm cycles it takes to craft, before any speed bonuses.
+    pub time: f32,
+    pub xp: (u64, u64),
+    pub destroys_plant: bool,
+}
+impl From<config::Recipe<&'static config::Archetype>> for RecipeDto {
+    fn from(recipe: config::Recipe<&'static config::Archetype>) -> Self {
+        Self {
+            title: recipe.title(),
+            needs: recipe
+                .needs
+                .iter()
+                .map(|(count, a)| CountedArchetypeDto {
+                    count: *count,
+                    archetype: a.name.clone(),
+                })
+                .collect(),
+            makes: recipe
+                .makes
+                .all()
+                .into_iter()
+                .map(|(a, count)| CountedArchetypeDto {
+                    count,
+                    archetype: a.name.clone(),
+                })
+                .collect(),
+            makes_one_of: matches!(recipe.makes, config::RecipeMakes::OneOf(_)),
+            time: recipe.time,
+            xp: recipe.xp,
+            destroys_plant: recipe.destroys_plant,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PlantArchetypeDto {
+    pub name: String,
+    /// Farm cycles between yields, before any speed bonuses, if it yields at all.
+    pub base_yield_duration: Option<f32>,
+    /// Everything this plant could ever craft, once it has all of its advancements.
+    pub recipes: Vec<RecipeDto>,
+}
+
+/// One plant that's growing somewhere.
+#[derive(Serialize)]
+pub struct PlantedDto {
+    pub owner: String,
+    /// Whoever grew the seed this plant came from.
+    pub seed_from: String,
+    pub level: usize,
+}
+
+#[derive(Serialize)]
+pub struct StateOfSteadingDto {
+    pub hacksteaders: usize,
+    pub tiles: usize,
+    /// Every plant that's growing, by the name of its archetype, lowest level first.
+    pub plants: BTreeMap<String, Vec<PlantedDto>>,
+}
+
+/// How everyone's hacksteads are doing, all together.
+pub async fn state_of_steading(db: &dyn Store) -> Result<StateOfSteadingDto, String> {
+    let hacksteaders = db.profiles().await?.len();
+    let tiles = db.tiles().await?;
+
+    let mut plants: BTreeMap<String, Vec<PlantedDto>> = BTreeMap::new();
+    for (tile, plant) in tiles.iter().filter_map(|t| Some((t, t.plant.as_ref()?))) {
+        plants
+            .entry(plant.name.clone())
+            .or_default()
+            .push(PlantedDto {
+                owner: tile.steader.clone(),
+                seed_from: plant
+                    .pedigree
+                    .last()
+                    .map(|x| x.id.clone())
+                    .unwrap_or("U013STH0TNG".to_string()),
+                level: plant.advancements.current_position(plant.xp),
+            });
+    }
+    for planted in plants.values_mut() {
+        planted.sort_by_key(|p| p.level);
+    }
+
+    Ok(StateOfSteadingDto {
+        hacksteaders,
+        tiles: tiles.len(),
+        plants,
+    })
+}
+
+#[get("/hacksteader/<user_id>")]
+async fn hacksteader(user_id: String) -> Result<Option<Json<HacksteaderDto>>, String> {
+    if !store().hacksteader_exists(&user_id).await? {
+        return Ok(None);
+    }
+
+    Ok(Some(Json(
+        Hacksteader::from_db(store(), user_id).await?.into(),
+    )))
+}
+
+#[get("/market/<category>")]
+async fn market(category: String) -> Result<Option<Json<Vec<ListingDto>>>, String> {
+    let cat = match category.as_str() {
+        "gotchi" => Category::Gotchi,
+        "misc" => Category::Misc,
+        _ => return Ok(None),
+    };
+
+    Ok(Some(Json(
+        store()
+            .market_listings(cat)
+            .await?
+            .iter()
+            .map(|(sale, p)| ListingDto {
+                sale: sale.into(),
+                possession: p.into(),
+            })
+            .collect(),
+    )))
+}
+
+#[get("/plants")]
+fn plants() -> Json<Vec<PlantArchetypeDto>> {
+    Json(
+        CONFIG
+            .plant_archetypes
+            .iter()
+            .map(|arch| PlantArchetypeDto {
+                name: arch.name.clone(),
+                base_yield_duration: arch.base_yield_duration,
+                recipes: arch
+                    .advancements
+                    .max(std::iter::empty())
+                    .recipes
+                    .into_iter()
+                    .filter_map(|r| r.lookup_handles())
+                    .map(RecipeDto::from)
+                    .collect(),
+            })
+            .collect(),
+    )
+}
+
+#[get("/stateofsteading")]
+async fn stateofsteading() -> Result<Json<StateOfSteadingDto>, String> {
+    state_of_steading(store()).await.map(Json)
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::FarmingInputEvent;
+    use rocket::http::{Header, Status};
+    use rocket::local::asynchronous::Client;
+
+    /// The whole API, on top of the in-memory store.
+    pub async fn client() -> Client {
+        std::env::set_var("STORE", "memory");
+        let (to_farming, _) = crossbeam_channel::unbounded::<FarmingInputEvent>();
+        Client::tracked(rocket::ignite().manage(to_farming).mount("/", routes()))
+            .await
+            .unwrap()
+    }
+
+    /// Someone new with a hackstead, and a token to act as them with.
+    pub async fn steader() -> (String, String) {
+        let user_id = format!("U{}", uuid::Uuid::new_v4().to_simple());
+        Hacksteader::new_in_db(store(), user_id.clone())
+            .await
+            .unwrap();
+        let token = tokens::mint(store(), &user_id).await.unwrap();
+        (user_id, token)
+    }
+
+    pub fn bearer(token: &str) -> Header<'static> {
+        Header::new("Authorization", format!("Bearer {}", token))
+    }
+
+    async fn get_json(client: &Client, path: String) -> serde_json::Value {
+        let res = client.get(path).dispatch().await;
+        assert_eq!(res.status(), Status::Ok);
+        serde_json::from_str(&res.into_string().await.unwrap()).unwrap()
+    }
+
+    #[rocket::async_test]
+    async fn hacksteads_are_there_for_whoever_has_one() {
+        let client = client().await;
+        let (user_id, _) = steader().await;
+
+        let hs = get_json(&client, format!("/hacksteader/{}", user_id)).await;
+        assert_eq!(hs["profile"]["user_id"], user_id.as_str());
+
+        let res = client.get("/hacksteader/UNOBODY").dispatch().await;
+        assert_eq!(res.status(), Status::NotFound);
+    }
+
+    #[rocket::async_test]
+    async fn the_market_shows_whats_for_sale() {
+        let client = client().await;
+        let (user_id, _) = steader().await;
+        let p = Possession::new(0, possess::Owner::farmer(user_id.clone()));
+        store()
+            .write(crate::store::Batch {
+                possessions: vec![p.clone()],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        store()
+            .place_on_market(p.key(), 10, p.name.clone())
+            .await
+            .unwrap();
+
+        let listings = get_json(&client, "/market/misc".to_string()).await;
+        let listing = listings
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|l| l["possession"]["id"] == p.id.to_string())
+            .expect("listing isn't on the market");
+        assert_eq!(listing["sale"]["price"], 10);
+        assert_eq!(listing["possession"]["steader"], user_id.as_str());
+
+        let res = client.get("/market/nonsense").dispatch().await;
+        assert_eq!(res.status(), Status::NotFound);
+    }
+
+    #[rocket::async_test]
+    async fn every_plant_is_listed() {
+        let client = client().await;
+        let plants = get_json(&client, "/plants".to_string()).await;
+        let names: Vec<_> = plants
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["name"].as_str().unwrap().to_string())
+            .collect();
+        let expected: Vec<_> = CONFIG
+            .plant_archetypes
+            .iter()
+            .map(|a| a.name.clone())
+            .collect();
+        assert_eq!(names, expected);
+    }
+
+    #[rocket::async_test]
+    async fn the_state_of_steading_counts_everyone() {
+        let client = client().await;
+        steader().await;
+        let state = get_json(&client, "/stateofsteading".to_string()).await;
+        assert!(state["hacksteaders"].as_u64().unwrap() >= 1);
+        assert!(state["plants"].is_object());
+    }
+
+    #[rocket::async_test]
+    async fn steaders_events_are_only_streamed_to_them() {
+        let client = client().await;
+        let (user_id, token) = steader().await;
+        let (_, someone_elses) = steader().await;
+        let path = format!("/events/steader/{}", user_id);
+
+        let res = client.get(path.clone()).dispatch().await;
+        assert_eq!(res.status(), Status::Unauthorized);
+
+        let res = client
+            .get(path.clone())
+            .header(bearer(&someone_elses))
+            .dispatch()
+            .await;
+        assert_eq!(res.status(), Status::Forbidden);
+
+        let res = client.get(path).header(bearer(&token)).dispatch().await;
+        assert_eq!(res.status(), Status::Ok);
+    }
+
+    #[rocket::async_test]
+    async fn anyone_can_follow_the_market() {
+        let client = client().await;
+        let res = client.get("/events/market").dispatch().await;
+        assert_eq!(res.status(), Status::Ok);
+        assert_eq!(
+            res.content_type(),
+            Some(rocket::http::ContentType::new("text", "event-stream"))
+        );
+    }
+}
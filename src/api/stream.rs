@@ -0,0 +1,144 @@
+//! Game events as they happen, as server-sent events: one `data:` line of JSON each.
+//!
+//! A comment is sent whenever nothing's happened for `KEEP_ALIVE`, so that proxies don't
+//! close quiet streams, and a `lagged` event saying how many events were missed is sent
+//! if a stream falls so far behind the bus that some of them are dropped.
+//!
+//! What happens on the hackmarket is public, but a steader's own stream is only for
+//! whoever holds one of their API tokens.
+use super::tokens::ApiUser;
+use crate::bus::{self, GameEvent};
+use futures::Future;
+use log::*;
+use rocket::http::{ContentType, Status};
+use rocket::response::status::Custom;
+use rocket::response::{self, Responder, Response};
+use rocket::tokio::io::{AsyncRead, ReadBuf};
+use rocket::tokio::sync::broadcast::{error::RecvError, Receiver};
+use rocket::tokio::time::{sleep, Instant, Sleep};
+use rocket::{get, Request};
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// How long a stream can go without sending anything before it sends a comment.
+pub const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+enum Watching {
+    Steader(String),
+    Market,
+}
+
+type Received = (Result<Arc<GameEvent>, RecvError>, Receiver<Arc<GameEvent>>);
+
+/// Waits for the next event, handing the receiver back along with it.
+fn next(mut events: Receiver<Arc<GameEvent>>) -> Pin<Box<dyn Future<Output = Received> + Send>> {
+    Box::pin(async move { (events.recv().await, events) })
+}
+
+pub struct EventStream {
+    next: Pin<Box<dyn Future<Output = Received> + Send>>,
+    keep_alive: Pin<Box<Sleep>>,
+    watching: Watching,
+    /// What's being written out, and how much of it has been so far.
+    pending: Vec<u8>,
+    written: usize,
+}
+impl EventStream {
+    fn new(watching: Watching) -> Self {
+        Self {
+            next: next(bus::subscribe()),
+            keep_alive: Box::pin(sleep(KEEP_ALIVE)),
+            watching,
+            pending: vec![],
+            written: 0,
+        }
+    }
+
+    fn wants(&self, event: &GameEvent) -> bool {
+        match &self.watching {
+            Watching::Steader(user_id) => event.steaders().contains(&user_id.as_str()),
+            Watching::Market => event.is_market(),
+        }
+    }
+
+    fn send(&mut self, message: String) {
+        self.pending = message.into_bytes();
+        self.written = 0;
+        self.keep_alive.as_mut().reset(Instant::now() + KEEP_ALIVE);
+    }
+}
+
+impl AsyncRead for EventStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+
+        while this.written == this.pending.len() {
+            match this.next.as_mut().poll(cx) {
+                Poll::Ready((received, events)) => {
+                    this.next = next(events);
+                    match received {
+                        Ok(event) if this.wants(&event) => {
+                            let json = serde_json::to_string(&*event)
+                                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+                            this.send(format!("data: {}\n\n", json));
+                        }
+                        Ok(_) => {}
+                        Err(RecvError::Lagged(missed)) => {
+                            warn!("event stream fell behind and missed {} events", missed);
+                            this.send(format!(
+                                "event: lagged\ndata: {{\"missed\":{}}}\n\n",
+                                missed
+                            ));
+                        }
+                        // nothing more will ever be published, so the stream is over
+                        Err(RecvError::Closed) => return Poll::Ready(Ok(())),
+                    }
+                }
+                Poll::Pending => match this.keep_alive.as_mut().poll(cx) {
+                    Poll::Ready(()) => this.send(":\n\n".to_string()),
+                    Poll::Pending => return Poll::Pending,
+                },
+            }
+        }
+
+        let n = buf.remaining().min(this.pending.len() - this.written);
+        buf.put_slice(&this.pending[this.written..this.written + n]);
+        this.written += n;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<'r> Responder<'r, 'static> for EventStream {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .header(ContentType::new("text", "event-stream"))
+            .raw_header("Cache-Control", "no-cache")
+            .streamed_body(self)
+            .ok()
+    }
+}
+
+/// Everything that happens to one steader, for them alone.
+#[get("/events/steader/<user_id>")]
+pub fn steader_events(user: ApiUser, user_id: String) -> Result<EventStream, Custom<String>> {
+    if user.user_id != user_id {
+        return Err(Custom(
+            Status::Forbidden,
+            format!("only {} can watch what happens to them", user_id),
+        ));
+    }
+    Ok(EventStream::new(Watching::Steader(user_id)))
+}
+
+/// Everything that happens on the hackmarket.
+#[get("/events/market")]
+pub fn market_events() -> EventStream {
+    EventStream::new(Watching::Market)
+}
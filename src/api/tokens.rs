@@ -0,0 +1,163 @@
+//! Personal API tokens, which let someone use the write API as themselves.
+//!
+//! We only keep the SHA-256 of each token, so a token can only ever be seen once,
+//! right after it's minted. Each token may only be used `API_RATE_LIMIT` times a minute.
+use crate::records::{self, Record};
+use crate::store::{store, Store};
+use log::*;
+use rand::Rng;
+use rocket::http::Status;
+use rocket::request::{self, FromRequest, Request};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+const TOKEN_PREFIX: &str = "hkg_";
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+use std::env::var;
+lazy_static::lazy_static! {
+    pub static ref API_RATE_LIMIT: usize = var("API_RATE_LIMIT")
+        .ok()
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(30);
+    /// When each token was used within the last `RATE_LIMIT_WINDOW`, by its hash.
+    static ref RECENT_USES: Mutex<HashMap<String, VecDeque<Instant>>> = Default::default();
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiToken {
+    /// The SHA-256 of the token, in hex.
+    pub hash: String,
+    pub user_id: String,
+    /// The start of the token, so that its owner can tell their tokens apart.
+    pub hint: String,
+    pub minted: SystemTime,
+}
+impl Record for ApiToken {
+    const KIND: &'static str = "api_token";
+
+    fn id(&self) -> String {
+        self.hash.clone()
+    }
+}
+
+fn hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Makes a new token for someone, returning the only copy of it there will ever be.
+pub async fn mint(db: &dyn Store, user_id: &str) -> Result<String, String> {
+    let token = format!(
+        "{}{}",
+        TOKEN_PREFIX,
+        hex::encode(rand::thread_rng().gen::<[u8; 20]>())
+    );
+    let minted = records::insert(
+        db,
+        &ApiToken {
+            hash: hash(&token),
+            user_id: user_id.to_string(),
+            hint: token.chars().take(TOKEN_PREFIX.len() + 6).collect(),
+            minted: SystemTime::now(),
+        },
+    )
+    .await?;
+    if !minted {
+        return Err("minted a token that already exists".to_string());
+    }
+
+    info!("minted api token for {}", user_id);
+    Ok(token)
+}
+
+/// Someone's tokens, oldest first.
+pub async fn tokens_of(db: &dyn Store, user_id: &str) -> Result<Vec<ApiToken>, String> {
+    let mut tokens: Vec<ApiToken> = records::all::<ApiToken>(db)
+        .await?
+        .into_iter()
+        .filter(|t| t.user_id == user_id)
+        .collect();
+    tokens.sort_by_key(|t| t.minted);
+    Ok(tokens)
+}
+
+/// Stops a token from working. Only its owner can do this.
+pub async fn revoke(db: &dyn Store, user_id: &str, hash: &str) -> Result<(), String> {
+    match records::get::<ApiToken>(db, hash).await? {
+        Some(t) if t.user_id == user_id => {}
+        Some(_) => return Err(format!("{} can't revoke someone else's token", user_id)),
+        None => return Err(format!("no token {} to revoke", hash)),
+    }
+    records::remove::<ApiToken>(db, hash).await?;
+    RECENT_USES.lock().unwrap().remove(hash);
+
+    info!("revoked api token {} of {}", hash, user_id);
+    Ok(())
+}
+
+/// Notes that a token was used, returning false if it's been used too much lately.
+fn within_rate_limit(hash: &str, now: Instant) -> bool {
+    let mut recent_uses = RECENT_USES.lock().unwrap();
+    let uses = recent_uses.entry(hash.to_string()).or_default();
+    while uses
+        .front()
+        .map_or(false, |&used| now.duration_since(used) >= RATE_LIMIT_WINDOW)
+    {
+        uses.pop_front();
+    }
+
+    if uses.len() >= *API_RATE_LIMIT {
+        false
+    } else {
+        uses.push_back(now);
+        true
+    }
+}
+
+/// Someone who's shown us one of their tokens, and hasn't been using it too much.
+pub struct ApiUser {
+    pub user_id: String,
+}
+
+async fn authenticate(req: &Request<'_>) -> Result<ApiUser, (Status, String)> {
+    let token = req
+        .headers()
+        .get_one("Authorization")
+        .and_then(|a| a.strip_prefix("Bearer "))
+        .ok_or_else(|| (Status::Unauthorized, "no bearer token".to_string()))?;
+
+    let hash = hash(token);
+    let api_token = records::get::<ApiToken>(store(), &hash)
+        .await
+        .map_err(|e| (Status::InternalServerError, e))?
+        .ok_or_else(|| (Status::Unauthorized, "unknown or revoked token".to_string()))?;
+
+    if !within_rate_limit(&hash, Instant::now()) {
+        return Err((
+            Status::TooManyRequests,
+            format!("tokens can only be used {} times a minute", *API_RATE_LIMIT),
+        ));
+    }
+
+    Ok(ApiUser {
+        user_id: api_token.user_id,
+    })
+}
+
+#[rocket::async_trait]
+impl<'a, 'r> FromRequest<'a, 'r> for ApiUser {
+    type Error = String;
+
+    async fn from_request(req: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        match authenticate(req).await {
+            Ok(user) => request::Outcome::Success(user),
+            Err((status, e)) => {
+                warn!("refusing api request: {}", e);
+                request::Outcome::Failure((status, e))
+            }
+        }
+    }
+}
@@ -0,0 +1,412 @@
+//! The parts of the API that change things, for whoever holds an API token.
+//!
+//! These hand the farm the same inputs the Slack modals do, but since anyone can
+//! send us anything here, everything the modals only offer to owners is checked first.
+use super::tokens::ApiUser;
+use crate::hacksteader::{Hacksteader, Plant, Tile};
+use crate::store::store;
+use crate::{farm, market, FarmingInputEvent, ItemApplication};
+use crossbeam_channel::Sender;
+use hcor::{config, possess, Possession};
+use possess::Possessed;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::{post, State};
+use rocket_contrib::json::Json;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::convert::TryInto;
+use std::time::Duration;
+
+type ApiResult = Result<Json<Value>, Custom<String>>;
+
+fn refuse(why: impl ToString) -> Custom<String> {
+    Custom(Status::Forbidden, why.to_string())
+}
+
+fn failed(e: String) -> Custom<String> {
+    Custom(Status::InternalServerError, e)
+}
+
+fn queued() -> ApiResult {
+    Ok(Json(json!({ "queued": true })))
+}
+
+async fn hacksteader_of(user: &ApiUser) -> Result<Hacksteader, Custom<String>> {
+    Hacksteader::from_db(store(), user.user_id.clone())
+        .await
+        .map_err(failed)
+}
+
+fn own_tile(hs: &Hacksteader, tile_id: uuid::Uuid) -> Result<&Tile, Custom<String>> {
+    hs.land
+        .iter()
+        .find(|t| t.id == tile_id)
+        .ok_or_else(|| refuse(format!("you don't have a tile {}", tile_id)))
+}
+
+fn own_plant(hs: &Hacksteader, tile_id: uuid::Uuid) -> Result<&Plant, Custom<String>> {
+    own_tile(hs, tile_id)?
+        .plant
+        .as_ref()
+        .ok_or_else(|| refuse(format!("nothing is growing on tile {}", tile_id)))
+}
+
+fn own_item(hs: &Hacksteader, item_id: uuid::Uuid) -> Result<&Possession, Custom<String>> {
+    hs.inventory
+        .iter()
+        .find(|p| p.id == item_id)
+        .ok_or_else(|| refuse(format!("you don't have an item {}", item_id)))
+}
+
+async fn activate(
+    to_farming: &Sender<FarmingInputEvent>,
+    user: &ApiUser,
+) -> Result<(), Custom<String>> {
+    farm::queue(
+        to_farming,
+        FarmingInputEvent::ActivateUser(user.user_id.clone()),
+    )
+    .await
+    .map_err(failed)
+}
+
+#[derive(Deserialize)]
+pub struct PlantSeed {
+    tile: uuid::Uuid,
+    seed: uuid::Uuid,
+}
+
+#[post("/plant", data = "<req>")]
+async fn plant(
+    to_farming: State<'_, Sender<FarmingInputEvent>>,
+    user: ApiUser,
+    req: Json<PlantSeed>,
+) -> ApiResult {
+    let hs = hacksteader_of(&user).await?;
+    if own_tile(&hs, req.tile)?.plant.is_some() {
+        return Err(refuse(format!(
+            "something is already growing on tile {}",
+            req.tile
+        )));
+    }
+    let seed: Result<Possessed<possess::Seed>, _> = own_item(&hs, req.seed)?.clone().try_into();
+    if seed.is_err() {
+        return Err(refuse(format!("{} isn't a seed", req.seed)));
+    }
+
+    crate::plant_seed(&to_farming, req.tile, req.seed)
+        .await
+        .map_err(failed)?;
+    activate(&to_farming, &user).await?;
+    queued()
+}
+
+#[derive(Deserialize)]
+pub struct BeginCraft {
+    tile: uuid::Uuid,
+    /// Which of the plant's recipes to craft, counting from 0.
+    recipe: config::ArchetypeHandle,
+}
+
+#[post("/craft", data = "<req>")]
+async fn craft(
+    to_farming: State<'_, Sender<FarmingInputEvent>>,
+    user: ApiUser,
+    req: Json<BeginCraft>,
+) -> ApiResult {
+    let hs = hacksteader_of(&user).await?;
+    let plant = own_plant(&hs, req.tile)?;
+    if plant.craft.is_some() {
+        return Err(refuse(format!("tile {} is already crafting", req.tile)));
+    }
+
+    let neighbor_bonuses = hs
+        .neighbor_bonuses()
+        .bonuses_for_plant(req.tile, plant.archetype_handle);
+    let recipe = plant
+        .advancements_sum(neighbor_bonuses.iter())
+        .recipes
+        .get(req.recipe)
+        .cloned()
+        .ok_or_else(|| refuse(format!("this plant can't craft recipe {} yet", req.recipe)))?;
+    if !recipe.satisfies(&hs.inventory) {
+        return Err(refuse("you don't have what that recipe needs"));
+    }
+
+    farm::queue(
+        &to_farming,
+        FarmingInputEvent::BeginCraft {
+            tile_id: req.tile,
+            recipe_archetype_handle: req.recipe,
+        },
+    )
+    .await
+    .map_err(failed)?;
+    activate(&to_farming, &user).await?;
+    queued()
+}
+
+#[derive(Deserialize)]
+pub struct ApplyItem {
+    tile: uuid::Uuid,
+    item: uuid::Uuid,
+}
+
+#[post("/apply", data = "<req>")]
+async fn apply(
+    to_farming: State<'_, Sender<FarmingInputEvent>>,
+    user: ApiUser,
+    req: Json<ApplyItem>,
+) -> ApiResult {
+    let hs = hacksteader_of(&user).await?;
+    let plant = own_plant(&hs, req.tile)?;
+    let applies = own_item(&hs, req.item)?
+        .kind
+        .keepsake()
+        .and_then(|k| k.item_application.as_ref())
+        .map_or(false, |a| {
+            a.effects.iter().any(|e| e.keep_plants.allows(&plant.name))
+        });
+    if !applies {
+        return Err(refuse(format!(
+            "{} can't be used on a {}",
+            req.item, plant.name
+        )));
+    }
+
+    farm::queue(
+        &to_farming,
+        FarmingInputEvent::ApplyItem(
+            ItemApplication {
+                tile: req.tile,
+                item: req.item,
+            },
+            user.user_id.clone(),
+        ),
+    )
+    .await
+    .map_err(failed)?;
+    activate(&to_farming, &user).await?;
+    queued()
+}
+
+#[derive(Deserialize)]
+pub struct HatchEgg {
+    egg: uuid::Uuid,
+}
+
+#[post("/hatch", data = "<req>")]
+async fn hatch(
+    to_farming: State<'_, Sender<FarmingInputEvent>>,
+    user: ApiUser,
+    req: Json<HatchEgg>,
+) -> ApiResult {
+    let hs = hacksteader_of(&user).await?;
+    let hatchable = hs
+        .gotchis
+        .iter()
+        .any(|g| g.id == req.egg && g.inner.hatch_table.is_some());
+    if !hatchable {
+        return Err(refuse(format!("you don't have an egg {}", req.egg)));
+    }
+
+    farm::queue(
+        &to_farming,
+        FarmingInputEvent::HatchEgg(req.egg, user.user_id.clone()),
+    )
+    .await
+    .map_err(failed)?;
+    activate(&to_farming, &user).await?;
+    queued()
+}
+
+#[derive(Deserialize)]
+pub struct RedeemLandCert {
+    cert: uuid::Uuid,
+}
+
+#[post("/redeem_land", data = "<req>")]
+async fn redeem_land(
+    to_farming: State<'_, Sender<FarmingInputEvent>>,
+    user: ApiUser,
+    req: Json<RedeemLandCert>,
+) -> ApiResult {
+    let hs = hacksteader_of(&user).await?;
+    let cert = own_item(&hs, req.cert)?
+        .kind
+        .keepsake()
+        .and_then(|k| k.unlocks_land.as_ref())
+        .ok_or_else(|| refuse(format!("{} isn't a land deed", req.cert)))?;
+    if cert.requires_xp && hs.land.len() >= hs.profile.advancements_sum().land as usize {
+        return Err(refuse("you need more xp before you can redeem that"));
+    }
+
+    farm::queue(
+        &to_farming,
+        FarmingInputEvent::RedeemLandCert(req.cert, user.user_id.clone()),
+    )
+    .await
+    .map_err(failed)?;
+    activate(&to_farming, &user).await?;
+    queued()
+}
+
+#[derive(Deserialize)]
+pub struct Sell {
+    possession: uuid::Uuid,
+    price: u64,
+    /// How many hours it stays up for, if not until it's taken down.
+    #[serde(default)]
+    hours: Option<u64>,
+}
+
+/// How long a listing asked to stay up for `hours` stays up, which is
+/// never any longer than the longest the sell modal offers.
+fn listing_duration(hours: Option<u64>) -> Option<Duration> {
+    let longest = market::LISTING_DURATIONS
+        .iter()
+        .filter_map(|(_, hours)| *hours)
+        .max()
+        .unwrap_or(0)
+        * 60
+        * 60;
+    hours.map(|h| {
+        Duration::from_secs(
+            h.checked_mul(60 * 60)
+                .map_or(longest, |secs| secs.min(longest)),
+        )
+    })
+}
+
+/// Bills the seller the listing fee; what they're selling goes up once they've paid it.
+#[post("/sell", data = "<req>")]
+async fn sell(user: ApiUser, req: Json<Sell>) -> ApiResult {
+    if req.price == 0 {
+        return Err(refuse("nothing can be sold for free"));
+    }
+    if req.hours == Some(0) {
+        return Err(refuse("listings have to stay up for at least an hour"));
+    }
+
+    let hs = hacksteader_of(&user).await?;
+    let possession = hs
+        .inventory
+        .iter()
+        .cloned()
+        .chain(hs.gotchis.iter().cloned().map(|g| g.into_possession()))
+        .find(|p| p.id == req.possession)
+        .ok_or_else(|| refuse(format!("you don't have a {}", req.possession)))?;
+    if possession.sale.is_some() {
+        return Err(refuse(format!("{} is already for sale", req.possession)));
+    }
+
+    let duration = listing_duration(req.hours);
+    let invoice = market::invoice_listing_fee(&user.user_id, &possession, req.price, duration)
+        .await
+        .map_err(failed)?;
+    Ok(Json(json!({ "invoice": invoice })))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![plant, craft, apply, hatch, redeem_land, sell]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::api::test::{bearer, client, steader};
+    use crate::api::tokens;
+    use crate::records;
+    use crate::store::Batch;
+    use rocket::http::ContentType;
+    use rocket::local::asynchronous::{Client, LocalResponse};
+
+    async fn sell_as<'c>(client: &'c Client, token: &str, body: Value) -> LocalResponse<'c> {
+        client
+            .post("/sell")
+            .header(ContentType::JSON)
+            .header(bearer(token))
+            .body(body.to_string())
+            .dispatch()
+            .await
+    }
+
+    #[rocket::async_test]
+    async fn nobody_sells_what_isnt_theirs() {
+        let client = client().await;
+        let (_, token) = steader().await;
+        let someone_elses = Possession::new(0, possess::Owner::farmer("U0".to_string()));
+        store()
+            .write(Batch {
+                possessions: vec![someone_elses.clone()],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let body = json!({ "possession": someone_elses.id, "price": 10 });
+        let res = sell_as(&client, &token, body).await;
+        assert_eq!(res.status(), Status::Forbidden);
+    }
+
+    #[rocket::async_test]
+    async fn tokens_are_limited_to_so_many_uses_a_minute() {
+        let client = client().await;
+        let (_, token) = steader().await;
+
+        for _ in 0..*tokens::API_RATE_LIMIT {
+            let res = sell_as(
+                &client,
+                &token,
+                json!({ "possession": uuid::Uuid::nil(), "price": 0 }),
+            )
+            .await;
+            assert_eq!(res.status(), Status::Forbidden);
+        }
+        let res = sell_as(
+            &client,
+            &token,
+            json!({ "possession": uuid::Uuid::nil(), "price": 0 }),
+        )
+        .await;
+        assert_eq!(res.status(), Status::TooManyRequests);
+    }
+
+    #[rocket::async_test]
+    async fn revoked_tokens_stop_working() {
+        let client = client().await;
+        let (user_id, token) = steader().await;
+        let hash = tokens::tokens_of(store(), &user_id).await.unwrap()[0]
+            .hash
+            .clone();
+
+        assert!(tokens::revoke(store(), "U0", &hash).await.is_err());
+        tokens::revoke(store(), &user_id, &hash).await.unwrap();
+        assert!(records::get::<tokens::ApiToken>(store(), &hash)
+            .await
+            .unwrap()
+            .is_none());
+
+        let res = sell_as(
+            &client,
+            &token,
+            json!({ "possession": uuid::Uuid::nil(), "price": 0 }),
+        )
+        .await;
+        assert_eq!(res.status(), Status::Unauthorized);
+    }
+
+    #[test]
+    fn listings_stay_up_no_longer_than_a_week() {
+        let week = Duration::from_secs(7 * 24 * 60 * 60);
+        assert_eq!(listing_duration(None), None);
+        assert_eq!(
+            listing_duration(Some(1)),
+            Some(Duration::from_secs(60 * 60))
+        );
+        assert_eq!(listing_duration(Some(7 * 24)), Some(week));
+        assert_eq!(listing_duration(Some(7 * 24 + 1)), Some(week));
+        assert_eq!(listing_duration(Some(u64::MAX)), Some(week));
+    }
+}
@@ -0,0 +1,581 @@
+//! Point-in-time backups of the whole game, as JSON lines.
+//!
+//! The first line of an archive is a `Header` saying which version of the format it's in;
+//! every line after that is one profile, tile or possession, as its DynamoDB attributes,
+//! or one of the records that goes along with them, like a listing or a trade.
+//!
+//! Records that stand for HN the banker is holding, like bids and buy orders, aren't
+//! archived: that HN can't be rolled back along with the store. A steader can't be
+//! restored while any of those involve them, so that none of it is left unaccounted for.
+//! Archives can be made and restored from with `gotchi backup <path>` and
+//! `gotchi restore <path> [steader]`, or by an operator through the goblin commands.
+use crate::auction::Auction;
+use crate::banker::PendingInvoice;
+use crate::hacksteader::Tile;
+use crate::market::{BuyOrder, LastTrade, Listing, Trade};
+use crate::records::{self, Record};
+use crate::store::{Batch, Store};
+use crate::trade::Offer;
+use hcor::{Category, Item, Key, Possession, Profile};
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::{BufRead, Write};
+use std::time::SystemTime;
+
+/// Bumped whenever archives made by older versions can't be read by newer ones.
+pub const VERSION: u32 = 1;
+
+/// Where the goblin commands keep archives.
+pub const BACKUP_DIR: &str = "./backups";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Header {
+    pub version: u32,
+    pub taken: SystemTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", content = "item", rename_all = "snake_case")]
+enum Entry {
+    Profile(Item),
+    Tile(Item),
+    Possession(Item),
+    Record(ArchivedRecord),
+}
+
+/// A record, as it's kept in the store.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ArchivedRecord {
+    pub kind: String,
+    pub id: String,
+    pub data: String,
+    /// Whose it is, if it's any one steader's.
+    pub steader: Option<String>,
+}
+
+/// How much was written out or read back in.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Counts {
+    pub profiles: usize,
+    pub tiles: usize,
+    pub possessions: usize,
+    pub records: usize,
+    /// Items and records in the store that weren't in the archive, and so were removed.
+    pub deleted: usize,
+}
+impl std::fmt::Display for Counts {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} profiles, {} tiles, {} possessions and {} records",
+            self.profiles, self.tiles, self.possessions, self.records
+        )?;
+        if self.deleted > 0 {
+            write!(f, ", deleting {} items", self.deleted)?;
+        }
+        Ok(())
+    }
+}
+
+async fn all_possessions(db: &dyn Store) -> Result<Vec<Possession>, String> {
+    let mut possessions = db.possessions(Category::Gotchi).await?;
+    possessions.append(&mut db.possessions(Category::Misc).await?);
+    Ok(possessions)
+}
+
+fn archived<R: Record>(
+    records: Vec<R>,
+    steader: impl Fn(&R) -> Option<String>,
+) -> Result<Vec<ArchivedRecord>, String> {
+    records
+        .iter()
+        .map(|r| {
+            Ok(ArchivedRecord {
+                kind: R::KIND.to_string(),
+                id: r.id(),
+                data: serde_json::to_string(r)
+                    .map_err(|e| format!("couldn't serialize {} record: {}", R::KIND, e))?,
+                steader: steader(r),
+            })
+        })
+        .collect()
+}
+
+/// The records that are archived along with the items.
+async fn game_records(db: &dyn Store) -> Result<Vec<ArchivedRecord>, String> {
+    let mut game_records = archived(records::all::<Listing>(db).await?, |l| {
+        Some(l.seller.clone())
+    })?;
+    game_records.append(&mut archived(records::all::<Trade>(db).await?, |_| None)?);
+    game_records.append(&mut archived(records::all::<LastTrade>(db).await?, |_| {
+        None
+    })?);
+    Ok(game_records)
+}
+
+/// Errors if the banker is holding HN for anyone in scope.
+async fn check_escrow(
+    db: &dyn Store,
+    in_scope: &(dyn Fn(&str) -> bool + Sync),
+) -> Result<(), String> {
+    let mut held = vec![];
+    for a in records::all::<Auction>(db).await? {
+        if in_scope(&a.seller) || a.high_bid.as_ref().map_or(false, |b| in_scope(&b.bidder)) {
+            held.push(format!("auction {}", a.id));
+        }
+    }
+    for o in records::all::<BuyOrder>(db).await? {
+        if in_scope(&o.buyer) {
+            held.push(format!("buy order {}", o.id));
+        }
+    }
+    for o in records::all::<Offer>(db).await? {
+        if in_scope(&o.proposer.steader) || in_scope(&o.recipient.steader) {
+            held.push(format!("trade offer {}", o.id));
+        }
+    }
+    for i in records::all::<PendingInvoice>(db).await? {
+        if in_scope(&i.invoicee) {
+            held.push(format!("invoice {}", i.transaction_id));
+        }
+    }
+
+    if held.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "can't restore while the banker is holding HN for {}; those have to be settled or called off first",
+            held.join(", ")
+        ))
+    }
+}
+
+fn write_line(out: &mut impl Write, line: &impl Serialize) -> Result<(), String> {
+    serde_json::to_writer(&mut *out, line)
+        .map_err(|e| format!("couldn't serialize archive line: {}", e))?;
+    writeln!(out).map_err(|e| format!("couldn't write archive: {}", e))
+}
+
+/// Writes out everything in the store.
+pub async fn export(db: &dyn Store, out: &mut impl Write) -> Result<Counts, String> {
+    let profiles = db.profiles().await?;
+    let tiles = db.tiles().await?;
+    let possessions = all_possessions(db).await?;
+    let records = game_records(db).await?;
+
+    write_line(
+        out,
+        &Header {
+            version: VERSION,
+            taken: SystemTime::now(),
+        },
+    )?;
+    for p in &profiles {
+        write_line(out, &Entry::Profile(p.item()))?;
+    }
+    for t in tiles.iter().cloned() {
+        write_line(
+            out,
+            &Entry::Tile(t.into_av().m.expect("tile attribute should be map")),
+        )?;
+    }
+    for p in &possessions {
+        write_line(out, &Entry::Possession(p.item()))?;
+    }
+    for r in records.iter().cloned() {
+        write_line(out, &Entry::Record(r))?;
+    }
+
+    Ok(Counts {
+        profiles: profiles.len(),
+        tiles: tiles.len(),
+        possessions: possessions.len(),
+        records: records.len(),
+        deleted: 0,
+    })
+}
+
+/// Reads an archive back into a batch that would put all of its items into the store,
+/// and the records that go along with them.
+pub fn read(input: impl BufRead) -> Result<(Header, Batch, Vec<ArchivedRecord>), String> {
+    let mut lines = input.lines().enumerate();
+    let header: Header = lines
+        .next()
+        .ok_or_else(|| "archive is empty".to_string())
+        .and_then(|(_, l)| l.map_err(|e| format!("couldn't read archive: {}", e)))
+        .and_then(|l| {
+            serde_json::from_str(&l).map_err(|e| format!("couldn't parse archive header: {}", e))
+        })?;
+    if header.version != VERSION {
+        return Err(format!(
+            "archive is version {}, but only version {} can be restored",
+            header.version, VERSION
+        ));
+    }
+
+    let mut batch = Batch::default();
+    let mut records = vec![];
+    for (n, line) in lines {
+        let line = line.map_err(|e| format!("couldn't read archive: {}", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let e = |e: hcor::AttributeParseError| format!("line {} of archive: {}", n + 1, e);
+        match serde_json::from_str(&line)
+            .map_err(|e| format!("couldn't parse line {} of archive: {}", n + 1, e))?
+        {
+            Entry::Profile(item) => batch.profiles.push(Profile::from_item(&item).map_err(e)?),
+            Entry::Tile(item) => batch.tiles.push(Tile::from_item(&item).map_err(e)?),
+            Entry::Possession(item) => batch
+                .possessions
+                .push(Possession::from_item(&item).map_err(e)?),
+            Entry::Record(record) => records.push(record),
+        }
+    }
+    Ok((header, batch, records))
+}
+
+/// Makes the store look like it did when the archive was taken, either entirely or
+/// just for one steader. Profiles, tiles, possessions and records that weren't around
+/// back then are removed. Records that aren't any one steader's, like the market's trades,
+/// are only restored when everything is.
+pub async fn restore(
+    db: &dyn Store,
+    input: impl BufRead,
+    steader: Option<&str>,
+) -> Result<Counts, String> {
+    let (header, mut batch, mut archived_records) = read(input)?;
+    let in_scope = |s: &str| steader.map_or(true, |steader| steader == s);
+    let record_in_scope = |r: &ArchivedRecord| {
+        r.steader
+            .as_deref()
+            .map_or(steader.is_none(), |s| in_scope(s))
+    };
+    check_escrow(db, &in_scope).await?;
+
+    batch.profiles.retain(|p| in_scope(&p.id));
+    batch.tiles.retain(|t| in_scope(&t.steader));
+    batch.possessions.retain(|p| in_scope(&p.steader));
+
+    let archived: HashSet<uuid::Uuid> = batch
+        .tiles
+        .iter()
+        .map(|t| t.id)
+        .chain(batch.possessions.iter().map(|p| p.id))
+        .collect();
+    batch.deletions = db
+        .tiles()
+        .await?
+        .into_iter()
+        .filter(|t| in_scope(&t.steader))
+        .map(|t| Key::tile(t.id))
+        .chain(
+            all_possessions(db)
+                .await?
+                .into_iter()
+                .filter(|p| in_scope(&p.steader))
+                .map(|p| p.key()),
+        )
+        .filter(|k| !archived.contains(&k.id))
+        .collect();
+    let archived_profiles: HashSet<&str> = batch.profiles.iter().map(|p| p.id.as_str()).collect();
+    batch.profile_deletions = db
+        .profiles()
+        .await?
+        .into_iter()
+        .map(|p| p.id)
+        .filter(|id| in_scope(id) && !archived_profiles.contains(id.as_str()))
+        .collect();
+
+    archived_records.retain(|r| record_in_scope(r));
+    let kept: HashSet<(&str, &str)> = archived_records
+        .iter()
+        .map(|r| (r.kind.as_str(), r.id.as_str()))
+        .collect();
+    let stale_records: Vec<ArchivedRecord> = game_records(db)
+        .await?
+        .into_iter()
+        .filter(|r| record_in_scope(r) && !kept.contains(&(r.kind.as_str(), r.id.as_str())))
+        .collect();
+
+    let counts = Counts {
+        profiles: batch.profiles.len(),
+        tiles: batch.tiles.len(),
+        possessions: batch.possessions.len(),
+        records: archived_records.len(),
+        deleted: batch.deletions.len() + batch.profile_deletions.len() + stale_records.len(),
+    };
+    // each item and record is restored on its own, so if a chunk fails,
+    // restoring again picks up where this left off
+    for chunk in batch.chunks() {
+        db.transact(chunk).await?;
+    }
+    for r in &stale_records {
+        db.remove_record(&r.kind, &r.id).await?;
+    }
+    for r in archived_records {
+        db.put_record(&r.kind, &r.id, r.data).await?;
+    }
+
+    info!(
+        "restored {} from an archive taken at {}",
+        counts,
+        humantime::format_rfc3339_seconds(header.taken)
+    );
+    Ok(counts)
+}
+
+/// Where a new archive in `BACKUP_DIR` should go.
+pub fn new_backup_path() -> String {
+    format!(
+        "{}/{}.jsonl",
+        BACKUP_DIR,
+        humantime::format_rfc3339_seconds(SystemTime::now())
+            .to_string()
+            .replace(':', "-")
+    )
+}
+
+/// Writes out an archive of everything to a file.
+pub async fn backup_to(db: &dyn Store, path: &str) -> Result<Counts, String> {
+    use std::fs::{self, File};
+    use std::io::BufWriter;
+
+    if let Some(dir) = std::path::Path::new(path).parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("couldn't make {:?}: {}", dir, e))?;
+    }
+    let mut out =
+        BufWriter::new(File::create(path).map_err(|e| format!("couldn't create {}: {}", path, e))?);
+    let counts = export(db, &mut out).await?;
+    out.flush()
+        .map_err(|e| format!("couldn't finish writing {}: {}", path, e))?;
+    Ok(counts)
+}
+
+/// Restores from an archive in a file.
+pub async fn restore_from(
+    db: &dyn Store,
+    path: &str,
+    steader: Option<&str>,
+) -> Result<Counts, String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("couldn't open {}: {}", path, e))?;
+    restore(db, std::io::BufReader::new(file), steader).await
+}
+
+/// Runs `backup` or `restore` if the process was started with either,
+/// returning `None` if it should go on to serve the game instead.
+pub async fn cli(db: &dyn Store, args: &[String]) -> Option<Result<(), String>> {
+    let result = match args {
+        [cmd, path] if cmd == "backup" => backup_to(db, path).await,
+        [cmd, path] if cmd == "restore" => restore_from(db, path, None).await,
+        [cmd, path, steader] if cmd == "restore" => restore_from(db, path, Some(steader)).await,
+        [cmd, ..] if cmd == "backup" || cmd == "restore" => {
+            Err("usage: gotchi backup <path> | gotchi restore <path> [steader]".to_string())
+        }
+        _ => return None,
+    };
+    Some(result.map(|counts| println!("{} {}", args[0], counts)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hacksteader::Hacksteader;
+    use crate::store::MemoryStore;
+    use hcor::possess;
+
+    async fn steaders(db: &dyn Store) -> Vec<String> {
+        let mut ids: Vec<String> = db
+            .profiles()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|p| p.id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    async fn possession_ids(db: &dyn Store) -> Vec<uuid::Uuid> {
+        all_possessions(db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|p| p.id)
+            .collect()
+    }
+
+    async fn give(db: &dyn Store, steader: &str) -> Possession {
+        let p = Possession::new(0, possess::Owner::farmer(steader.to_string()));
+        db.write(Batch {
+            possessions: vec![p.clone()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        p
+    }
+
+    fn trade() -> Trade {
+        Trade {
+            id: uuid::Uuid::new_v4(),
+            archetype_handle: 0,
+            name: "thing".to_string(),
+            price: 10,
+            buyer: "U2".to_string(),
+            seller: "U1".to_string(),
+            at: SystemTime::now(),
+        }
+    }
+
+    /// Two steaders, one with a possession, and a trade on the books.
+    async fn archived_game(db: &dyn Store) -> (Vec<u8>, Possession, Trade) {
+        for id in &["U1", "U2"] {
+            Hacksteader::new_in_db(db, id.to_string()).await.unwrap();
+        }
+        let kept = give(db, "U1").await;
+        let trade = trade();
+        records::put(db, &trade).await.unwrap();
+
+        let mut archive = vec![];
+        let counts = export(db, &mut archive).await.unwrap();
+        assert_eq!(
+            (
+                counts.profiles,
+                counts.tiles,
+                counts.possessions,
+                counts.records
+            ),
+            (2, 2, 1, 1)
+        );
+        (archive, kept, trade)
+    }
+
+    #[rocket::async_test]
+    async fn restores_everything() {
+        let db = MemoryStore::default();
+        let (archive, kept, trade) = archived_game(&db).await;
+
+        db.write(Batch {
+            deletions: vec![kept.key()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        Hacksteader::new_in_db(&db, "U3".to_string()).await.unwrap();
+        give(&db, "U2").await;
+        records::remove::<Trade>(&db, &trade.id()).await.unwrap();
+        records::put(&db, &self::trade()).await.unwrap();
+
+        let counts = restore(&db, &archive[..], None).await.unwrap();
+        // U3's profile and tile, U2's new possession and the new trade
+        assert_eq!(counts.deleted, 4);
+        assert_eq!(steaders(&db).await, vec!["U1", "U2"]);
+        assert_eq!(db.tiles().await.unwrap().len(), 2);
+        assert_eq!(possession_ids(&db).await, vec![kept.id]);
+        let trades = records::all::<Trade>(&db).await.unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].id, trade.id);
+    }
+
+    #[rocket::async_test]
+    async fn restores_one_steader() {
+        let db = MemoryStore::default();
+        let (archive, kept, trade) = archived_game(&db).await;
+
+        Hacksteader::new_in_db(&db, "U3".to_string()).await.unwrap();
+        let other = give(&db, "U2").await;
+        records::remove::<Trade>(&db, &trade.id()).await.unwrap();
+
+        let counts = restore(&db, &archive[..], Some("U3")).await.unwrap();
+        assert_eq!(counts.deleted, 2);
+        assert_eq!(steaders(&db).await, vec!["U1", "U2"]);
+        let mut possessions = possession_ids(&db).await;
+        possessions.sort();
+        let mut expected = vec![kept.id, other.id];
+        expected.sort();
+        assert_eq!(possessions, expected);
+        // trades aren't anyone's, so they're left alone
+        assert!(records::all::<Trade>(&db).await.unwrap().is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn refuses_while_hn_is_held() {
+        let db = MemoryStore::default();
+        let (archive, _, _) = archived_game(&db).await;
+        records::put(
+            &db,
+            &BuyOrder {
+                id: uuid::Uuid::new_v4(),
+                buyer: "U1".to_string(),
+                archetype_handle: 0,
+                max_price: 10,
+                quantity: 1,
+                placed: SystemTime::now(),
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(restore(&db, &archive[..], Some("U1")).await.is_err());
+        assert!(restore(&db, &archive[..], None).await.is_err());
+        assert!(restore(&db, &archive[..], Some("U2")).await.is_ok());
+    }
+
+    /// Only one steader is restored, so that nothing else on a shared DynamoDB Local is touched.
+    #[rocket::async_test]
+    async fn round_trips_through_dynamodb_local() {
+        let db = match crate::store::DynamoStore::for_tests() {
+            Some(db) => db,
+            None => return,
+        };
+        let steader = format!("U{}", uuid::Uuid::new_v4().to_simple());
+        Hacksteader::new_in_db(&db, steader.clone()).await.unwrap();
+        let kept = give(&db, &steader).await;
+
+        let mut archive = vec![];
+        export(&db, &mut archive).await.unwrap();
+        db.write(Batch {
+            deletions: vec![kept.key()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        give(&db, &steader).await;
+
+        let restored = restore(&db, &archive[..], Some(&steader)).await;
+        let theirs: Vec<Possession> = all_possessions(&db)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|p| p.steader == steader)
+            .collect();
+        let tiles: Vec<Key> = db
+            .tiles()
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|t| t.steader == steader)
+            .map(|t| Key::tile(t.id))
+            .collect();
+        db.write(Batch {
+            deletions: theirs
+                .iter()
+                .map(|p| p.key())
+                .chain(tiles.clone())
+                .collect(),
+            profile_deletions: vec![steader.clone()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(restored.unwrap().deleted, 1);
+        assert_eq!(
+            theirs.iter().map(|p| p.id).collect::<Vec<_>>(),
+            vec![kept.id]
+        );
+        assert_eq!(tiles.len(), 1);
+    }
+}
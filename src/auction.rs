@@ -0,0 +1,627 @@
+//! English auctions on Hackstreet, for things too rare to put a fixed price on.
+//!
+//! Sellers pay the usual 5% fee on their reserve to start one. Every bid is paid to the
+//! banker up front, and whoever's outbid gets theirs back. A bid that comes in just
+//! before the end pushes the end back, so that nobody can win by sniping. Once an
+//! auction's over, `settle_ended` gives the item to whoever bid the most and pays the seller.
+use crate::blocks::{actions, button, comment, image, mrkdwn, section, Block};
+use crate::bus::{self, GameEvent};
+use crate::chat::mention;
+use crate::market::{self, log_blocks};
+use crate::records::{self, Record};
+use crate::store::{Store, Transfer};
+use crate::{banker, dm_blocks, filify, URL};
+use hcor::{possess, Key, Possession};
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+/// A bid this close to the end of an auction pushes the end back to this long after the bid.
+pub const SNIPING_WINDOW: Duration = Duration::from_secs(2 * 60);
+/// How long an auction can be set to run for.
+pub const MAX_DURATION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+/// The lowest reserve an auction can have, so that there's always some fee to pay.
+pub const MIN_RESERVE: u64 = 20;
+
+lazy_static::lazy_static! {
+    /// Bids are taken one at a time, so that two can't both outbid the same high bid.
+    static ref BIDDING: rocket::tokio::sync::Mutex<()> = Default::default();
+}
+
+/// What a seller asks for when they start an auction.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Terms {
+    /// Nothing less than this will be accepted.
+    pub reserve: u64,
+    /// Each bid has to beat the last one by at least this much.
+    pub increment: u64,
+    pub duration: Duration,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Bid {
+    pub bidder: String,
+    pub amount: u64,
+    pub placed: SystemTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Auction {
+    pub id: uuid::Uuid,
+    pub seller: String,
+    pub key: Key,
+    pub name: String,
+    pub terms: Terms,
+    pub started: SystemTime,
+    pub ends: SystemTime,
+    /// The bid the banker is holding onto, if anyone's bid yet.
+    pub high_bid: Option<Bid>,
+}
+impl Record for Auction {
+    const KIND: &'static str = "auction";
+
+    fn id(&self) -> String {
+        self.id.to_simple().to_string()
+    }
+}
+impl Auction {
+    /// The least the next bid can be.
+    pub fn minimum_bid(&self) -> u64 {
+        match &self.high_bid {
+            Some(bid) => bid.amount + self.terms.increment.max(1),
+            None => self.terms.reserve,
+        }
+    }
+
+    pub fn ended(&self) -> bool {
+        SystemTime::now() >= self.ends
+    }
+
+    /// How long until it's over, to the second.
+    pub fn time_left(&self) -> Duration {
+        self.ends
+            .duration_since(SystemTime::now())
+            .map(|d| Duration::from_secs(d.as_secs()))
+            .unwrap_or_default()
+    }
+
+    fn image_url(&self) -> String {
+        format!(
+            "http://{}/gotchi/img/{}/{}.png",
+            *URL,
+            self.key.category,
+            filify(&self.name)
+        )
+    }
+
+    /// How it stands, with a button to bid on it.
+    pub fn blocks(&self) -> Vec<Block> {
+        vec![
+            section(mrkdwn(format!(
+                "{} is auctioning a *{}*! {}. Bids must be at least *{} HN*, \
+                and the auction ends in _{}_.",
+                mention(&self.seller),
+                self.name,
+                match &self.high_bid {
+                    Some(bid) => format!("{} leads at *{} HN*", mention(&bid.bidder), bid.amount),
+                    None => "Nobody's bid yet".to_string(),
+                },
+                self.minimum_bid(),
+                humantime::format_duration(self.time_left())
+            )))
+            .accessory(image(self.image_url(), "Hackpheus with a gavel!"))
+            .into(),
+            actions(vec![button("Bid", "auction_bid")
+                .primary()
+                .value(self.id())
+                .into()]),
+        ]
+    }
+}
+
+pub async fn get(db: &dyn Store, id: &str) -> Result<Option<Auction>, String> {
+    records::get(db, id).await
+}
+
+/// Every auction that hasn't been settled yet, ending soonest first.
+pub async fn auctions(db: &dyn Store) -> Result<Vec<Auction>, String> {
+    let mut auctions: Vec<Auction> = records::all(db).await?;
+    auctions.sort_by_key(|a| a.ends);
+    Ok(auctions)
+}
+
+/// The auction a possession is up in, if it's in one.
+pub async fn of_possession(db: &dyn Store, key: Key) -> Result<Option<Auction>, String> {
+    Ok(auctions(db).await?.into_iter().find(|a| a.key.id == key.id))
+}
+
+/// Bills someone the fee for auctioning a possession off.
+/// The auction starts once they've paid it, returning the id of the invoice.
+pub async fn invoice_auction_fee(
+    db: &dyn Store,
+    user_id: &str,
+    possession: &Possession,
+    terms: Terms,
+) -> Result<String, String> {
+    if possession.steader != user_id {
+        return Err(format!("{} doesn't own that {}", user_id, possession.name));
+    }
+    if possession.sale.is_some() || of_possession(db, possession.key()).await?.is_some() {
+        return Err(format!("that {} is already up for sale", possession.name));
+    }
+    if terms.reserve < MIN_RESERVE {
+        return Err(format!(
+            "auctions need a reserve of at least {} HN",
+            MIN_RESERVE
+        ));
+    }
+
+    banker::invoice(
+        user_id,
+        terms.reserve / 20,
+        &format!(
+            "hackmarket fees for auctioning {} with a reserve of {}hn",
+            possession.name, terms.reserve
+        ),
+        banker::InvoiceIntent::AuctionFee {
+            key: possession.key(),
+            terms,
+        },
+    )
+    .await
+}
+
+/// Starts an auction once its fee's been paid, if the possession's still free to auction.
+pub async fn open(db: &dyn Store, seller: String, key: Key, terms: Terms) -> Result<(), String> {
+    let possession = db.possession(key).await?;
+    if possession.steader != seller
+        || possession.sale.is_some()
+        || of_possession(db, key).await?.is_some()
+    {
+        banker::pay(
+            seller,
+            terms.reserve / 20,
+            format!(
+                "the {} you tried to auction is already spoken for",
+                possession.name
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let now = SystemTime::now();
+    let auction = Auction {
+        id: uuid::Uuid::new_v4(),
+        seller,
+        key,
+        name: possession.name.clone(),
+        terms,
+        started: now,
+        ends: now + terms.duration.min(MAX_DURATION),
+        high_bid: None,
+    };
+    records::put(db, &auction).await?;
+
+    log_blocks(
+        format!(
+            "{} is auctioning a {}, starting at {} HN!",
+            mention(&auction.seller),
+            auction.name,
+            auction.terms.reserve
+        ),
+        auction
+            .blocks()
+            .into_iter()
+            .chain(std::iter::once(comment("GOING ONCE, GOING TWICE...")))
+            .collect(),
+    )
+    .await?;
+    bus::publish(GameEvent::Listing {
+        seller: auction.seller.clone(),
+        item: auction.name.clone(),
+        price: auction.terms.reserve,
+    });
+
+    Ok(())
+}
+
+/// Bills someone for a bid; it's placed once they've paid.
+pub async fn invoice_bid(auction: &Auction, bidder: &str, amount: u64) -> Result<String, String> {
+    if bidder == auction.seller {
+        return Err("you can't bid on your own auction".to_string());
+    }
+    if auction.ended() {
+        return Err(format!("the auction for that {} is over", auction.name));
+    }
+    if amount < auction.minimum_bid() {
+        return Err(format!("bids must be at least {}hn", auction.minimum_bid()));
+    }
+
+    banker::invoice(
+        bidder,
+        amount,
+        &format!(
+            "bidding {}hn on {}'s {}",
+            amount, auction.seller, auction.name
+        ),
+        banker::InvoiceIntent::AuctionBid {
+            auction: auction.id,
+            amount,
+        },
+    )
+    .await
+}
+
+/// Places a bid that's been paid for, giving back whatever it beat.
+/// If it can't be placed anymore, the bidder gets their HN back instead.
+pub async fn bid(
+    db: &dyn Store,
+    id: uuid::Uuid,
+    bidder: String,
+    amount: u64,
+) -> Result<(), String> {
+    let _bidding = BIDDING.lock().await;
+
+    let auction = records::get::<Auction>(db, &id.to_simple().to_string()).await?;
+    let mut auction = match auction {
+        Some(a) if !a.ended() && amount >= a.minimum_bid() && a.seller != bidder => a,
+        other => {
+            let why = match other {
+                Some(a) if a.ended() => format!("the auction for that {} ended", a.name),
+                Some(a) if a.seller == bidder => format!("bidding on your own {}", a.name),
+                Some(a) => format!("someone outbid you on {} before your bid went in", a.name),
+                None => "that auction is over".to_string(),
+            };
+            return banker::owe(db, &bidder, amount, why).await;
+        }
+    };
+
+    let placed = auction.clone();
+    let now = SystemTime::now();
+    let outbid = auction.high_bid.replace(Bid {
+        bidder: bidder.clone(),
+        amount,
+        placed: now,
+    });
+    if auction.time_left() < SNIPING_WINDOW {
+        auction.ends = now + SNIPING_WINDOW;
+    }
+
+    // the bid that was beaten is owed back in the same write that places the new one
+    let refund = outbid.as_ref().map(|outbid| {
+        banker::OwedPayment::new(
+            &outbid.bidder,
+            outbid.amount,
+            format!("being outbid on {}'s {}", auction.seller, auction.name),
+        )
+    });
+    let mut swaps = vec![records::swap(&placed, Some(&auction))?];
+    if let Some(refund) = &refund {
+        swaps.push(records::create(refund)?);
+    }
+    if !db.transfer_if(vec![], swaps).await? {
+        let why = format!("the auction for that {} changing as you bid", auction.name);
+        return banker::owe(db, &bidder, amount, why).await;
+    }
+    banker::pay_owed(db, refund.into_iter().collect()).await;
+
+    if let Some(outbid) = outbid.filter(|o| o.bidder != bidder) {
+        if let Err(e) = dm_blocks(
+            outbid.bidder.clone(),
+            format!("You've been outbid on a {}!", auction.name),
+            auction.blocks(),
+        )
+        .await
+        {
+            warn!("couldn't tell {} they were outbid: {}", outbid.bidder, e);
+        }
+    }
+
+    if let Err(e) = log_blocks(
+        format!(
+            "{} bid {} HN on {}'s {}!",
+            mention(&bidder),
+            amount,
+            mention(&auction.seller),
+            auction.name
+        ),
+        auction.blocks(),
+    )
+    .await
+    {
+        warn!("couldn't log bid on auction {}: {}", auction.id(), e);
+    }
+    bus::publish(GameEvent::Bid {
+        bidder,
+        seller: auction.seller.clone(),
+        item: auction.name.clone(),
+        amount,
+    });
+
+    Ok(())
+}
+
+/// Wraps up an auction that's over: the item goes to the highest bidder and
+/// their bid to the seller. If the seller can't hand it over anymore, the bid is given back.
+///
+/// The auction's only removed as the item changes hands, and the seller's owed the bid in
+/// that same write, so if this fails before then, it's still there to be settled next time.
+async fn settle(db: &dyn Store, auction: Auction) -> Result<(), String> {
+    let bid = match auction.high_bid.clone() {
+        Some(bid) => bid,
+        None => {
+            if records::remove::<Auction>(db, &auction.id())
+                .await?
+                .is_none()
+            {
+                return Ok(());
+            }
+            return dm_blocks(
+                auction.seller.clone(),
+                format!("Nobody bid on your {}.", auction.name),
+                vec![section(mrkdwn(format!(
+                    "Your auction for a *{}* is over, but nobody met your reserve of *{} HN*. \
+                    It's still yours!",
+                    auction.name, auction.terms.reserve
+                )))
+                .into()],
+            )
+            .await;
+        }
+    };
+
+    let possession = db.possession(auction.key).await.ok();
+    let payout = banker::OwedPayment::new(
+        &auction.seller,
+        bid.amount,
+        format!("auctioning off your {}", auction.name),
+    );
+    let handed_over = db
+        .transfer_if(
+            vec![Transfer {
+                key: auction.key,
+                from: auction.seller.clone(),
+                price: None,
+                to: bid.bidder.clone(),
+                acquisition: possess::Acquisition::Purchase { price: bid.amount },
+            }],
+            vec![records::swap(&auction, None)?, records::create(&payout)?],
+        )
+        .await
+        .map_err(|e| format!("couldn't hand over {} from auction: {}", auction.id(), e))?;
+    if !handed_over {
+        let refund = banker::OwedPayment::new(
+            &bid.bidder,
+            bid.amount,
+            format!(
+                "the {} you won at auction is no longer {}'s to sell",
+                auction.name, auction.seller
+            ),
+        );
+        let swaps = vec![records::swap(&auction, None)?, records::create(&refund)?];
+        if db.transfer_if(vec![], swaps).await? {
+            banker::pay_owed(db, vec![refund]).await;
+        }
+        return Ok(());
+    }
+
+    banker::pay_owed(db, vec![payout]).await;
+    if let Err(e) = futures::try_join!(
+        log_blocks(
+            format!(
+                "{} won {}'s {} at auction for {} HN!",
+                mention(&bid.bidder),
+                mention(&auction.seller),
+                auction.name,
+                bid.amount
+            ),
+            vec![
+                section(mrkdwn(format!(
+                    "*SOLD!* {} won the auction for {}'s *{}* with a bid of *{} HN*!",
+                    mention(&bid.bidder),
+                    mention(&auction.seller),
+                    auction.name,
+                    bid.amount
+                )))
+                .accessory(image(auction.image_url(), "Hackpheus with a gavel!"))
+                .into(),
+                comment("SOLD TO THE GOTCHI IN THE BACK"),
+            ],
+        ),
+        dm_blocks(
+            bid.bidder.clone(),
+            format!("You won the auction for a {}!", auction.name),
+            vec![section(mrkdwn(format!(
+                "You won the auction for {}'s *{}* with a bid of *{} HN*. It's all yours!",
+                mention(&auction.seller),
+                auction.name,
+                bid.amount
+            )))
+            .into()],
+        ),
+    ) {
+        warn!("couldn't announce auction {} was won: {}", auction.id(), e);
+    }
+    if let Some(possession) = possession {
+        market::record_trade(db, &possession, &bid.bidder, bid.amount).await;
+    }
+    bus::publish(GameEvent::Sale {
+        buyer: bid.bidder,
+        seller: auction.seller,
+        item: auction.name,
+        price: bid.amount,
+    });
+
+    Ok(())
+}
+
+/// Settles every auction that's over.
+pub async fn settle_ended(db: &dyn Store) -> Result<(), String> {
+    for auction in auctions(db).await? {
+        if !auction.ended() {
+            // they're sorted by when they end
+            break;
+        }
+
+        // hold the bids off while it's settled, so nobody can get one in at the last second
+        let _bidding = BIDDING.lock().await;
+        let auction = match records::get::<Auction>(db, &auction.id()).await? {
+            Some(auction) if auction.ended() => auction,
+            // someone sniped it and pushed the end back, so it's not over after all
+            _ => continue,
+        };
+        let id = auction.id();
+        if let Err(e) = settle(db, auction).await {
+            error!("couldn't settle auction {}: {}", id, e);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::banker::{paying_through, OwedPayment, RecordingPayer};
+    use crate::chat::{recorded, Sent};
+    use crate::store::{Batch, MemoryStore};
+
+    const SELLER: &str = "U1";
+
+    /// Auctions off something of the seller's with a reserve of 20 and an increment of 5.
+    async fn opened(db: &dyn Store) -> Auction {
+        std::env::set_var("URL", "localhost");
+        let p = Possession::new(0, possess::Owner::farmer(SELLER.to_string()));
+        db.write(Batch {
+            possessions: vec![p.clone()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let terms = Terms {
+            reserve: 20,
+            increment: 5,
+            duration: Duration::from_secs(60 * 60),
+        };
+        let (res, _) = recorded(open(db, SELLER.to_string(), p.key(), terms)).await;
+        res.unwrap();
+        auctions(db).await.unwrap().remove(0)
+    }
+
+    /// Places each bid in turn, returning who was paid what and what was sent.
+    async fn bids(
+        db: &dyn Store,
+        auction: &Auction,
+        placed: &[(&str, u64)],
+    ) -> (Vec<(String, u64)>, Vec<Sent>) {
+        let payer = RecordingPayer::default().leak();
+        let (res, sent) = recorded(paying_through(payer, async {
+            for (bidder, amount) in placed {
+                bid(db, auction.id, bidder.to_string(), *amount).await?;
+            }
+            Ok::<(), String>(())
+        }))
+        .await;
+        res.unwrap();
+        let paid = payer.paid().into_iter().map(|p| (p.to, p.amount)).collect();
+        (paid, sent)
+    }
+
+    async fn current(db: &dyn Store, auction: &Auction) -> Option<Auction> {
+        get(db, &auction.id()).await.unwrap()
+    }
+
+    /// Makes it so the auction's over, and settles it.
+    async fn ended(db: &dyn Store, auction: &Auction) -> (Vec<(String, u64)>, Vec<Sent>) {
+        let mut over = current(db, auction).await.unwrap();
+        over.ends = SystemTime::now() - Duration::from_secs(1);
+        records::put(db, &over).await.unwrap();
+
+        let payer = RecordingPayer::default().leak();
+        let (res, sent) = recorded(paying_through(payer, settle_ended(db))).await;
+        res.unwrap();
+        let paid = payer.paid().into_iter().map(|p| (p.to, p.amount)).collect();
+        (paid, sent)
+    }
+
+    #[rocket::async_test]
+    async fn bids_under_the_reserve_or_increment_are_given_back() {
+        let db = MemoryStore::default();
+        let auction = opened(&db).await;
+
+        let (paid, _) = bids(&db, &auction, &[("U2", 15), ("U2", 20), ("U3", 24)]).await;
+
+        assert_eq!(paid, vec![("U2".to_string(), 15), ("U3".to_string(), 24)]);
+        let high_bid = current(&db, &auction).await.unwrap().high_bid.unwrap();
+        assert_eq!((high_bid.bidder.as_str(), high_bid.amount), ("U2", 20));
+        assert!(records::all::<OwedPayment>(&db).await.unwrap().is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn whoever_is_outbid_gets_their_bid_back() {
+        let db = MemoryStore::default();
+        let auction = opened(&db).await;
+
+        let (paid, sent) = bids(&db, &auction, &[("U2", 20), ("U3", 30)]).await;
+
+        assert_eq!(paid, vec![("U2".to_string(), 20)]);
+        assert!(sent.iter().any(|s| matches!(
+            s,
+            Sent::Dm { user_id, notif_msg, .. }
+                if user_id == "U2" && notif_msg.starts_with("You've been outbid")
+        )));
+        let high_bid = current(&db, &auction).await.unwrap().high_bid.unwrap();
+        assert_eq!((high_bid.bidder.as_str(), high_bid.amount), ("U3", 30));
+        assert!(records::all::<OwedPayment>(&db).await.unwrap().is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn bids_at_the_last_second_push_the_end_back() {
+        let db = MemoryStore::default();
+        let mut auction = opened(&db).await;
+        auction.ends = SystemTime::now() + Duration::from_secs(30);
+        records::put(&db, &auction).await.unwrap();
+
+        bids(&db, &auction, &[("U2", 20)]).await;
+
+        let left = current(&db, &auction).await.unwrap().time_left();
+        assert!(left > SNIPING_WINDOW - Duration::from_secs(5));
+    }
+
+    #[rocket::async_test]
+    async fn settling_hands_it_to_the_highest_bidder_and_pays_the_seller() {
+        let db = MemoryStore::default();
+        let auction = opened(&db).await;
+        bids(&db, &auction, &[("U2", 20)]).await;
+
+        let (paid, sent) = ended(&db, &auction).await;
+
+        assert_eq!(paid, vec![(SELLER.to_string(), 20)]);
+        assert_eq!(db.possession(auction.key).await.unwrap().steader, "U2");
+        assert!(current(&db, &auction).await.is_none());
+        assert!(sent.iter().any(|s| matches!(
+            s,
+            Sent::Dm { user_id, notif_msg, .. }
+                if user_id == "U2" && notif_msg.starts_with("You won the auction")
+        )));
+        assert!(records::all::<OwedPayment>(&db).await.unwrap().is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn settling_without_bids_leaves_it_with_the_seller() {
+        let db = MemoryStore::default();
+        let auction = opened(&db).await;
+
+        let (paid, sent) = ended(&db, &auction).await;
+
+        assert!(paid.is_empty());
+        assert_eq!(db.possession(auction.key).await.unwrap().steader, SELLER);
+        assert!(current(&db, &auction).await.is_none());
+        assert!(sent.iter().any(|s| matches!(
+            s,
+            Sent::Dm { user_id, notif_msg, .. }
+                if user_id == SELLER && notif_msg.starts_with("Nobody bid")
+        )));
+    }
+}
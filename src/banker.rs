@@ -1,10 +1,15 @@
-use crate::{dm_blocks, event::Message, mrkdwn, ID as BOT_ID, TOKEN};
-use log::{debug, info};
+use crate::blocks::{mrkdwn, section};
+use crate::chat::{chat, Channel};
+use crate::records::{self, Record};
+use crate::store::{store, Store};
+use crate::{dm_blocks, event::Message, ID as BOT_ID};
+use hcor::Key;
+use log::{debug, error, info, warn};
 use regex::Regex;
+use std::time::{Duration, SystemTime};
 
 use graphql_client::{GraphQLQuery, QueryBody, Response};
 use serde::de::DeserializeOwned;
-use serde_json::json;
 
 #[derive(Debug, Clone)]
 pub struct PaidInvoice {
@@ -14,6 +19,75 @@ pub struct PaidInvoice {
     pub reason: String,
 }
 
+/// What an invoice is for, so that paying it can be acted upon
+/// without having to read anything back out of its reason.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub enum InvoiceIntent {
+    /// The 5% fee for putting the possession at `key` up on the market.
+    MarketFee {
+        key: Key,
+        price: u64,
+        market_name: String,
+        /// How long it stays up for, if not until it's taken down.
+        #[serde(default)]
+        duration: Option<std::time::Duration>,
+    },
+    /// The 5% fee for putting all of the possessions at `keys` up on the market at `price` each.
+    BatchMarketFee {
+        keys: Vec<Key>,
+        price: u64,
+        market_name: String,
+        duration: Option<std::time::Duration>,
+    },
+    /// Buying the possession at `key` off of `seller`.
+    Purchase {
+        key: Key,
+        price: u64,
+        seller: String,
+    },
+    /// Buying everything in a batch of one archetype, each at the price it was listed for.
+    BatchPurchase {
+        archetype_handle: hcor::config::ArchetypeHandle,
+        items: Vec<crate::market::BatchItem>,
+    },
+    /// Holding `max_price` for each of `quantity` possessions of an archetype
+    /// while a buy order for them is open.
+    BuyOrder {
+        archetype_handle: hcor::config::ArchetypeHandle,
+        max_price: u64,
+        quantity: u64,
+    },
+    /// The 5% fee, on its reserve, for auctioning off the possession at `key`.
+    AuctionFee {
+        key: Key,
+        terms: crate::auction::Terms,
+    },
+    /// Bidding `amount` in an auction; the banker holds onto it until they're outbid.
+    AuctionBid { auction: uuid::Uuid, amount: u64 },
+    /// The HN someone's putting up in a trade, held until everything changes hands.
+    TradeEscrow { offer: uuid::Uuid, amount: u64 },
+    /// Getting a hackstead of one's own.
+    HacksteadSignup,
+}
+
+/// An invoice we've sent out but haven't been paid for yet,
+/// keyed by the id HN gave its transaction.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct PendingInvoice {
+    pub transaction_id: String,
+    pub invoicee: String,
+    pub amount: u64,
+    pub intent: InvoiceIntent,
+    pub created: SystemTime,
+}
+impl Record for PendingInvoice {
+    const KIND: &'static str = "pending_invoice";
+
+    fn id(&self) -> String {
+        self.transaction_id.clone()
+    }
+}
+
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "hn/schema.json",
@@ -31,23 +105,88 @@ pub struct GetBalance;
 
 use std::env::var;
 lazy_static::lazy_static! {
-    pub static ref CHAT_ID: String = var("BANKER_CHAT").unwrap();
     pub static ref HN_TOKEN: String = var("HN_TOKEN").unwrap();
 }
 
+/// Whatever actually moves HN out of the banker's account.
+#[rocket::async_trait]
+pub trait Payer: Send + Sync {
+    async fn send(&self, to: &str, amount: u64, reason: &str) -> Result<(), String>;
+}
+
+/// Pays people through HN itself.
+pub struct HnPayer;
+
+#[rocket::async_trait]
+impl Payer for HnPayer {
+    async fn send(&self, to: &str, amount: u64, reason: &str) -> Result<(), String> {
+        send_payment(to, amount, reason).await
+    }
+}
+
+rocket::tokio::task_local! {
+    /// Who pays for a task that's `paying_through` something other than HN.
+    static TASK_PAYER: &'static dyn Payer;
+}
+
+/// HN, unless this task is `paying_through` something else.
+pub fn payer() -> &'static dyn Payer {
+    TASK_PAYER.try_with(|p| *p).unwrap_or(&HnPayer)
+}
+
+/// Runs `f` with its payments going through `payer` instead of HN.
+#[cfg(test)]
+pub async fn paying_through<F: std::future::Future>(payer: &'static dyn Payer, f: F) -> F::Output {
+    TASK_PAYER.scope(payer, f).await
+}
+
+/// A payment a `RecordingPayer` was asked to make.
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Payment {
+    pub to: String,
+    pub amount: u64,
+    pub reason: String,
+}
+
+/// Keeps track of the payments it's asked to make instead of making them,
+/// refusing any to whoever's in `refusing`, as if HN were down for them.
+#[cfg(test)]
+#[derive(Default)]
+pub struct RecordingPayer {
+    pub refusing: Vec<String>,
+    paid: std::sync::Mutex<Vec<Payment>>,
+}
+#[cfg(test)]
+impl RecordingPayer {
+    /// A recording payer that lasts as long as the tests do, ready for `paying_through`.
+    pub fn leak(self) -> &'static Self {
+        Box::leak(Box::new(self))
+    }
+
+    /// Every payment made so far, oldest first.
+    pub fn paid(&self) -> Vec<Payment> {
+        self.paid.lock().unwrap().clone()
+    }
+}
+#[cfg(test)]
+#[rocket::async_trait]
+impl Payer for RecordingPayer {
+    async fn send(&self, to: &str, amount: u64, reason: &str) -> Result<(), String> {
+        if self.refusing.iter().any(|r| r == to) {
+            return Err(format!("couldn't pay {}", to));
+        }
+        self.paid.lock().unwrap().push(Payment {
+            to: to.to_string(),
+            amount,
+            reason: reason.to_string(),
+        });
+        Ok(())
+    }
+}
+
 pub async fn message(msg: String) -> Result<(), String> {
-    let client = reqwest::Client::new();
-    client
-        .post("https://slack.com/api/chat.postMessage")
-        .form(&[
-            ("token", TOKEN.clone()),
-            ("channel", CHAT_ID.clone()),
-            ("text", msg),
-        ])
-        .send()
-        .await
-        .map_err(|e| format!("Couldn't message banker: {}", e))?;
-    Ok(())
+    chat().log(Channel::Banker, msg, vec![]).await
 }
 
 pub async fn do_query<T: serde::ser::Serialize, U: serde::de::DeserializeOwned>(
@@ -66,7 +205,12 @@ pub async fn do_query<T: serde::ser::Serialize, U: serde::de::DeserializeOwned>(
     Ok(response_body)
 }
 
-pub async fn invoice(user: &str, amount: u64, reason: &str) -> Result<String, String> {
+pub async fn invoice(
+    user: &str,
+    amount: u64,
+    reason: &str,
+    intent: InvoiceIntent,
+) -> Result<String, String> {
     let query = CreateTransaction::build_query(create_transaction::Variables {
         to: BOT_ID.to_string(),
         from: user.to_string(),
@@ -80,40 +224,70 @@ pub async fn invoice(user: &str, amount: u64, reason: &str) -> Result<String, St
         .data
         .ok_or(String::from("something bad happened"))?;
 
-    dm_blocks(user.to_string(), format!("I've just invoiced you {} HN for \"{}\". Type `/pay {}` in the chat below to confirm.", amount, reason, result.transact.id), vec![
-        json!({
-            "type": "section",
-            "text": mrkdwn(format!("I've just invoiced you {} HN for \"{}\". Type `/pay {}` in the chat below to confirm.", amount, reason, result.transact.id))
-        })
-    ]).await?;
+    records::put(
+        store(),
+        &PendingInvoice {
+            transaction_id: result.transact.id.clone(),
+            invoicee: user.to_string(),
+            amount,
+            intent,
+            created: SystemTime::now(),
+        },
+    )
+    .await?;
+
+    dm_blocks(
+        user.to_string(),
+        format!(
+            "I've just invoiced you {} HN for \"{}\". Type `/pay {}` in the chat below to confirm.",
+            amount, reason, result.transact.id
+        ),
+        vec![section(mrkdwn(format!(
+            "I've just invoiced you {} HN for \"{}\". Type `/pay {}` in the chat below to confirm.",
+            amount, reason, result.transact.id
+        )))
+        .into()],
+    )
+    .await?;
 
     Ok(result.transact.id)
 }
 
+/// Sends someone HN and lets them know. It only fails if the HN couldn't be sent;
+/// once it has been, not being able to tell them is only logged.
 pub async fn pay(user: String, amount: u64, reason: String) -> Result<(), String> {
+    payer().send(&user, amount, &reason).await?;
+
+    if let Err(e) = dm_blocks(
+        user.to_string(),
+        format!("I've just sent you {} HN for \"{}\"!", amount, reason),
+        vec![section(mrkdwn(format!(
+            "I've just sent you {} HN for \"{}\"!",
+            amount, reason
+        )))
+        .into()],
+    )
+    .await
+    {
+        warn!("sent {} {} HN but couldn't tell them: {}", user, amount, e);
+    }
+
+    Ok(())
+}
+
+async fn send_payment(user: &str, amount: u64, reason: &str) -> Result<(), String> {
     let query = Pay::build_query(pay::Variables {
-        to: user.clone(),
+        to: user.to_string(),
         from: BOT_ID.to_string(),
-        amount: amount.clone() as f64,
-        reason: Some(reason.clone()),
+        amount: amount as f64,
+        reason: Some(reason.to_string()),
     });
 
-    let result = do_query::<_, pay::ResponseData>(&query)
+    do_query::<_, pay::ResponseData>(&query)
         .await
-        .expect("something bad happened")
+        .map_err(|_| format!("couldn't reach HN to pay {} {} HN", user, amount))?
         .data
-        .expect("something bad happened");
-
-    dm_blocks(
-        user.to_string(),
-        format!("I've just sent you {} HN for \"{}\"!", amount, reason),
-        vec![json!({
-            "type": "section",
-            "text": mrkdwn(format!("I've just sent you {} HN for \"{}\"!", amount, reason))
-        })],
-    )
-    .await?;
-
+        .ok_or_else(|| format!("HN wouldn't pay {} {} HN", user, amount))?;
     Ok(())
 }
 
@@ -124,9 +298,107 @@ pub async fn get_balance() -> Result<u64, String> {
 
     let result = do_query::<_, get_balance::ResponseData>(&query)
         .await
-        .expect("something bad happened")
+        .map_err(|_| String::from("couldn't reach HN to get the banker's balance"))?
         .data
-        .expect("something bad happened");
+        .ok_or_else(|| String::from("HN didn't give the banker's balance"))?;
 
     Ok(result.user.balance as u64)
 }
+
+/// HN the banker owes someone for something that's already happened, like a sale going through.
+///
+/// It's written in the same `Store::transfer_if` as whatever it's owed for, with `records::create`,
+/// and only forgotten once it's been sent, so a payment HN doesn't take is tried again by
+/// `sweep_owed` instead of being lost.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct OwedPayment {
+    pub id: uuid::Uuid,
+    pub to: String,
+    pub amount: u64,
+    pub reason: String,
+    pub owed_since: SystemTime,
+}
+impl Record for OwedPayment {
+    const KIND: &'static str = "owed_payment";
+
+    fn id(&self) -> String {
+        self.id.to_simple().to_string()
+    }
+}
+impl OwedPayment {
+    pub fn new(to: &str, amount: u64, reason: String) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4(),
+            to: to.to_string(),
+            amount,
+            reason,
+            owed_since: SystemTime::now(),
+        }
+    }
+}
+
+/// How long a payment has to have been owed for before `sweep_owed` has another go at it,
+/// so that it doesn't race whatever's paying it the first time.
+pub const OWED_RETRY_AFTER: Duration = Duration::from_secs(5 * 60);
+
+/// Pays everything that's owed, forgetting each payment once it's been sent. Payments to the
+/// same person for the same reason are sent together. Whatever can't be sent is left for `sweep_owed`.
+///
+/// HN can't tell us whether a payment we lost track of went through, so if the game stops between
+/// sending one and forgetting it, it's sent again; paying twice beats not paying at all.
+pub async fn pay_owed(db: &dyn Store, owed: Vec<OwedPayment>) {
+    let mut together: Vec<Vec<OwedPayment>> = vec![];
+    for o in owed {
+        match together
+            .iter_mut()
+            .find(|t| t[0].to == o.to && t[0].reason == o.reason)
+        {
+            Some(t) => t.push(o),
+            None => together.push(vec![o]),
+        }
+    }
+
+    for payments in together {
+        let (to, reason) = (payments[0].to.clone(), payments[0].reason.clone());
+        let amount = payments.iter().map(|p| p.amount).sum();
+        if let Err(e) = pay(to.clone(), amount, reason.clone()).await {
+            error!(
+                "couldn't pay {} the {} HN they're owed for {}, it'll be tried again: {}",
+                to, amount, reason, e
+            );
+            continue;
+        }
+        for p in payments {
+            if let Err(e) = records::remove::<OwedPayment>(db, &p.id()).await {
+                error!(
+                    "paid {} {} HN for {} but couldn't forget it, so it'll be paid again: {}",
+                    p.to, p.amount, p.reason, e
+                );
+            }
+        }
+    }
+}
+
+/// Owes someone HN for something that isn't written along with a `transfer_if`, like a refund,
+/// and has a go at paying it right away.
+pub async fn owe(db: &dyn Store, to: &str, amount: u64, reason: String) -> Result<(), String> {
+    let owed = OwedPayment::new(to, amount, reason);
+    records::put(db, &owed).await?;
+    pay_owed(db, vec![owed]).await;
+    Ok(())
+}
+
+/// Has another go at paying everything that's been owed for at least `OWED_RETRY_AFTER`.
+pub async fn sweep_owed(db: &dyn Store) -> Result<(), String> {
+    let due = records::all::<OwedPayment>(db)
+        .await?
+        .into_iter()
+        .filter(|o| {
+            o.owed_since
+                .elapsed()
+                .map_or(false, |e| e >= OWED_RETRY_AFTER)
+        })
+        .collect();
+    pay_owed(db, due).await;
+    Ok(())
+}
@@ -0,0 +1,654 @@
+//! Typed Slack Block Kit, so that a typo in a block is a compile error instead of
+//! something Slack quietly refuses to show.
+//!
+//! Slack's limits are enforced as things are built. Anything people only read, like
+//! text, titles, and how many blocks fit in a view, is clipped to fit. Anything we
+//! read back, like option values, can't be clipped without breaking it, so building
+//! one that's too long is an error.
+use log::*;
+use serde::Serialize;
+
+/// The most characters Slack shows in a text object.
+pub const MAX_TEXT_LEN: usize = 3000;
+/// The most characters in a view's title or submit button.
+pub const MAX_TITLE_LEN: usize = 24;
+/// The most characters in an option's text, description or value.
+pub const MAX_OPTION_LEN: usize = 75;
+/// The most blocks a modal or home tab can have.
+pub const MAX_VIEW_BLOCKS: usize = 100;
+
+/// Cuts `txt` down to `max` characters, ending it with "..." if anything was cut.
+fn clip(txt: String, max: usize) -> String {
+    if txt.chars().count() <= max {
+        return txt;
+    }
+    warn!("clipping to {} chars: {}", max, txt);
+    let mut clipped: String = txt.chars().take(max - 3).collect();
+    clipped.push_str("...");
+    clipped
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Text {
+    Mrkdwn { text: String },
+    PlainText { text: String },
+}
+impl Text {
+    fn clipped(self, max: usize) -> Self {
+        match self {
+            Text::Mrkdwn { text } => Text::Mrkdwn {
+                text: clip(text, max),
+            },
+            Text::PlainText { text } => Text::PlainText {
+                text: clip(text, max),
+            },
+        }
+    }
+}
+
+pub fn mrkdwn<S: ToString>(txt: S) -> Text {
+    Text::Mrkdwn {
+        text: clip(txt.to_string(), MAX_TEXT_LEN),
+    }
+}
+pub fn plain_text<S: ToString>(txt: S) -> Text {
+    Text::PlainText {
+        text: clip(txt.to_string(), MAX_TEXT_LEN),
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Style {
+    Primary,
+    Danger,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Block {
+    Section(Section),
+    Context { elements: Vec<Text> },
+    Divider,
+    Input(Input),
+    Actions { elements: Vec<Element> },
+    Image(Image),
+}
+
+/// A bit of small, grey text.
+pub fn comment<S: ToString>(txt: S) -> Block {
+    Block::Context {
+        elements: vec![mrkdwn(txt)],
+    }
+}
+pub fn divider() -> Block {
+    Block::Divider
+}
+pub fn actions(elements: Vec<Element>) -> Block {
+    Block::Actions { elements }
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Section {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<Text>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fields: Vec<Text>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    accessory: Option<Element>,
+}
+pub fn section(text: Text) -> Section {
+    Section {
+        text: Some(text),
+        ..Default::default()
+    }
+}
+/// A section laid out in two columns instead of having one bit of text.
+pub fn fields(fields: Vec<Text>) -> Section {
+    Section {
+        fields,
+        ..Default::default()
+    }
+}
+impl Section {
+    pub fn accessory(mut self, accessory: impl Into<Element>) -> Self {
+        self.accessory = Some(accessory.into());
+        self
+    }
+}
+impl From<Section> for Block {
+    fn from(s: Section) -> Self {
+        Block::Section(s)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Input {
+    block_id: String,
+    label: Text,
+    element: Element,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    optional: bool,
+}
+pub fn input(block_id: &str, label: &str, element: impl Into<Element>) -> Block {
+    Block::Input(Input {
+        block_id: block_id.to_string(),
+        label: plain_text(label),
+        element: element.into(),
+        optional: false,
+    })
+}
+/// An input that can be left empty.
+pub fn optional_input(block_id: &str, label: &str, element: impl Into<Element>) -> Block {
+    Block::Input(Input {
+        block_id: block_id.to_string(),
+        label: plain_text(label),
+        element: element.into(),
+        optional: true,
+    })
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Image {
+    image_url: String,
+    alt_text: String,
+}
+pub fn image(image_url: String, alt_text: impl ToString) -> Image {
+    Image {
+        image_url,
+        alt_text: alt_text.to_string(),
+    }
+}
+impl From<Image> for Block {
+    fn from(i: Image) -> Self {
+        Block::Image(i)
+    }
+}
+
+/// The interactive bits that go in an actions block, an input, or next to a section.
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Element {
+    Button(Button),
+    Image(Image),
+    StaticSelect(StaticSelect),
+    MultiStaticSelect(MultiStaticSelect),
+    UsersSelect(UsersSelect),
+    PlainTextInput(TextInput),
+}
+impl From<Image> for Element {
+    fn from(i: Image) -> Self {
+        Element::Image(i)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Confirm {
+    title: Text,
+    text: Text,
+    confirm: Text,
+    deny: Text,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    style: Option<Style>,
+}
+/// A dialog making sure someone really meant to click something.
+pub fn confirm(title: &str, text: Text, confirm: &str, deny: &str) -> Confirm {
+    Confirm {
+        title: plain_text(title),
+        text,
+        confirm: plain_text(confirm),
+        deny: plain_text(deny),
+        style: None,
+    }
+}
+impl Confirm {
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = Some(style);
+        self
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Button {
+    text: Text,
+    action_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    style: Option<Style>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    confirm: Option<Confirm>,
+}
+pub fn button<S: ToString>(text: S, action_id: &str) -> Button {
+    Button {
+        text: plain_text(text),
+        action_id: action_id.to_string(),
+        value: None,
+        style: None,
+        confirm: None,
+    }
+}
+impl Button {
+    pub fn value(mut self, value: impl ToString) -> Self {
+        self.value = Some(value.to_string());
+        self
+    }
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = Some(style);
+        self
+    }
+    pub fn primary(self) -> Self {
+        self.style(Style::Primary)
+    }
+    pub fn confirm(mut self, confirm: Confirm) -> Self {
+        self.confirm = Some(confirm);
+        self
+    }
+}
+impl From<Button> for Element {
+    fn from(b: Button) -> Self {
+        Element::Button(b)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SelectOption {
+    text: Text,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<Text>,
+    value: String,
+}
+/// Errors if `value` is too long for Slack, because a clipped value couldn't be read back.
+pub fn select_option<S: ToString>(text: S, value: String) -> Result<SelectOption, String> {
+    if value.chars().count() > MAX_OPTION_LEN {
+        return Err(format!(
+            "option value is over {} chars: {}",
+            MAX_OPTION_LEN, value
+        ));
+    }
+    Ok(SelectOption {
+        text: plain_text(text).clipped(MAX_OPTION_LEN),
+        description: None,
+        value,
+    })
+}
+impl SelectOption {
+    pub fn description<S: ToString>(mut self, description: S) -> Self {
+        self.description = Some(plain_text(description).clipped(MAX_OPTION_LEN));
+        self
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct OptionGroup {
+    label: Text,
+    options: Vec<SelectOption>,
+}
+pub fn option_group<S: ToString>(label: S, options: Vec<SelectOption>) -> OptionGroup {
+    OptionGroup {
+        label: plain_text(label).clipped(MAX_OPTION_LEN),
+        options,
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct StaticSelect {
+    action_id: String,
+    placeholder: Text,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<Vec<SelectOption>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    option_groups: Option<Vec<OptionGroup>>,
+}
+pub fn static_select(
+    action_id: &str,
+    placeholder: &str,
+    options: Vec<SelectOption>,
+) -> StaticSelect {
+    StaticSelect {
+        action_id: action_id.to_string(),
+        placeholder: plain_text(placeholder),
+        options: Some(options),
+        option_groups: None,
+    }
+}
+pub fn grouped_select(
+    action_id: &str,
+    placeholder: &str,
+    option_groups: Vec<OptionGroup>,
+) -> StaticSelect {
+    StaticSelect {
+        action_id: action_id.to_string(),
+        placeholder: plain_text(placeholder),
+        options: None,
+        option_groups: Some(option_groups),
+    }
+}
+impl From<StaticSelect> for Element {
+    fn from(s: StaticSelect) -> Self {
+        Element::StaticSelect(s)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct MultiStaticSelect {
+    action_id: String,
+    placeholder: Text,
+    options: Vec<SelectOption>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    initial_options: Vec<SelectOption>,
+}
+pub fn multi_static_select(
+    action_id: &str,
+    placeholder: &str,
+    options: Vec<SelectOption>,
+) -> MultiStaticSelect {
+    MultiStaticSelect {
+        action_id: action_id.to_string(),
+        placeholder: plain_text(placeholder),
+        options,
+        initial_options: vec![],
+    }
+}
+impl MultiStaticSelect {
+    /// Which of the options start out selected; these have to be among the options too.
+    pub fn initial_options(mut self, initial_options: Vec<SelectOption>) -> Self {
+        self.initial_options = initial_options;
+        self
+    }
+}
+impl From<MultiStaticSelect> for Element {
+    fn from(s: MultiStaticSelect) -> Self {
+        Element::MultiStaticSelect(s)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct UsersSelect {
+    action_id: String,
+    placeholder: Text,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    initial_user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    confirm: Option<Confirm>,
+}
+pub fn users_select(action_id: &str, placeholder: &str) -> UsersSelect {
+    UsersSelect {
+        action_id: action_id.to_string(),
+        placeholder: plain_text(placeholder),
+        initial_user: None,
+        confirm: None,
+    }
+}
+impl UsersSelect {
+    pub fn initial_user(mut self, user: impl ToString) -> Self {
+        self.initial_user = Some(user.to_string());
+        self
+    }
+    pub fn confirm(mut self, confirm: Confirm) -> Self {
+        self.confirm = Some(confirm);
+        self
+    }
+}
+impl From<UsersSelect> for Element {
+    fn from(s: UsersSelect) -> Self {
+        Element::UsersSelect(s)
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct TextInput {
+    action_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    placeholder: Option<Text>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    initial_value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_length: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_length: Option<usize>,
+}
+pub fn text_input(action_id: &str) -> TextInput {
+    TextInput {
+        action_id: action_id.to_string(),
+        ..Default::default()
+    }
+}
+impl TextInput {
+    pub fn placeholder(mut self, placeholder: &str) -> Self {
+        self.placeholder = Some(plain_text(placeholder));
+        self
+    }
+    pub fn initial_value(mut self, value: impl ToString) -> Self {
+        self.initial_value = Some(value.to_string());
+        self
+    }
+    pub fn length(mut self, min: usize, max: usize) -> Self {
+        self.min_length = Some(min);
+        self.max_length = Some(max);
+        self
+    }
+}
+impl From<TextInput> for Element {
+    fn from(t: TextInput) -> Self {
+        Element::PlainTextInput(t)
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ViewKind {
+    Modal,
+    Home,
+}
+
+/// A modal or a home tab.
+#[derive(Serialize, Debug, Clone)]
+pub struct View {
+    #[serde(rename = "type")]
+    kind: ViewKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<Text>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    submit: Option<Text>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    callback_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    private_metadata: Option<String>,
+    blocks: Vec<Block>,
+}
+impl View {
+    fn new(kind: ViewKind, mut blocks: Vec<Block>) -> Self {
+        if blocks.len() > MAX_VIEW_BLOCKS {
+            warn!(
+                "{} blocks won't fit in a view, dropping {}",
+                blocks.len(),
+                blocks.len() - MAX_VIEW_BLOCKS + 1
+            );
+            blocks.truncate(MAX_VIEW_BLOCKS - 1);
+            blocks.push(comment("_... and more than fits here._"));
+        }
+        Self {
+            kind,
+            title: None,
+            submit: None,
+            callback_id: None,
+            private_metadata: None,
+            blocks,
+        }
+    }
+
+    pub fn home(blocks: Vec<Block>) -> Self {
+        Self::new(ViewKind::Home, blocks)
+    }
+
+    pub fn modal<S: ToString>(title: S, callback_id: String, blocks: Vec<Block>) -> Self {
+        Self {
+            title: Some(plain_text(title).clipped(MAX_TITLE_LEN)),
+            callback_id: Some(callback_id),
+            private_metadata: Some(String::new()),
+            ..Self::new(ViewKind::Modal, blocks)
+        }
+    }
+
+    pub fn private_metadata(mut self, private_metadata: String) -> Self {
+        self.private_metadata = Some(private_metadata);
+        self
+    }
+
+    pub fn submit(mut self, submit: Option<String>) -> Self {
+        self.submit = submit.map(|s| plain_text(s).clipped(MAX_TITLE_LEN));
+        self
+    }
+}
+
+/// Checks that `blocks` still come out as the JSON kept in `snapshots/blocks/{name}.json`.
+///
+/// The first time a snapshot is checked, or whenever `UPDATE_SNAPSHOTS` is set, what came out
+/// is kept instead, so that a change to a screen shows up as a change to its snapshot. In CI,
+/// a missing snapshot is a failure, since nobody would ever see it being kept.
+#[cfg(test)]
+pub fn assert_snapshot(name: &str, blocks: &serde_json::Value) {
+    use std::{env::var, fs, path::Path};
+
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("snapshots/blocks")
+        .join(format!("{}.json", name));
+    let json = serde_json::to_string_pretty(blocks).unwrap() + "\n";
+
+    match fs::read_to_string(&path) {
+        Ok(kept) if var("UPDATE_SNAPSHOTS").is_err() => assert!(
+            kept == json,
+            "{} doesn't match its snapshot; run with UPDATE_SNAPSHOTS=1 if that's on purpose.\n\
+             kept:\n{}\nnow:\n{}",
+            name,
+            kept,
+            json
+        ),
+        Err(_) if var("CI").is_ok() => panic!("no snapshot for {} at {}", name, path.display()),
+        _ => {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, json).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::{json, to_value};
+
+    fn text_of(t: &Text) -> &str {
+        match t {
+            Text::Mrkdwn { text } | Text::PlainText { text } => text,
+        }
+    }
+
+    #[test]
+    fn text_up_to_the_limit_is_left_alone() {
+        let txt = "a".repeat(MAX_TEXT_LEN);
+        assert_eq!(text_of(&mrkdwn(&txt)), txt);
+        assert_eq!(text_of(&plain_text(&txt)), txt);
+    }
+
+    #[test]
+    fn long_text_is_clipped() {
+        let txt = "a".repeat(MAX_TEXT_LEN + 1);
+        for t in &[mrkdwn(&txt), plain_text(&txt)] {
+            let clipped = text_of(t);
+            assert_eq!(clipped.chars().count(), MAX_TEXT_LEN);
+            assert!(clipped.ends_with("..."));
+        }
+    }
+
+    #[test]
+    fn text_is_counted_in_chars_not_bytes() {
+        let txt = "\u{1f331}".repeat(MAX_TEXT_LEN);
+        assert_eq!(text_of(&mrkdwn(&txt)), txt);
+    }
+
+    #[test]
+    fn titles_are_clipped() {
+        let fits = "a".repeat(MAX_TITLE_LEN);
+        let view = View::modal(&fits, "cb".to_string(), vec![]).submit(Some(fits.clone()));
+        assert_eq!(view.title.as_ref().map(text_of), Some(fits.as_str()));
+        assert_eq!(view.submit.as_ref().map(text_of), Some(fits.as_str()));
+
+        let long = "a".repeat(MAX_TITLE_LEN + 1);
+        let view = View::modal(&long, "cb".to_string(), vec![]).submit(Some(long));
+        for t in view.title.iter().chain(view.submit.iter()) {
+            assert_eq!(text_of(t).chars().count(), MAX_TITLE_LEN);
+            assert!(text_of(t).ends_with("..."));
+        }
+    }
+
+    #[test]
+    fn option_text_is_clipped() {
+        let long = "a".repeat(MAX_OPTION_LEN + 1);
+        let option = select_option(&long, "value".to_string())
+            .unwrap()
+            .description(&long);
+        assert_eq!(text_of(&option.text).chars().count(), MAX_OPTION_LEN);
+        assert_eq!(
+            option
+                .description
+                .as_ref()
+                .map(|d| text_of(d).chars().count()),
+            Some(MAX_OPTION_LEN)
+        );
+        assert_eq!(
+            text_of(&option_group(&long, vec![option]).label)
+                .chars()
+                .count(),
+            MAX_OPTION_LEN
+        );
+    }
+
+    #[test]
+    fn long_option_values_are_refused() {
+        assert!(select_option("thing", "a".repeat(MAX_OPTION_LEN)).is_ok());
+        assert!(select_option("thing", "a".repeat(MAX_OPTION_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn views_up_to_the_limit_are_left_alone() {
+        let view = View::home(vec![divider(); MAX_VIEW_BLOCKS]);
+        assert_eq!(view.blocks.len(), MAX_VIEW_BLOCKS);
+        assert!(view.blocks.iter().all(|b| matches!(b, Block::Divider)));
+    }
+
+    #[test]
+    fn views_with_too_many_blocks_say_so() {
+        let view = View::modal("too much", "cb".to_string(), vec![divider(); 150]);
+        assert_eq!(view.blocks.len(), MAX_VIEW_BLOCKS);
+        assert_eq!(
+            to_value(view.blocks.last().unwrap()).unwrap(),
+            json!({
+                "type": "context",
+                "elements": [{ "type": "mrkdwn", "text": "_... and more than fits here._" }],
+            })
+        );
+    }
+
+    #[test]
+    fn blocks_are_serialized_the_way_slack_wants_them() {
+        let block: Block = section(mrkdwn("hi"))
+            .accessory(button("Go", "go").primary().value("there"))
+            .into();
+        assert_eq!(
+            to_value(&block).unwrap(),
+            json!({
+                "type": "section",
+                "text": { "type": "mrkdwn", "text": "hi" },
+                "accessory": {
+                    "type": "button",
+                    "text": { "type": "plain_text", "text": "Go" },
+                    "action_id": "go",
+                    "style": "primary",
+                    "value": "there",
+                },
+            })
+        );
+    }
+}
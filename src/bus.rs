@@ -0,0 +1,116 @@
+//! Things that happen in the game, as they happen, for anything that wants to hear about them.
+//!
+//! Whoever makes something happen publishes a `GameEvent` once it's saved, and every
+//! subscriber gets a copy. Nothing is kept around for subscribers that show up later,
+//! and a subscriber that falls more than `CAPACITY` events behind misses the oldest ones.
+use log::*;
+use rocket::tokio::sync::broadcast::{self, Receiver, Sender};
+use serde::Serialize;
+use std::sync::Arc;
+
+/// How many events are held onto for subscribers that haven't gotten to them yet.
+pub const CAPACITY: usize = 1024;
+
+lazy_static::lazy_static! {
+    static ref EVENTS: Sender<Arc<GameEvent>> = broadcast::channel(CAPACITY).0;
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GameEvent {
+    /// A plant produced a crop.
+    Yield {
+        steader: String,
+        tile: uuid::Uuid,
+        plant: String,
+        items: Vec<String>,
+        xp: u64,
+    },
+    CraftFinished {
+        steader: String,
+        tile: uuid::Uuid,
+        plant: String,
+        recipe: String,
+        items: Vec<String>,
+        xp: u64,
+    },
+    PlantLevelUp {
+        steader: String,
+        tile: uuid::Uuid,
+        plant: String,
+        title: String,
+    },
+    HacksteadLevelUp {
+        steader: String,
+        title: String,
+    },
+    Hatch {
+        steader: String,
+        egg: String,
+        items: Vec<String>,
+        percentile: f32,
+    },
+    /// Something went up for sale on the hackmarket.
+    Listing {
+        seller: String,
+        item: String,
+        price: u64,
+    },
+    /// Something was bought on the hackmarket, either off of it or through a buy order.
+    Sale {
+        buyer: String,
+        seller: String,
+        item: String,
+        price: u64,
+    },
+    /// Someone took the lead in an auction.
+    Bid {
+        bidder: String,
+        seller: String,
+        item: String,
+        amount: u64,
+    },
+    /// A gotchi collected some HN for its steader.
+    Harvest {
+        steader: String,
+        gotchi: String,
+        amount: u64,
+    },
+}
+impl GameEvent {
+    /// Everyone this happened to.
+    pub fn steaders(&self) -> Vec<&str> {
+        use GameEvent::*;
+        match self {
+            Yield { steader, .. }
+            | CraftFinished { steader, .. }
+            | PlantLevelUp { steader, .. }
+            | HacksteadLevelUp { steader, .. }
+            | Hatch { steader, .. }
+            | Harvest { steader, .. } => vec![steader],
+            Listing { seller, .. } => vec![seller],
+            Sale { buyer, seller, .. } => vec![buyer, seller],
+            Bid { bidder, seller, .. } => vec![bidder, seller],
+        }
+    }
+
+    /// Whether this happened on the hackmarket, where anyone can see it.
+    pub fn is_market(&self) -> bool {
+        match self {
+            GameEvent::Listing { .. } | GameEvent::Sale { .. } | GameEvent::Bid { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+/// Tells every subscriber about something that happened.
+pub fn publish(event: GameEvent) {
+    debug!("publishing {:?}", event);
+    // it's only an error if nobody's subscribed, which is fine
+    let _ = EVENTS.send(Arc::new(event));
+}
+
+/// Everything published from now on. Dropping the receiver unsubscribes.
+pub fn subscribe() -> Receiver<Arc<GameEvent>> {
+    EVENTS.subscribe()
+}
@@ -0,0 +1,99 @@
+//! Everything the game says to people goes through a `ChatFrontend`, so that the
+//! game doesn't have to know it's talking to Slack.
+//!
+//! Which one is used is picked with the `CHAT` environment variable: `slack` (the default)
+//! or `recording`, which sends nothing and just keeps track of what it was asked to send.
+//! Tests can also have a task record what it sends on its own, with `recorded`.
+use crate::blocks::{Block, View};
+
+mod recording;
+mod slack;
+
+pub use recording::{RecordingFrontend, Sent};
+pub use slack::SlackFrontend;
+
+use std::env::var;
+lazy_static::lazy_static! {
+    static ref RECORDING: RecordingFrontend = RecordingFrontend::default();
+    static ref CHAT: &'static dyn ChatFrontend = match var("CHAT").as_ref().map(|s| s.as_str()) {
+        Ok("recording") => &*RECORDING,
+        Ok("slack") | Err(_) => Box::leak(Box::new(SlackFrontend::new())),
+        Ok(other) => panic!("unknown CHAT: {}", other),
+    };
+}
+
+rocket::tokio::task_local! {
+    /// Where a task that's being `recorded` sends everything instead.
+    static TASK_RECORDING: &'static RecordingFrontend;
+}
+
+/// The frontend picked by the environment for this process,
+/// unless this task is being `recorded`.
+pub fn chat() -> &'static dyn ChatFrontend {
+    TASK_RECORDING
+        .try_with(|r| *r as &'static dyn ChatFrontend)
+        .unwrap_or(*CHAT)
+}
+
+/// Everything sent so far, if this task or process is only pretending to send things.
+pub fn recording() -> Option<&'static RecordingFrontend> {
+    if let Ok(r) = TASK_RECORDING.try_with(|r| *r) {
+        return Some(r);
+    }
+    match var("CHAT").as_ref().map(|s| s.as_str()) {
+        Ok("recording") => Some(&*RECORDING),
+        _ => None,
+    }
+}
+
+/// Runs `f` with a recording frontend of its own, handing back what it sent along
+/// with what it returned, so that tests running side by side don't see each other's messages.
+#[cfg(test)]
+pub async fn recorded<F: std::future::Future>(f: F) -> (F::Output, Vec<Sent>) {
+    let recording: &'static RecordingFrontend = Box::leak(Box::new(RecordingFrontend::default()));
+    let output = TASK_RECORDING.scope(recording, f).await;
+    (output, recording.take())
+}
+
+/// How the frontend we're on would point at a user in a message.
+pub fn mention(user_id: &str) -> String {
+    chat().mention(user_id)
+}
+
+/// The channels the game keeps people up to date in.
+#[derive(serde::Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum Channel {
+    /// Where everything that happens on the hackmarket is posted.
+    Market,
+    /// Where the banker talks about money, and admins get their answers.
+    Banker,
+}
+
+#[rocket::async_trait]
+pub trait ChatFrontend: Send + Sync {
+    async fn dm(&self, user_id: &str, notif_msg: String, blocks: Vec<Block>) -> Result<(), String>;
+
+    /// Posts to one of the game's channels. Without any blocks, only `notif_msg` is shown.
+    async fn log(
+        &self,
+        channel: Channel,
+        notif_msg: String,
+        blocks: Vec<Block>,
+    ) -> Result<(), String>;
+
+    async fn publish_home(&self, user_id: &str, view: View) -> Result<(), String>;
+
+    /// Opens a modal, or if `push` is set, puts it on top of the one that's already open.
+    async fn open_modal(&self, trigger_id: &str, view: View, push: bool) -> Result<(), String>;
+
+    /// Replaces a modal that's already open. If `hash` is given and the modal has changed
+    /// since, nothing happens.
+    async fn update_modal(
+        &self,
+        view_id: &str,
+        hash: Option<String>,
+        view: View,
+    ) -> Result<(), String>;
+
+    fn mention(&self, user_id: &str) -> String;
+}
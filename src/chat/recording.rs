@@ -0,0 +1,112 @@
+//! Doesn't send anything anywhere, just writes down what it was asked to send,
+//! so tests can check exactly what a farm cycle, hatch or sale would've said.
+use super::{Channel, ChatFrontend};
+use crate::blocks::{Block, View};
+use serde::Serialize;
+use std::sync::Mutex;
+
+/// Something the game tried to send.
+#[derive(Serialize, Debug, Clone)]
+pub enum Sent {
+    Dm {
+        user_id: String,
+        notif_msg: String,
+        blocks: Vec<Block>,
+    },
+    Log {
+        channel: Channel,
+        notif_msg: String,
+        blocks: Vec<Block>,
+    },
+    Home {
+        user_id: String,
+        view: View,
+    },
+    Modal {
+        trigger_id: String,
+        view: View,
+        push: bool,
+    },
+    ModalUpdate {
+        view_id: String,
+        hash: Option<String>,
+        view: View,
+    },
+}
+
+#[derive(Default)]
+pub struct RecordingFrontend {
+    sent: Mutex<Vec<Sent>>,
+}
+impl RecordingFrontend {
+    /// Everything sent so far, oldest first.
+    pub fn sent(&self) -> Vec<Sent> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    /// Everything sent so far, forgetting about it so the next check starts fresh.
+    pub fn take(&self) -> Vec<Sent> {
+        std::mem::take(&mut *self.sent.lock().unwrap())
+    }
+
+    fn send(&self, sent: Sent) -> Result<(), String> {
+        self.sent.lock().unwrap().push(sent);
+        Ok(())
+    }
+}
+
+#[rocket::async_trait]
+impl ChatFrontend for RecordingFrontend {
+    async fn dm(&self, user_id: &str, notif_msg: String, blocks: Vec<Block>) -> Result<(), String> {
+        self.send(Sent::Dm {
+            user_id: user_id.to_string(),
+            notif_msg,
+            blocks,
+        })
+    }
+
+    async fn log(
+        &self,
+        channel: Channel,
+        notif_msg: String,
+        blocks: Vec<Block>,
+    ) -> Result<(), String> {
+        self.send(Sent::Log {
+            channel,
+            notif_msg,
+            blocks,
+        })
+    }
+
+    async fn publish_home(&self, user_id: &str, view: View) -> Result<(), String> {
+        self.send(Sent::Home {
+            user_id: user_id.to_string(),
+            view,
+        })
+    }
+
+    async fn open_modal(&self, trigger_id: &str, view: View, push: bool) -> Result<(), String> {
+        self.send(Sent::Modal {
+            trigger_id: trigger_id.to_string(),
+            view,
+            push,
+        })
+    }
+
+    async fn update_modal(
+        &self,
+        view_id: &str,
+        hash: Option<String>,
+        view: View,
+    ) -> Result<(), String> {
+        self.send(Sent::ModalUpdate {
+            view_id: view_id.to_string(),
+            hash,
+            view,
+        })
+    }
+
+    fn mention(&self, user_id: &str) -> String {
+        format!("@{}", user_id)
+    }
+}
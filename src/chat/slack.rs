@@ -0,0 +1,132 @@
+//! Talks to Slack's Web API with the bot's `TOKEN`.
+use super::{Channel, ChatFrontend};
+use crate::blocks::{Block, View};
+use log::*;
+use serde_json::{json, Value};
+
+use std::env::var;
+lazy_static::lazy_static! {
+    pub static ref HACKMARKET_LOG_CHAT: String = var("HACKMARKET_LOG_CHAT").unwrap();
+    pub static ref BANKER_CHAT: String = var("BANKER_CHAT").unwrap();
+}
+
+pub struct SlackFrontend {
+    client: reqwest::Client,
+    token: String,
+}
+impl SlackFrontend {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            token: var("TOKEN").unwrap(),
+        }
+    }
+
+    async fn call(&self, method: &str, o: Value) -> Result<(), String> {
+        debug!("{}: {}", method, serde_json::to_string_pretty(&o).unwrap());
+
+        // TODO: use response
+        self.client
+            .post(&format!("https://slack.com/api/{}", method))
+            .bearer_auth(&self.token)
+            .json(&o)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+
+    async fn post_message(
+        &self,
+        channel: &str,
+        notif_msg: String,
+        blocks: Vec<Block>,
+    ) -> Result<(), String> {
+        let mut o = json!({
+            "channel": channel,
+            "text": notif_msg,
+        });
+        // Slack won't show a message with an empty list of blocks
+        if !blocks.is_empty() {
+            o.as_object_mut()
+                .unwrap()
+                .insert("blocks".to_string(), json!(blocks));
+        }
+
+        self.call("chat.postMessage", o).await
+    }
+}
+
+#[rocket::async_trait]
+impl ChatFrontend for SlackFrontend {
+    async fn dm(&self, user_id: &str, notif_msg: String, blocks: Vec<Block>) -> Result<(), String> {
+        self.post_message(user_id, notif_msg, blocks)
+            .await
+            .map_err(|e| format!("couldn't dm {}: {}", user_id, e))
+    }
+
+    async fn log(
+        &self,
+        channel: Channel,
+        notif_msg: String,
+        blocks: Vec<Block>,
+    ) -> Result<(), String> {
+        let id = match channel {
+            Channel::Market => &*HACKMARKET_LOG_CHAT,
+            Channel::Banker => &*BANKER_CHAT,
+        };
+        self.post_message(id, notif_msg, blocks)
+            .await
+            .map_err(|e| format!("couldn't log to {:?}: {}", channel, e))
+    }
+
+    async fn publish_home(&self, user_id: &str, view: View) -> Result<(), String> {
+        self.call(
+            "views.publish",
+            json!({
+                "user_id": user_id,
+                "view": view,
+            }),
+        )
+        .await
+        .map_err(|e| format!("couldn't publish home tab view: {}", e))
+    }
+
+    async fn open_modal(&self, trigger_id: &str, view: View, push: bool) -> Result<(), String> {
+        self.call(
+            if push { "views.push" } else { "views.open" },
+            json!({
+                "trigger_id": trigger_id,
+                "view": view,
+            }),
+        )
+        .await
+        .map_err(|e| format!("couldn't open modal: {}", e))
+    }
+
+    async fn update_modal(
+        &self,
+        view_id: &str,
+        hash: Option<String>,
+        view: View,
+    ) -> Result<(), String> {
+        let mut o = json!({
+            "view_id": view_id,
+            "view": view,
+        });
+        if let Some(hash) = hash {
+            o.as_object_mut()
+                .unwrap()
+                .insert("hash".to_string(), json!(hash));
+        }
+
+        self.call("views.update", o)
+            .await
+            .map_err(|e| format!("couldn't update modal: {}", e))
+    }
+
+    fn mention(&self, user_id: &str) -> String {
+        format!("<@{}>", user_id)
+    }
+}
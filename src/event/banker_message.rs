@@ -4,32 +4,12 @@ use super::BankerMessageTrigger;
 pub async fn banker_balance_trigger<'a>(balance: &u64) -> Result<(), String> {
     info!("I got {} problems and HN ain't one", balance);
 
-    let query = dyn_db()
-        .query(rusoto_dynamodb::QueryInput {
-            table_name: hcor::TABLE_NAME.to_string(),
-            key_condition_expression: Some("cat = :gotchi_cat".to_string()),
-            expression_attribute_values: Some({
-                [(":gotchi_cat".to_string(), Category::Gotchi.into_av())]
-                    .iter()
-                    .cloned()
-                    .collect()
-            }),
-            ..Default::default()
-        })
-        .await;
-
-    let gotchis = query
+    let gotchis = store()
+        .possessions(Category::Gotchi)
+        .await
         .map_err(|e| format!("couldn't query all gotchis: {}", e))?
-        .items
-        .ok_or("no gotchis found!")?
-        .iter()
-        .filter_map(|i| match Possession::from_item(i) {
-            Ok(p) => Some(Possessed::<Gotchi>::from_possession(p).unwrap()),
-            Err(e) => {
-                error!("error parsing gotchi: {}", e);
-                None
-            }
-        })
+        .into_iter()
+        .filter_map(|p| Possessed::<Gotchi>::from_possession(p))
         .filter(|g| g.inner.base_happiness > 0)
         .collect::<Vec<Possessed<Gotchi>>>();
 
@@ -37,70 +17,56 @@ pub async fn banker_balance_trigger<'a>(balance: &u64) -> Result<(), String> {
     let mut funds_awarded = 0;
 
     for _ in 0..balance / total_happiness {
-        stream::iter(gotchis.clone()).map(|x| Ok(x)).try_for_each_concurrent(None, |gotchi| {
+        stream::iter(gotchis.clone())
+            .map(|x| Ok(x))
+            .try_for_each_concurrent(None, |gotchi| {
                 let dm = vec![
-                    json!({
-                        "type": "section",
-                        "text": mrkdwn(format!(
-                            "_It's free HN time, ladies and gentlegotchis!_\n\n\
-                            It seems your lovely Gotchi *{}* has collected *{} HN* for you!",
-                            gotchi.inner.nickname,
-                            gotchi.inner.base_happiness
-                        )),
-                        "accessory": {
-                            "type": "image",
-                            "image_url": format!("http://{}/gotchi/img/{}/{}.png", *URL, Category::Gotchi, filify(&gotchi.name)),
-                            "alt_text": "Hackpheus holding a Gift!",
-                        }
-                    }),
-                    comment("IN HACK WE STEAD")
+                    section(mrkdwn(format!(
+                        "_It's free HN time, ladies and gentlegotchis!_\n\n\
+                        It seems your lovely Gotchi *{}* has collected *{} HN* for you!",
+                        gotchi.inner.nickname, gotchi.inner.base_happiness
+                    )))
+                    .accessory(image(
+                        format!(
+                            "http://{}/gotchi/img/{}/{}.png",
+                            *URL,
+                            Category::Gotchi,
+                            filify(&gotchi.name)
+                        ),
+                        "Hackpheus holding a Gift!",
+                    ))
+                    .into(),
+                    comment("IN HACK WE STEAD"),
                 ];
                 let payment_note = format!(
                     "{} collected {} HN for you",
-                    gotchi.inner.nickname,
-                    gotchi.inner.base_happiness,
+                    gotchi.inner.nickname, gotchi.inner.base_happiness,
                 );
-                let steader_in_harvest_log: bool = gotchi.inner.harvest_log.last().filter(|x| x.id == gotchi.steader).is_some();
-                let db_update = rusoto_dynamodb::UpdateItemInput {
-                    table_name: hcor::TABLE_NAME.to_string(),
-                    key: gotchi.clone().into_possession().key().into_item(),
-                    update_expression: Some(if steader_in_harvest_log {
-                        format!("ADD harvest_log[{}].harvested :harv", gotchi.inner.harvest_log.len() - 1)
-                    } else {
-                        "SET harvest_log = list_append(harvest_log, :harv)".to_string()
-                    }),
-                    expression_attribute_values: Some(
-                        [(
-                            ":harv".to_string(),
-                            if steader_in_harvest_log {
-                                AttributeValue {
-                                    n: Some(gotchi.inner.base_happiness.to_string()),
-                                    ..Default::default()
-                                }
-                            } else {
-                                AttributeValue {
-                                    l: Some(vec![possess::gotchi::GotchiHarvestOwner {
-                                        id: gotchi.steader.clone(),
-                                        harvested: gotchi.inner.base_happiness,
-                                    }.into()]),
-                                    ..Default::default()
-                                }
-                            }
-                        )]
-                        .iter()
-                        .cloned()
-                        .collect(),
-                    ),
-                    ..Default::default()
-                };
-                let db = dyn_db();
-
                 async move {
                     futures::try_join!(
-                        dm_blocks(gotchi.steader.clone(), "It's HN Time! Your Gotchi produced some HN for you...".to_string(), dm),
-                        banker::pay(gotchi.steader.clone(), gotchi.inner.base_happiness, payment_note),
-                        db.update_item(db_update).map_err(|e| format!("Couldn't update owner log: {}", e))
+                        dm_blocks(
+                            gotchi.steader.clone(),
+                            "It's HN Time! Your Gotchi produced some HN for you...".to_string(),
+                            dm
+                        ),
+                        banker::pay(
+                            gotchi.steader.clone(),
+                            gotchi.inner.base_happiness,
+                            payment_note
+                        ),
+                        store()
+                            .record_harvest(
+                                gotchi.clone().into_possession().key(),
+                                gotchi.steader.clone(),
+                                gotchi.inner.base_happiness
+                            )
+                            .map_err(|e| format!("Couldn't update harvest log: {}", e))
                     )?;
+                    bus::publish(GameEvent::Harvest {
+                        steader: gotchi.steader.clone(),
+                        gotchi: gotchi.inner.nickname.clone(),
+                        amount: gotchi.inner.base_happiness,
+                    });
                     Ok(())
                 }
             })
@@ -1,397 +1,389 @@
 use super::prelude::*;
-use super::InvoicePaymentTrigger;
+use crate::records;
+use crate::store::Transfer;
+use banker::{InvoiceIntent, PendingInvoice};
 
 use super::banker_message::banker_balance_trigger;
 
-pub struct Sale {
-    name: String,
-    price: u64,
-    id: uuid::Uuid,
-    category: Category,
-    from: Option<String>,
-}
-impl Sale {
-    fn from_captures(captures: &regex::Captures) -> Result<Self, String> {
-        Ok(Sale {
-            name: captures
-                .get(1)
-                .ok_or_else(|| "no name in sale".to_string())?
-                .as_str()
-                .to_string(),
-            price: captures
-                .get(2)
-                .ok_or_else(|| "no price in sale".to_string())?
-                .as_str()
-                .parse()
-                .map_err(|e| format!("sale price number parsing: {}", e))?,
-            id: uuid::Uuid::parse_str(
-                captures
-                    .get(3)
-                    .ok_or_else(|| "no id in sale".to_string())?
-                    .as_str(),
+/// Acts on a paid invoice according to what it was recorded to be for
+/// when it was sent out.
+pub async fn invoice_paid(
+    pending: PendingInvoice,
+    paid_invoice: banker::PaidInvoice,
+) -> Result<(), String> {
+    if pending.invoicee != paid_invoice.invoicee || pending.amount != paid_invoice.amount {
+        return Err(format!(
+            "invoice {} was for {}hn from {}, but {}hn came from {}",
+            pending.transaction_id,
+            pending.amount,
+            mention(&pending.invoicee),
+            paid_invoice.amount,
+            mention(&paid_invoice.invoicee)
+        ));
+    }
+
+    match pending.intent {
+        InvoiceIntent::MarketFee {
+            key,
+            price,
+            market_name,
+            duration,
+        } => hackmarket_fees(key, price, market_name, duration, paid_invoice).await,
+        InvoiceIntent::BatchMarketFee {
+            keys,
+            price,
+            market_name,
+            duration,
+        } => {
+            market::list_batch(
+                store(),
+                paid_invoice.invoicee,
+                keys,
+                price,
+                market_name,
+                duration,
             )
-            .map_err(|e| format!("invalid uuid in sale: {}", e))?,
-            category: captures
-                .get(4)
-                .ok_or_else(|| "no category in sale".to_string())?
-                .as_str()
-                .parse::<u8>()
-                .map_err(|e| format!("err parsing id number in sale: {}", e))?
-                .try_into()
-                .map_err(|e| format!("couldn't turn category number into id in sale: {}", e))?,
-            from: captures.get(5).map(|x| x.as_str().to_string()),
-        })
+            .await
+        }
+        InvoiceIntent::BatchPurchase {
+            archetype_handle,
+            items,
+        } => market::buy_batch(store(), paid_invoice.invoicee, archetype_handle, items).await,
+        InvoiceIntent::Purchase { key, price, seller } => {
+            hackmarket_purchase(key, price, seller, paid_invoice).await
+        }
+        InvoiceIntent::BuyOrder {
+            archetype_handle,
+            max_price,
+            quantity,
+        } => {
+            market::open_buy_order(
+                store(),
+                market::BuyOrder {
+                    id: uuid::Uuid::new_v4(),
+                    buyer: paid_invoice.invoicee,
+                    archetype_handle,
+                    max_price,
+                    quantity,
+                    placed: std::time::SystemTime::now(),
+                },
+            )
+            .await
+        }
+        InvoiceIntent::AuctionFee { key, terms } => {
+            auction::open(store(), paid_invoice.invoicee, key, terms).await
+        }
+        InvoiceIntent::AuctionBid { auction, amount } => {
+            auction::bid(store(), auction, paid_invoice.invoicee, amount).await
+        }
+        InvoiceIntent::TradeEscrow { offer, amount } => {
+            trade::escrow_paid(store(), offer, paid_invoice.invoicee, amount).await
+        }
+        InvoiceIntent::HacksteadSignup => start_hackstead_invoice_payment(paid_invoice).await,
     }
 }
 
-lazy_static::lazy_static! {
-    pub static ref HACKMARKET_FEES: InvoicePaymentTrigger = InvoicePaymentTrigger {
-        regex: Regex::new("hackmarket fees for selling (.+) at ([0-9]+)hn :(.+):([0-9])").unwrap(),
-        then: &hackmarket_fees
-    };
-}
-fn hackmarket_fees<'a>(
-    c: regex::Captures<'a>,
+async fn hackmarket_fees(
+    key: Key,
+    price: u64,
+    name: String,
+    duration: Option<std::time::Duration>,
     paid_invoice: banker::PaidInvoice,
-) -> HandlerOutput<'a> {
-    async move {
-        let Sale {
-            name,
-            price,
-            id,
-            category,
-            ..
-        } = Sale::from_captures(&c)?;
-
-        let db = dyn_db();
-        let key = Key { category, id };
-        let possession = hacksteader::get_possession(&db, key).await?;
-        match possession.sale {
-            None => futures::try_join!(
-                market::place_on_market(&db, key, price, name.clone()),
-                market::log_blocks(
-                    format!(
-                        "A {} has gone up for sale for {} HN!",
-                        possession.name, price
-                    )
-                    .to_string(),
-                    vec![
-                        json!({
-                            "type": "section",
-                            "text": mrkdwn(format!(
-                                "A *{}* has gone up for sale! \
-                                <@{}> is selling it on the hackmarket for *{} HN*!",
-                                possession.name, paid_invoice.invoicee, price
-                            )),
-                            "accessory": {
-                                "type": "image",
-                                "image_url": format!(
-                                    "http://{}/gotchi/img/{}/{}.png",
-                                    *URL,
-                                    category,
-                                    filify(&possession.name)
-                                ),
-                                "alt_text": "Hackpheus sitting on bags of money!",
-                            }
-                        }),
-                        comment("QWIK U BETTR BYE ET B4 SUM1 EYLS"),
-                    ]
-                ),
-            )
-            .map(|_| ()),
-            Some(_) => futures::try_join!(
-                banker::pay(
-                    possession.steader.clone(),
-                    price / 20,
-                    format!("the {} you tried to sell is already up for sale", name),
-                ),
-                dm_blocks(
-                    paid_invoice.invoicee.clone(),
-                    "Sale failed! Your market fee has been refunded.".to_string(),
-                    vec![json!({
-                        "type": "section",
-                        "text": mrkdwn(format!(
-                            concat!(
-                                "The {} you tried to sell for {}hn is no longer on the market{}"
-                            ),
-                            name,
-                            price,
-                            if price >= 20 {
-                                format!(", so your {}hn market fee has been refunded.", price/20)
-                            } else {
-                                ".".to_string()
-                            }
-                        ))
-                    })]
+) -> Result<(), String> {
+    let category = key.category;
+    let db = store();
+    let possession = hacksteader::get_possession(db, key).await?;
+    // something that's up for auction can't also go up for sale
+    let free = possession.sale.is_none() && auction::of_possession(db, key).await?.is_none();
+    // if someone's already looking to buy it, it goes straight to them
+    let filled = free && market::fill_buy_order(db, &possession, price).await?;
+    let listed = free && !filled;
+    let item = possession.name.clone();
+    match possession.sale {
+        _ if filled => Ok(()),
+        None if free => futures::try_join!(
+            market::place_on_market(
+                db,
+                key,
+                paid_invoice.invoicee.clone(),
+                price,
+                name.clone(),
+                duration
+            ),
+            market::log_blocks(
+                format!(
+                    "A {} has gone up for sale for {} HN!",
+                    possession.name, price
                 )
+                .to_string(),
+                vec![
+                    section(mrkdwn(format!(
+                        "A *{}* has gone up for sale! \
+                            {} is selling it on the hackmarket for *{} HN*!",
+                        possession.name,
+                        mention(&paid_invoice.invoicee),
+                        price
+                    )))
+                    .accessory(image(
+                        format!(
+                            "http://{}/gotchi/img/{}/{}.png",
+                            *URL,
+                            category,
+                            filify(&possession.name)
+                        ),
+                        "Hackpheus sitting on bags of money!"
+                    ))
+                    .into(),
+                    comment("QWIK U BETTR BYE ET B4 SUM1 EYLS"),
+                ]
+            ),
+        )
+        .map(|_| ()),
+        _ => futures::try_join!(
+            banker::pay(
+                possession.steader.clone(),
+                price / 20,
+                format!("the {} you tried to sell is already up for sale", name),
+            ),
+            dm_blocks(
+                paid_invoice.invoicee.clone(),
+                "Sale failed! Your market fee has been refunded.".to_string(),
+                vec![section(mrkdwn(format!(
+                    concat!("The {} you tried to sell for {}hn is no longer on the market{}"),
+                    name,
+                    price,
+                    if price >= 20 {
+                        format!(", so your {}hn market fee has been refunded.", price / 20)
+                    } else {
+                        ".".to_string()
+                    }
+                )))
+                .into()]
             )
-            .map(|_| ()),
-        }?;
+        )
+        .map(|_| ()),
+    }?;
 
-        let balance = banker::get_balance().await.expect("error getting balance");
+    if listed {
+        bus::publish(GameEvent::Listing {
+            seller: paid_invoice.invoicee.clone(),
+            item,
+            price,
+        });
+    }
 
-        banker_balance_trigger(&balance)
-            .await
-            .expect("error in balance trigger");
+    let balance = banker::get_balance().await.expect("error getting balance");
 
-        Ok(())
-    }
-    .boxed()
-}
+    banker_balance_trigger(&balance)
+        .await
+        .expect("error in balance trigger");
 
-lazy_static::lazy_static! {
-    pub static ref HACKMARKET_PURCHASE: InvoicePaymentTrigger = InvoicePaymentTrigger {
-        regex: Regex::new("hackmarket purchase buying (.+) at ([0-9]+)hn :(.+):([0-9]) from <@([A-z|0-9]+)>").unwrap(),
-        then: &hackmarket_purchase
-    };
+    Ok(())
 }
-fn hackmarket_purchase<'a>(
-    c: regex::Captures<'a>,
+
+async fn hackmarket_purchase(
+    key: Key,
+    price: u64,
+    seller: String,
     paid_invoice: banker::PaidInvoice,
-) -> HandlerOutput<'a> {
-    async move {
-        let Sale {
-            name,
-            price,
-            from,
-            id,
-            category,
-            ..
-        } = Sale::from_captures(&c)?;
-        let seller = from.ok_or_else(|| "no seller in sale object parsed from invoice reason".to_string())?;
+) -> Result<(), String> {
+    let Key { category, .. } = key;
+    let db = store();
+    let possession = hacksteader::get_possession(db, key).await?;
+    let name = possession.name.clone();
+    let buyer = paid_invoice.invoicee.clone();
 
-        let db = dyn_db();
-        let key = hcor::Key { category, id };
-        match hacksteader::get_possession(&db, key).await?.sale {
-            Some(sale) => sale,
-            None => {
-                futures::try_join!(
-                    banker::pay(
-                        paid_invoice.invoicee.clone(),
-                        price,
-                        format!("the {} you tried to buy has is no longer on the market", name),
-                    ),
-                    dm_blocks(
-                        paid_invoice.invoicee.clone(),
-                        "Sorry, you couldn't purchase that! Your HN has been refunded.".to_string(),
-                        vec![json!({
-                        "type": "section",
-                        "text": mrkdwn(format!(
-                            concat!(
-                                "The {} you tried to buy for {}hn is no longer on the market, ",
-                                "so your HN has been refunded."
-                            ),
-                            name,
-                            price
-                        ))
-                    })])
-                )?;
-                return Ok(());
-            }
-        };
+    // it only changes hands if it's still listed by the seller at the price that was invoiced for,
+    // and the seller's owed their HN in the same write; if it's been relisted since, refund.
+    let payout = banker::OwedPayment::new(&seller, price, format!("sale of your {}", name));
+    let sold = db
+        .transfer_if(
+            vec![Transfer {
+                key,
+                from: seller.clone(),
+                price: Some(price),
+                to: buyer.clone(),
+                acquisition: possess::Acquisition::Purchase { price },
+            }],
+            vec![records::create(&payout)?],
+        )
+        .await
+        .map_err(|e| format!("couldn't sell {}: {}", key.id, e))?;
+    if !sold {
+        banker::owe(
+            db,
+            &buyer,
+            price,
+            format!(
+                "the {} you tried to buy being no longer on the market",
+                name
+            ),
+        )
+        .await?;
+        if let Err(e) = dm_blocks(
+            buyer.clone(),
+            "Sorry, you couldn't purchase that! Your HN has been refunded.".to_string(),
+            vec![section(mrkdwn(format!(
+                concat!(
+                    "The {} you tried to buy for {}hn is no longer on the market, ",
+                    "so your HN has been refunded."
+                ),
+                name, price
+            )))
+            .into()],
+        )
+        .await
+        {
+            warn!("couldn't tell {} their purchase was refunded: {}", buyer, e);
+        }
+        return Ok(());
+    }
 
-        let paid_for = format!("sale of your {}", name);
-        futures::try_join!(
-            db.update_item(rusoto_dynamodb::UpdateItemInput {
-                key: [
-                    ("cat".to_string(), category.into_av()),
-                    (
-                        "id".to_string(),
-                        AttributeValue {
-                            s: Some(id.to_string()),
-                            ..Default::default()
-                        },
-                    ),
-                ]
-                .iter()
-                .cloned()
-                .collect(),
-                expression_attribute_values: Some([
-                    (":new_owner".to_string(), AttributeValue {
-                        s: Some(paid_invoice.invoicee.clone()),
-                        ..Default::default()
-                    }),
-                    (":ownership_entry".to_string(), AttributeValue {
-                        l: Some(vec![possess::Owner {
-                            id: paid_invoice.invoicee.clone(),
-                            acquisition: possess::Acquisition::Purchase {
-                                price: paid_invoice.amount,
-                            }
-                        }.into()]),
-                        ..Default::default()
-                    })
-                ].iter().cloned().collect()),
-                update_expression: Some(concat!(
-                    "REMOVE price, market_name ",
-                    "SET steader = :new_owner, ownership_log = list_append(ownership_log, :ownership_entry)"
-                ).to_string()),
-                table_name: hcor::TABLE_NAME.to_string(),
-                ..Default::default()
-            }).map_err(|e| format!("database err: {}", e)),
-            banker::pay(seller.clone(), price, paid_for),
-            market::log_blocks(
-                format!("<@{}> purchased a {} on hackmarket for {} HN!", 
-                paid_invoice.invoicee,
+    banker::pay_owed(db, vec![payout]).await;
+    if let Err(e) = futures::try_join!(
+        market::log_blocks(
+            format!(
+                "{} purchased a {} on hackmarket for {} HN!",
+                mention(&paid_invoice.invoicee),
                 name,
                 price
-                ).to_string(),
-                vec![
-                    json!({
-                        "type": "section",
-                        "text": mrkdwn(format!(
-                            "The sale of a *{}* has gone through! \
-                            <@{}> made the purchase on hackmarket, earning <@{}> *{} HN*!",
-                            name, paid_invoice.invoicee, seller, price
-                        )),
-                        "accessory": {
-                            "type": "image",
-                            "image_url": format!("http://{}/gotchi/img/{}/{}.png", *URL, category, filify(&name)),
-                            "alt_text": "Hackpheus sitting on bags of money!",
-                        }
-                    }),
-                    comment("U NO GET 2 BYE DAT 1"),
-                ]),
-                dm_blocks(
-                    seller.clone(),
-                    format!("Your sale went through! You earned {} hn.", price).to_string(),
-                    vec![
-                    json!({
-                        "type": "section",
-                        "text": mrkdwn(format!(
-                            "The sale of your *{}* has gone through! \
-                            <@{}> made the purchase on hackmarket, earning you *{} HN*!",
-                            name, paid_invoice.invoicee, price
-                        )),
-                        "accessory": {
-                            "type": "image",
-                            "image_url": format!("http://{}/gotchi/img/{}/{}.png", *URL, category, filify(&name)),
-                            "alt_text": "Hackpheus sitting on bags of money!",
-                        }
-                    }),
+            )
+            .to_string(),
+            vec![
+                section(mrkdwn(format!(
+                    "The sale of a *{}* has gone through! \
+                        {} made the purchase on hackmarket, earning {} *{} HN*!",
+                    name,
+                    mention(&paid_invoice.invoicee),
+                    mention(&seller),
+                    price
+                )))
+                .accessory(image(
+                    format!(
+                        "http://{}/gotchi/img/{}/{}.png",
+                        *URL,
+                        category,
+                        filify(&name)
+                    ),
+                    "Hackpheus sitting on bags of money!"
+                ))
+                .into(),
+                comment("U NO GET 2 BYE DAT 1"),
+            ]
+        ),
+        dm_blocks(
+            seller.clone(),
+            format!("Your sale went through! You earned {} hn.", price).to_string(),
+            vec![
+                section(mrkdwn(format!(
+                    "The sale of your *{}* has gone through! \
+                        {} made the purchase on hackmarket, earning you *{} HN*!",
+                    name,
+                    mention(&paid_invoice.invoicee),
+                    price
+                )))
+                .accessory(image(
+                    format!(
+                        "http://{}/gotchi/img/{}/{}.png",
+                        *URL,
+                        category,
+                        filify(&name)
+                    ),
+                    "Hackpheus sitting on bags of money!"
+                ))
+                .into(),
                 comment("BRUH UR LIKE ROLLING IN CASH"),
-            ])
+            ]
         )
-        .map_err(|e| {
-            let a = format!("Couldn't complete sale of {}: {}", id, e);
-            error!("{}", a);
-            a
-        })?;
-
-        Ok(())
+    ) {
+        warn!("couldn't announce sale of {}: {}", key.id, e);
     }
-    .boxed()
-}
 
-lazy_static::lazy_static! {
-    pub static ref START_HACKSTEAD_INVOICE_PAYMENT: InvoicePaymentTrigger = InvoicePaymentTrigger {
-        regex: Regex::new("let's hackstead, fred!").unwrap(),
-        then: &start_hackstead_invoice_payment
-    };
+    market::record_trade(db, &possession, &paid_invoice.invoicee, price).await;
+    bus::publish(GameEvent::Sale {
+        buyer: paid_invoice.invoicee,
+        seller,
+        item: name,
+        price,
+    });
+
+    Ok(())
 }
-fn start_hackstead_invoice_payment<'a>(
-    _: regex::Captures<'a>,
-    paid_invoice: banker::PaidInvoice,
-) -> HandlerOutput<'a> {
-    async move {
-        let new_user = paid_invoice.invoicee.clone();
-        if !hacksteader::exists(&dyn_db(), new_user.clone()).await {
-            Hacksteader::new_in_db(&dyn_db(), new_user.clone())
-                .await
-                .map_err(|_| "Couldn't put you in the hacksteader database!")?;
 
-            dm_blocks(
-                paid_invoice.invoicee.clone(),
-                "Welcome to Hackagotchi! Click me for more info.".to_string(),
-                vec![
-                 json!({
-                     "type": "section",
-                     "text": mrkdwn(
-                         "Happy Hacksteading, newcomer! Welcome to Hackagotchi!",
-                     )
-                 }),
-                 json!({ "type": "divider" }),
-                 json!({
-                     "type": "section",
-                     "text": mrkdwn(
-                         ":house: You can *manage and monitor* your hackstead with the *Home tab*!\n\n\
-                         \t_Here you can *keep inventory of the items, plants, and gotchi* you have! \
-                         This is also where you *plant seeds*, *hatch eggs*, and *use items*!_"
-                     ),
-                 }),
-                 json!({ "type": "divider" }),
-                 json!({
-                     "type": "section",
-                     "text": mrkdwn(
-                         ":information_source: \
-                         *Use commands* like `/hstead`, `/hstreet`, `/htome`, and `/stateofsteading` \
-                         for all the latest in Hackagotchi happenings! \n\n\
-                         \t_`/hstead @user` lets you *see a user's hackstead*, \
-                         `/hstreet` *opens Hackagotchi's market* to buy items, \
-                         `/htome <item name>` gives you basic *information about items*, \
-                         and `/stateofsteading` gives you an *overview of the agrarian economy*._"
-                     )
-                 }),
-                 json!({ "type": "divider" }),
-                 json!({
-                     "type": "section",
-                     "text": mrkdwn(
-                         ":left_speech_bubble: *Join channels* like #hackstead and #hackstreet \
-                         to *interact with your fellow Hacksteaders*!\n\n\
-                         \t_#hackstead is a great medium for *conversations or suggestions* with other players \
-                         and hackagotchi's developers. \
-                         #hackstreet provides access to *a live feed of market transactions* \
-                         and is a good place to *advertise offers and trades*._"
-                     )
-                 }),
-                 json!({ "type": "divider" }),
-                 json!({
-                     "type": "section",
-                     "text": mrkdwn(
-                         "We're working on an achievements system to make it easier to get started, \
-                         but until then, see if you can complete each of the following:\n\n\
-                         * Buy a seed ( :coffea_cyl_seed: / :hacker_vibes_vine_seed: / :bractus_seed: ) from `/hstreet` and plant it!\n\
-                         * Craft a compressed resource ( :crystcyl: / :hacksprit: / :bressence: )!\n\
-                         * Sacrifice your plant to craft an egg ( :cyl_egg: / :hacker_egg: / :bread_egg: )!\n\
-                         * Get a Land Deed :land_deed: from an egg or from a friend to grow two plants at once!\n\
-                         * Collect enough Baglings ( :crystalline_buzzwing_bagling: / :spirited_buzzwing_bagling: / :doughy_buzzwing_bagling: ) to get a Megabox ( :crystalline_buzzwing_megabox: / :spirited_buzzwing_megabox: / :doughy_buzzwing_megabox: ), and open it for loot!\n\
-                         * Get a :tinkerspore: or an :aloe_avanta_seed: from an egg or from a friend to grow start growing some very rare and overpowered plants!",
-                     )
-                 }),
-                 json!({ "type": "divider" }),
-                 json!({
-                     "type": "section",
-                     "text": mrkdwn(
-                         "_For more information, \
-                         feel free to contact the Hackagotchi Dev Team with @hackagotchi-dev-team, \
-                         visit our website hackagotch.io, \
-                         or avail yourself to the <https://hackstead.fandom.com|community run wiki>._"
-                     ),
-                 }),
-                 comment("LET'S HACKSTEAD, FRED!")
-            ]).await?;
+async fn start_hackstead_invoice_payment(paid_invoice: banker::PaidInvoice) -> Result<(), String> {
+    let new_user = paid_invoice.invoicee.clone();
+    if !hacksteader::exists(store(), new_user.clone()).await {
+        Hacksteader::new_in_db(store(), new_user.clone())
+            .await
+            .map_err(|_| "Couldn't put you in the hacksteader database!")?;
 
-            let welcome_gifts = CONFIG
-                .possession_archetypes
-                .iter()
-                .enumerate()
-                .filter_map(|(i, p)| {
-                    p
-                        .kind
-                        .gotchi()
-                        .filter(|g| dbg!(g.welcome_gift))
-                        .map(|_| i)
-                });
-            for ah in welcome_gifts {
-                Hacksteader::spawn_possession(&dyn_db(), new_user.clone(), ah)
-                    .await
-                    .map_err(|e| {
-                        let a = format!("couldn't spawn possession: {}", e);
-                        error!("{}", e);
-                        a
-                    })?;
-            }
+        dm_blocks(
+            paid_invoice.invoicee.clone(),
+            "Welcome to Hackagotchi! Click me for more info.".to_string(),
+            vec![
+             section(mrkdwn(
+                     "Happy Hacksteading, newcomer! Welcome to Hackagotchi!",
+                 )).into(),
+             divider(),
+             section(mrkdwn(
+                     ":house: You can *manage and monitor* your hackstead with the *Home tab*!\n\n\
+                     \t_Here you can *keep inventory of the items, plants, and gotchi* you have! \
+                     This is also where you *plant seeds*, *hatch eggs*, and *use items*!_"
+                 )).into(),
+             divider(),
+             section(mrkdwn(
+                     ":information_source: \
+                     *Use commands* like `/hstead`, `/hstreet`, `/htome`, and `/stateofsteading` \
+                     for all the latest in Hackagotchi happenings! \n\n\
+                     \t_`/hstead @user` lets you *see a user's hackstead*, \
+                     `/hstreet` *opens Hackagotchi's market* to buy items, \
+                     `/htome <item name>` gives you basic *information about items*, \
+                     and `/stateofsteading` gives you an *overview of the agrarian economy*._"
+                 )).into(),
+             divider(),
+             section(mrkdwn(
+                     ":left_speech_bubble: *Join channels* like #hackstead and #hackstreet \
+                     to *interact with your fellow Hacksteaders*!\n\n\
+                     \t_#hackstead is a great medium for *conversations or suggestions* with other players \
+                     and hackagotchi's developers. \
+                     #hackstreet provides access to *a live feed of market transactions* \
+                     and is a good place to *advertise offers and trades*._"
+                 )).into(),
+             divider(),
+             section(mrkdwn(
+                     "We're working on an achievements system to make it easier to get started, \
+                     but until then, see if you can complete each of the following:\n\n\
+                     * Buy a seed ( :coffea_cyl_seed: / :hacker_vibes_vine_seed: / :bractus_seed: ) from `/hstreet` and plant it!\n\
+                     * Craft a compressed resource ( :crystcyl: / :hacksprit: / :bressence: )!\n\
+                     * Sacrifice your plant to craft an egg ( :cyl_egg: / :hacker_egg: / :bread_egg: )!\n\
+                     * Get a Land Deed :land_deed: from an egg or from a friend to grow two plants at once!\n\
+                     * Collect enough Baglings ( :crystalline_buzzwing_bagling: / :spirited_buzzwing_bagling: / :doughy_buzzwing_bagling: ) to get a Megabox ( :crystalline_buzzwing_megabox: / :spirited_buzzwing_megabox: / :doughy_buzzwing_megabox: ), and open it for loot!\n\
+                     * Get a :tinkerspore: or an :aloe_avanta_seed: from an egg or from a friend to grow start growing some very rare and overpowered plants!",
+                 )).into(),
+             divider(),
+             section(mrkdwn(
+                     "_For more information, \
+                     feel free to contact the Hackagotchi Dev Team with @hackagotchi-dev-team, \
+                     visit our website hackagotch.io, \
+                     or avail yourself to the <https://hackstead.fandom.com|community run wiki>._"
+                 )).into(),
+             comment("LET'S HACKSTEAD, FRED!")
+        ]).await?;
+
+        let welcome_gifts = CONFIG
+            .possession_archetypes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, p)| p.kind.gotchi().filter(|g| dbg!(g.welcome_gift)).map(|_| i));
+        for ah in welcome_gifts {
+            Hacksteader::spawn_possession(store(), new_user.clone(), ah)
+                .await
+                .map_err(|e| {
+                    let a = format!("couldn't spawn possession: {}", e);
+                    error!("{}", e);
+                    a
+                })?;
         }
-        Ok(())
     }
-    .boxed()
+    Ok(())
 }
@@ -1,9 +1,9 @@
 use super::banker;
-use crate::{update_user_home_tab, ID};
+use crate::admin;
+use crate::{slack_verify::SlackJson, update_user_home_tab, ID};
 use crossbeam_channel::Sender;
 use regex::Regex;
 use rocket::{post, State};
-use rocket_contrib::json::Json;
 use std::pin::Pin;
 use std::{error::Error, future::Future};
 
@@ -11,6 +11,8 @@ mod banker_message;
 mod invoice_payment;
 mod special_user_message;
 
+pub use invoice_payment::invoice_paid;
+
 mod prelude {
     // std/util
     pub use crossbeam_channel::Sender;
@@ -19,14 +21,14 @@ mod prelude {
     pub use serde_json::{json, Value};
     pub use std::convert::TryInto;
     // db
-    pub use crate::dyn_db;
-    pub use rusoto_dynamodb::{AttributeValue, DynamoDb};
+    pub use crate::store::{store, Batch, Store};
     // futures
     pub use futures::future::{FutureExt, TryFutureExt};
     pub use futures::stream::{self, StreamExt, TryStreamExt};
     // us
     pub use super::{HandlerOutput, Message, Trigger};
-    pub use crate::{banker, hacksteader, market};
+    pub use crate::bus::{self, GameEvent};
+    pub use crate::{auction, banker, hacksteader, market, trade};
     pub use crate::{FarmingInputEvent, URL};
     pub use config::CONFIG;
     pub use hacksteader::Hacksteader;
@@ -35,7 +37,9 @@ mod prelude {
     pub use hcor::{Category, Key};
     pub use possess::{Gotchi, Keepsake, Possessed, Possession, Seed};
     // slack frontend
-    pub use crate::{comment, dm_blocks, filify, mrkdwn};
+    pub use crate::blocks::{comment, divider, image, mrkdwn, section, Block};
+    pub use crate::chat::mention;
+    pub use crate::{dm_blocks, filify};
     pub use hcor::frontend::emojify;
 }
 use prelude::*;
@@ -52,7 +56,7 @@ pub fn challenge(event_data: ChallengeEvent) -> String {
 #[post("/event", format = "application/json", data = "<event_data>")]
 pub async fn event<'a>(
     to_farming: State<'a, Sender<FarmingInputEvent>>,
-    event_data: Json<Value>,
+    event_data: SlackJson<Value>,
 ) -> String {
     if event_data.get("challenge").is_some() {
         challenge(serde_json::from_value(event_data.clone()).unwrap())
@@ -98,24 +102,24 @@ pub async fn non_challenge_event<'a>(
         );
         if let ("app_home_opened", Some("home"), user_id) = kind_tab_id {
             info!("Rendering app_home!");
-            to_farming
-                .send(crate::FarmingInputEvent::ActivateUser(user_id.clone()))
-                .map_err(|v| v.to_string())
-                .expect("couldn't send active user");
+            crate::farm::queue(
+                &to_farming,
+                crate::FarmingInputEvent::ActivateUser(user_id.clone()),
+            )
+            .await
+            .unwrap_or_else(|e| error!("couldn't activate user: {}", e));
             update_user_home_tab(user_id.clone())
                 .await
                 .unwrap_or_else(|e| error!("{}", e));
-        } else if hcor::config::CONFIG.special_users.contains(&r.user_id) {
-            for SpecialUserMessageTrigger { regex, then } in SPECIAL_USER_MESSAGE_TRIGGERS.iter() {
-                let c = match regex.captures(&r.text) {
-                    Some(c) => c,
-                    None => continue,
-                };
-                if let Err(e) = then(c, r.clone(), &to_farming).await {
-                    banker::message(format!("special user handler err : {}", e))
-                        .await
-                        .unwrap_or_else(|e| error!("{}", e));
-                }
+        } else {
+            let role = admin::role_of(store(), &r.user_id)
+                .await
+                .unwrap_or_else(|e| {
+                    error!("couldn't look up role of {}: {}", r.user_id, e);
+                    None
+                });
+            if let Some(role) = role {
+                goblin_commands(role, r, &to_farming).await;
             }
         }
     });
@@ -123,37 +127,119 @@ pub async fn non_challenge_event<'a>(
     Ok(())
 }
 
+/// Runs each command in an admin's message that their role lets them,
+/// or sets it aside for a second admin to confirm if it needs one.
+async fn goblin_commands(role: admin::Role, r: Message, to_farming: &Sender<FarmingInputEvent>) {
+    let db = store();
+    for trigger in SPECIAL_USER_MESSAGE_TRIGGERS.iter() {
+        let c = match trigger.regex.captures(&r.text) {
+            Some(c) => c,
+            None => continue,
+        };
+
+        let outcome = if !role.can(trigger.needs) {
+            let refusal = format!(
+                "{} is a {}, and can't `{}`.",
+                mention(&r.user_id),
+                role.name(),
+                trigger.name
+            );
+            banker::message(refusal.clone()).await.map(|_| refusal)
+        } else if trigger.two_person {
+            ask_for_confirmation(trigger, &r).await
+        } else {
+            run_goblin_command(trigger, c, r.clone(), to_farming).await;
+            continue;
+        };
+
+        let outcome = outcome.unwrap_or_else(|e| format!("failed: {}", e));
+        admin::audit(db, &r.user_id, trigger.name, &r.text, outcome)
+            .await
+            .unwrap_or_else(|e| error!("couldn't write audit log: {}", e));
+    }
+}
+
+async fn ask_for_confirmation(
+    trigger: &SpecialUserMessageTrigger,
+    r: &Message,
+) -> Result<String, String> {
+    let id =
+        admin::await_confirmation(store(), trigger.name, &r.text, &r.channel, &r.user_id).await?;
+    banker::message(format!(
+        "{} wants to `{}`. Someone else who could do that has {} to \
+         confirm it with `confirm {}`.",
+        mention(&r.user_id),
+        trigger.name,
+        humantime::format_duration(admin::CONFIRM_WINDOW),
+        id
+    ))
+    .await?;
+    Ok(format!("awaiting confirmation as {}", id))
+}
+
+/// Runs a goblin command that's been cleared to run, noting how it went in the audit log.
+pub async fn run_goblin_command<'a>(
+    trigger: &SpecialUserMessageTrigger,
+    c: regex::Captures<'a>,
+    r: Message,
+    to_farming: &'a Sender<FarmingInputEvent>,
+) {
+    let (user_id, text) = (r.user_id.clone(), r.text.clone());
+    let outcome = match (trigger.then)(c, r, to_farming).await {
+        Ok(()) => "ok".to_string(),
+        Err(e) => {
+            banker::message(format!("special user handler err : {}", e))
+                .await
+                .unwrap_or_else(|e| error!("{}", e));
+            format!("failed: {}", e)
+        }
+    };
+    admin::audit(store(), &user_id, trigger.name, &text, outcome)
+        .await
+        .unwrap_or_else(|e| error!("couldn't write audit log: {}", e));
+}
+
 pub type HandlerOutput<'a> = Pin<Box<dyn Future<Output = Result<(), String>> + 'a + Send>>;
 pub type CaptureHandler = dyn for<'a> Fn(regex::Captures<'a>, Message, &'a Sender<FarmingInputEvent>) -> HandlerOutput<'a>
     + 'static
     + Sync
     + Send;
-pub type PaidInvoiceHandler = dyn for<'a> Fn(regex::Captures<'a>, banker::PaidInvoice) -> HandlerOutput<'a>
-    + 'static
-    + Sync
-    + Send;
 pub struct Trigger<T> {
     pub regex: Regex,
     pub then: T,
 }
-pub type SpecialUserMessageTrigger = Trigger<&'static CaptureHandler>;
-pub type InvoicePaymentTrigger = Trigger<&'static PaidInvoiceHandler>;
+/// A goblin command, which only admins whose role has the capability it `needs` may run.
+pub struct SpecialUserMessageTrigger {
+    /// What the command is called in the audit log, and when it's being confirmed.
+    pub name: &'static str,
+    pub needs: admin::Capability,
+    /// Whether a second admin has to confirm this before it happens.
+    pub two_person: bool,
+    pub regex: Regex,
+    pub then: &'static CaptureHandler,
+}
 pub type BankerMessageTrigger = Trigger<&'static CaptureHandler>;
 
 lazy_static::lazy_static! {
-    pub static ref SPECIAL_USER_MESSAGE_TRIGGERS: [&'static SpecialUserMessageTrigger; 8] = [
+    pub static ref SPECIAL_USER_MESSAGE_TRIGGERS: [&'static SpecialUserMessageTrigger; 19] = [
         &*special_user_message::SPAWN_COMMAND,
         &*special_user_message::GP_DUMP_COMMAND,
         &*special_user_message::STOMP_COMMAND,
+        &*special_user_message::STOMP_DRY_RUN,
         &*special_user_message::SLAUGHTER_COMMAND,
+        &*special_user_message::SLAUGHTER_DRY_RUN,
         &*special_user_message::NAB_COMMAND,
+        &*special_user_message::NAB_DRY_RUN,
+        &*special_user_message::UNDO_COMMAND,
         &*special_user_message::YANK_CONFIG,
         &*special_user_message::RESTART_SERVER,
         &*special_user_message::DEPLOY_COMMAND,
-    ];
-    pub static ref INVOICE_PAYMENT_TRIGGERS: [&'static InvoicePaymentTrigger; 3] = [
-        &*invoice_payment::HACKMARKET_FEES,
-        &*invoice_payment::HACKMARKET_PURCHASE,
-        &*invoice_payment::START_HACKSTEAD_INVOICE_PAYMENT,
+        &*special_user_message::EXPLAIN_ROLL,
+        &*special_user_message::CONFIRM_COMMAND,
+        &*special_user_message::GRANT_ROLE,
+        &*special_user_message::REVOKE_ROLE,
+        &*special_user_message::AUDIT_LOG,
+        &*special_user_message::BACKUP_COMMAND,
+        &*special_user_message::RESTORE_COMMAND,
     ];
 }
@@ -1,9 +1,13 @@
 use super::prelude::*;
 use super::SpecialUserMessageTrigger;
+use crate::admin::{self, Capability, Role};
 use serde::Deserialize;
 
 lazy_static::lazy_static! {
     pub static ref SPAWN_COMMAND: SpecialUserMessageTrigger = SpecialUserMessageTrigger {
+        name: "spawn",
+        needs: Capability::Economy,
+        two_person: false,
         regex: Regex::new("<@([A-z|0-9]+)> spawn (<@([A-z|0-9]+)> )?(([0-9]+) )?(.+)").unwrap(),
         then: &spawn_command
     };
@@ -40,52 +44,41 @@ fn spawn_command<'a>(
 
         market::log_blocks(
             format!(
-                "<@{}> spawned {} {} for <@{}>!",
-                &r.user_id,
+                "{} spawned {} {} for {}!",
+                mention(&r.user_id),
                 if amount == 1 {
                     "a".to_string()
                 } else {
                     amount.to_string()
                 },
                 arch.name,
-                &receiver
+                mention(&receiver)
             )
             .to_string(),
             vec![
-                json!({
-                    "type": "section",
-                    "text": mrkdwn(format!(
-                        concat!(
-                            "*{}* new {} *{}* {} been spawned! ",
-                            "Special user <@{}> spawned {} for <@{}>.",
-                        ),
-                        amount,
-                        emojify(&arch.name),
-                        arch.name,
-                        if amount == 1 {
-                            "has"
-                        } else {
-                            "have"
-                        },
-                        &r.user_id,
-                        if amount == 1 {
-                            "it"
-                        } else {
-                            "them"
-                        },
-                        &receiver,
-                    )),
-                    "accessory": {
-                        "type": "image",
-                        "image_url": format!(
-                            "http://{}/gotchi/img/{}/{}.png",
-                            *URL,
-                            format!("{:?}", arch.kind.category()).to_lowercase(),
-                            filify(&arch.name)
-                        ),
-                        "alt_text": "Hackpheus holding a Gift!",
-                    }
-                }),
+                section(mrkdwn(format!(
+                    concat!(
+                        "*{}* new {} *{}* {} been spawned! ",
+                        "Special user {} spawned {} for {}.",
+                    ),
+                    amount,
+                    emojify(&arch.name),
+                    arch.name,
+                    if amount == 1 { "has" } else { "have" },
+                    mention(&r.user_id),
+                    if amount == 1 { "it" } else { "them" },
+                    mention(&receiver),
+                )))
+                .accessory(image(
+                    format!(
+                        "http://{}/gotchi/img/{}/{}.png",
+                        *URL,
+                        format!("{:?}", arch.kind.category()).to_lowercase(),
+                        filify(&arch.name)
+                    ),
+                    "Hackpheus holding a Gift!",
+                ))
+                .into(),
                 comment("U GET AN EGG, U GET AN EGG, U GET AN EGG!"),
             ],
         )
@@ -93,7 +86,7 @@ fn spawn_command<'a>(
 
         // todo: async concurrency
         for _ in 0_usize..amount {
-            Hacksteader::spawn_possession(&dyn_db(), receiver.clone(), archetype_handle)
+            Hacksteader::spawn_possession(store(), receiver.clone(), archetype_handle)
                 .await
                 .map_err(|e| {
                     let a = format!("couldn't spawn possession: {}", e);
@@ -108,6 +101,9 @@ fn spawn_command<'a>(
 
 lazy_static::lazy_static! {
     pub static ref GP_DUMP_COMMAND: SpecialUserMessageTrigger = SpecialUserMessageTrigger {
+        name: "dump",
+        needs: Capability::Economy,
+        two_person: false,
         regex: Regex::new("<@([A-z|0-9]+)> dump <@([A-z|0-9]+)> ([0-9]+)").unwrap(),
         then: &gp_dump_command
     };
@@ -139,6 +135,9 @@ fn gp_dump_command<'a>(
 
 lazy_static::lazy_static! {
     pub static ref YANK_CONFIG: SpecialUserMessageTrigger = SpecialUserMessageTrigger {
+        name: "goblin chant",
+        needs: Capability::World,
+        two_person: false,
         regex: Regex::new("<@([A-z|0-9]+)> goblin chant").unwrap(),
         then: &yank_config
     };
@@ -176,152 +175,246 @@ fn yank_config<'a>(
     .boxed()
 }
 
+/// Tells everyone what a goblin command did, or would have done on a dry run.
+async fn goblin_report<T>(
+    command: &str,
+    dry_run: bool,
+    goblined: hacksteader::Goblined<T>,
+    describe: impl Fn(&T) -> String,
+) -> Result<(), String> {
+    const SHOWN: usize = 30;
+
+    let hacksteader::Goblined { touched, snapshot } = goblined;
+    let mut lines = vec![if dry_run {
+        format!(
+            "*Dry run:* `{}` would get its hands on {} things:",
+            command,
+            touched.len()
+        )
+    } else {
+        format!("`{}` got its hands on {} things:", command, touched.len())
+    }];
+    lines.extend(
+        touched
+            .iter()
+            .take(SHOWN)
+            .map(|t| format!("• {}", describe(t))),
+    );
+    if touched.len() > SHOWN {
+        lines.push(format!("...and {} more.", touched.len() - SHOWN));
+    }
+    if let Some(id) = snapshot {
+        lines.push(format!(
+            "They were snapshotted first; undo it with `goblin undo {}`.",
+            id
+        ));
+    }
+    banker::message(lines.join("\n")).await
+}
+
+fn scope_of(c: &regex::Captures) -> Result<hacksteader::Scope, String> {
+    hacksteader::Scope::parse(
+        c.get(2)
+            .ok_or_else(|| "no scope; say `all` or mention some steaders".to_string())?
+            .as_str(),
+    )
+}
+
 lazy_static::lazy_static! {
     pub static ref STOMP_COMMAND: SpecialUserMessageTrigger = SpecialUserMessageTrigger {
-        regex: Regex::new("<@([A-z|0-9]+)> goblin stomp").unwrap(),
+        name: "goblin stomp",
+        needs: Capability::World,
+        two_person: true,
+        regex: Regex::new("<@([A-z|0-9]+)> goblin stomp ((all|<@).*)").unwrap(),
         then: &stomp_command
     };
+    pub static ref STOMP_DRY_RUN: SpecialUserMessageTrigger = SpecialUserMessageTrigger {
+        name: "goblin stomp dry-run",
+        needs: Capability::Inspect,
+        two_person: false,
+        regex: Regex::new("<@([A-z|0-9]+)> goblin stomp dry-run ((all|<@).*)").unwrap(),
+        then: &stomp_dry_run_command
+    };
 }
 fn stomp_command<'a>(
-    _: regex::Captures<'a>,
+    c: regex::Captures<'a>,
     _: Message,
     to_farming: &'a Sender<FarmingInputEvent>,
 ) -> HandlerOutput<'a> {
-    async move {
-        info!("goblin_stomp time!");
+    stomp(c, to_farming, false).boxed()
+}
+fn stomp_dry_run_command<'a>(
+    c: regex::Captures<'a>,
+    _: Message,
+    to_farming: &'a Sender<FarmingInputEvent>,
+) -> HandlerOutput<'a> {
+    stomp(c, to_farming, true).boxed()
+}
+async fn stomp<'a>(
+    c: regex::Captures<'a>,
+    to_farming: &'a Sender<FarmingInputEvent>,
+    dry_run: bool,
+) -> Result<(), String> {
+    info!("goblin_stomp time! dry run: {}", dry_run);
 
-        match hacksteader::goblin_stomp(&dyn_db(), &to_farming).await {
-            Ok(()) => {}
-            Err(e) => error!("goblin stomp error: {}", e),
-        }
-        Ok(())
-    }
-    .boxed()
+    let scope = scope_of(&c)?;
+    let stomped = hacksteader::goblin_stomp(store(), to_farming, &scope, dry_run)
+        .await
+        .map_err(|e| format!("goblin stomp error: {}", e))?;
+    goblin_report("goblin stomp", dry_run, stomped, |t| {
+        format!(
+            "{}'s {} on tile `{}`",
+            mention(&t.steader),
+            t.plant.as_ref().map_or("nothing", |p| p.name.as_str()),
+            t.id
+        )
+    })
+    .await
 }
 
 lazy_static::lazy_static! {
     pub static ref SLAUGHTER_COMMAND: SpecialUserMessageTrigger = SpecialUserMessageTrigger {
-        regex: Regex::new("<@([A-z|0-9]+)> goblin slaughter").unwrap(),
+        name: "goblin slaughter",
+        needs: Capability::World,
+        two_person: true,
+        regex: Regex::new("<@([A-z|0-9]+)> goblin slaughter ((all|<@).*)").unwrap(),
         then: &slaughter_command
     };
+    pub static ref SLAUGHTER_DRY_RUN: SpecialUserMessageTrigger = SpecialUserMessageTrigger {
+        name: "goblin slaughter dry-run",
+        needs: Capability::Inspect,
+        two_person: false,
+        regex: Regex::new("<@([A-z|0-9]+)> goblin slaughter dry-run ((all|<@).*)").unwrap(),
+        then: &slaughter_dry_run_command
+    };
 }
 fn slaughter_command<'a>(
-    _: regex::Captures<'a>,
+    c: regex::Captures<'a>,
     _: Message,
     _: &'a Sender<FarmingInputEvent>,
 ) -> HandlerOutput<'a> {
-    async move {
-        info!("goblin slaughter time!");
+    slaughter(c, false).boxed()
+}
+fn slaughter_dry_run_command<'a>(
+    c: regex::Captures<'a>,
+    _: Message,
+    _: &'a Sender<FarmingInputEvent>,
+) -> HandlerOutput<'a> {
+    slaughter(c, true).boxed()
+}
+async fn slaughter<'a>(c: regex::Captures<'a>, dry_run: bool) -> Result<(), String> {
+    info!("goblin slaughter time! dry run: {}", dry_run);
 
-        match hacksteader::goblin_slaughter(&dyn_db()).await {
-            Ok(()) => {}
-            Err(e) => error!("goblin slaughter error: {}", e),
-        };
-        Ok(())
-    }
-    .boxed()
+    let scope = scope_of(&c)?;
+    let slaughtered = hacksteader::goblin_slaughter(store(), &scope, dry_run)
+        .await
+        .map_err(|e| format!("goblin slaughter error: {}", e))?;
+    goblin_report("goblin slaughter", dry_run, slaughtered, |p| {
+        format!("{}'s profile, at {}xp", mention(&p.id), p.xp)
+    })
+    .await
 }
 
 lazy_static::lazy_static! {
     pub static ref NAB_COMMAND: SpecialUserMessageTrigger = SpecialUserMessageTrigger {
-        regex: Regex::new("<@([A-z|0-9]+)> goblin nab (.+)").unwrap(),
+        name: "goblin nab",
+        needs: Capability::World,
+        two_person: true,
+        regex: Regex::new("<@([A-z|0-9]+)> goblin nab (all|(?:<@[A-z|0-9]+> ?)+) (.+)").unwrap(),
         then: &nab_command
     };
+    pub static ref NAB_DRY_RUN: SpecialUserMessageTrigger = SpecialUserMessageTrigger {
+        name: "goblin nab dry-run",
+        needs: Capability::Inspect,
+        two_person: false,
+        regex: Regex::new(
+            "<@([A-z|0-9]+)> goblin nab dry-run (all|(?:<@[A-z|0-9]+> ?)+) (.+)"
+        ).unwrap(),
+        then: &nab_dry_run_command
+    };
 }
 fn nab_command<'a>(
     c: regex::Captures<'a>,
     _: Message,
     _: &'a Sender<FarmingInputEvent>,
 ) -> HandlerOutput<'a> {
-    async move {
-        info!("goblin nab time!");
+    nab(c, false).boxed()
+}
+fn nab_dry_run_command<'a>(
+    c: regex::Captures<'a>,
+    _: Message,
+    _: &'a Sender<FarmingInputEvent>,
+) -> HandlerOutput<'a> {
+    nab(c, true).boxed()
+}
+async fn nab<'a>(c: regex::Captures<'a>, dry_run: bool) -> Result<(), String> {
+    info!("goblin nab time! dry run: {}", dry_run);
 
-        let archetype_handle = CONFIG
-            .find_possession_handle(
-                &c.get(2)
-                    .ok_or_else(|| "no item to nab".to_string())?
-                    .as_str(),
-            )
-            .map_err(|e| format!("unknown item: {}", e))?;
-
-        let db = &dyn_db();
-
-        let scan = db.scan(rusoto_dynamodb::ScanInput {
-            table_name: hcor::TABLE_NAME.to_string(),
-            filter_expression: Some("cat = :item_cat AND archetype_handle = :ah".to_string()),
-            expression_attribute_values: Some(
-                [
-                    (":item_cat".to_string(), Category::Misc.into_av()),
-                    (
-                        ":ah".to_string(),
-                        rusoto_dynamodb::AttributeValue {
-                            n: Some(archetype_handle.to_string()),
-                            ..Default::default()
-                        },
-                    ),
-                ]
-                .iter()
-                .cloned()
-                .collect(),
-            ),
-            ..Default::default()
-        });
+    let scope = scope_of(&c)?;
+    let archetype_handle = CONFIG
+        .find_possession_handle(
+            &c.get(3)
+                .ok_or_else(|| "no item to nab".to_string())?
+                .as_str(),
+        )
+        .map_err(|e| format!("unknown item: {}", e))?;
 
-        match scan.await {
-            Ok(rusoto_dynamodb::ScanOutput {
-                items: Some(items), ..
-            }) => {
-                stream::iter(
-                    items
-                        .into_iter()
-                        .map(|mut i| rusoto_dynamodb::WriteRequest {
-                            delete_request: Some(rusoto_dynamodb::DeleteRequest {
-                                key: [
-                                    ("cat".to_string(), i.remove("cat").unwrap()),
-                                    ("id".to_string(), i.remove("id").unwrap()),
-                                ]
-                                .iter()
-                                .cloned()
-                                .collect(),
-                            }),
-                            ..Default::default()
-                        })
-                        .collect::<Vec<_>>()
-                        .chunks(25)
-                        .map(|items| {
-                            db.batch_write_item(rusoto_dynamodb::BatchWriteItemInput {
-                                request_items: [(hcor::TABLE_NAME.to_string(), items.to_vec())]
-                                    .iter()
-                                    .cloned()
-                                    .collect(),
-                                ..Default::default()
-                            })
-                        }),
-                )
-                .map(|x| Ok(x))
-                .try_for_each_concurrent(None, |r| async move {
-                    match r.await {
-                        Ok(_) => Ok(()),
-                        Err(e) => Err(format!("error deleting for goblin nab: {}", e)),
-                    }
-                })
-                .await
-                .map_err(|e| {
-                    let a = format!("goblin nab async err: {}", e);
-                    error!("{}", a);
-                    a
-                })?;
-            }
-            Err(e) => error!("goblin nab error: {}", e),
-            _ => error!("scan returned no items for nab request!"),
-        }
-        Ok(())
+    let nabbed = hacksteader::goblin_nab(store(), archetype_handle, &scope, dry_run)
+        .await
+        .map_err(|e| {
+            let a = format!("goblin nab error: {}", e);
+            error!("{}", a);
+            a
+        })?;
+    goblin_report("goblin nab", dry_run, nabbed, |p| {
+        format!("{}'s {} `{}`", mention(&p.steader), p.name, p.id)
+    })
+    .await
+}
+
+lazy_static::lazy_static! {
+    pub static ref UNDO_COMMAND: SpecialUserMessageTrigger = SpecialUserMessageTrigger {
+        name: "goblin undo",
+        needs: Capability::World,
+        two_person: true,
+        regex: Regex::new("<@([A-z|0-9]+)> goblin undo ([0-9a-f]+)").unwrap(),
+        then: &undo_command
+    };
+}
+/// Puts back everything a goblin command snapshotted before it ran.
+fn undo_command<'a>(
+    c: regex::Captures<'a>,
+    _: Message,
+    _: &'a Sender<FarmingInputEvent>,
+) -> HandlerOutput<'a> {
+    async move {
+        let id = c
+            .get(2)
+            .ok_or_else(|| "no snapshot to undo".to_string())?
+            .as_str();
+        let snapshot = crate::snapshot::restore(store(), id).await?;
+        banker::message(format!(
+            "Put back the {} things `{}` got its hands on {} ago.",
+            snapshot.items,
+            snapshot.reason,
+            humantime::format_duration(
+                snapshot
+                    .taken
+                    .elapsed()
+                    .map(|d| std::time::Duration::from_secs(d.as_secs()))
+                    .unwrap_or_default()
+            )
+        ))
+        .await
     }
     .boxed()
 }
 
 lazy_static::lazy_static! {
     pub static ref DEPLOY_COMMAND: SpecialUserMessageTrigger = SpecialUserMessageTrigger {
+        name: "deploy",
+        needs: Capability::Server,
+        two_person: true,
         regex: Regex::new("<@([A-z|0-9]+)> deploy (.*)").unwrap(),
         then: &deploy_command,
     };
@@ -384,6 +477,9 @@ fn deploy_command<'a>(
 
 lazy_static::lazy_static! {
     pub static ref RESTART_SERVER: SpecialUserMessageTrigger = SpecialUserMessageTrigger {
+            name: "restart",
+            needs: Capability::Server,
+            two_person: false,
             regex: Regex::new("<@([A-z|0-9]+)> restart").unwrap(),
             then: &restart_command,
     };
@@ -406,3 +502,335 @@ fn restart_command<'a>(
     }
     .boxed()
 }
+
+lazy_static::lazy_static! {
+    pub static ref EXPLAIN_ROLL: SpecialUserMessageTrigger = SpecialUserMessageTrigger {
+            name: "explain roll",
+            needs: Capability::Inspect,
+            two_person: false,
+            regex: Regex::new(
+                "<@([A-z|0-9]+)> explain roll ([a-z_]+) ([A-z|0-9|-]+) ([0-9]+)( ([0-9]+))?"
+            ).unwrap(),
+            then: &explain_roll_command,
+    };
+}
+
+fn explain_roll_command<'a>(
+    c: regex::Captures<'a>,
+    _: Message,
+    _: &'a Sender<FarmingInputEvent>,
+) -> HandlerOutput<'a> {
+    use crate::records;
+    use crate::roll::{Roll, RollFor, DICE};
+
+    async move {
+        let what = RollFor::new(
+            c.get(2).ok_or_else(|| "no roll kind".to_string())?.as_str(),
+            c.get(3)
+                .ok_or_else(|| "no roll subject".to_string())?
+                .as_str(),
+            c.get(4)
+                .and_then(|x| x.as_str().parse().ok())
+                .ok_or_else(|| "no roll cycle".to_string())?,
+            c.get(6).and_then(|x| x.as_str().parse().ok()).unwrap_or(0),
+        );
+
+        let seed = hex::encode(DICE.seed(&what));
+        let explanation = match records::get::<Roll>(store(), &what.id()).await? {
+            Some(roll) => format!(
+                "`{}` was rolled against {} with seed `{}`, and {}.\n{}",
+                what.id(),
+                roll.odds,
+                seed,
+                roll.outcome,
+                match crate::farm::reroll(&DICE, &roll) {
+                    Ok(outcome) if outcome == roll.outcome => {
+                        "Rolling it again from that seed comes out the same.".to_string()
+                    }
+                    Ok(outcome) => format!(
+                        ":warning: Rolling it again from that seed comes out differently: {}.",
+                        outcome
+                    ),
+                    Err(e) => format!("It can't be rolled again: {}.", e),
+                }
+            ),
+            None => format!(
+                "There's no record of `{}`, but if it were rolled, its seed would be `{}`.",
+                what.id(),
+                seed
+            ),
+        };
+        banker::message(explanation).await?;
+
+        Ok(())
+    }
+    .boxed()
+}
+
+lazy_static::lazy_static! {
+    pub static ref CONFIRM_COMMAND: SpecialUserMessageTrigger = SpecialUserMessageTrigger {
+        name: "confirm",
+        needs: Capability::Inspect,
+        two_person: false,
+        regex: Regex::new("<@([A-z|0-9]+)> confirm ([0-9a-f]+)").unwrap(),
+        then: &confirm_command,
+    };
+}
+
+/// Runs a command someone else asked for, if whoever's confirming it could've run it themselves.
+fn confirm_command<'a>(
+    c: regex::Captures<'a>,
+    r: Message,
+    to_farming: &'a Sender<FarmingInputEvent>,
+) -> HandlerOutput<'a> {
+    async move {
+        let db = store();
+        let id = c
+            .get(2)
+            .ok_or_else(|| "nothing to confirm".to_string())?
+            .as_str();
+        let pending = admin::pending(db, id)
+            .await?
+            .ok_or_else(|| format!("no command {} is waiting to be confirmed", id))?;
+        let trigger = super::SPECIAL_USER_MESSAGE_TRIGGERS
+            .iter()
+            .find(|t| t.name == pending.command)
+            .ok_or_else(|| format!("no command by the name of {}", pending.command))?;
+        admin::confirm(db, &pending, &r.user_id, trigger.needs).await?;
+
+        banker::message(format!(
+            "{} confirmed {}'s `{}`, here goes!",
+            mention(&r.user_id),
+            mention(&pending.requested_by),
+            pending.command
+        ))
+        .await?;
+
+        let text = pending.text.clone();
+        let c = trigger
+            .regex
+            .captures(&text)
+            .ok_or_else(|| format!("`{}` doesn't look like `{}`", text, pending.command))?;
+        let requested = Message {
+            user_id: pending.requested_by,
+            channel: pending.channel,
+            text: pending.text,
+            ..r
+        };
+        super::run_goblin_command(trigger, c, requested, to_farming).await;
+        Ok(())
+    }
+    .boxed()
+}
+
+lazy_static::lazy_static! {
+    pub static ref GRANT_ROLE: SpecialUserMessageTrigger = SpecialUserMessageTrigger {
+        name: "grant",
+        needs: Capability::ManageRoles,
+        two_person: false,
+        regex: Regex::new("<@([A-z|0-9]+)> grant <@([A-z|0-9]+)> ([a-z_]+)").unwrap(),
+        then: &grant_role_command,
+    };
+}
+
+fn grant_role_command<'a>(
+    c: regex::Captures<'a>,
+    r: Message,
+    _: &'a Sender<FarmingInputEvent>,
+) -> HandlerOutput<'a> {
+    async move {
+        let user_id = c
+            .get(2)
+            .ok_or_else(|| "no one to grant a role to".to_string())?
+            .as_str();
+        let role_name = c
+            .get(3)
+            .ok_or_else(|| "no role to grant".to_string())?
+            .as_str();
+        let role = Role::from_name(role_name).ok_or_else(|| {
+            format!(
+                "there's no role called {}, only {}",
+                role_name,
+                Role::ALL
+                    .iter()
+                    .map(|r| r.name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })?;
+
+        admin::grant(store(), user_id, role, &r.user_id).await?;
+        banker::message(format!(
+            "{} is now a {}, thanks to {}.",
+            mention(user_id),
+            role.name(),
+            mention(&r.user_id)
+        ))
+        .await
+    }
+    .boxed()
+}
+
+lazy_static::lazy_static! {
+    pub static ref REVOKE_ROLE: SpecialUserMessageTrigger = SpecialUserMessageTrigger {
+        name: "revoke",
+        needs: Capability::ManageRoles,
+        two_person: false,
+        regex: Regex::new("<@([A-z|0-9]+)> revoke <@([A-z|0-9]+)>").unwrap(),
+        then: &revoke_role_command,
+    };
+}
+
+fn revoke_role_command<'a>(
+    c: regex::Captures<'a>,
+    r: Message,
+    _: &'a Sender<FarmingInputEvent>,
+) -> HandlerOutput<'a> {
+    async move {
+        let user_id = c
+            .get(2)
+            .ok_or_else(|| "no one to revoke a role from".to_string())?
+            .as_str();
+        let revoked = admin::revoke(store(), user_id)
+            .await?
+            .ok_or_else(|| format!("{} doesn't have a role to revoke", mention(user_id)))?;
+
+        banker::message(format!(
+            "{} is no longer a {}, thanks to {}.{}",
+            mention(user_id),
+            revoked.role.name(),
+            mention(&r.user_id),
+            if admin::ADMIN_OPERATORS.iter().any(|op| op == user_id) {
+                " They're still an operator through `ADMIN_OPERATORS`, though."
+            } else {
+                ""
+            }
+        ))
+        .await
+    }
+    .boxed()
+}
+
+lazy_static::lazy_static! {
+    pub static ref AUDIT_LOG: SpecialUserMessageTrigger = SpecialUserMessageTrigger {
+        name: "audit",
+        needs: Capability::Inspect,
+        two_person: false,
+        regex: Regex::new("<@([A-z|0-9]+)> audit( ([0-9]+))?").unwrap(),
+        then: &audit_log_command,
+    };
+}
+
+/// Shows who's been running which commands lately, along with who has which role.
+fn audit_log_command<'a>(
+    c: regex::Captures<'a>,
+    _: Message,
+    _: &'a Sender<FarmingInputEvent>,
+) -> HandlerOutput<'a> {
+    use humantime::format_rfc3339_seconds;
+
+    async move {
+        let db = store();
+        let limit = c.get(3).and_then(|x| x.as_str().parse().ok()).unwrap_or(10);
+
+        let mut lines = vec!["*Roles*".to_string()];
+        lines.extend(admin::grants(db).await?.into_iter().map(|g| {
+            format!(
+                "{} is a {}, granted by {}",
+                mention(&g.user_id),
+                g.role.name(),
+                mention(&g.granted_by)
+            )
+        }));
+        lines.push(format!("*Last {} commands*", limit));
+        lines.extend(admin::audit_log(db, limit).await?.into_iter().map(|e| {
+            format!(
+                "{} {} ran `{}` (`{}`): {}",
+                format_rfc3339_seconds(e.at),
+                mention(&e.user_id),
+                e.command,
+                e.text,
+                e.outcome
+            )
+        }));
+
+        banker::message(lines.join("\n")).await
+    }
+    .boxed()
+}
+
+lazy_static::lazy_static! {
+    pub static ref BACKUP_COMMAND: SpecialUserMessageTrigger = SpecialUserMessageTrigger {
+        name: "backup",
+        needs: Capability::Server,
+        two_person: false,
+        regex: Regex::new("<@([A-z|0-9]+)> backup$").unwrap(),
+        then: &backup_command,
+    };
+}
+
+fn backup_command<'a>(
+    _: regex::Captures<'a>,
+    _: Message,
+    _: &'a Sender<FarmingInputEvent>,
+) -> HandlerOutput<'a> {
+    use crate::archive;
+
+    async move {
+        let path = archive::new_backup_path();
+        let counts = archive::backup_to(store(), &path).await?;
+        banker::message(format!("Backed up {} to `{}`.", counts, path)).await
+    }
+    .boxed()
+}
+
+lazy_static::lazy_static! {
+    pub static ref RESTORE_COMMAND: SpecialUserMessageTrigger = SpecialUserMessageTrigger {
+        name: "restore",
+        needs: Capability::Server,
+        two_person: true,
+        regex: Regex::new(
+            "<@([A-z|0-9]+)> restore ([A-Za-z0-9_.-]+\\.jsonl)( <@([A-z|0-9]+)>)?"
+        ).unwrap(),
+        then: &restore_command,
+    };
+}
+
+/// Restores from one of the archives in the backup directory, backing up what's
+/// there now first so that the restore can itself be undone.
+fn restore_command<'a>(
+    c: regex::Captures<'a>,
+    _: Message,
+    _: &'a Sender<FarmingInputEvent>,
+) -> HandlerOutput<'a> {
+    use crate::archive;
+
+    async move {
+        let file = c
+            .get(2)
+            .ok_or_else(|| "no archive to restore".to_string())?
+            .as_str();
+        let steader = c.get(4).map(|s| s.as_str());
+
+        let before = archive::new_backup_path();
+        archive::backup_to(store(), &before).await?;
+        let counts = archive::restore_from(
+            store(),
+            &format!("{}/{}", archive::BACKUP_DIR, file),
+            steader,
+        )
+        .await?;
+
+        banker::message(format!(
+            "Restored {} from `{}`{}. What was there before is backed up in `{}`.",
+            counts,
+            file,
+            steader
+                .map(|s| format!(" for {}", mention(s)))
+                .unwrap_or_default(),
+            before
+        ))
+        .await
+    }
+    .boxed()
+}
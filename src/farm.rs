@@ -0,0 +1,1775 @@
+//! The rules of the farm: how plants grow, craft and yield, how effects wear off,
+//! how xp is earned, and what happens to queued land certs, eggs and item applications.
+//!
+//! `tick` doesn't touch the database, Slack or the clock; it's given everything it
+//! needs and hands back what should be written and who should be told what.
+//!
+//! Someone who's been away for a while is caught up a chunk of cycles at a time,
+//! skipping ahead over stretches where nothing happens, and told what they missed
+//! in one DM once they're caught up.
+//!
+//! Everything random is rolled with `Dice`, so each roll can be explained later;
+//! the rolls made are handed back to be kept alongside everything else.
+//!
+//! Inputs are saved as `QueuedInput` records when they come in, and forgotten once
+//! the cycle that used them up is saved, so a restart doesn't lose anything.
+use crate::blocks::{comment, divider, image, mrkdwn, section, Block};
+use crate::bus::GameEvent;
+use crate::chat::mention;
+use crate::hacksteader::{self, Hacksteader, Plant, Tile};
+use crate::records::{self, Record};
+use crate::roll::{self, Dice, Odds, Roll, RollFor};
+use crate::store::{store, Batch, RecordSwap, Store, TRANSACT_LIMIT};
+use crate::{filify, format_yield, FarmingInputEvent, ItemApplication, FARM_CYCLE_MILLIS, URL};
+use config::CONFIG;
+use crossbeam_channel::Sender;
+use hcor::{config, frontend::emojify, possess, Key, Possession, Profile};
+use log::*;
+use rand::Rng;
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryInto;
+use std::time::{Duration, SystemTime};
+
+/// Users who haven't done anything for this long stop being farmed every tick.
+pub const ACTIVE_DURATION_SECS: u64 = 60 * 5;
+
+/// Farms that have this many cycles to catch up on get a "while you were away" DM
+/// instead of a DM for everything that happened.
+pub const AWAY_CYCLES: u128 = (ACTIVE_DURATION_SECS * 1000 / FARM_CYCLE_MILLIS) as u128;
+
+/// No farm is moved forward more than a day's worth of cycles in a single tick;
+/// the rest is left for the ticks after that.
+pub const MAX_CYCLES_PER_TICK: u128 = (24 * 60 * 60 * 1000 / FARM_CYCLE_MILLIS) as u128;
+
+/// Things people have asked for that get done on the next tick.
+#[derive(Default)]
+pub struct InputQueues {
+    pub plant_queue: HashMap<uuid::Uuid, Plant>,
+    pub craft_queue: HashMap<uuid::Uuid, config::ArchetypeHandle>,
+    pub item_application_queue: HashMap<String, ItemApplication>,
+    pub land_cert_queue: HashMap<String, uuid::Uuid>,
+    pub hatch_egg_queue: HashMap<String, uuid::Uuid>,
+}
+impl InputQueues {
+    pub fn is_empty(&self) -> bool {
+        self.plant_queue.is_empty()
+            && self.craft_queue.is_empty()
+            && self.item_application_queue.is_empty()
+            && self.land_cert_queue.is_empty()
+            && self.hatch_egg_queue.is_empty()
+    }
+}
+
+/// What the farm remembers from one tick to the next.
+#[derive(Default)]
+pub struct FarmState {
+    /// Who we're farming for, and whether they've done anything since the last tick.
+    pub active_users: HashMap<String, bool>,
+    pub queues: InputQueues,
+    /// Who still has cycles to catch up on after the last tick, active or not.
+    pub catching_up: HashSet<String>,
+    /// What's happened to those who've been away, to tell them once they're caught up.
+    pub away: HashMap<String, AwaySummary>,
+    /// How many cycles a tick can move these steaders forward, since moving them forward
+    /// any further made more than could be saved in one transaction. Kept until they're caught up.
+    pub cycle_caps: HashMap<String, u128>,
+}
+impl FarmState {
+    /// Whose hacksteads `tick` will need once these events have come in.
+    pub fn users_to_load(&self, events: &[FarmingInputEvent]) -> Vec<String> {
+        let mut users: Vec<String> = self.active_users.keys().cloned().collect();
+        for user in self.catching_up.iter() {
+            if !users.contains(user) {
+                users.push(user.clone());
+            }
+        }
+        for e in events {
+            if let FarmingInputEvent::ActivateUser(name) = e {
+                if !users.contains(name) {
+                    users.push(name.clone());
+                }
+            }
+        }
+        users
+    }
+}
+
+/// Who was catching up and what they'd missed, from before a tick,
+/// to put back for anyone whose part of the tick couldn't be saved.
+pub struct Progress {
+    catching_up: HashSet<String>,
+    away: HashMap<String, AwaySummary>,
+    cycle_caps: HashMap<String, u128>,
+}
+impl FarmState {
+    pub fn progress(&self) -> Progress {
+        Progress {
+            catching_up: self.catching_up.clone(),
+            away: self.away.clone(),
+            cycle_caps: self.cycle_caps.clone(),
+        }
+    }
+
+    /// Forgets what a tick did for `who`, since it wasn't saved and they'll be farmed
+    /// from where they were all over again.
+    pub fn roll_back(&mut self, who: &str, before: &Progress) {
+        if before.catching_up.contains(who) {
+            self.catching_up.insert(who.to_string());
+        } else {
+            self.catching_up.remove(who);
+        }
+        match before.away.get(who) {
+            Some(summary) => self.away.insert(who.to_string(), summary.clone()),
+            None => self.away.remove(who),
+        };
+        match before.cycle_caps.get(who) {
+            Some(&cap) => self.cycle_caps.insert(who.to_string(), cap),
+            None => self.cycle_caps.remove(who),
+        };
+    }
+
+    /// Has `who` moved forward half as many cycles at a time as they were last tick,
+    /// since that was too much to save at once.
+    pub fn farm_fewer_cycles(&mut self, who: &str, cycles: u64) {
+        self.catching_up.insert(who.to_string());
+        self.cycle_caps
+            .insert(who.to_string(), (cycles as u128 / 2).max(1));
+    }
+}
+
+/// What a farm did while its steader was away.
+#[derive(Default, Clone)]
+pub struct AwaySummary {
+    pub cycles: u64,
+    /// How many of each possession they got, by name.
+    pub items: BTreeMap<String, usize>,
+    /// How many times each recipe was finished, by title.
+    pub crafts: BTreeMap<String, usize>,
+    pub level_ups: Vec<String>,
+}
+impl AwaySummary {
+    fn got(&mut self, possessions: &[Possession]) {
+        for p in possessions {
+            *self.items.entry(p.name.clone()).or_insert(0) += 1;
+        }
+    }
+
+    pub fn blocks(&self) -> Vec<Block> {
+        // keep each section well under Slack's limit on how long its text can be
+        const MAX_LINES: usize = 20;
+        fn list(title: &str, lines: Vec<String>) -> Block {
+            let extra = lines.len().saturating_sub(MAX_LINES);
+            let mut text = format!("*{}*\n", title);
+            text.push_str(&lines[..lines.len().min(MAX_LINES)].join("\n"));
+            if extra > 0 {
+                text.push_str(&format!("\n_...and {} more_", extra));
+            }
+            section(mrkdwn(text)).into()
+        }
+
+        let secs = self.cycles * FARM_CYCLE_MILLIS / 1000;
+        let mut blocks = vec![section(mrkdwn(format!(
+            "*While you were away...*\nYour hackstead kept farming for {} days and {} hours.",
+            secs / (60 * 60 * 24),
+            secs % (60 * 60 * 24) / (60 * 60),
+        )))
+        .into()];
+
+        if !self.items.is_empty() {
+            blocks.push(list(
+                "You got:",
+                self.items
+                    .iter()
+                    .map(|(name, count)| format!("{} *{}x* _{}_", emojify(name), count, name))
+                    .collect(),
+            ));
+        }
+        if !self.crafts.is_empty() {
+            blocks.push(list(
+                "Crafts finished:",
+                self.crafts
+                    .iter()
+                    .map(|(title, count)| format!("*{}x* {}", count, title))
+                    .collect(),
+            ));
+        }
+        if !self.level_ups.is_empty() {
+            blocks.push(list(
+                "Level ups:",
+                self.level_ups
+                    .iter()
+                    .map(|l| format!(":tada: {}", l))
+                    .collect(),
+            ));
+        }
+
+        blocks.push(comment("WELCOME BACK NOISES"));
+        blocks
+    }
+}
+
+/// An input someone gave us that a tick hasn't used up yet.
+#[derive(Serialize, Deserialize)]
+pub struct QueuedInput {
+    pub event: FarmingInputEvent,
+    pub queued_at: SystemTime,
+}
+impl Record for QueuedInput {
+    const KIND: &'static str = "queued_farming_input";
+
+    fn id(&self) -> String {
+        self.event.queue_id()
+    }
+}
+
+impl FarmingInputEvent {
+    /// Inputs with the same id replace each other in the queues,
+    /// so only the latest one of them needs to be kept around.
+    pub fn queue_id(&self) -> String {
+        use FarmingInputEvent::*;
+        match self {
+            ActivateUser(user_id) => format!("active_user:{}", user_id),
+            RedeemLandCert(_, user_id) => format!("land_cert:{}", user_id),
+            HatchEgg(_, user_id) => format!("hatch_egg:{}", user_id),
+            ApplyItem(_, user_id) => format!("item_application:{}", user_id),
+            PlantSeed(tile_id, _) => format!("plant:{}", tile_id),
+            BeginCraft { tile_id, .. } => format!("craft:{}", tile_id),
+        }
+    }
+}
+
+/// Saves an input, then hands it to the farm.
+/// Once this returns Ok the input will survive a restart,
+/// so it's safe to tell the user we've got it.
+pub async fn queue(
+    to_farming: &Sender<FarmingInputEvent>,
+    event: FarmingInputEvent,
+) -> Result<(), String> {
+    let queued = QueuedInput {
+        event,
+        queued_at: SystemTime::now(),
+    };
+    records::put(store(), &queued)
+        .await
+        .map_err(|e| format!("couldn't save farming input: {}", e))?;
+    to_farming
+        .send(queued.event)
+        .map_err(|e| format!("couldn't send farming input: {}", e))
+}
+
+/// Everything that was queued but never used up, oldest first,
+/// so that it can be fed back into the farm when the server starts.
+pub async fn queued_inputs(db: &dyn Store) -> Result<Vec<FarmingInputEvent>, String> {
+    let mut queued = records::all::<QueuedInput>(db).await?;
+    queued.sort_by_key(|q| q.queued_at);
+    Ok(queued.into_iter().map(|q| q.event).collect())
+}
+
+/// Stops keeping inputs that a saved cycle has used up.
+/// Inputs queued again after `collected_at` took their place and are kept,
+/// even if they're queued again while this is forgetting the old ones.
+pub async fn forget_inputs(db: &dyn Store, events: &[FarmingInputEvent], collected_at: SystemTime) {
+    for event in events {
+        let id = event.queue_id();
+        let data = match db.get_record(QueuedInput::KIND, &id).await {
+            Ok(Some(data)) => data,
+            Ok(None) => continue,
+            Err(e) => {
+                error!("couldn't look up farming input {}: {}", id, e);
+                continue;
+            }
+        };
+        match serde_json::from_str::<QueuedInput>(&data) {
+            Ok(q) if q.queued_at <= collected_at => {}
+            Ok(_) => continue,
+            Err(e) => {
+                error!("couldn't parse farming input {}: {}", id, e);
+                continue;
+            }
+        }
+
+        // removed only if it's still exactly what was read
+        let forget = RecordSwap {
+            kind: QueuedInput::KIND.to_string(),
+            id: id.clone(),
+            old: Some(data),
+            new: None,
+        };
+        match db.transfer_if(vec![], vec![forget]).await {
+            Ok(true) => {}
+            Ok(false) => debug!("farming input {} was queued again, keeping it", id),
+            Err(e) => error!("couldn't forget farming input {}: {}", id, e),
+        }
+    }
+}
+
+/// Saves one steader's part of a tick.
+///
+/// Anything that fits in one transaction is saved all at once or not at all, and the farm
+/// moves steaders forward fewer cycles at a time until it does fit (see `SteaderOutput::too_big`).
+///
+/// A single cycle that makes more than that can't be split up though, so it's saved in parts,
+/// which isn't atomic: the new possessions go in first, a transaction at a time, and if the
+/// rest can't be saved they're deleted again as well as they can be.
+pub async fn save(db: &dyn Store, writes: Batch) -> Result<(), String> {
+    if writes.len() <= TRANSACT_LIMIT {
+        return db.transact(writes).await;
+    }
+
+    let Batch {
+        profiles,
+        tiles,
+        mut possessions,
+        deletions,
+        profile_deletions,
+    } = writes;
+    let rest = Batch {
+        profiles,
+        tiles,
+        possessions: vec![],
+        deletions,
+        profile_deletions,
+    };
+    let room = TRANSACT_LIMIT
+        .saturating_sub(rest.len())
+        .min(possessions.len());
+    let last = possessions.split_off(possessions.len() - room);
+
+    let mut saved: Vec<Key> = vec![];
+    let mut result = Ok(());
+    for chunk in possessions.chunks(TRANSACT_LIMIT) {
+        result = db
+            .transact(Batch {
+                possessions: chunk.to_vec(),
+                ..Default::default()
+            })
+            .await;
+        if result.is_err() {
+            break;
+        }
+        saved.extend(chunk.iter().map(|p| p.key()));
+    }
+    if result.is_ok() {
+        result = db
+            .transact(Batch {
+                possessions: last,
+                ..rest
+            })
+            .await;
+    }
+
+    if result.is_err() && !saved.is_empty() {
+        if let Err(e) = db
+            .write(Batch {
+                deletions: saved,
+                ..Default::default()
+            })
+            .await
+        {
+            error!(
+                "couldn't delete possessions from an unsaved farm cycle: {}",
+                e
+            );
+        }
+    }
+    result
+}
+
+/// Sends the DMs and hackmarket logs a tick asked for, all at once.
+pub async fn announce(
+    dms: Vec<(String, Vec<Block>, String)>,
+    market_logs: Vec<(Vec<Block>, String)>,
+) -> Result<(), String> {
+    use futures::stream::{self, StreamExt, TryStreamExt};
+
+    futures::try_join!(
+        stream::iter(dms)
+            .map(Ok)
+            .try_for_each_concurrent(None, |(who, blocks, notif_msg)| {
+                crate::dm_blocks(who, notif_msg, blocks)
+            }),
+        stream::iter(market_logs)
+            .map(Ok)
+            .try_for_each_concurrent(None, |(blocks, notif_msg)| {
+                crate::market::log_blocks(notif_msg, blocks)
+            }),
+    )?;
+    Ok(())
+}
+
+fn names(possessions: &[Possession]) -> Vec<String> {
+    possessions.iter().map(|p| p.name.clone()).collect()
+}
+
+/// What a tick wants done to the rest of the world on behalf of one steader.
+#[derive(Default)]
+pub struct SteaderOutput {
+    /// Everything that needs to be saved for this steader; either all of it or none of it should be.
+    pub writes: Batch,
+    /// Who to DM, the blocks to send them, and the notification text.
+    pub dms: Vec<(String, Vec<Block>, String)>,
+    /// Blocks to log to the hackmarket channel, and their notification text.
+    pub market_logs: Vec<(Vec<Block>, String)>,
+    /// The queued inputs this tick used up. If `writes` can't be saved,
+    /// these should be fed back into the next tick so they aren't lost.
+    pub consumed: Vec<FarmingInputEvent>,
+    /// The rolls that went into this steader's cycle, to be kept if it's saved.
+    pub rolls: Vec<Roll>,
+    /// What happened to this steader, to be published once it's saved.
+    pub events: Vec<GameEvent>,
+    /// How many cycles they were moved forward.
+    pub cycles: u64,
+}
+impl SteaderOutput {
+    /// Whether there's more to save than fits in one transaction,
+    /// when moving them forward fewer cycles at a time would help.
+    pub fn too_big(&self) -> bool {
+        self.writes.len() > TRANSACT_LIMIT && self.cycles > 1
+    }
+}
+
+/// What a tick wants done to the rest of the world, by steader.
+#[derive(Default)]
+pub struct FarmOutput {
+    pub steaders: HashMap<String, SteaderOutput>,
+}
+
+/// Moves the farm forward by however many farm cycles have elapsed
+/// since each of the given hacksteaders was last farmed.
+///
+/// `hacksteaders` should be the hacksteads of everyone `state.users_to_load(&events)`
+/// named, as they're currently stored.
+pub fn tick(
+    state: FarmState,
+    hacksteaders: Vec<Hacksteader>,
+    events: Vec<FarmingInputEvent>,
+    dice: &Dice,
+    now: SystemTime,
+) -> (FarmState, FarmOutput) {
+    let FarmState {
+        mut active_users,
+        away: mut away_summaries,
+        catching_up: _,
+        mut cycle_caps,
+        queues:
+            InputQueues {
+                mut plant_queue,
+                mut craft_queue,
+                mut item_application_queue,
+                mut land_cert_queue,
+                mut hatch_egg_queue,
+            },
+    } = state;
+
+    for (_, fresh) in active_users.iter_mut() {
+        *fresh = false;
+    }
+
+    for farming_event in events {
+        use FarmingInputEvent::*;
+        match farming_event {
+            ActivateUser(name) => {
+                debug!("activated: {}", name);
+                active_users.insert(name, true);
+            }
+            ApplyItem(application, user_id) => {
+                item_application_queue.insert(user_id, application);
+            }
+            PlantSeed(tile_id, plant) => {
+                plant_queue.insert(tile_id, plant);
+            }
+            RedeemLandCert(cert_id, user_id) => {
+                land_cert_queue.insert(user_id, cert_id);
+            }
+            HatchEgg(egg_id, user_id) => {
+                hatch_egg_queue.insert(user_id, egg_id);
+            }
+            BeginCraft {
+                tile_id,
+                recipe_archetype_handle,
+            } => {
+                craft_queue.insert(tile_id, recipe_archetype_handle);
+            }
+        }
+    }
+
+    let mut hacksteaders = hacksteaders;
+    let mut deletions: Vec<(String, Key)> = vec![];
+    let mut consumed: Vec<(String, FarmingInputEvent)> = vec![];
+    let mut clear_plants = vec![];
+    let mut possessions = vec![];
+    let mut new_tiles = vec![];
+    let mut dms: Vec<(String, Vec<Block>, String)> = Vec::new();
+    let mut market_logs: Vec<(String, Vec<Block>, String)> = Vec::new();
+    let mut rolls: Vec<(String, Roll)> = Vec::new();
+    let mut events: Vec<(String, GameEvent)> = Vec::new();
+    let now_cycle = roll::cycle_at(now);
+
+    // Give away requested land/hatch eggs
+    for hs in hacksteaders.iter_mut() {
+        let application = item_application_queue.remove(&hs.user_id);
+        if let Some((plant, appl, item)) = application.as_ref().and_then(|appl| {
+            let i = hs.inventory.iter().find(|i| i.id == appl.item)?;
+
+            Some((
+                hs.land
+                    .iter_mut()
+                    .find(|t| t.id == appl.tile)?
+                    .plant
+                    .as_mut()?,
+                i.kind.keepsake()?.item_application.as_ref()?,
+                i,
+            ))
+        }) {
+            deletions.push((hs.user_id.clone(), Key::misc(item.id)));
+            for (i, e) in appl.effects.iter().enumerate() {
+                match &e.kind {
+                    config::ItemApplicationEffectKind::TurnsPlantInto(name) => {
+                        plant.archetype_handle =
+                            CONFIG.find_plant_handle(name).expect("invalid handle");
+
+                        for (i, e) in plant
+                            .effects
+                            .clone()
+                            .iter()
+                            .filter_map(|e| {
+                                CONFIG.get_item_application_effect(
+                                    e.item_archetype_handle,
+                                    e.effect_archetype_handle,
+                                )
+                            })
+                            .enumerate()
+                        {
+                            if !e
+                                .keep_plants
+                                .lookup_handles()
+                                .unwrap()
+                                .allows(&plant.archetype_handle)
+                            {
+                                plant.effects.swap_remove(i);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+
+                plant.effects.push(hacksteader::Effect {
+                    until_finish: e.duration,
+                    item_archetype_handle: item.archetype_handle,
+                    effect_archetype_handle: i,
+                });
+            }
+        }
+        if let Some(application) = application {
+            consumed.push((
+                hs.user_id.clone(),
+                FarmingInputEvent::ApplyItem(application, hs.user_id.clone()),
+            ));
+        }
+        if let Some(cert_id) = land_cert_queue.remove(&hs.user_id) {
+            consumed.push((
+                hs.user_id.clone(),
+                FarmingInputEvent::RedeemLandCert(cert_id, hs.user_id.clone()),
+            ));
+            if hs.inventory.iter().any(|p| {
+                let same_id = p.id == cert_id;
+                let actually_land_cert = p
+                    .kind
+                    .keepsake()
+                    .filter(|k| k.unlocks_land.is_some())
+                    .is_some();
+
+                same_id && actually_land_cert
+            }) {
+                deletions.push((hs.user_id.clone(), Key::misc(cert_id)));
+                let new_tile = hacksteader::Tile::new(hs.user_id.clone());
+                hs.land.push(new_tile.clone());
+                new_tiles.push(new_tile.clone());
+            }
+        }
+        if let Some(egg_id) = hatch_egg_queue.remove(&hs.user_id) {
+            consumed.push((
+                hs.user_id.clone(),
+                FarmingInputEvent::HatchEgg(egg_id, hs.user_id.clone()),
+            ));
+            debug!("egg hatch requested!");
+
+            if let Some((p, hatch_table)) = hs.gotchis.iter().find_map(|g| {
+                Some(g).filter(|g| g.id == egg_id).and_then(|g| {
+                    debug!("hatching {:?}", g);
+                    Some((g, g.inner.hatch_table.as_ref()?))
+                })
+            }) {
+                deletions.push((hs.user_id.clone(), Key::gotchi(egg_id)));
+
+                let ((spawn_handles, percentile), roll) = dice.roll(
+                    RollFor::new("hatch", egg_id, now_cycle, 0),
+                    format!("the hatch table of a {}", p.name),
+                    Odds::Hatch(table_json(hatch_table)),
+                    |rng| config::spawn_with_percentile(hatch_table, rng),
+                    |(handles, percentile)| hatched(handles, *percentile),
+                );
+                rolls.push((hs.user_id.clone(), roll));
+
+                let spawned: Vec<Possession> = spawn_handles
+                    .into_iter()
+                    .map(|h| {
+                        Possession::new(
+                            CONFIG.find_possession_handle(&h).unwrap(),
+                            possess::Owner::hatcher(hs.user_id.clone()),
+                        )
+                    })
+                    .collect();
+
+                let mut msg = vec![
+                    section(mrkdwn(format!(
+                        concat!(
+                            "*{} hatched a {}!*\n",
+                            "The rarity of this loot puts it in the ",
+                            "*{:.2}th* percentile for loot from eggs of this type.",
+                        ),
+                        mention(&hs.user_id),
+                        p.name,
+                        percentile * 100.0
+                    )))
+                    .accessory(image(
+                        format!(
+                            "http://{}/gotchi/img/{}/{}.png",
+                            *URL,
+                            format!("{:?}", p.kind.category()).to_lowercase(),
+                            filify(&p.name)
+                        ),
+                        "happy shiny egg give u stuffs",
+                    ))
+                    .into(),
+                    comment("WAT I TAUGHT ET WAZ ROCC!?!?!!"),
+                    divider(),
+                ];
+
+                possessions.extend_from_slice(&spawned);
+                events.push((
+                    hs.user_id.clone(),
+                    GameEvent::Hatch {
+                        steader: hs.user_id.clone(),
+                        egg: p.name.clone(),
+                        items: names(&spawned),
+                        percentile,
+                    },
+                ));
+
+                msg.append(&mut format_yield(spawned, hs.user_id.clone()));
+                dms.push((
+                    hs.user_id.clone(),
+                    msg.clone(),
+                    format!("Your {} hatched!", p.name),
+                ));
+                market_logs.push((
+                    hs.user_id.clone(),
+                    msg,
+                    format!("{} hatched a {}!", mention(&hs.user_id), p.name),
+                ));
+            } else {
+                warn!("egg hatch ignored; hack attempt?")
+            }
+        }
+    }
+
+    // Launch requested crafts
+    for hs in hacksteaders.iter_mut() {
+        let nb = hs.neighbor_bonuses();
+        let Hacksteader {
+            inventory, land, ..
+        } = hs;
+
+        let mut land_iter = land.iter_mut();
+        while let Some(Tile {
+            plant: Some(ref mut plant),
+            steader,
+            id,
+            ..
+        }) = land_iter.next()
+        {
+            let config::PlantAdvancementSum {
+                recipes,
+                craft_return_chance,
+                ..
+            } = plant.advancements_sum(
+                nb.clone()
+                    .bonuses_for_plant(*id, plant.archetype_handle)
+                    .iter(),
+            );
+
+            let queued_craft = craft_queue.remove(&id);
+            if let Some(recipe_archetype_handle) = queued_craft {
+                consumed.push((
+                    steader.clone(),
+                    FarmingInputEvent::BeginCraft {
+                        tile_id: *id,
+                        recipe_archetype_handle,
+                    },
+                ));
+            }
+            if let Some((recipe_archetype_handle, recipe)) = queued_craft
+                .filter(|_| plant.craft.is_none())
+                .and_then(|i| Some((i, recipes.get(i)?)))
+            {
+                let should_take: usize = recipe.needs.iter().map(|(n, _)| n).sum::<usize>();
+                let used_resources = recipe
+                    .needs
+                    .clone()
+                    .into_iter()
+                    .flat_map(|(count, ah)| {
+                        inventory
+                            .iter()
+                            .filter(move |p| p.archetype_handle == ah)
+                            .take(count)
+                    })
+                    .collect::<Vec<_>>();
+
+                if should_take == used_resources.len() {
+                    deletions.append(
+                        &mut used_resources
+                            .into_iter()
+                            .filter(|p| {
+                                let (drawn, roll) = dice.roll(
+                                    RollFor::new("craft_return", p.id, now_cycle, 0),
+                                    format!("a {} chance to keep it", craft_return_chance),
+                                    Odds::Chance(craft_return_chance),
+                                    draw,
+                                    drew,
+                                );
+                                rolls.push((steader.clone(), roll));
+                                let keep = drawn < craft_return_chance;
+                                if keep {
+                                    debug!("mommy can we keep it? YES? YESSS");
+                                    dms.push((
+                                        steader.clone(),
+                                        vec![
+                                            comment(
+                                                "your craft return bonus just came in quite handy!",
+                                            ),
+                                            comment(format!(
+                                                "looks like you get to keep a {} from that craft!",
+                                                &p.name,
+                                            )),
+                                        ],
+                                        "What's this, a crafting bonus‽".to_string(),
+                                    ));
+                                }
+                                !keep
+                            })
+                            .map(|p| (steader.clone(), p.key()))
+                            .collect(),
+                    );
+
+                    debug!("submitting craft");
+                    plant.craft = Some(hacksteader::Craft {
+                        until_finish: recipe.time,
+                        recipe_archetype_handle,
+                    });
+                } else {
+                    dms.push((
+                        steader.clone(),
+                        vec![
+                            comment("you don't have enough resources to craft that"),
+                            comment("nice try tho"),
+                        ],
+                        "You sure you have enough to craft that? Check again...".to_string(),
+                    ));
+                }
+            }
+        }
+    }
+
+    // we'll be frequently looking up profiles by who owns them to award xp.
+    let mut profiles: HashMap<String, Profile> = hacksteaders
+        .iter()
+        .map(|hs| (hs.user_id.clone(), hs.profile.clone()))
+        .collect();
+
+    // we only want to update the time on someone's profile once
+    // even though they might have several plants, any of which
+    // might be boosted, so we give them a "plant token" for
+    // each of their plants, and move them forward when they
+    // run out of tokens
+    let mut plant_tokens: HashMap<String, usize> = hacksteaders
+        .iter()
+        .map(|hs| {
+            (
+                hs.user_id.clone(),
+                hs.land.iter().filter_map(|t| t.plant.as_ref()).count(),
+            )
+        })
+        .collect();
+
+    // same goes with the neighbor bonuses for each hackstead
+    let neighbor_bonuses: HashMap<String, _> = hacksteaders
+        .iter()
+        .map(|hs| (hs.user_id.clone(), hs.neighbor_bonuses()))
+        .collect();
+
+    // we can only farm on tiles with plants,
+    let mut tiles: Vec<(Plant, Tile)> = hacksteaders
+        .into_iter()
+        .flat_map(|hs| hs.land.into_iter())
+        .filter_map(|mut t| {
+            Some((
+                t.plant.take().or_else(|| {
+                    plant_queue.remove(&t.id).map(|plant| {
+                        consumed.push((
+                            t.steader.clone(),
+                            FarmingInputEvent::PlantSeed(t.id, plant.clone()),
+                        ));
+                        profiles
+                            .get_mut(&t.steader)
+                            .expect("tile has no owner")
+                            .last_farm = now;
+                        plant
+                    })
+                })?,
+                t,
+            ))
+        })
+        .collect();
+
+    // remove inactive users
+    for (user, fresh) in active_users.clone().into_iter() {
+        let profile = match profiles.get_mut(&user) {
+            Some(profile) => profile,
+            None => continue,
+        };
+        if fresh {
+            profile.last_active = now;
+        } else if now
+            .duration_since(profile.last_active)
+            .ok()
+            .filter(|r| r.as_secs() >= ACTIVE_DURATION_SECS)
+            .is_some()
+        {
+            active_users.remove(&user);
+        }
+    }
+
+    // game tick loop:
+    // this is where we go through and we increment each xp/craft/yield
+    let mut catching_up = HashSet::new();
+    let mut moved: HashMap<String, u128> = HashMap::new();
+    for (plant, tile) in tiles.iter_mut() {
+        let profile = match profiles.get_mut(&tile.steader) {
+            Some(profile) => profile,
+            None => {
+                error!(
+                    concat!(
+                        "ignoring 1 active user: ",
+                        "couldn't get tile[{}]'s steader[{}]'s profile",
+                    ),
+                    tile.id, tile.steader
+                );
+                continue;
+            }
+        };
+
+        let neighbor_bonuses = match neighbor_bonuses.get(&tile.steader) {
+            Some(bonuses) => bonuses,
+            None => {
+                error!(
+                    concat!(
+                        "ignoring 1 active user: ",
+                        "couldn't get tile[{}]'s steader[{}]'s neighbor bonuses",
+                    ),
+                    tile.id, tile.steader
+                );
+                continue;
+            }
+        };
+        let neighbor_bonuses = neighbor_bonuses
+            .clone()
+            .bonuses_for_plant(tile.id, plant.archetype_handle);
+
+        // amount of farm cycles since the last farm, rounded down
+        let elapsed = now
+            .duration_since(profile.last_farm)
+            .unwrap_or_default()
+            .as_millis()
+            / (FARM_CYCLE_MILLIS as u128);
+
+        // which cycle it was when they were last farmed, so rolls know which cycle they're for
+        let first_cycle = roll::cycle_at(profile.last_farm);
+
+        // if they've been gone long enough, only some of that will be done this tick
+        let cap = cycle_caps.get(&profile.id).copied();
+        let cycles = elapsed
+            .min(MAX_CYCLES_PER_TICK)
+            .min(cap.unwrap_or(u128::MAX));
+        if cycles < elapsed {
+            catching_up.insert(profile.id.clone());
+        }
+        moved.insert(profile.id.clone(), cycles);
+
+        // and rather than hearing about everything as it happens,
+        // they'll get told what they missed once they're caught up
+        let mut away = if elapsed >= AWAY_CYCLES || away_summaries.contains_key(&profile.id) {
+            Some(away_summaries.entry(profile.id.clone()).or_default())
+        } else {
+            None
+        };
+
+        // increment their profile's "last farm" time so we can calculate
+        // an accurate "elapsed" during the next update.
+        if cycles > 0 {
+            if let Some(tokens) = plant_tokens.get_mut(&profile.id) {
+                *tokens = *tokens - 1;
+                if *tokens == 0 {
+                    debug!("all plants finished for {}", profile.id);
+                    // we don't want to add the boosted_elapsed here, then your item effects
+                    // would have to be "paid for" later (your farm wouldn't work for however
+                    // much time the effect gave you).
+                    profile.last_farm += Duration::from_millis(
+                        (FARM_CYCLE_MILLIS as u128 * cycles)
+                            .try_into()
+                            .unwrap_or_else(|e| {
+                                error!("too many farm cycle millis * cycles[{}]: {}", cycles, e);
+                                0
+                            }),
+                    );
+                    if let Some(away) = away.as_mut() {
+                        away.cycles += cycles as u64;
+                    }
+                }
+            }
+        }
+
+        debug!(
+            "elapsing {} of {} cycles for {}",
+            cycles, elapsed, profile.id
+        );
+
+        let mut cycle = 0;
+        while cycle < cycles {
+            if let Some(skipped) = fast_forward(plant, &neighbor_bonuses, profile, cycles - cycle) {
+                cycle += skipped;
+                continue;
+            }
+            let this_cycle = first_cycle + cycle as u64;
+            cycle += 1;
+
+            plant.effects = plant
+                .effects
+                .iter_mut()
+                .filter_map(|e| {
+                    if let Some(uf) = e.until_finish.as_mut() {
+                        // decrement counter, remove if 0
+                        *uf = (*uf - 1.0).max(0.0);
+                        if *uf == 0.0 {
+                            debug!("removing effect: {:?}", e);
+                            return None;
+                        }
+                    }
+
+                    Some(*e)
+                })
+                .collect::<Vec<_>>();
+
+            // you want to recalculate this every update because it's dependent
+            // on what effects are active, especially the `total_extra_time_ticks`.
+            let plant_sum = plant.advancements_sum(neighbor_bonuses.iter());
+
+            let ticks = plant_sum.total_extra_time_ticks + 1;
+            debug!("triggering {} ticks for {}'s cycle", ticks, profile.id);
+            for tick in 0..ticks {
+                let roll_for = |kind| RollFor::new(kind, tile.id, this_cycle, tick as u64);
+
+                plant.craft = match plant
+                    .current_recipe_raw()
+                    .and_then(|r| Some((r, plant.craft.take()?)))
+                {
+                    Some((recipe, mut craft)) => {
+                        if craft.until_finish > plant_sum.crafting_speed_multiplier {
+                            craft.until_finish -= plant_sum.crafting_speed_multiplier;
+                            Some(craft)
+                        } else {
+                            let (lo, hi) = recipe.xp;
+                            let (earned_xp, roll) = dice.roll(
+                                roll_for("craft_xp"),
+                                format!("{} to {} xp", lo, hi),
+                                Odds::Between(lo, hi),
+                                |rng| rng.gen_range(lo, hi),
+                                got_xp,
+                            );
+                            rolls.push((tile.steader.clone(), roll));
+                            plant.queued_xp_bonus += earned_xp;
+
+                            let makes: Vec<config::ArchetypeHandle> =
+                                recipe.makes.clone().output().into_iter().collect();
+
+                            let chance = plant_sum.double_craft_yield_chance;
+                            let (drawn, roll) = dice.roll(
+                                roll_for("double_craft"),
+                                format!("a {} chance to double", chance),
+                                Odds::Chance(chance),
+                                draw,
+                                drew,
+                            );
+                            rolls.push((tile.steader.clone(), roll));
+                            let output = crafted(&makes, &tile.steader, drawn < chance);
+                            debug!("crafted: {:?}", output);
+                            possessions.extend_from_slice(&output);
+
+                            let title = recipe.clone().lookup_handles().unwrap().title();
+                            events.push((
+                                tile.steader.clone(),
+                                GameEvent::CraftFinished {
+                                    steader: tile.steader.clone(),
+                                    tile: tile.id,
+                                    plant: plant.name.clone(),
+                                    recipe: title.clone(),
+                                    items: names(&output),
+                                    xp: earned_xp,
+                                },
+                            ));
+                            if let Some(away) = away.as_mut() {
+                                away.got(&output);
+                                *away.crafts.entry(title).or_insert(0) += 1;
+                            } else {
+                                let mut msg = vec![
+                                    section(mrkdwn(format!(
+                                        concat!(
+                                            "Your *{}* has finished crafting *{}* for you!\n",
+                                            "This earned it {} xp!",
+                                        ),
+                                        plant.name, title, earned_xp,
+                                    )))
+                                    .accessory(image(
+                                        format!(
+                                            "http://{}/gotchi/img/plant/{}.gif",
+                                            *URL,
+                                            filify(&plant.current_advancement().art)
+                                        ),
+                                        "happy shiny plant give u stuffs",
+                                    ))
+                                    .into(),
+                                    comment("YAY FREE STUFFZ 'CEPT LIKE IT'S NOT FREE"),
+                                    divider(),
+                                ];
+
+                                msg.append(&mut format_yield(output, tile.steader.clone()));
+                                dms.push((
+                                    tile.steader.clone(),
+                                    msg,
+                                    format!("What's this, a new {}?", title),
+                                ));
+                            }
+
+                            if recipe.destroys_plant {
+                                clear_plants.push(tile.id.clone());
+                            }
+
+                            None
+                        }
+                    }
+                    None => None,
+                };
+
+                plant.until_yield = match plant.until_yield - plant_sum.yield_speed_multiplier {
+                    n if n > 0.0 => n,
+                    _ if plant.base_yield_duration.is_some() => {
+                        let owner = &tile.steader;
+                        let (spawned, roll) = dice.roll(
+                            roll_for("yield"),
+                            format!("the yield table of a {}", plant.name),
+                            Odds::Yields(table_json(&plant_sum.yields)),
+                            |rng| config::spawn(&plant_sum.yields, rng).collect::<Vec<_>>(),
+                            got,
+                        );
+                        rolls.push((tile.steader.clone(), roll));
+                        let (yielded, xp_bonuses): (Vec<_>, Vec<_>) = spawned
+                            .into_iter()
+                            .map(|(ah, xp)| {
+                                (
+                                    Possession::new(ah, possess::Owner::farmer(owner.clone())),
+                                    xp,
+                                )
+                            })
+                            .unzip();
+                        let earned_xp = xp_bonuses.into_iter().sum::<usize>() as u64;
+
+                        plant.queued_xp_bonus += earned_xp;
+                        possessions.extend_from_slice(&yielded);
+                        events.push((
+                            tile.steader.clone(),
+                            GameEvent::Yield {
+                                steader: tile.steader.clone(),
+                                tile: tile.id,
+                                plant: plant.name.clone(),
+                                items: names(&yielded),
+                                xp: earned_xp,
+                            },
+                        ));
+
+                        if let Some(away) = away.as_mut() {
+                            away.got(&yielded);
+                        } else {
+                            let mut msg = vec![
+                                section(mrkdwn(format!(
+                                    concat!(
+                                        "Your *{}* has produced a crop yield for you!\n",
+                                        "This earned it {} xp!"
+                                    ),
+                                    plant.name, earned_xp,
+                                )))
+                                .accessory(image(
+                                    format!(
+                                        "http://{}/gotchi/img/plant/{}.gif",
+                                        *URL,
+                                        filify(&plant.current_advancement().art)
+                                    ),
+                                    "happy shiny plant give u stuffs",
+                                ))
+                                .into(),
+                                comment("FREE STUFF FROM CUTE THING"),
+                                divider(),
+                            ];
+
+                            msg.append(&mut format_yield(yielded, tile.steader.clone()));
+
+                            dms.push((
+                                tile.steader.clone(),
+                                msg,
+                                "FREE STUFF FROM CUTE THING".to_string(),
+                            ));
+                        }
+
+                        plant.base_yield_duration.unwrap_or(0.0)
+                    }
+                    n => n,
+                };
+
+                if let Some(advancement) = plant.increase_xp(plant_sum.xp_multiplier) {
+                    events.push((
+                        tile.steader.clone(),
+                        GameEvent::PlantLevelUp {
+                            steader: tile.steader.clone(),
+                            tile: tile.id,
+                            plant: plant.name.clone(),
+                            title: advancement.achiever_title.clone(),
+                        },
+                    ));
+                    let notif = format!(
+                        "Your {} is now a {}!",
+                        plant.name, advancement.achiever_title
+                    );
+                    if let Some(away) = away.as_mut() {
+                        away.level_ups.push(notif);
+                    } else {
+                        dms.push((
+                            tile.steader.clone(),
+                            vec![
+                                section(mrkdwn(format!(
+                                    concat!(
+                                        ":tada: Your _{}_ is now a *{}*!\n\n",
+                                        "*{}* Achieved:\n _{}_\n\n",
+                                        ":stonks: Total XP: *{}xp*",
+                                    ),
+                                    plant.name,
+                                    advancement.achiever_title,
+                                    advancement.title,
+                                    advancement.description,
+                                    advancement.xp
+                                )))
+                                .accessory(image(
+                                    format!(
+                                        "http://{}/gotchi/img/plant/{}.gif",
+                                        *URL,
+                                        filify(&advancement.art)
+                                    ),
+                                    "happy shiny better plant",
+                                ))
+                                .into(),
+                                comment("EXCITING LEVELING UP NOISES"),
+                            ],
+                            notif,
+                        ))
+                    }
+                }
+                let profile_sum = profile.advancements.sum(profile.xp, std::iter::empty());
+                if let Some(advancement) = profile.increase_xp(plant_sum.xp_multiplier) {
+                    events.push((
+                        tile.steader.clone(),
+                        GameEvent::HacksteadLevelUp {
+                            steader: tile.steader.clone(),
+                            title: advancement.achiever_title.clone(),
+                        },
+                    ));
+                    let notif = format!("Your Hackstead is now a {}!", advancement.achiever_title);
+                    if let Some(away) = away.as_mut() {
+                        away.level_ups.push(notif);
+                    } else {
+                        dms.push((
+                            tile.steader.clone(),
+                            vec![
+                                section(mrkdwn(format!(
+                                    concat!(
+                                        ":tada: Your _Hackstead_ is now a *{}*!\n\n",
+                                        "*{}* Achieved:\n_{}_\n\n",
+                                        ":stonks: Total XP: *{}xp*\n",
+                                        ":mountain: Land Available: *{} pieces* _(+{} pieces)_"
+                                    ),
+                                    advancement.achiever_title,
+                                    advancement.title,
+                                    advancement.description,
+                                    advancement.xp,
+                                    profile_sum.land,
+                                    match advancement.kind {
+                                        config::HacksteadAdvancementKind::Land { pieces } => pieces,
+                                    }
+                                )))
+                                .accessory(image(
+                                    format!("http://{}/gotchi/img/icon/seedlet.png", *URL),
+                                    "happy shiny better hackstead",
+                                ))
+                                .into(),
+                                comment("SUPER EXCITING LEVELING UP NOISES"),
+                            ],
+                            notif,
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    // tell whoever's all caught up what they missed
+    let caught_up: Vec<String> = away_summaries
+        .keys()
+        .filter(|who| !catching_up.contains(*who))
+        .cloned()
+        .collect();
+    for who in caught_up {
+        if let Some(summary) = away_summaries.remove(&who) {
+            dms.push((who, summary.blocks(), "While you were away...".to_string()));
+        }
+    }
+
+    let mut steaders: HashMap<String, SteaderOutput> = HashMap::new();
+    for (id, profile) in profiles {
+        steaders
+            .entry(id)
+            .or_default()
+            .writes
+            .profiles
+            .push(profile);
+    }
+    for (plant, mut tile) in tiles {
+        tile.plant = if clear_plants.iter().any(|id| *id == tile.id) {
+            None
+        } else {
+            Some(plant)
+        };
+        steaders
+            .entry(tile.steader.clone())
+            .or_default()
+            .writes
+            .tiles
+            .push(tile);
+    }
+    for tile in new_tiles {
+        steaders
+            .entry(tile.steader.clone())
+            .or_default()
+            .writes
+            .tiles
+            .push(tile);
+    }
+    for p in possessions {
+        steaders
+            .entry(p.steader.clone())
+            .or_default()
+            .writes
+            .possessions
+            .push(p);
+    }
+    for (who, key) in deletions {
+        steaders.entry(who).or_default().writes.deletions.push(key);
+    }
+    for dm in dms {
+        steaders.entry(dm.0.clone()).or_default().dms.push(dm);
+    }
+    for (who, blocks, notif) in market_logs {
+        steaders
+            .entry(who)
+            .or_default()
+            .market_logs
+            .push((blocks, notif));
+    }
+    for (who, e) in consumed {
+        steaders.entry(who).or_default().consumed.push(e);
+    }
+    for (who, roll) in rolls {
+        steaders.entry(who).or_default().rolls.push(roll);
+    }
+    for (who, event) in events {
+        steaders.entry(who).or_default().events.push(event);
+    }
+    for (who, cycles) in moved {
+        steaders.entry(who).or_default().cycles = cycles as u64;
+    }
+    cycle_caps.retain(|who, _| catching_up.contains(who));
+
+    (
+        FarmState {
+            active_users,
+            catching_up,
+            away: away_summaries,
+            cycle_caps,
+            queues: InputQueues {
+                plant_queue,
+                craft_queue,
+                item_application_queue,
+                land_cert_queue,
+                hatch_egg_queue,
+            },
+        },
+        FarmOutput { steaders },
+    )
+}
+
+/// What finishing a craft that makes `makes` gives `steader`, twice over if it's `doubled`.
+/// Each copy is a possession of its own, so none of them share a key.
+fn crafted(makes: &[config::ArchetypeHandle], steader: &str, doubled: bool) -> Vec<Possession> {
+    let times = if doubled { 2 } else { 1 };
+    (0..times)
+        .flat_map(|_| makes.iter())
+        .map(|&ah| Possession::new(ah, possess::Owner::crafter(steader.to_string())))
+        .collect()
+}
+
+// how each kind of roll is made and described, so that `reroll` can do it the same way
+
+fn draw(rng: &mut ChaCha20Rng) -> f32 {
+    rng.gen_range(0.0, 1.0)
+}
+
+fn drew(drawn: &f32) -> String {
+    format!("drew {}", drawn)
+}
+
+fn got_xp(xp: &u64) -> String {
+    format!("got {} xp", xp)
+}
+
+fn got(spawned: &impl std::fmt::Debug) -> String {
+    format!("got {:?}", spawned)
+}
+
+fn hatched(handles: &impl std::fmt::Debug, percentile: f32) -> String {
+    format!("got {:?}, {:.2}th percentile", handles, percentile * 100.0)
+}
+
+fn table_json(table: &impl Serialize) -> serde_json::Value {
+    serde_json::to_value(table).unwrap_or_else(|e| {
+        error!("couldn't keep the table a roll was made against: {}", e);
+        serde_json::Value::Null
+    })
+}
+
+/// Makes a kept roll over again from its seed, the same way `tick` made it, and describes
+/// what came of it, so that it can be checked against what the roll says came of it.
+pub fn reroll(dice: &Dice, roll: &Roll) -> Result<String, String> {
+    fn table<T: serde::de::DeserializeOwned>(json: &serde_json::Value) -> Result<T, String> {
+        serde_json::from_value(json.clone())
+            .map_err(|e| format!("couldn't parse the table it was rolled against: {}", e))
+    }
+
+    let rng = &mut dice.rng(&roll.what);
+    match roll
+        .against
+        .as_ref()
+        .ok_or_else(|| "it was kept without its odds".to_string())?
+    {
+        Odds::Chance(_) => Ok(drew(&draw(rng))),
+        Odds::Between(lo, hi) => Ok(got_xp(&rng.gen_range(*lo, *hi))),
+        Odds::Yields(json) => {
+            let yields: Vec<_> = table(json)?;
+            Ok(got(&config::spawn(&yields, rng).collect::<Vec<_>>()))
+        }
+        Odds::Hatch(json) => {
+            let hatch_table = table(json)?;
+            let (handles, percentile) = config::spawn_with_percentile(&hatch_table, rng);
+            Ok(hatched(&handles, percentile))
+        }
+    }
+}
+
+/// Moves a plant and its steader's profile forward by as many whole cycles as it can
+/// (up to `max_cycles`) without anything happening that anyone would need to hear about.
+/// Returns None if the next cycle has to be simulated on its own.
+///
+/// This only works for plants that aren't crafting and don't have effects that wear off,
+/// since every cycle for those is the same as the last until they yield or level up.
+fn fast_forward(
+    plant: &mut Plant,
+    neighbor_bonuses: &[config::PlantAdvancement],
+    profile: &mut Profile,
+    max_cycles: u128,
+) -> Option<u128> {
+    if plant.craft.is_some()
+        || plant.queued_xp_bonus > 0
+        || plant.effects.iter().any(|e| e.until_finish.is_some())
+    {
+        return None;
+    }
+
+    let sum = plant.advancements_sum(neighbor_bonuses.iter());
+    let ticks_per_cycle = sum.total_extra_time_ticks as u128 + 1;
+    let xp_per_tick = sum.xp_multiplier as u128;
+
+    // how many ticks can go by before something happens
+    let mut quiet_ticks = u128::MAX;
+    if plant.base_yield_duration.is_some() {
+        if sum.yield_speed_multiplier > 0.0 {
+            let until_yield = (plant.until_yield / sum.yield_speed_multiplier).ceil();
+            quiet_ticks = quiet_ticks.min(until_yield.max(1.0) as u128 - 1);
+        } else if plant.until_yield - sum.yield_speed_multiplier <= 0.0 {
+            return None;
+        }
+    }
+    if xp_per_tick > 0 {
+        let plant_next = plant.next_advancement().map(|a| (plant.xp, a.xp));
+        let profile_next = profile
+            .advancements
+            .next(profile.xp)
+            .map(|a| (profile.xp, a.xp));
+        for (xp, next) in plant_next.into_iter().chain(profile_next) {
+            quiet_ticks = quiet_ticks.min(next.saturating_sub(xp + 1) as u128 / xp_per_tick);
+        }
+    }
+
+    let skipped = (quiet_ticks / ticks_per_cycle).min(max_cycles);
+    if skipped == 0 {
+        return None;
+    }
+
+    let ticks = skipped * ticks_per_cycle;
+    let xp = (ticks * xp_per_tick).try_into().ok()?;
+    plant.until_yield -= ticks as f32 * sum.yield_speed_multiplier;
+    plant.increase_xp(xp);
+    profile.increase_xp(xp);
+
+    Some(skipped)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::chat::{recorded, Channel, Sent};
+    use possess::Possessed;
+    use std::time::UNIX_EPOCH;
+
+    const STEADER: &str = "U1";
+
+    fn now() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_600_000_000)
+    }
+
+    fn dice() -> Dice {
+        Dice::new("not very secret")
+    }
+
+    /// A plant that's learned everything it can, so that nothing it does changes
+    /// how fast it grows.
+    fn grown(archetype_handle: config::ArchetypeHandle) -> Plant {
+        let mut plant = Plant {
+            archetype_handle,
+            ..Default::default()
+        };
+        while let Some(xp) = plant
+            .next_advancement()
+            .map(|a| a.xp)
+            .filter(|&xp| xp > plant.xp)
+        {
+            plant.xp = xp;
+        }
+        plant
+    }
+
+    fn yielding_plant() -> Plant {
+        (0..CONFIG.plant_archetypes.len())
+            .map(grown)
+            .find(|p| p.base_yield_duration.is_some())
+            .expect("no plant yields")
+    }
+
+    /// A hackstead with one plant on it, last farmed `cycles_ago`.
+    fn hackstead(plant: Plant, cycles_ago: u64) -> Hacksteader {
+        std::env::set_var("URL", "localhost");
+        std::env::set_var("CHAT", "recording");
+        let mut profile = Profile::new(STEADER.to_string());
+        profile.last_farm = now() - Duration::from_millis(FARM_CYCLE_MILLIS * cycles_ago);
+        profile.last_active = now();
+        let mut tile = Tile::new(STEADER.to_string());
+        tile.plant = Some(plant);
+        Hacksteader {
+            user_id: STEADER.to_string(),
+            profile,
+            land: vec![tile],
+            inventory: vec![],
+            gotchis: vec![],
+        }
+    }
+
+    fn farm(hs: Hacksteader) -> SteaderOutput {
+        let (_, mut output) = tick(FarmState::default(), vec![hs], vec![], &dice(), now());
+        output
+            .steaders
+            .remove(STEADER)
+            .expect("nothing for the steader")
+    }
+
+    fn farmed_plant(out: &SteaderOutput) -> Option<&Plant> {
+        out.writes.tiles[0].plant.as_ref()
+    }
+
+    fn yields(out: &SteaderOutput) -> usize {
+        out.events
+            .iter()
+            .filter(|e| matches!(e, GameEvent::Yield { .. }))
+            .count()
+    }
+
+    /// How many ticks the plant gets each cycle, and how much closer each brings it to yielding.
+    fn pace(plant: &Plant) -> (f32, f32) {
+        let sum = plant.advancements_sum(std::iter::empty());
+        (
+            sum.total_extra_time_ticks as f32 + 1.0,
+            sum.yield_speed_multiplier,
+        )
+    }
+
+    #[test]
+    fn nothing_happens_before_a_cycle_is_up() {
+        let plant = yielding_plant();
+        let out = farm(hackstead(plant.clone(), 0));
+        assert!(out.events.is_empty());
+        assert_eq!(farmed_plant(&out).unwrap().until_yield, plant.until_yield);
+        assert_eq!(out.writes.profiles[0].last_farm, now());
+    }
+
+    #[test]
+    fn yields_when_its_time() {
+        let mut plant = yielding_plant();
+        let (ticks, speed) = pace(&plant);
+        // ten cycles' worth of growing, less half a tick
+        plant.until_yield = speed * ticks * 10.0 - speed / 2.0;
+
+        let out = farm(hackstead(plant.clone(), 9));
+        assert_eq!(yields(&out), 0);
+        assert!(farmed_plant(&out).unwrap().until_yield > 0.0);
+
+        let out = farm(hackstead(plant.clone(), 10));
+        assert_eq!(yields(&out), 1);
+        assert_eq!(
+            farmed_plant(&out).unwrap().until_yield,
+            plant.base_yield_duration.unwrap()
+        );
+        assert_eq!(out.writes.profiles[0].last_farm, now());
+    }
+
+    #[test]
+    fn yields_are_rolled_the_same_way_every_time() {
+        let mut plant = yielding_plant();
+        plant.until_yield = 0.0;
+        let outcomes = |out: SteaderOutput| -> Vec<(String, String)> {
+            out.rolls
+                .into_iter()
+                .map(|r| (r.what.id(), r.outcome))
+                .collect()
+        };
+
+        let first = outcomes(farm(hackstead(plant.clone(), 1)));
+        assert!(!first.is_empty());
+        assert_eq!(first, outcomes(farm(hackstead(plant, 1))));
+    }
+
+    #[test]
+    fn rolls_can_be_made_again() {
+        let mut plant = yielding_plant();
+        plant.until_yield = 0.0;
+        let out = farm(hackstead(plant, 1));
+        assert!(!out.rolls.is_empty());
+        for roll in out.rolls {
+            assert_eq!(reroll(&dice(), &roll), Ok(roll.outcome.clone()));
+        }
+    }
+
+    /// What a farm cycle sends once it's saved.
+    async fn announced(out: SteaderOutput) -> Vec<Sent> {
+        let (sent, recording) = recorded(announce(out.dms, out.market_logs)).await;
+        sent.expect("couldn't announce farm cycle");
+        recording
+    }
+
+    #[rocket::async_test]
+    async fn yields_are_sent_to_the_steader() {
+        let mut plant = yielding_plant();
+        plant.until_yield = 0.0;
+
+        let sent = announced(farm(hackstead(plant, 1))).await;
+        assert_eq!(sent.len(), 1);
+        assert!(matches!(
+            &sent[0],
+            Sent::Dm { user_id, notif_msg, .. }
+                if user_id == STEADER && notif_msg == "FREE STUFF FROM CUTE THING"
+        ));
+    }
+
+    #[rocket::async_test]
+    async fn hatches_are_sent_to_the_steader_and_the_market() {
+        let egg = (0..CONFIG.possession_archetypes.len())
+            .filter_map(|ah| {
+                Possessed::<possess::Gotchi>::from_possession(Possession::new(
+                    ah,
+                    possess::Owner::hatcher(STEADER.to_string()),
+                ))
+            })
+            .find(|g| g.inner.hatch_table.is_some())
+            .expect("no gotchi hatches");
+        let mut hs = hackstead(yielding_plant(), 0);
+        hs.gotchis.push(egg.clone());
+
+        let (_, mut output) = tick(
+            FarmState::default(),
+            vec![hs],
+            vec![FarmingInputEvent::HatchEgg(egg.id, STEADER.to_string())],
+            &dice(),
+            now(),
+        );
+        let out = output.steaders.remove(STEADER).unwrap();
+        assert_eq!(out.writes.deletions.len(), 1);
+        assert!(out
+            .events
+            .iter()
+            .any(|e| matches!(e, GameEvent::Hatch { egg: name, .. } if *name == egg.name)));
+
+        let sent = announced(out).await;
+        assert_eq!(sent.len(), 2);
+        let hatched = format!("Your {} hatched!", egg.name);
+        assert!(sent.iter().any(|s| matches!(
+            s,
+            Sent::Dm { user_id, notif_msg, .. } if user_id == STEADER && *notif_msg == hatched
+        )));
+        assert!(sent.iter().any(|s| matches!(
+            s,
+            Sent::Log {
+                channel: Channel::Market,
+                ..
+            }
+        )));
+    }
+
+    #[test]
+    fn finishes_crafts() {
+        let mut plant = (0..CONFIG.plant_archetypes.len())
+            .map(grown)
+            .find(|p| !p.advancements_sum(std::iter::empty()).recipes.is_empty())
+            .expect("no plant crafts");
+        plant.craft = Some(hacksteader::Craft {
+            until_finish: 0.0,
+            recipe_archetype_handle: 0,
+        });
+
+        let out = farm(hackstead(plant, 1));
+        let tile_id = out.writes.tiles[0].id;
+        assert!(out.events.iter().any(|e| matches!(
+            e,
+            GameEvent::CraftFinished { tile, .. } if *tile == tile_id
+        )));
+        assert!(farmed_plant(&out).map_or(true, |p| p.craft.is_none()));
+    }
+
+    #[test]
+    fn keeps_crafting_until_its_done() {
+        let (mut plant, speed) = (0..CONFIG.plant_archetypes.len())
+            .map(grown)
+            .find_map(|p| {
+                let sum = p.advancements_sum(std::iter::empty());
+                if sum.recipes.is_empty() {
+                    None
+                } else {
+                    Some((p, sum.crafting_speed_multiplier))
+                }
+            })
+            .expect("no plant crafts");
+        let (ticks, _) = pace(&plant);
+        plant.craft = Some(hacksteader::Craft {
+            until_finish: speed * ticks * 2.0 + speed / 2.0,
+            recipe_archetype_handle: 0,
+        });
+
+        let out = farm(hackstead(plant, 1));
+        assert!(!out
+            .events
+            .iter()
+            .any(|e| matches!(e, GameEvent::CraftFinished { .. })));
+        assert!(farmed_plant(&out).unwrap().craft.is_some());
+    }
+
+    #[test]
+    fn doubled_crafts_are_possessions_of_their_own() {
+        let output = crafted(&[0, 0], STEADER, true);
+        assert_eq!(output.len(), 4);
+        let keys: HashSet<uuid::Uuid> = output.iter().map(|p| p.key().id).collect();
+        assert_eq!(keys.len(), 4);
+        assert!(output.iter().all(|p| p.steader == STEADER));
+    }
+
+    #[test]
+    fn steaders_too_big_to_save_are_farmed_fewer_cycles_at_a_time() {
+        let mut state = FarmState::default();
+        state.farm_fewer_cycles(STEADER, 10);
+
+        let hs = hackstead(yielding_plant(), 10);
+        let (state, mut output) = tick(state, vec![hs], vec![], &dice(), now());
+        let out = output.steaders.remove(STEADER).unwrap();
+        assert_eq!(out.cycles, 5);
+        assert_eq!(
+            out.writes.profiles[0].last_farm,
+            now() - Duration::from_millis(FARM_CYCLE_MILLIS * 5)
+        );
+        assert!(state.catching_up.contains(STEADER));
+
+        // once they're caught up, there's no need to hold them back anymore
+        let hs = hackstead(yielding_plant(), 5);
+        let (state, _) = tick(state, vec![hs], vec![], &dice(), now());
+        assert!(!state.catching_up.contains(STEADER));
+        assert!(state.cycle_caps.is_empty());
+    }
+
+    #[test]
+    fn levels_up() {
+        let mut plant = Plant {
+            archetype_handle: (0..CONFIG.plant_archetypes.len())
+                .find(|&ah| {
+                    Plant {
+                        archetype_handle: ah,
+                        ..Default::default()
+                    }
+                    .next_advancement()
+                    .is_some()
+                })
+                .expect("no plant levels up"),
+            ..Default::default()
+        };
+        plant.until_yield = f32::MAX;
+        let next = plant.next_advancement().unwrap().xp;
+        plant.xp = next.saturating_sub(1);
+
+        let out = farm(hackstead(plant, 1));
+        assert!(out
+            .events
+            .iter()
+            .any(|e| matches!(e, GameEvent::PlantLevelUp { .. })));
+        assert!(farmed_plant(&out).unwrap().xp >= next);
+    }
+}
@@ -1,139 +1,218 @@
+use crate::store::{Batch, Store};
 use config::{ArchetypeHandle, PlantArchetype, CONFIG};
 use hcor::config;
 use hcor::possess;
-use hcor::{AttributeParseError, Category, Item, Key, Profile, TABLE_NAME};
+use hcor::{AttributeParseError, Category, Item, Key, Profile};
 use log::*;
 use possess::{Possessed, Possession};
-use rusoto_core::RusotoError;
-use rusoto_dynamodb::{AttributeValue, DynamoDb, DynamoDbClient, PutItemError};
+use rusoto_dynamodb::AttributeValue;
 use std::time::SystemTime;
 
-pub async fn exists(db: &DynamoDbClient, user_id: String) -> bool {
-    db.get_item(rusoto_dynamodb::GetItemInput {
-        key: Profile::key_item(user_id),
-        table_name: TABLE_NAME.to_string(),
-        ..Default::default()
-    })
-    .await
-    .map(|x| x.item.is_some())
-    .unwrap_or_else(|e| {
+pub async fn exists(db: &dyn Store, user_id: String) -> bool {
+    db.hacksteader_exists(&user_id).await.unwrap_or_else(|e| {
         error!("couldn't see if hacksteader exists: {}", e);
         false
     })
 }
 
-pub async fn get_possession(db: &DynamoDbClient, key: Key) -> Result<Possession, String> {
-    db.get_item(rusoto_dynamodb::GetItemInput {
-        key: key.clone().into_item(),
-        table_name: TABLE_NAME.to_string(),
-        ..Default::default()
-    })
-    .await
-    .map_err(|e| format!("couldn't read {:?} from db to get possession: {}", key, e))
-    .and_then(|x| {
-        match Possession::from_item(
-            &x.item
-                .ok_or_else(|| format!("no item at {:?} to get possession for", key))?,
-        ) {
-            Ok(p) => Ok(p),
-            Err(e) => Err(format!(
-                "couldn't parse possession to get possession: {}",
-                e
-            )),
-        }
-    })
+pub async fn get_possession(db: &dyn Store, key: Key) -> Result<Possession, String> {
+    db.possession(key).await
 }
 
-pub async fn get_tile(db: &DynamoDbClient, id: uuid::Uuid) -> Result<Tile, String> {
-    db.get_item(rusoto_dynamodb::GetItemInput {
-        key: Key::tile(id).into_item(),
-        table_name: TABLE_NAME.to_string(),
-        ..Default::default()
-    })
-    .await
-    .map_err(|e| format!("couldn't read {:?} from db to get tile: {}", id, e))
-    .and_then(|x| {
-        match Tile::from_item(
-            &x.item
-                .ok_or_else(|| format!("no item at {:?} to get possession for", id))?,
-        ) {
-            Ok(p) => Ok(p),
-            Err(e) => Err(format!(
-                "couldn't parse possession to get possession: {}",
-                e
-            )),
+pub async fn get_tile(db: &dyn Store, id: uuid::Uuid) -> Result<Tile, String> {
+    db.tile(id).await
+}
+
+/// Whose things a goblin command gets its hands on.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Scope {
+    All,
+    Steaders(Vec<String>),
+}
+impl Scope {
+    /// Either `all`, or the mentions of each steader to include.
+    pub fn parse(s: &str) -> Result<Scope, String> {
+        lazy_static::lazy_static! {
+            static ref MENTION: regex::Regex = regex::Regex::new("<@([A-z|0-9]+)>").unwrap();
         }
-    })
+
+        if s.trim() == "all" {
+            return Ok(Scope::All);
+        }
+        let steaders: Vec<String> = MENTION.captures_iter(s).map(|c| c[1].to_string()).collect();
+        if steaders.is_empty() {
+            Err(format!(
+                "`{}` should be `all` or some steaders to mention",
+                s
+            ))
+        } else {
+            Ok(Scope::Steaders(steaders))
+        }
+    }
+
+    pub fn includes(&self, steader: &str) -> bool {
+        match self {
+            Scope::All => true,
+            Scope::Steaders(steaders) => steaders.iter().any(|s| s == steader),
+        }
+    }
 }
 
-/// This function empties someone's profile, setting their xp to zero.
-pub async fn goblin_slaughter(db: &DynamoDbClient) -> Result<(), String> {
-    let profiles = Profile::fetch_all(db).await?;
-
-    db.batch_write_item(rusoto_dynamodb::BatchWriteItemInput {
-        request_items: [(
-            TABLE_NAME.to_string(),
-            profiles
-                .into_iter()
-                .map(|mut p| rusoto_dynamodb::WriteRequest {
-                    put_request: Some(rusoto_dynamodb::PutRequest {
-                        item: {
-                            p.last_farm = std::time::SystemTime::now();
-                            p.xp = 0;
-                            p.item()
-                        },
-                    }),
-                    ..Default::default()
-                })
-                .collect(),
-        )]
-        .iter()
-        .cloned()
-        .collect(),
+/// What a goblin command got its hands on, or would have on a dry run,
+/// as it was before the goblin got to it.
+#[derive(Debug, Clone, Default)]
+pub struct Goblined<T> {
+    pub touched: Vec<T>,
+    /// The snapshot to undo it with, if it wasn't a dry run.
+    pub snapshot: Option<String>,
+}
+
+/// Keeps a copy of what's about to be changed, unless nothing is really going to be.
+async fn goblin_snapshot(
+    db: &dyn Store,
+    dry_run: bool,
+    reason: &str,
+    batch: Batch,
+) -> Result<Option<String>, String> {
+    if dry_run || batch.is_empty() {
+        return Ok(None);
+    }
+    crate::snapshot::take(db, reason.to_string(), &batch)
+        .await
+        .map(Some)
+}
+
+/// This function empties the profiles in scope, setting their xp to zero.
+pub async fn goblin_slaughter(
+    db: &dyn Store,
+    scope: &Scope,
+    dry_run: bool,
+) -> Result<Goblined<Profile>, String> {
+    let touched: Vec<Profile> = db
+        .profiles()
+        .await?
+        .into_iter()
+        .filter(|p| scope.includes(&p.id))
+        .collect();
+    let snapshot = goblin_snapshot(
+        db,
+        dry_run,
+        "goblin slaughter",
+        Batch {
+            profiles: touched.clone(),
+            ..Default::default()
+        },
+    )
+    .await?;
+    if dry_run {
+        return Ok(Goblined { touched, snapshot });
+    }
+
+    db.write(Batch {
+        profiles: touched
+            .iter()
+            .cloned()
+            .map(|mut p| {
+                p.last_farm = std::time::SystemTime::now();
+                p.xp = 0;
+                p
+            })
+            .collect(),
         ..Default::default()
     })
     .await
     .map_err(|e| format!("couldn't write wiped profiles into db: {}", e))?;
 
-    Ok(())
+    Ok(Goblined { touched, snapshot })
 }
 
-/// This function removes the plants from all tiles.
+/// This function removes the plants from the tiles in scope.
 pub async fn goblin_stomp(
-    db: &DynamoDbClient,
+    db: &dyn Store,
     to_farming: &crossbeam_channel::Sender<super::FarmingInputEvent>,
-) -> Result<(), String> {
-    let tiles = Tile::fetch_all(db).await?;
-
-    db.batch_write_item(rusoto_dynamodb::BatchWriteItemInput {
-        request_items: [(
-            TABLE_NAME.to_string(),
-            tiles
-                .into_iter()
-                .map(|mut tile| rusoto_dynamodb::WriteRequest {
-                    put_request: Some(rusoto_dynamodb::PutRequest {
-                        item: {
-                            to_farming
-                                .send(super::FarmingInputEvent::ActivateUser(tile.steader.clone()))
-                                .unwrap();
-                            tile.plant.take();
-
-                            tile.into_av().m.expect("tile attribute should be map")
-                        },
-                    }),
-                    ..Default::default()
-                })
-                .collect(),
-        )]
-        .iter()
-        .cloned()
-        .collect(),
+    scope: &Scope,
+    dry_run: bool,
+) -> Result<Goblined<Tile>, String> {
+    let touched: Vec<Tile> = db
+        .tiles()
+        .await?
+        .into_iter()
+        .filter(|t| t.plant.is_some() && scope.includes(&t.steader))
+        .collect();
+    let snapshot = goblin_snapshot(
+        db,
+        dry_run,
+        "goblin stomp",
+        Batch {
+            tiles: touched.clone(),
+            ..Default::default()
+        },
+    )
+    .await?;
+    if dry_run {
+        return Ok(Goblined { touched, snapshot });
+    }
+
+    let mut steaders: Vec<String> = touched.iter().map(|t| t.steader.clone()).collect();
+    steaders.sort();
+    steaders.dedup();
+
+    db.write(Batch {
+        tiles: touched
+            .iter()
+            .cloned()
+            .map(|mut tile| {
+                tile.plant.take();
+                tile
+            })
+            .collect(),
         ..Default::default()
     })
     .await
     .map_err(|e| format!("couldn't write new land into db: {}", e))?;
 
-    Ok(())
+    for steader in steaders {
+        crate::farm::queue(to_farming, super::FarmingInputEvent::ActivateUser(steader)).await?;
+    }
+
+    Ok(Goblined { touched, snapshot })
+}
+
+/// This function deletes every misc item of an archetype that belongs to someone in scope.
+pub async fn goblin_nab(
+    db: &dyn Store,
+    archetype_handle: ArchetypeHandle,
+    scope: &Scope,
+    dry_run: bool,
+) -> Result<Goblined<Possession>, String> {
+    let touched: Vec<Possession> = db
+        .possessions(Category::Misc)
+        .await?
+        .into_iter()
+        .filter(|p| p.archetype_handle == archetype_handle && scope.includes(&p.steader))
+        .collect();
+    let snapshot = goblin_snapshot(
+        db,
+        dry_run,
+        "goblin nab",
+        Batch {
+            possessions: touched.clone(),
+            ..Default::default()
+        },
+    )
+    .await?;
+    if dry_run {
+        return Ok(Goblined { touched, snapshot });
+    }
+
+    db.write(Batch {
+        deletions: touched.iter().map(|p| p.key()).collect(),
+        ..Default::default()
+    })
+    .await
+    .map_err(|e| format!("goblin nab async err: {}", e))?;
+
+    Ok(Goblined { touched, snapshot })
 }
 
 #[derive(Debug, Clone)]
@@ -153,34 +232,8 @@ impl Tile {
         }
     }
 
-    pub async fn fetch_all(db: &DynamoDbClient) -> Result<Vec<Tile>, String> {
-        let query = db
-            .query(rusoto_dynamodb::QueryInput {
-                table_name: TABLE_NAME.to_string(),
-                key_condition_expression: Some("cat = :land_cat".to_string()),
-                expression_attribute_values: Some(
-                    [(":land_cat".to_string(), Category::Land.into_av())]
-                        .iter()
-                        .cloned()
-                        .collect(),
-                ),
-                ..Default::default()
-            })
-            .await;
-
-        Ok(query
-            .map_err(|e| format!("Couldn't search land category: {}", e))?
-            .items
-            .ok_or_else(|| format!("land query returned no items"))?
-            .iter_mut()
-            .filter_map(|i| match Tile::from_item(i) {
-                Ok(tile) => Some(tile),
-                Err(e) => {
-                    println!("error parsing tile: {}", e);
-                    None
-                }
-            })
-            .collect())
+    pub async fn fetch_all(db: &dyn Store) -> Result<Vec<Tile>, String> {
+        db.tiles().await
     }
 
     pub fn from_item(item: &Item) -> Result<Self, AttributeParseError> {
@@ -671,6 +724,23 @@ impl Plant {
     }
 }
 
+/// For `#[serde(with = "plant_av")]`, so that plants waiting to be planted
+/// can be saved the same way the plants on tiles are.
+pub mod plant_av {
+    use super::Plant;
+    use rusoto_dynamodb::AttributeValue;
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(plant: &Plant, s: S) -> Result<S::Ok, S::Error> {
+        plant.clone().into_av().serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Plant, D::Error> {
+        Plant::from_av(&AttributeValue::deserialize(d)?)
+            .map_err(|e| D::Error::custom(format!("couldn't parse plant: {}", e)))
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct NeighborBonuses(
     Vec<(
@@ -712,25 +782,11 @@ pub struct Hacksteader {
     pub gotchis: Vec<Possessed<possess::Gotchi>>,
 }
 impl Hacksteader {
-    pub async fn new_in_db(db: &DynamoDbClient, user_id: String) -> Result<(), String> {
+    pub async fn new_in_db(db: &dyn Store, user_id: String) -> Result<(), String> {
         // just give them a profile for now
-        db.batch_write_item(rusoto_dynamodb::BatchWriteItemInput {
-            request_items: [(
-                TABLE_NAME.to_string(),
-                vec![
-                    Profile::new(user_id.clone()).item(),
-                    Tile::new(user_id.clone()).into_av().m.unwrap(),
-                ]
-                .into_iter()
-                .map(|item| rusoto_dynamodb::WriteRequest {
-                    put_request: Some(rusoto_dynamodb::PutRequest { item }),
-                    ..Default::default()
-                })
-                .collect(),
-            )]
-            .iter()
-            .cloned()
-            .collect(),
+        db.write(Batch {
+            profiles: vec![Profile::new(user_id.clone())],
+            tiles: vec![Tile::new(user_id.clone())],
             ..Default::default()
         })
         .await
@@ -795,26 +851,24 @@ impl Hacksteader {
     }
 
     pub async fn give_possession(
-        db: &DynamoDbClient,
+        db: &dyn Store,
         user_id: String,
         possession: &Possession,
-    ) -> Result<(), RusotoError<PutItemError>> {
+    ) -> Result<(), String> {
         let mut new_poss = possession.clone();
         new_poss.steader = user_id;
-        db.put_item(rusoto_dynamodb::PutItemInput {
-            item: possession.item(),
-            table_name: TABLE_NAME.to_string(),
+        db.write(Batch {
+            possessions: vec![possession.clone()],
             ..Default::default()
         })
         .await
-        .map(|_| ())
     }
 
     pub async fn spawn_possession(
-        db: &DynamoDbClient,
+        db: &dyn Store,
         receiver: String,
         archetype_handle: ArchetypeHandle,
-    ) -> Result<(), RusotoError<PutItemError>> {
+    ) -> Result<(), String> {
         Hacksteader::give_possession(
             db,
             receiver.clone(),
@@ -830,117 +884,37 @@ impl Hacksteader {
     }
 
     #[allow(dead_code)]
-    pub async fn delete(db: &DynamoDbClient, key: Key) -> Result<(), String> {
-        match db
-            .delete_item(rusoto_dynamodb::DeleteItemInput {
-                key: key.into_item(),
-                table_name: TABLE_NAME.to_string(),
-                ..Default::default()
-            })
-            .await
-        {
-            Ok(_) => Ok(()),
-            Err(e) => Err(format!("couldn't delete in db: {}", e)),
-        }
+    pub async fn delete(db: &dyn Store, key: Key) -> Result<(), String> {
+        db.write(Batch {
+            deletions: vec![key],
+            ..Default::default()
+        })
+        .await
+        .map_err(|e| format!("couldn't delete in db: {}", e))
     }
 
     #[allow(dead_code)]
-    pub async fn take(db: &DynamoDbClient, key: Key) -> Result<Possession, String> {
-        match db
-            .delete_item(rusoto_dynamodb::DeleteItemInput {
-                key: key.into_item(),
-                table_name: TABLE_NAME.to_string(),
-                return_values: Some("ALL_OLD".to_string()),
-                ..Default::default()
-            })
-            .await
-        {
-            Ok(rusoto_dynamodb::DeleteItemOutput {
-                attributes: Some(item),
-                ..
-            }) => Possession::from_item(&item)
-                .map_err(|e| format!("couldn't parse value returned from delete: {}", e)),
-            Err(e) => Err(format!("couldn't delete in db: {}", e)),
-            _ => Err(format!("no attributes returned!")),
-        }
+    pub async fn take(db: &dyn Store, key: Key) -> Result<Possession, String> {
+        db.take(key).await
     }
 
     pub async fn transfer_possession(
-        db: &DynamoDbClient,
+        db: &dyn Store,
         new_owner: String,
         acquisition: possess::Acquisition,
         key: Key,
     ) -> Result<(), String> {
-        db.update_item(rusoto_dynamodb::UpdateItemInput {
-            table_name: TABLE_NAME.to_string(),
-            key: key.into_item(),
-            update_expression: Some(
-                concat!(
-                    "SET ",
-                    "steader = :new_owner, ",
-                    "ownership_log = list_append(ownership_log, :ownership_entry)",
-                )
-                .to_string(),
-            ),
-            expression_attribute_values: Some(
-                [
-                    (
-                        ":new_owner".to_string(),
-                        AttributeValue {
-                            s: Some(new_owner.clone()),
-                            ..Default::default()
-                        },
-                    ),
-                    (
-                        ":ownership_entry".to_string(),
-                        AttributeValue {
-                            l: Some(vec![possess::Owner {
-                                id: new_owner.clone(),
-                                acquisition,
-                            }
-                            .into()]),
-                            ..Default::default()
-                        },
-                    ),
-                ]
-                .iter()
-                .cloned()
-                .collect(),
-            ),
-            ..Default::default()
-        })
-        .await
-        .map_err(|e| format!("Couldn't transfer ownership in database: {}", e))?;
-
-        Ok(())
+        db.transfer_possession(new_owner, acquisition, key, false)
+            .await
+            .map_err(|e| format!("Couldn't transfer ownership in database: {}", e))
     }
 
-    pub async fn from_db(db: &DynamoDbClient, user_id: String) -> Result<Self, String> {
-        let query = db
-            .query(rusoto_dynamodb::QueryInput {
-                table_name: TABLE_NAME.to_string(),
-                key_condition_expression: Some("steader = :steader_id".to_string()),
-                index_name: Some("steader_index".to_string()),
-                expression_attribute_values: Some({
-                    [(
-                        ":steader_id".to_string(),
-                        AttributeValue {
-                            s: Some(user_id.clone()),
-                            ..Default::default()
-                        },
-                    )]
-                    .iter()
-                    .cloned()
-                    .collect()
-                }),
-                ..Default::default()
-            })
-            .await;
-        let items = query
-            .map_err(|e| format!("couldn't profile query: {}", e))?
-            .items
-            .ok_or_else(|| format!("no items returned from profile query"))?;
+    pub async fn from_db(db: &dyn Store, user_id: String) -> Result<Self, String> {
+        db.hacksteader(&user_id).await
+    }
 
+    /// Sorts out everything a steader owns from the items stored under their name.
+    pub fn from_items(user_id: String, items: &[Item]) -> Result<Self, String> {
         let mut profile = None;
         let mut gotchis = Vec::new();
         let mut inventory = Vec::new();
@@ -1,16 +1,20 @@
-use log::{error, info};
+use log::{error, info, warn};
+use rocket::http::Status;
 use rocket::post;
+use rocket::request::{self, FromRequest, Request};
 
 use rocket_contrib::json::Json;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
 
 use graphql_client::GraphQLQuery;
 
 use crate::banker;
-use crate::banker::do_query;
+use crate::banker::{do_query, PendingInvoice};
+use crate::records::{self, Record};
+use crate::{store::store, ID as BOT_ID};
 
-use crate::event::InvoicePaymentTrigger;
-use crate::event::INVOICE_PAYMENT_TRIGGERS;
+use crate::event::invoice_paid;
 
 #[derive(GraphQLQuery)]
 #[graphql(
@@ -41,44 +45,179 @@ pub struct HnWebhookBody {
     id: String,
 }
 
+use std::env::var;
+lazy_static::lazy_static! {
+    pub static ref HN_WEBHOOK_SECRET: String = var("HN_WEBHOOK_SECRET").unwrap();
+}
+
+/// Makes sure there's a secret to check webhooks against, so that a server started
+/// without one doesn't come up only to fall over on the first payment.
+pub fn check_webhook_secret() -> Result<(), String> {
+    match var("HN_WEBHOOK_SECRET") {
+        Ok(secret) if !secret.is_empty() => {
+            lazy_static::initialize(&HN_WEBHOOK_SECRET);
+            Ok(())
+        }
+        _ => Err("HN_WEBHOOK_SECRET has to be set to check webhooks from HN".to_string()),
+    }
+}
+
+/// Request guard for webhooks that carry our shared secret in their `secret` header,
+/// the same header we use to authenticate ourselves to HN.
+pub struct HnWebhookSecret;
+
+#[rocket::async_trait]
+impl<'a, 'r> FromRequest<'a, 'r> for HnWebhookSecret {
+    type Error = String;
+
+    async fn from_request(req: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        match req.headers().get_one("secret") {
+            Some(secret) if constant_time_eq(secret.as_bytes(), HN_WEBHOOK_SECRET.as_bytes()) => {
+                request::Outcome::Success(HnWebhookSecret)
+            }
+            _ => {
+                let a = format!(
+                    "refusing hn webhook to {} without the right secret",
+                    req.uri()
+                );
+                warn!("{}", a);
+                request::Outcome::Failure((Status::Unauthorized, a))
+            }
+        }
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// An HN transaction whose payment webhook we're acting on, or already have.
+/// HN retries webhooks, so each transaction is claimed here before its invoice's
+/// intent is carried out by `invoice_paid`. It's only let go of if that never started.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProcessedTransaction {
+    pub id: String,
+    pub processed_at: SystemTime,
+    /// Set if carrying out the invoice's intent failed part of the way through.
+    /// Some of it may have happened, so it's left for an admin to sort out by hand.
+    #[serde(default)]
+    pub failure: Option<FailedIntent>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FailedIntent {
+    pub error: String,
+    pub invoice: PendingInvoice,
+}
+impl Record for ProcessedTransaction {
+    const KIND: &'static str = "hn_processed_transaction";
+
+    fn id(&self) -> String {
+        self.id.clone()
+    }
+}
+
 #[post("/hn/transaction", data = "<webhook>")]
-pub async fn transaction(webhook: Json<HnWebhook>) {
+pub async fn transaction(_secret: HnWebhookSecret, webhook: Json<HnWebhook>) {
     // To possibly be used later
 }
 
 #[post("/hn/payment", data = "<webhook>")]
-pub async fn payment(webhook: Json<HnWebhook>) {
+pub async fn payment(_secret: HnWebhookSecret, webhook: Json<HnWebhook>) -> Result<(), String> {
     let query = GetTransaction::build_query(get_transaction::Variables {
         id: webhook.body.id.clone(),
     });
 
     let resp = do_query::<_, get_transaction::ResponseData>(&query)
         .await
-        .expect("getting transaction failed")
+        .map_err(|_| format!("getting transaction {} failed", webhook.body.id))?
         .data
-        .expect("getting transaction failed");
+        .ok_or_else(|| format!("no data for transaction {}", webhook.body.id))?;
+
+    if resp.transaction.to.id != *BOT_ID {
+        let a = format!(
+            "ignoring payment webhook for transaction {} which doesn't pay us",
+            webhook.body.id
+        );
+        warn!("{}", a);
+        return Err(a);
+    }
+
+    let mut processed = ProcessedTransaction {
+        id: resp.transaction.id.clone(),
+        processed_at: SystemTime::now(),
+        failure: None,
+    };
+    let claimed = records::insert(store(), &processed).await?;
+    if !claimed {
+        info!(
+            "invoice {} was already processed, ignoring retried webhook",
+            webhook.body.id
+        );
+        return Ok(());
+    }
 
     info!("invoice {} just paid", webhook.body.id);
 
-    for InvoicePaymentTrigger { regex, then } in INVOICE_PAYMENT_TRIGGERS.iter() {
-        let c = match regex.captures(&resp.transaction.for_) {
-            Some(c) => c,
-            None => continue,
-        };
-        if let Err(e) = then(
-            c,
-            banker::PaidInvoice {
-                invoicer: resp.transaction.to.id.clone(),
-                invoicee: resp.transaction.from.id.clone(),
-                reason: resp.transaction.for_.clone(),
-                amount: resp.transaction.balance.clone() as u64,
-            },
-        )
+    let pending = match records::remove::<PendingInvoice>(store(), &resp.transaction.id).await {
+        Ok(Some(pending)) => pending,
+        // nothing's been done yet, so HN's retry can have another go
+        Err(e) => {
+            if let Err(release_err) =
+                records::remove::<ProcessedTransaction>(store(), &resp.transaction.id).await
+            {
+                error!(
+                    "couldn't release transaction {} for a retry, it won't be processed: {}",
+                    resp.transaction.id, release_err
+                );
+            }
+            return Err(e);
+        }
+        Ok(None) => {
+            let a = format!(
+                "no pending invoice for paid transaction {} ({})",
+                resp.transaction.id, resp.transaction.for_
+            );
+            error!("{}", a);
+            return Err(a);
+        }
+    };
+
+    if let Err(e) = invoice_paid(
+        pending.clone(),
+        banker::PaidInvoice {
+            invoicer: resp.transaction.to.id.clone(),
+            invoicee: resp.transaction.from.id.clone(),
+            reason: resp.transaction.for_.clone(),
+            amount: resp.transaction.balance.clone() as u64,
+        },
+    )
+    .await
+    {
+        // some of it may already have happened, and none of the intents can safely be
+        // carried out twice, so it stays claimed and is left for an admin instead
+        banker::message(format!(
+            "invoice payment handler err for transaction {}, which needs sorting out by hand: {}",
+            resp.transaction.id, e
+        ))
         .await
-        {
-            banker::message(format!("invoice payment handler err : {}", e))
-                .await
-                .unwrap_or_else(|e| error!("{}", e));
+        .unwrap_or_else(|e| error!("{}", e));
+
+        processed.failure = Some(FailedIntent {
+            error: e.clone(),
+            invoice: pending,
+        });
+        if let Err(record_err) = records::put(store(), &processed).await {
+            error!(
+                "couldn't note that transaction {} failed: {}",
+                resp.transaction.id, record_err
+            );
         }
+        return Err(format!(
+            "couldn't process payment for transaction {}: {}",
+            resp.transaction.id, e
+        ));
     }
+
+    Ok(())
 }
@@ -10,39 +10,56 @@ use hcor::{config, frontend::emojify, possess, Category, Key};
 use log::*;
 use possess::{Possessed, Possession};
 use regex::Regex;
-use rocket::{get, post, request::LenientForm, routes, FromForm, State};
+use rocket::{get, post, routes, State};
 use rocket_contrib::json::Json;
-use rusoto_dynamodb::{AttributeValue, DynamoDb, DynamoDbClient};
 use serde_json::{json, Value};
 use std::{collections::HashMap, convert::TryInto};
 
+pub mod admin;
+pub mod api;
+pub mod archive;
+pub mod auction;
 pub mod banker;
+pub mod blocks;
+pub mod bus;
+pub mod chat;
 pub mod event;
+pub mod farm;
 pub mod hacksteader;
 mod hn_webhook;
 pub mod market;
-
+pub mod records;
+pub mod render;
+pub mod roll;
+mod slack_verify;
+pub mod snapshot;
+pub mod store;
+pub mod trade;
+
+use blocks::{actions, button, comment, divider, image, input, mrkdwn, section, Block, Style};
+use chat::{chat, mention};
 use hn_webhook::{payment, transaction};
+use records::Record;
+use slack_verify::SlackForm;
+use store::{store, Store};
 
 use hacksteader::Hacksteader;
 
-pub fn dyn_db() -> DynamoDbClient {
-    DynamoDbClient::new(if *LOCAL_DB {
-        rusoto_core::Region::Custom {
-            name: "local".to_string(),
-            endpoint: "http://dynamodb-local:8000".to_string(),
-        }
-    } else {
-        rusoto_core::Region::UsWest2
-    })
-}
-
 const FARM_CYCLE_SECS: u64 = 5;
 const FARM_CYCLE_MILLIS: u64 = FARM_CYCLE_SECS * 1000;
 const FARM_CYCLES_PER_MIN: u64 = 60 / FARM_CYCLE_SECS;
+/// How often auctions are checked to see whether any of them are over.
+const AUCTION_SETTLE_SECS: u64 = 15;
+/// How often the market is checked for listings that have been up for as long as they're allowed.
+const LISTING_SWEEP_SECS: u64 = 60;
+/// How often trade offers are checked to see whether any of them have expired.
+const TRADE_SWEEP_SECS: u64 = 60;
+/// How often payments the banker couldn't make are tried again.
+const OWED_SWEEP_SECS: u64 = 60;
+/// How many farm cycles a restart waits for the farming queues to empty out.
+const MAX_RESTART_DRAIN_CYCLES: usize = 6;
 
 lazy_static::lazy_static! {
-    pub static ref TOKEN: String = std::env::var("TOKEN").unwrap();
     pub static ref ID: String = std::env::var("ID").unwrap();
     pub static ref APP_ID: String = std::env::var("APP_ID").unwrap();
     pub static ref URL: String = std::env::var("URL").unwrap();
@@ -50,26 +67,6 @@ lazy_static::lazy_static! {
     pub static ref LOCAL_DB: bool = std::env::var("LOCAL_DB").is_ok();
 }
 
-pub fn mrkdwn<S: std::string::ToString>(txt: S) -> Value {
-    json!({
-        "type": "mrkdwn",
-        "text": txt.to_string(),
-    })
-}
-pub fn plain_text<S: std::string::ToString>(txt: S) -> Value {
-    json!({
-        "type": "plain_text",
-        "text": txt.to_string(),
-    })
-}
-pub fn comment<S: ToString>(txt: S) -> Value {
-    json!({
-        "type": "context",
-        "elements": [
-            mrkdwn(txt)
-        ]
-    })
-}
 pub fn filify<S: ToString>(txt: S) -> String {
     txt.to_string().to_lowercase().replace(" ", "_")
 }
@@ -77,28 +74,9 @@ pub fn filify<S: ToString>(txt: S) -> String {
 pub async fn dm_blocks(
     user_id: String,
     notif_msg: String,
-    blocks: Vec<Value>,
+    blocks: Vec<Block>,
 ) -> Result<(), String> {
-    let o = json!({
-        "channel": user_id,
-        "token": *TOKEN,
-        "blocks": blocks,
-        "text": notif_msg
-    });
-
-    debug!("{}", serde_json::to_string_pretty(&o).unwrap());
-
-    // TODO: use response
-    let client = reqwest::Client::new();
-    client
-        .post("https://slack.com/api/chat.postMessage")
-        .bearer_auth(&*TOKEN)
-        .json(&o)
-        .send()
-        .await
-        .map_err(|e| format!("couldn't dm {}: {}", user_id, e))?;
-
-    Ok(())
+    chat().dm(&user_id, notif_msg, blocks).await
 }
 
 async fn gift_dm(
@@ -111,20 +89,18 @@ async fn gift_dm(
     dm_blocks(new_owner.to_string(), notif_msg, {
         // TODO: with_capacity optimization
         let mut blocks = vec![
-            json!({
-                "type": "section",
-                "text": mrkdwn(format!(
-                    "<@{}> has been so kind as to gift you {} {} _{}_!",
-                    giver,
-                    match count {
-                        1 => "a".to_string(),
-                        other => format!("*{}*", other),
-                    },
-                    emojify(&possession.name),
-                    possession.nickname()
-                ))
-            }),
-            json!({ "type": "divider" }),
+            section(mrkdwn(format!(
+                "{} has been so kind as to gift you {} {} _{}_!",
+                mention(giver),
+                match count {
+                    1 => "a".to_string(),
+                    other => format!("*{}*", other),
+                },
+                emojify(&possession.name),
+                possession.nickname()
+            )))
+            .into(),
+            divider(),
         ];
         let page = PossessionPage {
             interactivity: Interactivity::Read,
@@ -132,7 +108,7 @@ async fn gift_dm(
             possession: possession.clone(),
         };
         blocks.append(&mut page.blocks());
-        blocks.push(json!({ "type": "divider" }));
+        blocks.push(divider());
         blocks.push(comment(format!(
             "Manage all of your possessions like this one at your <slack://app?team=T0266FRGM&id={}&tab=home|hackstead>",
             *APP_ID,
@@ -148,33 +124,35 @@ fn gotchi_block(
     interactivity: Interactivity,
     credentials: Credentials,
     push: bool,
-) -> Value {
-    json!({
-        "type": "section",
-        "text": mrkdwn(format!(
-            "_{} ({}, {})_",
-            emojify(&gotchi.name),
-            gotchi.name,
-            match gotchi.inner.hatch_table {
-                None => format!("{} happiness", gotchi.inner.base_happiness),
-                Some(_) => "ready to hatch!".to_string(),
-            }
-        )),
-        "accessory": {
-            "type": "button",
-            "style": "primary",
-            "text": plain_text(&gotchi.inner.nickname),
-            "value": serde_json::to_string(&PossessionPage {
+) -> Block {
+    section(mrkdwn(format!(
+        "_{} ({}, {})_",
+        emojify(&gotchi.name),
+        gotchi.name,
+        match gotchi.inner.hatch_table {
+            None => format!("{} happiness", gotchi.inner.base_happiness),
+            Some(_) => "ready to hatch!".to_string(),
+        }
+    )))
+    .accessory(
+        button(
+            &gotchi.inner.nickname,
+            match push {
+                true => "push_possession_page",
+                _ => "possession_page",
+            },
+        )
+        .primary()
+        .value(
+            serde_json::to_string(&PossessionPage {
                 possession: gotchi.into_possession(),
                 interactivity,
                 credentials,
-            }).unwrap(),
-            "action_id": match push {
-                true => "push_possession_page",
-                _ => "possession_page",
-            }
-        }
-    })
+            })
+            .unwrap(),
+        ),
+    )
+    .into()
 }
 
 fn inventory_occurences(inventory: Vec<Possession>) -> HashMap<String, Vec<Possession>> {
@@ -195,13 +173,10 @@ fn inventory_section(
     credentials: Credentials,
     push: bool,
     user_id: String,
-) -> Vec<Value> {
+) -> Vec<Block> {
     let mut blocks = vec![];
 
-    blocks.push(json!({
-        "type": "section",
-        "text": mrkdwn("*Inventory*"),
-    }));
+    blocks.push(section(mrkdwn("*Inventory*")).into());
 
     let mut inv_entries = inv_occurrences.into_iter().collect::<Vec<_>>();
     inv_entries.sort_unstable_by_key(|(_, p)| p.last().unwrap().archetype_handle);
@@ -211,54 +186,58 @@ fn inventory_section(
         let last = possessions.last().unwrap().clone();
 
         if possessions.len() == 1 {
-            blocks.push(json!({
-                "type": "section",
-                "text": mrkdwn(format!(
-                    "{} _{}_",
-                    emojify(&name),
-                    name
-                )),
-                "accessory": {
-                    "type": "button",
-                    "style": "primary",
-                    "text": plain_text(&name),
-                    "value": serde_json::to_string(&PossessionPage {
-                        possession: last,
-                        interactivity,
-                        credentials,
-                    }).unwrap(),
-                    "action_id": match push {
-                        false => "possession_page",
-                        true => "push_possession_page"
-                    },
-                }
-            }));
+            blocks.push(
+                section(mrkdwn(format!("{} _{}_", emojify(&name), name)))
+                    .accessory(
+                        button(
+                            &name,
+                            match push {
+                                false => "possession_page",
+                                true => "push_possession_page",
+                            },
+                        )
+                        .primary()
+                        .value(
+                            serde_json::to_string(&PossessionPage {
+                                possession: last,
+                                interactivity,
+                                credentials,
+                            })
+                            .unwrap(),
+                        ),
+                    )
+                    .into(),
+            );
         } else {
-            blocks.push(json!({
-                "type": "section",
-                "text": mrkdwn(format!(
+            blocks.push(
+                section(mrkdwn(format!(
                     "*{}* {} _{}_",
                     possessions.len(),
                     emojify(&name),
                     name
-                )),
-                "accessory": {
-                    "type": "button",
-                    "style": "primary",
-                    "text": plain_text(&name),
-                    "value": serde_json::to_string(&PossessionOverviewPage {
-                        source: PossessionOverviewSource::Hacksteader(user_id.clone()),
-                        page: 0,
-                        item_name: name,
-                        interactivity,
-                        credentials,
-                    }).unwrap(),
-                    "action_id": match push {
-                        false => "possession_overview_page",
-                        true => "push_possession_overview_page"
-                    },
-                }
-            }));
+                )))
+                .accessory(
+                    button(
+                        &name,
+                        match push {
+                            false => "possession_overview_page",
+                            true => "push_possession_overview_page",
+                        },
+                    )
+                    .primary()
+                    .value(
+                        serde_json::to_string(&PossessionOverviewPage {
+                            source: PossessionOverviewSource::Hacksteader(user_id.clone()),
+                            page: 0,
+                            item_name: name,
+                            interactivity,
+                            credentials,
+                        })
+                        .unwrap(),
+                    ),
+                )
+                .into(),
+            );
         }
     }
 
@@ -270,16 +249,16 @@ fn gotchi_section(
     interactivity: Interactivity,
     credentials: Credentials,
     push: bool,
-) -> Vec<Value> {
+) -> Vec<Block> {
     let mut blocks = vec![];
 
-    blocks.push(json!({
-        "type": "section",
-        "text": mrkdwn(match gotchis.len() {
+    blocks.push(
+        section(mrkdwn(match gotchis.len() {
             1 => "*Your Hackagotchi*".into(),
-            _ => format!("*Your {} Hackagotchi*", gotchis.len())
-        }),
-    }));
+            _ => format!("*Your {} Hackagotchi*", gotchis.len()),
+        }))
+        .into(),
+    );
 
     let total_happiness = gotchis.iter().map(|g| g.inner.base_happiness).sum::<u64>();
 
@@ -287,10 +266,7 @@ fn gotchi_section(
         blocks.push(gotchi_block(g, interactivity, credentials, push));
     }
 
-    blocks.push(json!({
-        "type": "section",
-        "text": mrkdwn(format!("Total happiness: *{}*", total_happiness))
-    }));
+    blocks.push(section(mrkdwn(format!("Total happiness: *{}*", total_happiness))).into());
     blocks.push(comment(
         "The total happiness of all your gotchi is equivalent to the \
          amount of hn you'll get at the next Harvest.",
@@ -316,21 +292,12 @@ pub struct PossessionOverviewPage {
 impl PossessionOverviewPage {
     const PAGE_SIZE: usize = 20;
 
-    fn title(&self) -> String {
-        let mut title = self.item_name.clone();
-        if title.len() > 24 {
-            title.truncate(24 - 3);
-            title.push_str("...");
-        }
-        title
-    }
-
     async fn modal(self, trigger_id: String, method: &'static str) -> Result<Modal, String> {
         Ok(Modal {
             callback_id: self.callback_id(),
             blocks: self.blocks().await?,
             submit: None,
-            title: self.title(),
+            title: self.item_name.clone(),
             method: method.to_string(),
             trigger_id,
             private_metadata: String::new(),
@@ -354,7 +321,7 @@ impl PossessionOverviewPage {
         "possession_overview_page_".to_string() + self.interactivity.id()
     }
 
-    async fn blocks(&self) -> Result<Vec<Value>, String> {
+    async fn blocks(&self) -> Result<Vec<Block>, String> {
         let Self {
             source,
             item_name,
@@ -365,7 +332,7 @@ impl PossessionOverviewPage {
 
         let inventory: Vec<_> = match source {
             PossessionOverviewSource::Hacksteader(hacksteader) => {
-                let hs = Hacksteader::from_db(&dyn_db(), hacksteader.clone()).await?;
+                let hs = Hacksteader::from_db(store(), hacksteader.clone()).await?;
                 let mut inv: Vec<_> = hs
                     .inventory
                     .into_iter()
@@ -379,7 +346,7 @@ impl PossessionOverviewPage {
                 });
                 inv
             }
-            PossessionOverviewSource::Market(cat) => market::market_search(&dyn_db(), *cat)
+            PossessionOverviewSource::Market(cat) => market::market_search(store(), *cat)
                 .await
                 .map_err(|e| error!("couldn't search market: {}", e))
                 .unwrap_or_default()
@@ -400,48 +367,57 @@ impl PossessionOverviewPage {
             .skip(page * Self::PAGE_SIZE)
             .take(Self::PAGE_SIZE)
             .map(|possession| {
-                json!({
-                    "type": "section",
-                    "text": mrkdwn(format!(
-                        "{} _{}_{}",
-                        emojify(&item_name),
-                        item_name,
-                        if let Some(hcor::market::Sale { price, .. }) = possession.sale {
-                            match source {
-                                PossessionOverviewSource::Hacksteader(..) => {
-                                    format!(" (selling at *{}hn*)", price)
-                                },
-                                PossessionOverviewSource::Market(..) => {
-                                    format!(
-                                        " (sold by *<@{}>*)",
-                                        possession.steader,
-                                    )
-                                }
+                section(mrkdwn(format!(
+                    "{} _{}_{}",
+                    emojify(&item_name),
+                    item_name,
+                    if let Some(hcor::market::Sale { price, .. }) = possession.sale {
+                        match source {
+                            PossessionOverviewSource::Hacksteader(..) => {
+                                format!(" (selling at *{}hn*)", price)
+                            }
+                            PossessionOverviewSource::Market(..) => {
+                                format!(" (sold by *{}*)", mention(&possession.steader))
                             }
-                        } else {
-                            "".to_string()
                         }
-                    )),
-                    "accessory": {
-                        "type": "button",
-                        "style": "primary",
-                        "text": plain_text(match (source, possession.sale.as_ref()) {
+                    } else {
+                        "".to_string()
+                    }
+                )))
+                .accessory(
+                    button(
+                        match (source, possession.sale.as_ref()) {
                             (PossessionOverviewSource::Market(..), Some(s)) => {
                                 format!("{}hn", s.price)
                             }
                             _ => item_name.clone(),
-                        }),
-                        "value": serde_json::to_string(&(
-                            possession,
-                            interactivity,
-                            credentials
-                        )).unwrap(),
-                        "action_id": "push_possession_page",
-                    }
-                })
+                        },
+                        "push_possession_page",
+                    )
+                    .primary()
+                    .value(
+                        serde_json::to_string(&(possession, interactivity, credentials)).unwrap(),
+                    ),
+                )
+                .into()
             })
             .collect::<Vec<_>>();
 
+        if let (PossessionOverviewSource::Market(cat), Some(p)) = (source, first_item.as_ref()) {
+            let mut buttons = vec![button("Price History", "market_price_history")
+                .value(p.archetype_handle)
+                .into()];
+            if *cat == Category::Misc && *credentials == Credentials::Hacksteader {
+                buttons.push(
+                    button("Buy in Bulk", "market_bulk_buy")
+                        .primary()
+                        .value(p.archetype_handle)
+                        .into(),
+                );
+            }
+            blocks.insert(0, actions(buttons));
+        }
+
         if let Some(p) = first_item.filter(|p| p.kind.is_keepsake()) {
             blocks.push(comment(format!(
                 "hmm, maybe \"*/hgive <@U01581HFAGZ> {} {}*\" is in your future?",
@@ -453,37 +429,32 @@ impl PossessionOverviewPage {
         let needs_back_page = *page != 0;
         let needs_next_page = inventory_len > Self::PAGE_SIZE * (page + 1);
         if needs_back_page || needs_next_page {
-            blocks.push(json!({
-                "type": "actions",
-                "elements": ({
-                    let mut buttons = vec![];
-                    let mut current_page = self.clone();
-
-                    if needs_back_page {
-                        let mut back_page = &mut current_page;
-                        back_page.page = *page - 1;
-                        buttons.push(json!({
-                            "type": "button",
-                            "text": plain_text("Previous Page"),
-                            "style": "primary",
-                            "value": serde_json::to_string(back_page).unwrap(),
-                            "action_id": "possession_overview_page"
-                        }));
-                    }
-                    if needs_next_page {
-                        let mut next_page = &mut current_page;
-                        next_page.page = *page + 1;
-                        buttons.push(json!({
-                            "type": "button",
-                            "text": plain_text("Next Page"),
-                            "style": "primary",
-                            "value": serde_json::to_string(next_page).unwrap(),
-                            "action_id": "possession_overview_page"
-                        }));
-                    }
+            blocks.push(actions({
+                let mut buttons: Vec<blocks::Element> = vec![];
+                let mut current_page = self.clone();
+
+                if needs_back_page {
+                    let mut back_page = &mut current_page;
+                    back_page.page = *page - 1;
+                    buttons.push(
+                        button("Previous Page", "possession_overview_page")
+                            .primary()
+                            .value(serde_json::to_string(back_page).unwrap())
+                            .into(),
+                    );
+                }
+                if needs_next_page {
+                    let mut next_page = &mut current_page;
+                    next_page.page = *page + 1;
+                    buttons.push(
+                        button("Next Page", "possession_overview_page")
+                            .primary()
+                            .value(serde_json::to_string(next_page).unwrap())
+                            .into(),
+                    );
+                }
 
-                    buttons
-                })
+                buttons
             }));
         }
 
@@ -500,12 +471,7 @@ pub struct PossessionPage {
 
 impl PossessionPage {
     fn title(&self) -> String {
-        let mut title = self.possession.nickname().to_string();
-        if title.len() > 24 {
-            title.truncate(24 - 3);
-            title.push_str("...");
-        }
-        title
+        self.possession.nickname().to_string()
     }
 
     fn modal(self, trigger_id: String, method: &'static str) -> Modal {
@@ -566,42 +532,42 @@ impl PossessionPage {
         "possession_page_".to_string() + self.interactivity.id()
     }
 
-    fn blocks(&self) -> Vec<Value> {
+    fn blocks(&self) -> Vec<Block> {
         // TODO: with_capacity optimization
-        let mut blocks: Vec<Value> = Vec::new();
+        let mut blocks: Vec<Block> = Vec::new();
         let Self {
             possession,
             interactivity,
             credentials,
         } = self;
 
-        let actions = |prefix: &str, buttons: &[(&str, Option<Value>)]| -> Value {
+        let buttons = |prefix: &str, buttons: &[(&str, Option<String>)]| -> Block {
             match interactivity {
-                Interactivity::Write => json!({
-                    "type": "actions",
-                    "elements": buttons.iter().map(|(action, value)| {
-                        let mut o = json!({
-                            "type": "button",
-                            "text": plain_text(action),
-                            "action_id": format!("{}_{}", prefix, action.to_lowercase()),
-                        });
-                        if let Some(v) = value {
-                            o.as_object_mut().unwrap().insert("value".to_string(), v.clone());
-                        }
-                        o
-                    }).collect::<Vec<_>>()
-                }),
+                Interactivity::Write => actions(
+                    buttons
+                        .iter()
+                        .map(|(action, value)| {
+                            let b =
+                                button(action, &format!("{}_{}", prefix, action.to_lowercase()));
+                            match value {
+                                Some(v) => b.value(v),
+                                None => b,
+                            }
+                            .into()
+                        })
+                        .collect(),
+                ),
                 _ => comment("This page is read only."),
             }
         };
 
         if let Some(g) = possession.kind.gotchi() {
-            blocks.push(actions("gotchi", &{
-                let mut a = vec![("Nickname", Some(json!(possession.nickname())))];
+            blocks.push(buttons("gotchi", &{
+                let mut a = vec![("Nickname", Some(possession.nickname().to_string()))];
                 if g.hatch_table.is_some() {
                     a.push((
                         "Hatch",
-                        Some(json!(serde_json::to_string(&possession.id).unwrap())),
+                        Some(serde_json::to_string(&possession.id).unwrap()),
                     ));
                 }
                 a
@@ -616,7 +582,7 @@ impl PossessionPage {
                 possession
                     .ownership_log
                     .iter()
-                    .map(|o| format!("[{}]<@{}>", o.acquisition, o.id))
+                    .map(|o| format!("[{}]{}", o.acquisition, mention(&o.id)))
                     .collect::<Vec<_>>()
                     .join(" -> ")
                     .to_string(),
@@ -633,22 +599,29 @@ impl PossessionPage {
             .collect::<Vec<_>>()
             .join("\n");
 
-        blocks.push(json!({
-            "type": "section",
-            "text": mrkdwn(text),
-            "accessory": {
-                "type": "image",
-                "image_url": format!(
-                    "http://{}/gotchi/img/{}/{}.png",
-                    *URL,
-                    format!("{:?}", possession.kind.category()).to_lowercase(),
-                    filify(&possession.name)
-                ),
-                "alt_text": "hackagotchi img",
-            }
-        }));
+        blocks.push(
+            section(mrkdwn(text))
+                .accessory(image(
+                    format!(
+                        "http://{}/gotchi/img/{}/{}.png",
+                        *URL,
+                        format!("{:?}", possession.kind.category()).to_lowercase(),
+                        filify(&possession.name)
+                    ),
+                    "hackagotchi img",
+                ))
+                .into(),
+        );
 
-        blocks.push(actions("possession", &[("Give", None), ("Sell", None)]));
+        blocks.push(buttons(
+            "possession",
+            &[
+                ("Give", None),
+                ("Trade", None),
+                ("Sell", None),
+                ("Auction", None),
+            ],
+        ));
 
         if let Some(g) = possession.kind.gotchi() {
             blocks.push(comment(format!(
@@ -658,14 +631,15 @@ impl PossessionPage {
 
             for owner in g.harvest_log.iter().rev() {
                 blocks.push(comment(format!(
-                    "{}hn harvested for <@{}>",
-                    owner.harvested, owner.id
+                    "{}hn harvested for {}",
+                    owner.harvested,
+                    mention(&owner.id)
                 )));
             }
         }
 
         if let (Credentials::None, true) = (credentials, interactivity.market(*credentials)) {
-            blocks.push(json!({ "type": "divider" }));
+            blocks.push(divider());
             blocks.push(comment(format!(
                 "In order to buy this, you have to have a \
                 <slack://app?team=T0266FRGM&id={}&tab=home|hackstead>.",
@@ -679,32 +653,22 @@ impl PossessionPage {
 
 async fn update_user_home_tab(user_id: String) -> Result<(), String> {
     update_home_tab(
-        Hacksteader::from_db(&dyn_db(), user_id.clone()).await.ok(),
+        Hacksteader::from_db(store(), user_id.clone()).await.ok(),
         user_id.clone(),
     )
     .await
 }
 async fn update_home_tab(hs: Option<Hacksteader>, user_id: String) -> Result<(), String> {
-    let o = json!({
-        "user_id": user_id,
-        "view": {
-            "type": "home",
-            "blocks": hacksteader_greeting_blocks(hs, Interactivity::Write, Credentials::Owner),
-        }
-    });
-
-    debug!("home screen: {}", serde_json::to_string_pretty(&o).unwrap());
-
-    let client = reqwest::Client::new();
-    client
-        .post("https://slack.com/api/views.publish")
-        .bearer_auth(&*TOKEN)
-        .json(&o)
-        .send()
+    chat()
+        .publish_home(
+            &user_id,
+            blocks::View::home(hacksteader_greeting_blocks(
+                hs,
+                Interactivity::Write,
+                Credentials::Owner,
+            )),
+        )
         .await
-        .map_err(|e| format!("couldn't publish home tab view: {}", e))?;
-
-    Ok(())
 }
 
 fn progress_bar(size: usize, progress_ratio: f32) -> String {
@@ -726,12 +690,12 @@ fn hackstead_blocks(
     hs: Hacksteader,
     interactivity: Interactivity,
     credentials: Credentials,
-) -> Vec<Value> {
+) -> Vec<Block> {
     use humantime::format_duration;
     use std::time::SystemTime;
 
     // TODO: with_capacity optimization
-    let mut blocks: Vec<Value> = Vec::new();
+    let mut blocks: Vec<Block> = Vec::new();
 
     let neighbor_bonuses = hs.neighbor_bonuses();
     let Hacksteader {
@@ -751,34 +715,43 @@ fn hackstead_blocks(
     let next_hs_adv = profile.next_advancement();
     let hs_adv_sum = profile.advancements_sum();
 
-    blocks.push(json!({
-        "type": "section",
-        "text": mrkdwn(format!(
-            "*_<@{}>'s {}_* - *{}lvl* - _{}xp_",
-            user_id,
+    blocks.push(
+        section(mrkdwn(format!(
+            "*_{}'s {}_* - *{}lvl* - _{}xp_",
+            mention(&user_id),
             hs_adv.achiever_title,
             profile.advancements.current_position(profile.xp),
             profile.xp,
-        )),
-    }));
+        )))
+        .into(),
+    );
 
     blocks.push(comment(format!(
         "founded {} ago (roughly)",
         format_duration(SystemTime::now().duration_since(profile.joined).unwrap()),
     )));
+    blocks.push(
+        image(
+            // Slack holds on to images by their url, so this changes it as the hackstead grows
+            format!(
+                "http://{}/gotchi/render/{}.png?xp={}",
+                *URL, user_id, profile.xp
+            ),
+            "A bird's eye view of this hackstead",
+        )
+        .into(),
+    );
     if let Some(na) = next_hs_adv {
         blocks.push({
             let (have, need) = (profile.xp - hs_adv_sum.xp, na.xp);
-            json!({
-                "type": "section",
-                "text": mrkdwn(format!(
-                    "Next: *{}*\n{}  {}xp to go\n_{}_",
-                    na.title,
-                    progress_bar(50, have as f32 / need as f32),
-                    need - have,
-                    na.description
-                )),
-            })
+            section(mrkdwn(format!(
+                "Next: *{}*\n{}  {}xp to go\n_{}_",
+                na.title,
+                progress_bar(50, have as f32 / need as f32),
+                need - have,
+                na.description
+            )))
+            .into()
         });
     }
     blocks.push(comment(format!("Last Advancement: \"{}\"", hs_adv.title)));
@@ -790,7 +763,7 @@ fn hackstead_blocks(
         hs_adv_sum.land
     )));
 
-    blocks.push(json!({ "type": "divider" }));
+    blocks.push(divider());
 
     /*if !bottom_inventory {
         let mut actions = vec![];
@@ -834,9 +807,8 @@ fn hackstead_blocks(
             let unboosted_sum = p.neighborless_advancements_sum(std::iter::empty());
             let ca = p.current_advancement();
 
-            blocks.push(json!({
-                "type": "section",
-                "text": mrkdwn({
+            blocks.push(
+                section(mrkdwn({
                     let mut s = String::new();
                     s.push_str(&format!(
                         "*{}* - _{}_ - *{}lvl* - {}xp {}\n\n",
@@ -844,8 +816,7 @@ fn hackstead_blocks(
                         ca.achiever_title,
                         p.advancements.current_position(p.xp),
                         p.xp,
-                        p
-                            .effects
+                        p.effects
                             .iter()
                             .filter_map(|e| Some(emojify(
                                 &CONFIG
@@ -861,80 +832,79 @@ fn hackstead_blocks(
                         s.push_str(&format!(
                             "Next: *{}*\n{}  {}xp to go\n_{}_",
                             na.title,
-                            progress_bar(35, have as f32/need as f32),
+                            progress_bar(35, have as f32 / need as f32),
                             need - have,
                             na.description
                         ));
                     }
                     s
-                }),
-                "accessory": {
-                    "type": "image",
-                    "image_url": format!("http://{}/gotchi/img/plant/{}.gif", *URL, filify(&ca.art)),
-                    "alt_text": format!("A healthy, growing {}!", p.name),
-                }
-            }));
+                }))
+                .accessory(image(
+                    format!("http://{}/gotchi/img/plant/{}.gif", *URL, filify(&ca.art)),
+                    format!("A healthy, growing {}!", p.name),
+                ))
+                .into(),
+            );
             if let (false, Some(base_yield_duration)) =
                 (sum.yields.is_empty(), p.base_yield_duration)
             {
-                blocks.push(json!({
-                    "type": "section",
-                    "text": mrkdwn(format!(
+                blocks.push(
+                    section(mrkdwn(format!(
                         "*Yield*\n{}  {:.3} minutes to go",
-                        progress_bar(30, 1.0 - p.until_yield/base_yield_duration),
+                        progress_bar(30, 1.0 - p.until_yield / base_yield_duration),
                         (p.until_yield / sum.yield_speed_multiplier) / FARM_CYCLES_PER_MIN as f32
-                    )),
-                    "accessory": {
-                        "type": "button",
-                        "text": plain_text("Yield Stats"),
-                        "value": serde_json::to_string(&(&user_id, tile.id)).unwrap(),
-                        "action_id": "yield_stats",
-                    }
-                }));
+                    )))
+                    .accessory(
+                        button("Yield Stats", "yield_stats")
+                            .value(serde_json::to_string(&(&user_id, tile.id)).unwrap()),
+                    )
+                    .into(),
+                );
             }
             if !sum.recipes.is_empty() {
                 if let (Some(craft), Some(recipe)) = (p.craft.as_ref(), p.current_recipe()) {
-                    blocks.push(json!({
-                        "type": "section",
-                        "text": mrkdwn(format!(
+                    blocks.push(
+                        section(mrkdwn(format!(
                             "*Crafting {}*\n{}  {:.3} minutes to go",
                             recipe.title(),
-                            progress_bar(30, 1.0 - craft.until_finish/recipe.time),
-                            (craft.until_finish / sum.crafting_speed_multiplier) / FARM_CYCLES_PER_MIN as f32
-                        ))
-                    }));
+                            progress_bar(30, 1.0 - craft.until_finish / recipe.time),
+                            (craft.until_finish / sum.crafting_speed_multiplier)
+                                / FARM_CYCLES_PER_MIN as f32
+                        )))
+                        .into(),
+                    );
                 } else {
-                    blocks.push(json!({
-                        "type": "section",
-                        "text": mrkdwn(format!(
+                    blocks.push(
+                        section(mrkdwn(format!(
                             "*{}/{}* recipes craftable",
-                            sum.recipes.iter().filter(|r| r.satisfies(&inventory)).count(),
+                            sum.recipes
+                                .iter()
+                                .filter(|r| r.satisfies(&inventory))
+                                .count(),
                             sum.recipes.len()
-                        )),
-                        "accessory": {
-                            "type": "button",
-                            "text": plain_text("Crafting"),
-                            "value": serde_json::to_string(&(tile.id, &user_id, 0)).unwrap(),
-                            "action_id": "crafting",
-                        }
-                    }));
+                        )))
+                        .accessory(
+                            button("Crafting", "crafting")
+                                .value(serde_json::to_string(&(tile.id, &user_id, 0)).unwrap()),
+                        )
+                        .into(),
+                    );
                 }
             }
         } else {
-            blocks.push(json!({
-                "type": "section",
-                "text": mrkdwn("*Empty Land*\nOpportunity Awaits!"),
-                "accessory": {
-                    "type": "image",
-                    "image_url": format!("http://{}/gotchi/img/icon/dirt.png", *URL),
-                    "alt_text": "Land, waiting to be monopolized upon!",
-                }
-            }));
+            blocks.push(
+                section(mrkdwn("*Empty Land*\nOpportunity Awaits!"))
+                    .accessory(image(
+                        format!("http://{}/gotchi/img/icon/dirt.png", *URL),
+                        "Land, waiting to be monopolized upon!",
+                    ))
+                    .into(),
+            );
         }
         match tile.plant {
             Some(p) => {
                 let ca = p.current_advancement();
-                let mut actions = vec![];
+                let mut buttons: Vec<blocks::Element> = vec![];
 
                 let applicables: Vec<String> =
                     inventory
@@ -949,28 +919,26 @@ fn hackstead_blocks(
                         .collect();
 
                 if !applicables.is_empty() && interactivity.write() {
-                    actions.push(json!({
-                        "type": "button",
-                        "text": plain_text("Apply Item"),
-                        "style": "primary",
-                        "value": serde_json::to_string(&(
-                            tile.id.to_simple().to_string(),
-                            user_id.clone()
-                        )).unwrap(),
-                        "action_id": "item_apply",
-                    }))
+                    buttons.push(
+                        button("Apply Item", "item_apply")
+                            .primary()
+                            .value(
+                                serde_json::to_string(&(
+                                    tile.id.to_simple().to_string(),
+                                    user_id.clone(),
+                                ))
+                                .unwrap(),
+                            )
+                            .into(),
+                    )
                 }
-                actions.push(json!({
-                    "type": "button",
-                    "text": plain_text("Levels"),
-                    "value": serde_json::to_string(&(p.archetype_handle, p.xp)).unwrap(),
-                    "action_id": "levels",
-                }));
-
-                blocks.push(json!({
-                    "type": "actions",
-                    "elements": actions,
-                }));
+                buttons.push(
+                    button("Levels", "levels")
+                        .value(serde_json::to_string(&(p.archetype_handle, p.xp)).unwrap())
+                        .into(),
+                );
+
+                blocks.push(actions(buttons));
                 blocks.push(comment(format!("Last Advancement: \"{}\"", ca.title)));
             }
             None => {
@@ -983,16 +951,10 @@ fn hackstead_blocks(
                 blocks.push(if seeds.is_empty() {
                     comment(":seedlet: No seeds! See if you can buy some on the /hackmarket")
                 } else if let Interactivity::Write = interactivity {
-                    json!({
-                        "type": "actions",
-                        "elements": [{
-                            "type": "button",
-                            "text": plain_text("Plant Seed"),
-                            "style": "primary",
-                            "value": tile.id.to_simple().to_string(),
-                            "action_id": "seed_plant",
-                        }],
-                    })
+                    actions(vec![button("Plant Seed", "seed_plant")
+                        .primary()
+                        .value(tile.id.to_simple())
+                        .into()])
                 } else {
                     comment(":seedling: No planting seeds for you! This page is read only.")
                 });
@@ -1032,22 +994,16 @@ fn hackstead_blocks(
             })
             .map(|_| possession)
     }) {
-        blocks.push(json!({ "type": "divider" }));
+        blocks.push(divider());
 
-        blocks.push(json!({
-            "type": "actions",
-            "elements": [{
-                "type": "button",
-                "text": plain_text("Redeem Land Deed"),
-                "style": "primary",
-                "value": land_deed.id.to_simple().to_string(),
-                "action_id": "unlock_land",
-            }],
-        }));
+        blocks.push(actions(vec![button("Redeem Land Deed", "unlock_land")
+            .primary()
+            .value(land_deed.id.to_simple())
+            .into()]));
     }
 
     //if bottom_inventory {
-    blocks.push(json!({ "type": "divider" }));
+    blocks.push(divider());
 
     if inv_occurrences.is_empty() {
         blocks.push(comment("Your inventory is empty"));
@@ -1064,7 +1020,7 @@ fn hackstead_blocks(
 
     //if bottom_gotchi && gotchis.len() > 0 {
     if gotchis.len() > 0 {
-        blocks.push(json!({ "type": "divider" }));
+        blocks.push(divider());
 
         blocks.append(&mut gotchi_section(
             gotchis,
@@ -1074,14 +1030,20 @@ fn hackstead_blocks(
         ));
     }
 
+    if interactivity.write() && credentials == Credentials::Owner {
+        blocks.push(divider());
+
+        blocks.push(actions(vec![button("API Tokens", "api_tokens").into()]));
+    }
+
     if let Interactivity::Read = interactivity {
-        blocks.push(json!({ "type": "divider" }));
+        blocks.push(divider());
 
         blocks.push(comment(format!(
-            "This is a read-only snapshot of <@{}>'s Hackagotchi Hackstead at a specific point in time. \
+            "This is a read-only snapshot of {}'s Hackagotchi Hackstead at a specific point in time. \
             You can manage your own Hackagotchi Hackstead in real time at your \
             <slack://app?team=T0266FRGM&id={}&tab=home|hackstead>.",
-            &user_id,
+            mention(&user_id),
             *APP_ID,
         )));
     }
@@ -1094,6 +1056,55 @@ fn hackstead_blocks(
     blocks
 }
 
+/// Someone's API tokens, with buttons to revoke them or mint another.
+/// A token that's just been minted is shown, since it can't be seen again later.
+async fn api_token_blocks(user_id: &str, minted: Option<String>) -> Result<Vec<Block>, String> {
+    let mut blocks = vec![section(mrkdwn(format!(
+        "API tokens let you plant, craft, apply items, hatch eggs and sell things \
+        through the API at `http://{}/gotchi/api/v1`. \
+        Each token can be used *{}* times a minute.",
+        *URL,
+        *api::tokens::API_RATE_LIMIT,
+    )))
+    .into()];
+
+    if let Some(token) = minted {
+        blocks.push(
+            section(mrkdwn(format!(
+                "Here's your new token! Keep it somewhere safe, you won't see it again:\n`{}`",
+                token
+            )))
+            .into(),
+        );
+    }
+    blocks.push(divider());
+
+    let tokens = api::tokens::tokens_of(store(), user_id).await?;
+    if tokens.is_empty() {
+        blocks.push(comment("You don't have any tokens yet."));
+    }
+    for token in tokens {
+        blocks.push(
+            section(mrkdwn(format!(
+                "`{}...`, minted {}",
+                token.hint,
+                humantime::format_rfc3339_seconds(token.minted)
+            )))
+            .accessory(
+                button("Revoke", "api_token_revoke")
+                    .style(Style::Danger)
+                    .value(token.hash),
+            )
+            .into(),
+        );
+    }
+
+    blocks.push(actions(vec![button("Mint Token", "api_token_mint")
+        .primary()
+        .into()]));
+    Ok(blocks)
+}
+
 macro_rules! hacksteader_opening_blurb { () => { format!(
 "
 *Build Your Own Hackstead With Hackagotchi!*
@@ -1115,31 +1126,27 @@ Grow your Hacksteading empire by starting today!
 HACKSTEAD_PRICE.to_string()
 ) } }
 
-fn hackstead_explanation_blocks() -> Vec<Value> {
+fn hackstead_explanation_blocks() -> Vec<Block> {
     vec![
-        json!({
-            "type": "section",
-            "text": mrkdwn(hacksteader_opening_blurb!()),
-        }),
-        json!({
-            "type": "actions",
-            "elements": [{
-                "type": "button",
-                "action_id": "hackstead_confirm",
-                "style": "danger",
-                "text": plain_text("Monopolize on Adorableness?"),
-                "confirm": {
-                    "style": "danger",
-                    "title": plain_text("Let's Hackstead, Fred!"),
-                    "text": mrkdwn(
-                         "(P.S. once you click that button, \
-                         expect a direct message on what to do next!)"
-                    ),
-                    "deny": plain_text("I'm short on HN"),
-                    "confirm": plain_text("LET'S HACKSTEAD, FRED!"),
-                }
-            }]
-        }),
+        section(mrkdwn(hacksteader_opening_blurb!())).into(),
+        actions(vec![button(
+            "Monopolize on Adorableness?",
+            "hackstead_confirm",
+        )
+        .style(Style::Danger)
+        .confirm(
+            blocks::confirm(
+                "Let's Hackstead, Fred!",
+                mrkdwn(
+                    "(P.S. once you click that button, \
+                     expect a direct message on what to do next!)",
+                ),
+                "LET'S HACKSTEAD, FRED!",
+                "I'm short on HN",
+            )
+            .style(Style::Danger),
+        )
+        .into()]),
     ]
 }
 
@@ -1150,7 +1157,7 @@ fn hacksteader_greeting_blocks(
     hacksteader: Option<Hacksteader>,
     interactivity: Interactivity,
     creds: Credentials,
-) -> Vec<Value> {
+) -> Vec<Block> {
     let o = match hacksteader {
         Some(hs) => hackstead_blocks(hs, interactivity, creds),
         None => hackstead_explanation_blocks(),
@@ -1161,10 +1168,10 @@ fn hacksteader_greeting_blocks(
     o
 }
 
-async fn hackmarket_blocks(cat: Category, viewer: String) -> Vec<Value> {
+async fn hackmarket_blocks(cat: Category, viewer: String) -> Vec<Block> {
     use config::ArchetypeHandle;
 
-    let sales = market::market_search(&dyn_db(), cat)
+    let sales = market::market_search(store(), cat)
         .await
         .map_err(|e| error!("couldn't search market: {}", e))
         .unwrap_or_default();
@@ -1178,29 +1185,103 @@ async fn hackmarket_blocks(cat: Category, viewer: String) -> Vec<Value> {
         .map(|(s, _)| s.price)
         .fold((0, 0), |(n, sum), p| (n + 1, sum + p));
 
-    // things for sale, sorted by the type of thing they are.
-    let entries: Vec<(String, (u64, usize))> = {
-        let mut entries: HashMap<String, (u64, usize, ArchetypeHandle)> = Default::default();
+    let orders: Vec<market::BuyOrder> = market::buy_orders(store(), None)
+        .await
+        .map_err(|e| error!("couldn't fetch buy orders: {}", e))
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|o| market::archetype_category(o.archetype_handle) == cat)
+        .collect();
+
+    // both sides of the market, sorted by the type of thing they are.
+    // for each, the cheapest one for sale and how many are, and
+    // the most anyone will pay for one and how many they want.
+    let last_prices = market::last_prices(store())
+        .await
+        .map_err(|e| error!("couldn't fetch last prices: {}", e))
+        .unwrap_or_default();
+
+    #[derive(Default)]
+    struct Entry {
+        sales: Option<(u64, usize)>,
+        wanted: Option<(u64, u64)>,
+        last_trade: Option<u64>,
+        archetype_handle: ArchetypeHandle,
+    }
+    let entries: Vec<(String, Entry)> = {
+        let mut entries: HashMap<String, Entry> = Default::default();
 
         for (sale, p) in sales.into_iter() {
-            entries
-                .entry(sale.market_name.clone())
-                .and_modify(|e| {
-                    if sale.price < e.0 {
-                        e.0 = sale.price
-                    }
-                    e.1 += 1;
-                })
-                .or_insert((sale.price, 1, p.archetype_handle));
+            let e = entries.entry(sale.market_name.clone()).or_default();
+            e.archetype_handle = p.archetype_handle;
+            e.sales = Some(match e.sales {
+                Some((lowest_price, count)) => (lowest_price.min(sale.price), count + 1),
+                None => (sale.price, 1),
+            });
+        }
+        for o in orders.iter() {
+            let e = entries.entry(o.name().to_string()).or_default();
+            e.archetype_handle = o.archetype_handle;
+            e.wanted = Some(match e.wanted {
+                Some((highest_price, count)) => {
+                    (highest_price.max(o.max_price), count + o.quantity)
+                }
+                None => (o.max_price, o.quantity),
+            });
+        }
+
+        for e in entries.values_mut() {
+            e.last_trade = last_prices.get(&e.archetype_handle).copied();
         }
 
         let mut v: Vec<_> = entries.into_iter().collect();
-        v.sort_by_key(|&(_, (_, _, ah))| ah);
-        v.into_iter()
-            .map(|(name, (lowest_price, count, _))| (name, (lowest_price, count)))
-            .collect()
+        v.sort_by_key(|(_, e)| e.archetype_handle);
+        v
     };
 
+    let auctions: Vec<Block> = auction::auctions(store())
+        .await
+        .map_err(|e| error!("couldn't fetch auctions: {}", e))
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|a| a.key.category == cat && !a.ended())
+        .map(|a| {
+            section(mrkdwn(format!(
+                "{} _{}_ up for auction by {}, {}, ending in _{}_.",
+                emojify(&a.name),
+                a.name,
+                mention(&a.seller),
+                match &a.high_bid {
+                    Some(bid) => format!("*{}hn* bid", bid.amount),
+                    None => format!("starting at *{}hn*", a.terms.reserve),
+                },
+                humantime::format_duration(a.time_left()),
+            )))
+            .accessory(button("Bid", "auction_bid").primary().value(a.id()))
+            .into()
+        })
+        .collect();
+
+    let your_orders: Vec<Block> = orders
+        .iter()
+        .filter(|o| o.buyer == viewer)
+        .map(|o| {
+            section(mrkdwn(format!(
+                "You want *{}* {} _{}_ for up to *{}hn* each.",
+                o.quantity,
+                emojify(o.name()),
+                o.name(),
+                o.max_price,
+            )))
+            .accessory(
+                button("Cancel", "buy_order_cancel")
+                    .style(Style::Danger)
+                    .value(o.id()),
+            )
+            .into()
+        })
+        .collect();
+
     let entry_count = entries.len();
     std::iter::once(comment(format!(
         concat!(
@@ -1214,39 +1295,125 @@ async fn hackmarket_blocks(cat: Category, viewer: String) -> Vec<Value> {
         all_goods_price,
         all_goods_count,
     )))
+    .chain(std::iter::once(actions(vec![button(
+        "Post a Buy Order",
+        "buy_order_new",
+    )
+    .value(serde_json::to_string(&cat).unwrap())
+    .into()])))
+    .chain(your_orders)
+    .chain(std::iter::once(divider()))
+    .chain(auctions.into_iter().flat_map(|a| vec![a, divider()]))
     .chain(
         entries
             .into_iter()
-            .flat_map(|(name, (lowest_price, count))| {
-                std::iter::once(json!({
-                    "type": "section",
-                    "fields": [
-                        mrkdwn(format!(
-                            "{} _{}_",
-                            emojify(&name),
-                            name,
-                        )),
-                    ],
-                    "accessory": {
-                        "type": "button",
-                        "style": "primary",
-                        "text": plain_text(format!(
-                            "{} for sale starting at {}hn",
-                            count,
-                            lowest_price
-                        )),
-                        "action_id": "possession_market_overview_page",
-                        "value": serde_json::to_string(&(name, cat)).unwrap(),
-                    }
-                }))
-                .chain(std::iter::once(json!({ "type": "divider" })))
+            .flat_map(|(name, entry)| {
+                let mut listing = blocks::fields(
+                    std::iter::once(mrkdwn(format!("{} _{}_", emojify(&name), name,)))
+                        .chain(entry.wanted.map(|(highest_price, count)| {
+                            mrkdwn(format!(
+                                "*{}* wanted for up to *{}hn*",
+                                count, highest_price
+                            ))
+                        }))
+                        .chain(
+                            entry
+                                .last_trade
+                                .map(|price| mrkdwn(format!("last sold for *{}hn*", price))),
+                        )
+                        .collect(),
+                );
+                if let Some((lowest_price, count)) = entry.sales {
+                    listing = listing.accessory(
+                        button(
+                            format!("{} for sale starting at {}hn", count, lowest_price),
+                            "possession_market_overview_page",
+                        )
+                        .primary()
+                        .value(serde_json::to_string(&(name, cat)).unwrap()),
+                    );
+                }
+
+                std::iter::once(Block::from(listing)).chain(std::iter::once(divider()))
             })
             .take((entry_count * 2).saturating_sub(1)),
     )
     .collect()
 }
 
-#[derive(FromForm, Debug, Clone)]
+/// Options for picking things to trade out of `tradeable`, and which of them are
+/// picked already. Slack won't show more than 100 options.
+fn trade_item_options(
+    tradeable: &[Possession],
+    selected: &[uuid::Uuid],
+) -> Result<(Vec<blocks::SelectOption>, Vec<blocks::SelectOption>), String> {
+    let mut options = vec![];
+    let mut initial = vec![];
+    for p in tradeable.iter().take(100) {
+        let option = blocks::select_option(
+            format!("{} {}", emojify(&p.name), p.nickname()),
+            p.id.to_simple().to_string(),
+        )?;
+        if selected.contains(&p.id) {
+            initial.push(option.clone());
+        }
+        options.push(option);
+    }
+    Ok((options, initial))
+}
+
+/// What an archetype's gone for on the market, and how often.
+async fn price_history_blocks(
+    archetype_handle: config::ArchetypeHandle,
+    name: &str,
+) -> Result<Vec<Block>, String> {
+    const RECENT_TRADES: usize = 10;
+
+    let trades = market::trades(store(), Some(archetype_handle)).await?;
+    let history = match market::price_history(&trades) {
+        Some(history) => history,
+        None => {
+            return Ok(vec![section(mrkdwn(format!(
+                "Nobody's sold a {} _{}_ on the market yet.",
+                emojify(name),
+                name
+            )))
+            .into()])
+        }
+    };
+
+    Ok(vec![
+        section(mrkdwn(format!("{} _{}_", emojify(name), name))).into(),
+        blocks::fields(vec![
+            mrkdwn(format!("*Last price:* {}hn", history.last_price)),
+            mrkdwn(format!("*Median price:* {}hn", history.median_price)),
+            mrkdwn(format!("*Sold this week:* {}", history.week_volume)),
+            mrkdwn(format!("*All time:* {}", trades.len())),
+        ])
+        .into(),
+        section(mrkdwn(format!("`{}`", history.sparkline))).into(),
+        comment(format!(
+            "the last {} sales, oldest first",
+            trades.len().min(market::SPARKLINE_LEN)
+        )),
+        divider(),
+    ]
+    .into_iter()
+    .chain(trades.iter().rev().take(RECENT_TRADES).map(|t| {
+        comment(format!(
+            "{} sold one to {} for *{}hn* {} ago",
+            mention(&t.seller),
+            mention(&t.buyer),
+            t.price,
+            humantime::format_duration(std::time::Duration::from_secs(
+                t.at.elapsed().map_or(0, |e| e.as_secs())
+            )),
+        ))
+    }))
+    .collect())
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
 struct SlashCommand {
     token: String,
     team_id: String,
@@ -1262,7 +1429,7 @@ struct SlashCommand {
 }
 
 #[post("/hackmarket", data = "<slash_command>")]
-async fn hackmarket<'a>(slash_command: LenientForm<SlashCommand>) -> Result<(), String> {
+async fn hackmarket<'a>(slash_command: SlackForm<SlashCommand>) -> Result<(), String> {
     debug!("{} | {}", slash_command.command, slash_command.text);
 
     Modal {
@@ -1287,102 +1454,60 @@ async fn hackmarket<'a>(slash_command: LenientForm<SlashCommand>) -> Result<(),
     Ok(())
 }
 
-pub async fn stateofsteading_blocks() -> Vec<Value> {
-    let profiles = hcor::Profile::fetch_all(&dyn_db()).await.unwrap();
-    let tiles = hacksteader::Tile::fetch_all(&dyn_db()).await.unwrap();
-
-    struct PlantEntry {
-        owner: String,
-        seed_from: String,
-        level: usize,
-    }
-
-    let mut archetype_occurrences: HashMap<config::PlantArchetype, Vec<PlantEntry>> =
-        Default::default();
-
-    for tile in tiles.iter() {
-        let plant = match &tile.plant {
-            Some(plant) => plant,
-            None => continue,
-        };
-
-        archetype_occurrences
-            .entry((**plant).clone())
-            .or_default()
-            .push(PlantEntry {
-                owner: tile.steader.clone(),
-                seed_from: plant
-                    .pedigree
-                    .last()
-                    .map(|x| x.id.clone())
-                    .unwrap_or("U013STH0TNG".to_string()),
-                level: plant.advancements.current_position(plant.xp),
-            });
-    }
+pub async fn stateofsteading_blocks() -> Vec<Block> {
+    let state = api::state_of_steading(store()).await.unwrap();
 
-    std::iter::once(json!({
-        "type": "section",
-        "text": mrkdwn(format!(
-            concat!(
-                "Total Hacksteaders: *{}*\n",
-                "Total Tiles: *{}*\n",
-                "{}",
-            ),
-            profiles.len(),
-            tiles.len(),
-            archetype_occurrences
+    std::iter::once(Block::from(
+        section(mrkdwn(format!(
+            concat!("Total Hacksteaders: *{}*\n", "Total Tiles: *{}*\n", "{}",),
+            state.hacksteaders,
+            state.tiles,
+            state
+                .plants
                 .iter()
-                .map(|(plant_archetype, plants)| format!(
-                    "*{}* _{}_ plants",
-                    plants.len(),
-                    plant_archetype.name
-                ))
+                .map(|(name, plants)| format!("*{}* _{}_ plants", plants.len(), name))
                 .collect::<Vec<String>>()
                 .join("\n")
+        )))
+        .accessory(image(
+            format!("http://{}/gotchi/img/icon/seedlet.png", *URL),
+            "happy shiny better hackstead",
         )),
-        "accessory": {
-            "type": "image",
-            "image_url": format!("http://{}/gotchi/img/icon/seedlet.png", *URL),
-            "alt_text": "happy shiny better hackstead",
-        }
-    }))
-    .chain(
-        archetype_occurrences
-            .iter_mut()
-            .map(|(plant_archetype, plants)| {
-                plants.sort_by_key(|p| p.level);
-                json!({
-                    "type": "section",
-                    "text": mrkdwn(
-                        plants
-                            .iter_mut()
-                            .map(|plant| format!(
-                                "<@{}> grows a *{}lvl* _{}_ from <@{}>'s seed",
-                                plant.owner,
-                                plant.level,
-                                plant_archetype.name,
-                                plant.seed_from
-                            ))
-                            .collect::<Vec<_>>()
-                            .join("\n")
-                    ),
-                    "accessory": {
-                        "type": "image",
-                        "image_url": format!(
-                            "http://{}/gotchi/img/plant/{}.gif",
-                            *URL,
-                            filify(&plant_archetype.advancements.base.art)
-                        ),
-                        "alt_text": "happy shiny plant give u stuffs",
-                    }
+    ))
+    .chain(state.plants.iter().map(|(name, plants)| {
+        let art = CONFIG
+            .plant_archetypes
+            .iter()
+            .find(|a| a.name == *name)
+            .map(|a| a.advancements.base.art.clone())
+            .unwrap_or_default();
+
+        section(mrkdwn(
+            plants
+                .iter()
+                .map(|plant| {
+                    format!(
+                        "{} grows a *{}lvl* _{}_ from {}'s seed",
+                        mention(&plant.owner),
+                        plant.level,
+                        name,
+                        mention(&plant.seed_from)
+                    )
                 })
-            }),
-    )
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ))
+        .accessory(image(
+            format!("http://{}/gotchi/img/plant/{}.gif", *URL, filify(&art)),
+            "happy shiny plant give u stuffs",
+        ))
+        .into()
+    }))
     .collect()
 }
 
 #[post("/stateofsteading", data = "<slash_command>")]
-async fn stateofsteading<'a>(slash_command: LenientForm<SlashCommand>) -> Result<(), String> {
+async fn stateofsteading<'a>(slash_command: SlackForm<SlashCommand>) -> Result<(), String> {
     Modal {
         method: "open".to_string(),
         trigger_id: slash_command.trigger_id.clone(),
@@ -1399,16 +1524,13 @@ async fn stateofsteading<'a>(slash_command: LenientForm<SlashCommand>) -> Result
 }
 
 #[post("/hgive", data = "<slash_command>")]
-async fn hgive<'a>(slash_command: LenientForm<SlashCommand>) -> Json<Value> {
+async fn hgive<'a>(slash_command: SlackForm<SlashCommand>) -> Json<Value> {
     use regex::Regex;
 
     fn res<S: std::string::ToString>(s: S) -> Json<Value> {
         debug!("{}", s.to_string());
         Json(json!({
-            "blocks": [{
-                "type": "section",
-                "text": mrkdwn(s),
-            }],
+            "blocks": [Block::from(section(mrkdwn(s)))],
             "response_type": "ephemeral",
         }))
     }
@@ -1453,7 +1575,7 @@ async fn hgive<'a>(slash_command: LenientForm<SlashCommand>) -> Json<Value> {
     };
 
     let user = slash_command.user_id.to_string();
-    let hs = match Hacksteader::from_db(&dyn_db(), user.clone()).await {
+    let hs = match Hacksteader::from_db(store(), user.clone()).await {
         Ok(hs) => hs,
         Err(_) => {
             return res(format!(
@@ -1486,31 +1608,32 @@ async fn hgive<'a>(slash_command: LenientForm<SlashCommand>) -> Json<Value> {
         res("Well, I mean ... that's not really anything but ... ok")
     } else {
         let notif_msg = format!(
-            "<@{}> gave <@{}> {} {}!",
-            user, receiver, amount, possession_archetype.name
+            "{} gave {} {} {}!",
+            mention(&user),
+            mention(&receiver),
+            amount,
+            possession_archetype.name
         );
         let res_msg = json!({
             "blocks": [
-                {
-                    "type": "section",
-                    "text": mrkdwn(format!(
-                        "<@{}> shall soon happen across *{}* of <@{}>'s :{}: _{}_!",
-                        receiver,
+                Block::from(
+                    section(mrkdwn(format!(
+                        "{} shall soon happen across *{}* of {}'s :{}: _{}_!",
+                        mention(&receiver),
                         amount,
-                        user,
+                        mention(&user),
                         possession_name.replace(" ", "_"),
                         possession_archetype.name,
-                    )),
-                    "accessory": {
-                        "type": "image",
-                        "image_url": format!(
+                    )))
+                    .accessory(image(
+                        format!(
                             "http://{}/gotchi/img/misc/{}.png",
                             *URL,
                             filify(&possession_name)
                         ),
-                        "alt_text": "hackagotchi img",
-                    }
-                },
+                        "hackagotchi img",
+                    ))
+                ),
                 comment("EY BRO TAEK DIS N DONT TELL MOM, OwO")
             ],
             "response_type": "in_channel",
@@ -1532,7 +1655,7 @@ async fn hgive<'a>(slash_command: LenientForm<SlashCommand>) -> Json<Value> {
 
             for possession in possessions {
                 match Hacksteader::transfer_possession(
-                    &dyn_db(),
+                    store(),
                     receiver.clone(),
                     possess::Acquisition::Trade,
                     Key::misc(possession.id),
@@ -1553,7 +1676,7 @@ async fn hgive<'a>(slash_command: LenientForm<SlashCommand>) -> Json<Value> {
 
 #[post("/egghatchwhen", data = "<slash_command>")]
 async fn egghatchwhen<'a>(
-    slash_command: LenientForm<SlashCommand>,
+    slash_command: SlackForm<SlashCommand>,
     to_farming: State<'_, Sender<FarmingInputEvent>>,
 ) -> Json<Value> {
     use rand::seq::SliceRandom;
@@ -1567,16 +1690,13 @@ async fn egghatchwhen<'a>(
 
     fn res<S: std::string::ToString>(s: S) -> Json<Value> {
         Json(json!({
-            "blocks": [{
-                "type": "section",
-                "text": mrkdwn(s),
-            }],
+            "blocks": [Block::from(section(mrkdwn(s)))],
             "response_type": "in_channel",
         }))
     }
 
     let user = user_id.to_string();
-    let hs = match Hacksteader::from_db(&dyn_db(), user.clone()).await {
+    let hs = match Hacksteader::from_db(store(), user.clone()).await {
         Ok(hs) => hs,
         Err(_) => {
             return res(concat!(
@@ -1600,18 +1720,23 @@ async fn egghatchwhen<'a>(
         Some(id) => id,
     };
 
-    to_farming
-        .send(FarmingInputEvent::ActivateUser(user.clone()))
-        .unwrap();
-
-    to_farming
-        .send(FarmingInputEvent::HatchEgg(egg_id, user.clone()))
-        .unwrap();
+    let queued = async {
+        farm::queue(&to_farming, FarmingInputEvent::ActivateUser(user.clone())).await?;
+        farm::queue(
+            &to_farming,
+            FarmingInputEvent::HatchEgg(egg_id, user.clone()),
+        )
+        .await
+    };
+    if let Err(e) = queued.await {
+        error!("couldn't queue egg hatch: {}", e);
+        return res("Couldn't get to your eggs right now, try again in a bit!");
+    }
 
     res("Selected one of your eggs and hatched it!")
 }
 #[post("/hackstead", data = "<slash_command>")]
-async fn hackstead<'a>(slash_command: LenientForm<SlashCommand>) -> Json<Value> {
+async fn hackstead<'a>(slash_command: SlackForm<SlashCommand>) -> Json<Value> {
     debug!("{:#?}", slash_command);
 
     lazy_static::lazy_static! {
@@ -1626,7 +1751,7 @@ async fn hackstead<'a>(slash_command: LenientForm<SlashCommand>) -> Json<Value>
         .and_then(|c| c.get(2).map(|x| x.as_str()))
         .unwrap_or(&slash_command.user_id);
 
-    let hs = Hacksteader::from_db(&dyn_db(), user.to_string()).await;
+    let hs = Hacksteader::from_db(store(), user.to_string()).await;
     Json(json!({
         "blocks": hacksteader_greeting_blocks(
             hs.ok(),
@@ -1637,7 +1762,7 @@ async fn hackstead<'a>(slash_command: LenientForm<SlashCommand>) -> Json<Value>
     }))
 }
 
-#[derive(FromForm, Debug)]
+#[derive(serde::Deserialize, Debug)]
 struct ActionData {
     payload: String,
 }
@@ -1673,48 +1798,32 @@ pub struct Modal {
     callback_id: String,
     title: String,
     private_metadata: String,
-    blocks: Vec<Value>,
+    blocks: Vec<Block>,
     submit: Option<String>,
 }
 
 impl Modal {
     async fn launch(self) -> Result<Value, String> {
-        let submit = self.submit.clone();
-        let method = self.method.clone();
-
-        let mut o = json!({
-            "trigger_id": self.trigger_id,
-            "view": self.view()
+        let push = match self.method.as_str() {
+            "open" => false,
+            "push" => true,
+            other => return Err(format!("can't {} a modal", other)),
+        };
+        let trigger_id = self.trigger_id.clone();
+        let view = self.view();
+        let o = json!({
+            "trigger_id": trigger_id,
+            "view": view,
         });
 
-        if let Some(submit_msg) = submit {
-            o["view"]
-                .as_object_mut()
-                .unwrap()
-                .insert("submit".to_string(), plain_text(submit_msg));
-        }
-
-        let client = reqwest::Client::new();
-        client
-            .post(&format!("https://slack.com/api/views.{}", method))
-            .bearer_auth(&*TOKEN)
-            .json(&o)
-            .send()
-            .await
-            .map_err(|e| format!("couldn't open modal: {}", e))?;
-
-        debug!("{}", serde_json::to_string_pretty(&o).unwrap());
+        chat().open_modal(&trigger_id, view, push).await?;
         Ok(o)
     }
 
-    fn view(self) -> Value {
-        json!({
-            "type": "modal",
-            "private_metadata": self.private_metadata,
-            "callback_id": self.callback_id,
-            "title": plain_text(self.title),
-            "blocks": self.blocks
-        })
+    fn view(self) -> blocks::View {
+        blocks::View::modal(self.title, self.callback_id, self.blocks)
+            .private_metadata(self.private_metadata)
+            .submit(self.submit)
     }
 }
 
@@ -1726,47 +1835,22 @@ pub struct ModalUpdate {
     private_metadata: String,
     hash: Option<String>,
     view_id: String,
-    blocks: Vec<Value>,
+    blocks: Vec<Block>,
     submit: Option<String>,
 }
 
 impl ModalUpdate {
     async fn launch(self) -> Result<Value, String> {
-        let mut o = json!({
+        let view = blocks::View::modal(self.title, self.callback_id, self.blocks)
+            .private_metadata(self.private_metadata)
+            .submit(self.submit);
+        let o = json!({
             "trigger_id": self.trigger_id,
             "view_id": self.view_id,
-            "view": {
-                "type": "modal",
-                "private_metadata": self.private_metadata,
-                "callback_id": self.callback_id,
-                "title": plain_text(self.title),
-                "blocks": self.blocks
-            }
+            "view": view,
         });
 
-        if let Some(hash) = self.hash {
-            o.as_object_mut()
-                .unwrap()
-                .insert("hash".to_string(), json!(hash));
-        }
-
-        if let Some(submit_msg) = self.submit {
-            o["view"]
-                .as_object_mut()
-                .unwrap()
-                .insert("submit".to_string(), plain_text(submit_msg));
-        }
-
-        let client = reqwest::Client::new();
-        client
-            .post("https://slack.com/api/views.update")
-            .bearer_auth(&*TOKEN)
-            .json(&o)
-            .send()
-            .await
-            .map_err(|e| format!("couldn't open modal: {}", e))?;
-
-        debug!("{}", serde_json::to_string_pretty(&o).unwrap());
+        chat().update_modal(&self.view_id, self.hash, view).await?;
         Ok(o)
     }
 }
@@ -1819,7 +1903,7 @@ pub enum Credentials {
 #[post("/interact", data = "<action_data>")]
 async fn action_endpoint(
     to_farming: State<'_, Sender<FarmingInputEvent>>,
-    action_data: LenientForm<ActionData>,
+    action_data: SlackForm<ActionData>,
 ) -> Result<ActionResponse, String> {
     debug!("{:?}", action_data);
     let v = serde_json::from_str::<Value>(&action_data.payload).unwrap();
@@ -1851,7 +1935,7 @@ async fn action_endpoint(
             match view.callback_id.as_str() {
                 "sale_removal" => {
                     info!("Revoking sale");
-                    market::take_off_market(&dyn_db(), key).await?;
+                    market::withdraw_listing(store(), key, &user.id).await?;
 
                     return Ok(ActionResponse::Json(Json(json!({
                         "response_action": "clear",
@@ -1859,20 +1943,21 @@ async fn action_endpoint(
                 }
                 "sale_complete" => {
                     info!("Completing sale!");
-                    let possession = hacksteader::get_possession(&dyn_db(), key).await?;
+                    let possession = hacksteader::get_possession(store(), key).await?;
 
                     if let Some(sale) = possession.sale.as_ref() {
                         banker::invoice(
                             &user.id,
                             sale.price,
                             &format!(
-                                "hackmarket purchase buying {} at {}hn :{}:{} from <@{}>",
-                                possession.name,
-                                sale.price,
-                                key.id,
-                                key.category as u8,
-                                possession.steader,
+                                "hackmarket purchase buying {} at {}hn from <@{}>",
+                                possession.name, sale.price, possession.steader,
                             ),
+                            banker::InvoiceIntent::Purchase {
+                                key,
+                                price: sale.price,
+                                seller: possession.steader.clone(),
+                            },
                         )
                         .await?;
                     }
@@ -1881,6 +1966,75 @@ async fn action_endpoint(
                         "response_action": "clear",
                     }))));
                 }
+                "possession_auction_modal" => {
+                    let number = |block: &str, input: &str| {
+                        values
+                            .get(block)
+                            .and_then(|i| i.get(input))
+                            .and_then(|s| s.get("value"))
+                            .and_then(|s| s.as_str())
+                            .and_then(|s| s.trim().parse::<u64>().ok())
+                            .filter(|&n| n > 0)
+                    };
+                    let reserve = number(
+                        "possession_auction_reserve_block",
+                        "possession_auction_reserve_input",
+                    )
+                    .filter(|&r| r >= auction::MIN_RESERVE);
+                    let increment = number(
+                        "possession_auction_increment_block",
+                        "possession_auction_increment_input",
+                    );
+                    let hours = number(
+                        "possession_auction_hours_block",
+                        "possession_auction_hours_input",
+                    )
+                    .filter(|&h| h * 60 * 60 <= auction::MAX_DURATION.as_secs());
+
+                    let terms = match (reserve, increment, hours) {
+                        (Some(reserve), Some(increment), Some(hours)) => auction::Terms {
+                            reserve,
+                            increment,
+                            duration: std::time::Duration::from_secs(hours * 60 * 60),
+                        },
+                        (r, i, h) => {
+                            let mut errors = serde_json::Map::new();
+                            if r.is_none() {
+                                errors.insert(
+                                    "possession_auction_reserve_block".to_string(),
+                                    json!(format!(
+                                        "the reserve has to be at least {}hn",
+                                        auction::MIN_RESERVE
+                                    )),
+                                );
+                            }
+                            if i.is_none() {
+                                errors.insert(
+                                    "possession_auction_increment_block".to_string(),
+                                    json!("bids have to go up by at least 1hn"),
+                                );
+                            }
+                            if h.is_none() {
+                                errors.insert(
+                                    "possession_auction_hours_block".to_string(),
+                                    json!(format!(
+                                        "auctions can run for 1 to {} hours",
+                                        auction::MAX_DURATION.as_secs() / 60 / 60
+                                    )),
+                                );
+                            }
+                            return Ok(ActionResponse::Json(Json(json!({
+                                "response_action": "errors",
+                                "errors": errors,
+                            }))));
+                        }
+                    };
+
+                    let possession = hacksteader::get_possession(store(), key).await?;
+                    auction::invoice_auction_fee(store(), &user.id, &possession, terms).await?;
+
+                    return Ok(ActionResponse::Ok(()));
+                }
                 _ => {}
             };
 
@@ -1890,30 +2044,13 @@ async fn action_endpoint(
                 .and_then(|s| s.get("value"))
             {
                 // update the nickname in the DB
-                let db = dyn_db();
-                db.update_item(rusoto_dynamodb::UpdateItemInput {
-                    table_name: hcor::TABLE_NAME.to_string(),
-                    key: key.into_item(),
-                    update_expression: Some("SET nickname = :new_name".to_string()),
-                    expression_attribute_values: Some(
-                        [(
-                            ":new_name".to_string(),
-                            AttributeValue {
-                                s: Some(nickname.clone()),
-                                ..Default::default()
-                            },
-                        )]
-                        .iter()
-                        .cloned()
-                        .collect(),
-                    ),
-                    ..Default::default()
-                })
-                .await
-                .map_err(|e| format!("Couldn't change nickname in database: {}", e))?;
+                let db = store();
+                db.rename_gotchi(key, nickname.clone())
+                    .await
+                    .map_err(|e| format!("Couldn't change nickname in database: {}", e))?;
 
                 // TODO: parse what the above could return
-                let mut possession = hacksteader::get_possession(&db, key).await?;
+                let mut possession = hacksteader::get_possession(db, key).await?;
 
                 let gotchi = possession
                     .kind
@@ -1935,9 +2072,11 @@ async fn action_endpoint(
                     .await?;
 
                 // update the home tab
-                to_farming
-                    .send(FarmingInputEvent::ActivateUser(user.id.clone()))
-                    .unwrap();
+                farm::queue(
+                    &to_farming,
+                    FarmingInputEvent::ActivateUser(user.id.clone()),
+                )
+                .await?;
 
                 // this will close the "enter nickname" modal
                 return Ok(ActionResponse::Ok(()));
@@ -1948,20 +2087,63 @@ async fn action_endpoint(
                 .and_then(|x| x.as_str())
                 .and_then(|s| s.parse::<u64>().ok())
             {
-                let possession = hacksteader::get_possession(&dyn_db(), key).await?;
-
-                banker::invoice(
-                    &user.id,
-                    price / 20_u64,
-                    &format!(
-                        "hackmarket fees for selling {} at {}hn :{}:{}",
-                        possession.name,
-                        price,
-                        possession.id,
-                        possession.kind.category() as u8
-                    ),
-                )
-                .await?;
+                // the hours to list it for, or none if it stays up until it's taken down
+                let duration = values
+                    .get("possession_sell_duration_block")
+                    .and_then(|i| i.get("possession_sell_duration_input"))
+                    .and_then(|s| s.get("selected_option"))
+                    .and_then(|s| s.get("value"))
+                    .and_then(|s| s.as_str())
+                    .and_then(|s| serde_json::from_str::<Option<u64>>(s).ok())
+                    .flatten()
+                    .map(|hours| std::time::Duration::from_secs(hours * 60 * 60));
+                let quantity = values
+                    .get("possession_sell_quantity_block")
+                    .and_then(|i| i.get("possession_sell_quantity_input"))
+                    .and_then(|s| s.get("value"))
+                    .and_then(|s| s.as_str())
+                    .map(|s| s.trim().parse::<usize>().ok().filter(|&n| n > 0));
+                let possession = hacksteader::get_possession(store(), key).await?;
+
+                match quantity {
+                    None | Some(Some(1)) => {
+                        market::invoice_listing_fee(&user.id, &possession, price, duration).await?;
+                    }
+                    Some(Some(n)) => {
+                        // the one they picked, then as many more of it as they asked for
+                        let mut batch = vec![possession.clone()];
+                        batch.extend(
+                            market::unlisted(store(), &user.id, possession.archetype_handle)
+                                .await?
+                                .into_iter()
+                                .filter(|p| p.id != possession.id)
+                                .take(n - 1),
+                        );
+                        let invoiced = match batch.len() {
+                            got if got < n => Err(format!("you only have {} to sell", got)),
+                            _ => {
+                                market::invoice_batch_listing_fee(&user.id, &batch, price, duration)
+                                    .await
+                            }
+                        };
+                        if let Err(e) = invoiced {
+                            return Ok(ActionResponse::Json(Json(json!({
+                                "response_action": "errors",
+                                "errors": {
+                                    "possession_sell_quantity_block": e,
+                                }
+                            }))));
+                        }
+                    }
+                    Some(None) => {
+                        return Ok(ActionResponse::Json(Json(json!({
+                            "response_action": "errors",
+                            "errors": {
+                                "possession_sell_quantity_block": "that's not how many you're selling",
+                            }
+                        }))));
+                    }
+                }
 
                 return Ok(ActionResponse::Ok(()));
             } else if let Some(Value::String(new_owner)) = values
@@ -1987,7 +2169,7 @@ async fn action_endpoint(
 
                 // update the owner in the DB
                 Hacksteader::transfer_possession(
-                    &dyn_db(),
+                    store(),
                     new_owner.clone(),
                     possess::Acquisition::Trade,
                     key,
@@ -1998,9 +2180,12 @@ async fn action_endpoint(
                 // TODO: make this not read from the database
                 update_user_home_tab(user.id.clone()).await?;
 
-                let possession = hacksteader::get_possession(&dyn_db(), key).await?;
-                let notif_msg =
-                    format!("<@{}> has gifted you a {}!", user.id, possession.nickname());
+                let possession = hacksteader::get_possession(store(), key).await?;
+                let notif_msg = format!(
+                    "{} has gifted you a {}!",
+                    mention(&user.id),
+                    possession.nickname()
+                );
 
                 // DM the new_owner about their new acquisition!
                 gift_dm(&user.id, new_owner, &possession, notif_msg, 1).await?;
@@ -2022,12 +2207,234 @@ async fn action_endpoint(
                             .map_err(|e| error!("{}", e))
                             .unwrap();
 
-                    to_farming
-                        .send(FarmingInputEvent::BeginCraft {
+                    farm::queue(
+                        &to_farming,
+                        FarmingInputEvent::BeginCraft {
                             tile_id,
                             recipe_archetype_handle,
+                        },
+                    )
+                    .await?;
+
+                    return Ok(ActionResponse::Json(Json(json!({
+                        "response_action": "clear",
+                    }))));
+                }
+                "buy_order_modal" => {
+                    let archetype_handle: Option<config::ArchetypeHandle> = values
+                        .get("buy_order_item_block")
+                        .and_then(|i| i.get("buy_order_item_input"))
+                        .and_then(|s| s.get("selected_option"))
+                        .and_then(|s| s.get("value"))
+                        .and_then(|s| s.as_str())
+                        .and_then(|s| s.parse().ok());
+                    let number = |block: &str, input: &str| {
+                        values
+                            .get(block)
+                            .and_then(|i| i.get(input))
+                            .and_then(|s| s.get("value"))
+                            .and_then(|s| s.as_str())
+                            .and_then(|s| s.trim().parse::<u64>().ok())
+                            .filter(|&n| n > 0)
+                    };
+                    let max_price = number("buy_order_price_block", "buy_order_price_input");
+                    let quantity = number("buy_order_quantity_block", "buy_order_quantity_input");
+
+                    let (archetype_handle, max_price, quantity) =
+                        match (archetype_handle, max_price, quantity) {
+                            (Some(ah), Some(p), Some(q)) => (ah, p, q),
+                            (_, p, q) => {
+                                let mut errors = serde_json::Map::new();
+                                if p.is_none() {
+                                    errors.insert(
+                                        "buy_order_price_block".to_string(),
+                                        json!("that's not a price"),
+                                    );
+                                }
+                                if q.is_none() {
+                                    errors.insert(
+                                        "buy_order_quantity_block".to_string(),
+                                        json!("that's not how many you want"),
+                                    );
+                                }
+                                return Ok(ActionResponse::Json(Json(json!({
+                                    "response_action": "errors",
+                                    "errors": errors,
+                                }))));
+                            }
+                        };
+                    let name = &CONFIG
+                        .possession_archetypes
+                        .get(archetype_handle)
+                        .ok_or_else(|| format!("no archetype {}", archetype_handle))?
+                        .name;
+
+                    banker::invoice(
+                        &user.id,
+                        max_price * quantity,
+                        &format!(
+                            "hackmarket buy order for {} {} at up to {}hn each",
+                            quantity, name, max_price
+                        ),
+                        banker::InvoiceIntent::BuyOrder {
+                            archetype_handle,
+                            max_price,
+                            quantity,
+                        },
+                    )
+                    .await?;
+
+                    return Ok(ActionResponse::Json(Json(json!({
+                        "response_action": "clear",
+                    }))));
+                }
+                "trade_propose_modal" | "trade_offer_modal" => {
+                    let text = |block: &str, input: &str| {
+                        values
+                            .get(block)
+                            .and_then(|i| i.get(input))
+                            .and_then(|s| s.get("value"))
+                            .and_then(|s| s.as_str())
+                            .map(|s| s.trim().to_string())
+                            .unwrap_or_default()
+                    };
+                    let hn = match text("trade_hn_block", "trade_hn_input").as_str() {
+                        "" => Some(0),
+                        hn => hn.parse::<u64>().ok(),
+                    };
+                    let selected: Vec<String> = values
+                        .get("trade_items_block")
+                        .and_then(|i| i.get("trade_items_input"))
+                        .and_then(|s| s.get("selected_options"))
+                        .and_then(|s| s.as_array())
+                        .map(|options| {
+                            options
+                                .iter()
+                                .filter_map(|o| o.get("value")?.as_str())
+                                .map(|v| v.to_string())
+                                .collect()
                         })
-                        .expect("couldn't send to farming");
+                        .unwrap_or_default();
+                    let items: Vec<trade::TradeItem> = trade::tradeable(store(), &user.id)
+                        .await?
+                        .iter()
+                        .filter(|p| selected.contains(&p.id.to_simple().to_string()))
+                        .map(trade::TradeItem::from)
+                        .collect();
+
+                    let hn = match hn {
+                        Some(hn) => hn,
+                        None => {
+                            return Ok(ActionResponse::Json(Json(json!({
+                                "response_action": "errors",
+                                "errors": {
+                                    "trade_hn_block": "that's not an amount of HN",
+                                }
+                            }))));
+                        }
+                    };
+                    let (result, error_block) = match view.callback_id.as_str() {
+                        "trade_propose_modal" => {
+                            let recipient = values
+                                .get("trade_recipient_block")
+                                .and_then(|i| i.get("trade_recipient_input"))
+                                .and_then(|s| s.get("selected_user"))
+                                .and_then(|s| s.as_str())
+                                .unwrap_or_default()
+                                .to_string();
+                            let note = text("trade_note_block", "trade_note_input");
+                            (
+                                trade::propose(
+                                    store(),
+                                    user.id.clone(),
+                                    recipient,
+                                    items,
+                                    hn,
+                                    note,
+                                )
+                                .await,
+                                "trade_recipient_block",
+                            )
+                        }
+                        _ => (
+                            trade::respond(store(), &view.private_metadata, &user.id, items, hn)
+                                .await,
+                            "trade_items_block",
+                        ),
+                    };
+                    if let Err(e) = result {
+                        return Ok(ActionResponse::Json(Json(json!({
+                            "response_action": "errors",
+                            "errors": {
+                                error_block: e,
+                            }
+                        }))));
+                    }
+
+                    return Ok(ActionResponse::Json(Json(json!({
+                        "response_action": "clear",
+                    }))));
+                }
+                "market_bulk_buy_modal" => {
+                    let quantity = values
+                        .get("market_bulk_buy_quantity_block")
+                        .and_then(|i| i.get("market_bulk_buy_quantity_input"))
+                        .and_then(|s| s.get("value"))
+                        .and_then(|s| s.as_str())
+                        .and_then(|s| s.trim().parse::<usize>().ok());
+                    let archetype_handle: config::ArchetypeHandle = view
+                        .private_metadata
+                        .parse()
+                        .map_err(|e| {
+                        let a =
+                            format!("couldn't parse archetype {}: {}", view.private_metadata, e);
+                        error!("{}", a);
+                        a
+                    })?;
+
+                    let invoiced = match quantity {
+                        Some(n) => {
+                            market::invoice_batch_purchase(store(), &user.id, archetype_handle, n)
+                                .await
+                        }
+                        None => Err("that's not how many you want".to_string()),
+                    };
+                    if let Err(e) = invoiced {
+                        return Ok(ActionResponse::Json(Json(json!({
+                            "response_action": "errors",
+                            "errors": {
+                                "market_bulk_buy_quantity_block": e,
+                            }
+                        }))));
+                    }
+
+                    return Ok(ActionResponse::Json(Json(json!({
+                        "response_action": "clear",
+                    }))));
+                }
+                "auction_bid_modal" => {
+                    let amount = values
+                        .get("auction_bid_amount_block")
+                        .and_then(|i| i.get("auction_bid_amount_input"))
+                        .and_then(|s| s.get("value"))
+                        .and_then(|s| s.as_str())
+                        .and_then(|s| s.trim().parse::<u64>().ok());
+                    let auction = auction::get(store(), &view.private_metadata)
+                        .await?
+                        .ok_or_else(|| "that auction is over".to_string())?;
+
+                    let invoiced = match amount {
+                        Some(amount) => auction::invoice_bid(&auction, &user.id, amount).await,
+                        None => Err("that's not a bid".to_string()),
+                    };
+                    if let Err(e) = invoiced {
+                        return Ok(ActionResponse::Json(Json(json!({
+                            "response_action": "errors",
+                            "errors": {
+                                "auction_bid_amount_block": e,
+                            }
+                        }))));
+                    }
 
                     return Ok(ActionResponse::Json(Json(json!({
                         "response_action": "clear",
@@ -2045,31 +2452,13 @@ async fn action_endpoint(
                 .and_then(|v| serde_json::from_str(v).ok())
             {
                 debug!("planting seed!");
-                let db = dyn_db();
-                let seed = Hacksteader::take(&db, Key::misc(seed_id))
-                    .await
-                    .map_err(|e| {
-                        let a = format!("couldn't delete seed: {}", e);
-                        error!("{}", a);
-                        a
-                    })?
-                    .try_into()
-                    .map_err(|e| {
-                        let a = format!("seed_id wrong type: {}", e);
-                        error!("{}", a);
-                        a
-                    })?;
+                plant_seed(&to_farming, tile_id, seed_id).await?;
 
-                to_farming
-                    .send(FarmingInputEvent::PlantSeed(
-                        tile_id,
-                        hacksteader::Plant::from_seed(seed),
-                    ))
-                    .unwrap();
-
-                to_farming
-                    .send(FarmingInputEvent::ActivateUser(user.id.clone()))
-                    .unwrap();
+                farm::queue(
+                    &to_farming,
+                    FarmingInputEvent::ActivateUser(user.id.clone()),
+                )
+                .await?;
 
                 update_user_home_tab(user.id).await.map_err(|e| {
                     let a = format!("{}", e);
@@ -2089,15 +2478,17 @@ async fn action_endpoint(
             {
                 debug!("applying item!");
 
-                to_farming
-                    .send(FarmingInputEvent::ApplyItem(
+                farm::queue(
+                    &to_farming,
+                    FarmingInputEvent::ApplyItem(
                         ItemApplication {
                             tile: tile_id,
                             item: item_id,
                         },
                         user.id.clone(),
-                    ))
-                    .unwrap();
+                    ),
+                )
+                .await?;
 
                 return Ok(ActionResponse::Ok(()));
             }
@@ -2121,22 +2512,124 @@ async fn action_endpoint(
     let output_json = match route.as_str() {
         "hackstead_confirm" => {
             info!("confirming new user!");
-            if !hacksteader::exists(&dyn_db(), i.user.id.clone()).await {
-                let transaction_id =
-                    banker::invoice(&i.user.id, *HACKSTEAD_PRICE, "let's hackstead, fred!")
-                        .await
-                        .map_err(|e| format!("couldn't send Banker invoice DM: {}", e))?;
+            if !hacksteader::exists(store(), i.user.id.clone()).await {
+                let transaction_id = banker::invoice(
+                    &i.user.id,
+                    *HACKSTEAD_PRICE,
+                    "let's hackstead, fred!",
+                    banker::InvoiceIntent::HacksteadSignup,
+                )
+                .await
+                .map_err(|e| format!("couldn't send Banker invoice DM: {}", e))?;
 
                 json!({})
             } else {
-                mrkdwn("you're already signed up!")
+                json!(mrkdwn("you're already signed up!"))
+            }
+        }
+        "buy_order_new" => {
+            let cat: Category = serde_json::from_str(&action.value).map_err(|e| {
+                let a = format!("couldn't parse buy order category: {}", e);
+                error!("{}", a);
+                a
+            })?;
+
+            Modal {
+                method: "push".to_string(),
+                trigger_id: i.trigger_id,
+                callback_id: "buy_order_modal".to_string(),
+                title: "Post a Buy Order".to_string(),
+                private_metadata: String::new(),
+                blocks: vec![
+                    input(
+                        "buy_order_item_block",
+                        "Item",
+                        blocks::static_select(
+                            "buy_order_item_input",
+                            "What do you want?",
+                            // Slack won't show more than 100 options
+                            CONFIG
+                                .possession_archetypes
+                                .iter()
+                                .enumerate()
+                                .filter(|(ah, _)| market::archetype_category(*ah) == cat)
+                                .take(100)
+                                .map(|(ah, a)| {
+                                    blocks::select_option(
+                                        format!("{} {}", emojify(&a.name), a.name),
+                                        ah.to_string(),
+                                    )
+                                })
+                                .collect::<Result<_, _>>()?,
+                        ),
+                    ),
+                    input(
+                        "buy_order_price_block",
+                        "Most you'll pay for each (hn)",
+                        blocks::text_input("buy_order_price_input").initial_value(50),
+                    ),
+                    input(
+                        "buy_order_quantity_block",
+                        "How many",
+                        blocks::text_input("buy_order_quantity_input").initial_value(1),
+                    ),
+                    divider(),
+                    comment(
+                        "You'll get an invoice for the most you'd pay for all of them, \
+                        which the banker holds onto while your order is up. \
+                        Whenever someone sells one for that much or less, it's yours, \
+                        and you get back whatever you didn't need to spend. \
+                        Cancel the order to get back the rest.",
+                    ),
+                ],
+                submit: Some("Post!".to_string()),
             }
+            .launch()
+            .await?
+        }
+        "buy_order_cancel" => {
+            market::cancel_buy_order(store(), &action.value, &i.user.id).await?;
+
+            json!({})
         }
         "possession_sell" => {
             let page_json = i.view.ok_or("no view!".to_string())?.private_metadata;
             //let page: PossessionPage = serde_json::from_str(&page_json)
             // .map_err(|e| dbg!(format!("couldn't parse {}: {}", page_json, e)))?;
 
+            // stackable things can be sold a bunch at a time
+            let key: Key = serde_json::from_str(&page_json).map_err(|e| {
+                let a = format!("couldn't parse {}: {}", page_json, e);
+                error!("{}", a);
+                a
+            })?;
+            let possession = hacksteader::get_possession(store(), key).await?;
+            let unlisted = match key.category {
+                Category::Misc => {
+                    market::unlisted(store(), &i.user.id, possession.archetype_handle)
+                        .await?
+                        .len()
+                }
+                _ => 1,
+            };
+            let quantity_blocks = match unlisted {
+                0 | 1 => vec![],
+                n => vec![
+                    input(
+                        "possession_sell_quantity_block",
+                        "How many",
+                        blocks::text_input("possession_sell_quantity_input").initial_value(1),
+                    ),
+                    comment(format!(
+                        "You have {} {}s that aren't up for sale, and can sell up to {} at once, \
+                        each at the price above.",
+                        n,
+                        possession.name,
+                        n.min(market::MAX_BATCH)
+                    )),
+                ],
+            };
+
             Modal {
                 method: "push".to_string(),
                 trigger_id: i.trigger_id,
@@ -2144,162 +2637,371 @@ async fn action_endpoint(
                 title: "Sell Item".to_string(),
                 private_metadata: page_json,
                 blocks: vec![
-                    json!({
-                        "type": "input",
-                        "block_id": "possession_sell_price_block",
-                        "label": plain_text("Price (hn)"),
-                        "element": {
-                            "type": "plain_text_input",
-                            "action_id": "possession_sell_price_input",
-                            "placeholder": plain_text("Price Item"),
-                            "initial_value": "50",
-                        }
-                    }),
-                    json!({ "type": "divider" }),
+                    input(
+                        "possession_sell_price_block",
+                        "Price (hn)",
+                        blocks::text_input("possession_sell_price_input")
+                            .placeholder("Price Item")
+                            .initial_value(50),
+                    ),
+                ]
+                .into_iter()
+                .chain(quantity_blocks)
+                .chain(vec![
+                    input(
+                        "possession_sell_duration_block",
+                        "List for",
+                        blocks::static_select(
+                            "possession_sell_duration_input",
+                            "How long should it stay up?",
+                            market::LISTING_DURATIONS
+                                .iter()
+                                .map(|(label, hours)| {
+                                    blocks::select_option(
+                                        label,
+                                        serde_json::to_string(hours).unwrap(),
+                                    )
+                                })
+                                .collect::<Result<_, _>>()?,
+                        ),
+                    ),
+                    divider(),
                     comment("As a form of confirmation, you'll get an invoice to pay before your Item goes up on the market. \
                         To fund Harvests and to encourage Hacksteaders to keep prices sensible, \
                         this invoice is 5% of the price of your sale \
                         rounded down to the nearest HN (meaning that sales below 20hn aren't taxed at all)."),
-                ],
+                    comment(match *market::WITHDRAWAL_REFUND_PERCENT {
+                        0 => "If it doesn't sell in time, it comes back off the market. \
+                            Taking it down yourself doesn't get your fee back."
+                            .to_string(),
+                        percent => format!(
+                            "If it doesn't sell in time, it comes back off the market. \
+                            Taking it down yourself before then gets you back {}% of your fee.",
+                            percent
+                        ),
+                    }),
+                ])
+                .collect(),
                 submit: Some("Sell!".to_string()),
                 ..Default::default()
             }
             .launch()
             .await?
         }
-        "possession_give" => {
+        "possession_trade" => {
             let key_json = i.view.ok_or("no view!".to_string())?.private_metadata;
             let key: Key = serde_json::from_str(&key_json).map_err(|e| {
                 let a = format!("couldn't parse {}: {}", key_json, e);
                 error!("{}", a);
                 a
             })?;
-
-            let possession = hacksteader::get_possession(&dyn_db(), key).await?;
+            let tradeable = trade::tradeable(store(), &i.user.id).await?;
+            let (options, initial) = trade_item_options(&tradeable, &[key.id])?;
 
             Modal {
                 method: "push".to_string(),
                 trigger_id: i.trigger_id,
-                callback_id: "possession_give_modal".to_string(),
-                title: "Give Item".to_string(),
-                blocks: vec![json!({
-                    "type": "input",
-                    "block_id": "possession_give_receiver_block",
-                    "label": plain_text("Give Item"),
-                    "element": {
-                        "type": "users_select",
-                        "action_id": "possession_give_receiver_input",
-                        "placeholder": plain_text("Who Really Gets your Gotchi?"),
-                        "initial_user": ({
-                            let s = &CONFIG.special_users;
-                            &s.get(key_json.len() % s.len()).unwrap_or(&*ID)
-                        }),
-                        "confirm": {
-                            "title": plain_text("You sure?"),
-                            "text": mrkdwn(format!(
-                                "Are you sure you want to give away {} _{}_? You might not get them back. :frowning:",
-                                emojify(&possession.name),
-                                possession.nickname()
-                            )),
-                            "confirm": plain_text("Give!"),
-                            "deny": plain_text("No!"),
-                            "style": "danger",
-                        }
-                    }
-                })],
-                private_metadata: key_json,
-                submit: Some("Trade Away!".to_string()),
+                callback_id: "trade_propose_modal".to_string(),
+                title: "Propose a Trade".to_string(),
+                blocks: vec![
+                    input(
+                        "trade_recipient_block",
+                        "Trade with",
+                        blocks::users_select("trade_recipient_input", "Who are you trading with?"),
+                    ),
+                    blocks::optional_input(
+                        "trade_items_block",
+                        "You'd give",
+                        blocks::multi_static_select(
+                            "trade_items_input",
+                            "What are you putting up?",
+                            options,
+                        )
+                        .initial_options(initial),
+                    ),
+                    blocks::optional_input(
+                        "trade_hn_block",
+                        "And HN",
+                        blocks::text_input("trade_hn_input").initial_value(0),
+                    ),
+                    blocks::optional_input(
+                        "trade_note_block",
+                        "What do you want for it?",
+                        blocks::text_input("trade_note_input").length(0, 200),
+                    ),
+                    divider(),
+                    comment(format!(
+                        "They'll get to say what they'll give you, and you'll both have to accept \
+                        before anything changes hands. Any HN you put up is invoiced once you both \
+                        have. The offer expires in {}.",
+                        humantime::format_duration(trade::OFFER_LIFETIME)
+                    )),
+                ],
+                submit: Some("Propose!".to_string()),
                 ..Default::default()
             }
             .launch()
             .await?
         }
-        "unlock_land" => {
-            // id of the item which allowed them to unlock this land
-            let cert_id: uuid::Uuid = uuid::Uuid::parse_str(&action.value).map_err(|e| {
-                let a = format!("couldn't parse land cert id: {}", e);
-                error!("{}", a);
-                a
-            })?;
+        "trade_respond" => {
+            let offer = trade::get(store(), &action.value)
+                .await?
+                .filter(|o| o.involves(&i.user.id))
+                .ok_or_else(|| "that trade offer's gone".to_string())?;
+            let (yours, theirs) = offer.sides_of(&i.user.id);
+            let tradeable = trade::tradeable(store(), &i.user.id).await?;
+            let selected: Vec<uuid::Uuid> = yours.items.iter().map(|t| t.key.id).collect();
+            let (options, initial) = trade_item_options(&tradeable, &selected)?;
 
-            to_farming
-                .send(FarmingInputEvent::RedeemLandCert(
-                    cert_id,
-                    i.user.id.clone(),
-                ))
-                .unwrap();
+            Modal {
+                method: "open".to_string(),
+                trigger_id: i.trigger_id,
+                callback_id: "trade_offer_modal".to_string(),
+                title: "Trade".to_string(),
+                private_metadata: offer.id(),
+                blocks: vec![
+                    section(mrkdwn(format!(
+                        "{} would give you {}.",
+                        mention(&theirs.steader),
+                        theirs.describe()
+                    )))
+                    .into(),
+                    blocks::optional_input(
+                        "trade_items_block",
+                        "You'd give",
+                        blocks::multi_static_select(
+                            "trade_items_input",
+                            "What are you putting up?",
+                            options,
+                        )
+                        .initial_options(initial),
+                    ),
+                    blocks::optional_input(
+                        "trade_hn_block",
+                        "And HN",
+                        blocks::text_input("trade_hn_input").initial_value(yours.hn),
+                    ),
+                    divider(),
+                    comment(
+                        "If you change what you're putting up, they'll have to accept again. \
+                        Once you've both accepted, any HN in the trade is invoiced, \
+                        and everything changes hands once it's paid.",
+                    ),
+                ],
+                submit: Some("Accept".to_string()),
+                ..Default::default()
+            }
+            .launch()
+            .await?
+        }
+        "trade_cancel" => {
+            trade::cancel(store(), &action.value, &i.user.id).await?;
 
             json!({})
         }
-        "seed_plant" => {
-            let tile_id: uuid::Uuid = uuid::Uuid::parse_str(&action.value).unwrap();
-            let hs = match Hacksteader::from_db(&dyn_db(), i.user.id.clone()).await {
-                Ok(hs) => hs,
-                Err(e) => {
-                    let a = format!("error fetching user for seed plant: {}", e);
-                    error!("{}", a);
-                    return Err(a);
-                }
-            };
-            let seeds: Vec<Possessed<possess::Seed>> = hs
-                .inventory
-                .iter()
-                .cloned()
-                .filter_map(|p| p.try_into().ok())
-                .collect();
+        "possession_auction" => {
+            let page_json = i.view.ok_or("no view!".to_string())?.private_metadata;
 
             Modal {
-                method: "open".to_string(),
+                method: "push".to_string(),
                 trigger_id: i.trigger_id,
-                callback_id: "seed_plant_modal".to_string(),
-                title: "Plant a Seed!".to_string(),
-                private_metadata: String::new(),
-                blocks: vec![json!({
-                    "type": "input",
-                    "label": plain_text("Seed Select"),
-                    "block_id": "seed_plant_input",
-                    "element": {
-                        "type": "static_select",
-                        "placeholder": plain_text("Which seed do ya wanna plant?"),
-                        "action_id": "seed_plant_select",
-                        // show them each seed they have that grows a given plant
-                        "option_groups": CONFIG
-                            .plant_archetypes
-                            .iter()
-                            .filter_map(|pa| { // plant archetype
-                                let mut seed_iter = seeds
-                                    .iter()
-                                    .filter(|s| s.inner.grows_into == pa.name);
-                                let first_seed = seed_iter.next()?;
-                                let seed_count = seed_iter.count() + 1;
+                callback_id: "possession_auction_modal".to_string(),
+                title: "Auction Item".to_string(),
+                private_metadata: page_json,
+                blocks: vec![
+                    input(
+                        "possession_auction_reserve_block",
+                        "Reserve price (hn)",
+                        blocks::text_input("possession_auction_reserve_input").initial_value(50),
+                    ),
+                    input(
+                        "possession_auction_increment_block",
+                        "Smallest raise (hn)",
+                        blocks::text_input("possession_auction_increment_input").initial_value(5),
+                    ),
+                    input(
+                        "possession_auction_hours_block",
+                        "Hours to run for",
+                        blocks::text_input("possession_auction_hours_input").initial_value(24),
+                    ),
+                    divider(),
+                    comment(format!(
+                        "Nobody can win your Item for less than the reserve, \
+                        and each bid has to beat the last by at least the smallest raise. \
+                        A bid in the last {} minutes keeps the auction going {} minutes longer. \
+                        Like selling, you'll get an invoice for 5% of the reserve \
+                        before your Item goes up for auction.",
+                        auction::SNIPING_WINDOW.as_secs() / 60,
+                        auction::SNIPING_WINDOW.as_secs() / 60,
+                    )),
+                ],
+                submit: Some("Auction!".to_string()),
+                ..Default::default()
+            }
+            .launch()
+            .await?
+        }
+        "auction_bid" => {
+            let auction = auction::get(store(), &action.value)
+                .await?
+                .ok_or_else(|| "that auction is over".to_string())?;
 
-                                let mut desc = format!(
-                                    "{} - {}",
-                                    seed_count,
-                                    first_seed.description
-                                );
-                                desc.truncate(75);
-                                if dbg!(desc.len()) == 75 {
-                                    desc.truncate(71);
-                                    desc.push_str("...")
-                                }
+            Modal {
+                method: "open".to_string(),
+                trigger_id: i.trigger_id,
+                callback_id: "auction_bid_modal".to_string(),
+                title: "Place a Bid".to_string(),
+                private_metadata: auction.id(),
+                blocks: auction
+                    .blocks()
+                    .into_iter()
+                    .take(1)
+                    .chain(vec![
+                        input(
+                            "auction_bid_amount_block",
+                            "Your bid (hn)",
+                            blocks::text_input("auction_bid_amount_input")
+                                .initial_value(auction.minimum_bid()),
+                        ),
+                        comment(
+                            "You'll get an invoice for your bid, which the banker holds onto. \
+                            If someone outbids you, you'll get it back.",
+                        ),
+                    ])
+                    .collect(),
+                submit: Some("Bid!".to_string()),
+                ..Default::default()
+            }
+            .launch()
+            .await?
+        }
+        "possession_give" => {
+            let key_json = i.view.ok_or("no view!".to_string())?.private_metadata;
+            let key: Key = serde_json::from_str(&key_json).map_err(|e| {
+                let a = format!("couldn't parse {}: {}", key_json, e);
+                error!("{}", a);
+                a
+            })?;
+
+            let possession = hacksteader::get_possession(store(), key).await?;
+
+            Modal {
+                method: "push".to_string(),
+                trigger_id: i.trigger_id,
+                callback_id: "possession_give_modal".to_string(),
+                title: "Give Item".to_string(),
+                blocks: vec![input(
+                    "possession_give_receiver_block",
+                    "Give Item",
+                    blocks::users_select(
+                        "possession_give_receiver_input",
+                        "Who Really Gets your Gotchi?",
+                    )
+                    .initial_user({
+                        let s = &CONFIG.special_users;
+                        s.get(key_json.len() % s.len()).unwrap_or(&*ID)
+                    })
+                    .confirm(
+                        blocks::confirm(
+                            "You sure?",
+                            mrkdwn(format!(
+                                "Are you sure you want to give away {} _{}_? You might not get them back. :frowning:",
+                                emojify(&possession.name),
+                                possession.nickname()
+                            )),
+                            "Give!",
+                            "No!",
+                        )
+                        .style(Style::Danger),
+                    ),
+                )],
+                private_metadata: key_json,
+                submit: Some("Trade Away!".to_string()),
+                ..Default::default()
+            }
+            .launch()
+            .await?
+        }
+        "unlock_land" => {
+            // id of the item which allowed them to unlock this land
+            let cert_id: uuid::Uuid = uuid::Uuid::parse_str(&action.value).map_err(|e| {
+                let a = format!("couldn't parse land cert id: {}", e);
+                error!("{}", a);
+                a
+            })?;
+
+            farm::queue(
+                &to_farming,
+                FarmingInputEvent::RedeemLandCert(cert_id, i.user.id.clone()),
+            )
+            .await?;
 
-                                Some(json!({
-                                    "label": plain_text(&pa.name),
-                                    "options": [{
-                                        "text": plain_text(format!("{} {}", emojify(&first_seed.name), first_seed.name)),
-                                        "description": plain_text(desc),
+            json!({})
+        }
+        "seed_plant" => {
+            let tile_id: uuid::Uuid = uuid::Uuid::parse_str(&action.value).unwrap();
+            let hs = match Hacksteader::from_db(store(), i.user.id.clone()).await {
+                Ok(hs) => hs,
+                Err(e) => {
+                    let a = format!("error fetching user for seed plant: {}", e);
+                    error!("{}", a);
+                    return Err(a);
+                }
+            };
+            let seeds: Vec<Possessed<possess::Seed>> = hs
+                .inventory
+                .iter()
+                .cloned()
+                .filter_map(|p| p.try_into().ok())
+                .collect();
+
+            Modal {
+                method: "open".to_string(),
+                trigger_id: i.trigger_id,
+                callback_id: "seed_plant_modal".to_string(),
+                title: "Plant a Seed!".to_string(),
+                private_metadata: String::new(),
+                blocks: vec![input(
+                    "seed_plant_input",
+                    "Seed Select",
+                    blocks::grouped_select(
+                        "seed_plant_select",
+                        "Which seed do ya wanna plant?",
+                        // show them each seed they have that grows a given plant
+                        CONFIG
+                            .plant_archetypes
+                            .iter()
+                            .filter_map(|pa| {
+                                // plant archetype
+                                let mut seed_iter =
+                                    seeds.iter().filter(|s| s.inner.grows_into == pa.name);
+                                let first_seed = seed_iter.next()?;
+                                let seed_count = seed_iter.count() + 1;
+
+                                Some(
+                                    blocks::select_option(
+                                        format!(
+                                            "{} {}",
+                                            emojify(&first_seed.name),
+                                            first_seed.name
+                                        ),
                                         // this is fucky-wucky because value can only be 75 chars
-                                        "value": serde_json::to_string(&(
+                                        serde_json::to_string(&(
                                             &tile_id.to_simple().to_string(),
                                             first_seed.id.to_simple().to_string(),
-                                        )).unwrap(),
-                                    }]
-                                }))
+                                        ))
+                                        .unwrap(),
+                                    )
+                                    .map(|o| {
+                                        let desc =
+                                            format!("{} - {}", seed_count, first_seed.description);
+                                        blocks::option_group(&pa.name, vec![o.description(desc)])
+                                    }),
+                                )
                             })
-                            .collect::<Vec<Value>>(),
-                    }
-                })],
+                            .collect::<Result<_, _>>()?,
+                    ),
+                )],
                 submit: Some("Plant it!".to_string()),
             }
             .launch()
@@ -2313,7 +3015,7 @@ async fn action_endpoint(
                 bool,
             ) = serde_json::from_str(&action.value).unwrap();
 
-            let hs = Hacksteader::from_db(&dyn_db(), steader).await?;
+            let hs = Hacksteader::from_db(store(), steader).await?;
 
             let gotchi_count = hs.gotchis.len();
             let blocks = gotchi_section(hs.gotchis, interactivity, credentials, true);
@@ -2341,7 +3043,7 @@ async fn action_endpoint(
                 bool,
             ) = serde_json::from_str(&action.value).unwrap();
 
-            let hs = Hacksteader::from_db(&dyn_db(), steader.clone()).await?;
+            let hs = Hacksteader::from_db(store(), steader.clone()).await?;
 
             let inv_count = hs.inventory.len();
             let blocks = inventory_section(
@@ -2372,7 +3074,7 @@ async fn action_endpoint(
             let (plant_id, recipe_index): (uuid::Uuid, config::ArchetypeHandle) =
                 serde_json::from_str(&craft_json).unwrap();
 
-            let hs = Hacksteader::from_db(&dyn_db(), i.user.id.clone()).await?;
+            let hs = Hacksteader::from_db(store(), i.user.id.clone()).await?;
             let all_nb = hs.neighbor_bonuses();
             let plant = hs
                 .land
@@ -2410,48 +3112,41 @@ async fn action_endpoint(
                 callback_id: "crafting_confirm_modal".to_string(),
                 title: "Crafting Confirmation".to_string(),
                 private_metadata: craft_json.to_string(),
-                blocks: vec![json!({
-                    "type": "section",
-                    "text": mrkdwn(format!(
-                        concat!(
-                            "Are you sure you want your plant to ",
-                            "spend the next {:.2} minutes crafting {} ",
-                            "using\n\n{}\n{}",
-                        ),
-                        (recipe.time / sum.crafting_speed_multiplier) / FARM_CYCLES_PER_MIN as f32,
-                        recipe.makes,
-                        recipe
-                            .needs
-                            .iter()
-                            .map(|(n, what)| {
-                                format!(
-                                    "*{}* {} _{}_",
-                                    n,
-                                    emojify(&what.name),
-                                    what.name
-                                )
-                            })
-                            .collect::<Vec<_>>()
-                            .join("\n"),
-                        if recipe.destroys_plant {
-                            "WARNING: THIS WILL DESTROY YOUR PLANT"
-                        } else {
-                            ""
-                        }
-                    )),
-                    "accessory": {
-                        "type": "image",
-                        "image_url": match possible_output {
-                            Some(po) => format!("http://{}/gotchi/img/{}/{}.png",
-                                *URL,
-                                po.kind.category(),
-                                filify(&po.name)
-                            ),
-                            None => format!("http://{}/gotchi/img/icon/dirt.png", *URL),
-                        },
-                        "alt_text": "The thing you'd like to craft",
+                blocks: vec![section(mrkdwn(format!(
+                    concat!(
+                        "Are you sure you want your plant to ",
+                        "spend the next {:.2} minutes crafting {} ",
+                        "using\n\n{}\n{}",
+                    ),
+                    (recipe.time / sum.crafting_speed_multiplier) / FARM_CYCLES_PER_MIN as f32,
+                    recipe.makes,
+                    recipe
+                        .needs
+                        .iter()
+                        .map(|(n, what)| {
+                            format!("*{}* {} _{}_", n, emojify(&what.name), what.name)
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    if recipe.destroys_plant {
+                        "WARNING: THIS WILL DESTROY YOUR PLANT"
+                    } else {
+                        ""
                     }
-                })],
+                )))
+                .accessory(image(
+                    match possible_output {
+                        Some(po) => format!(
+                            "http://{}/gotchi/img/{}/{}.png",
+                            *URL,
+                            po.kind.category(),
+                            filify(&po.name)
+                        ),
+                        None => format!("http://{}/gotchi/img/icon/dirt.png", *URL),
+                    },
+                    "The thing you'd like to craft",
+                ))
+                .into()],
                 submit: Some("Craft!".to_string()),
             }
             .launch()
@@ -2463,7 +3158,7 @@ async fn action_endpoint(
 
             let (plant_id, steader, page): (uuid::Uuid, String, usize) =
                 serde_json::from_str(&action.value).unwrap();
-            let hs = Hacksteader::from_db(&dyn_db(), steader.to_string()).await?;
+            let hs = Hacksteader::from_db(store(), steader.to_string()).await?;
             let plant = hs
                 .land
                 .iter()
@@ -2512,53 +3207,40 @@ async fn action_endpoint(
                 let craft_output_count: usize = output.iter().map(|(_, n)| n).sum();
                 let (hi, lo) = recipe.xp;
 
-                let mut head = json!({
-                    "type": "section",
-                    "text": mrkdwn(format!(
-                        "{} + around {}xp\n_{}_",
-                        if craft_output_count <= 1 {
-                            format!("_{}_", recipe.title())
-                        } else {
-                            output
-                                .iter()
-                                .map(|(a, n)| format!(
-                                    "*{}* {} _{}_",
-                                    n,
-                                    emojify(&a.name),
-                                    a.name,
-                                ))
-                                .collect::<Vec<String>>()
-                                .join(match recipe.makes {
-                                    config::RecipeMakes::OneOf(_) => " *or*\n",
-                                    _ => " *and*\n",
-                                })
-                        },
-                        (hi + lo) / 2,
-                        recipe.explanation()
-                    )),
-                });
+                let mut head = section(mrkdwn(format!(
+                    "{} + around {}xp\n_{}_",
+                    if craft_output_count <= 1 {
+                        format!("_{}_", recipe.title())
+                    } else {
+                        output
+                            .iter()
+                            .map(|(a, n)| format!("*{}* {} _{}_", n, emojify(&a.name), a.name,))
+                            .collect::<Vec<String>>()
+                            .join(match recipe.makes {
+                                config::RecipeMakes::OneOf(_) => " *or*\n",
+                                _ => " *and*\n",
+                            })
+                    },
+                    (hi + lo) / 2,
+                    recipe.explanation()
+                )));
                 if possible && steader == i.user.id {
-                    head.as_object_mut().unwrap().insert(
-                        "accessory".to_string(),
-                        json!({
-                            "type": "button",
-                            "style": "primary",
-                            "text": plain_text(format!(
+                    head = head.accessory(
+                        button(
+                            format!(
                                 "Craft {}",
                                 emojify(match recipe.makes.any() {
                                     Some(i) => &i.name,
-                                    None => "seedlet"
+                                    None => "seedlet",
                                 })
-                            )),
-                            "value": serde_json::to_string(&(
-                                &plant_id,
-                                recipe_handle,
-                            )).unwrap(),
-                            "action_id": "crafting_confirm",
-                        }),
+                            ),
+                            "crafting_confirm",
+                        )
+                        .primary()
+                        .value(serde_json::to_string(&(&plant_id, recipe_handle)).unwrap()),
                     );
                 }
-                b.push(head);
+                b.push(head.into());
 
                 b.push(comment("*needs:* ".to_string()));
                 for (count, resource) in recipe.needs {
@@ -2569,11 +3251,11 @@ async fn action_endpoint(
                         resource.name
                     )));
                 }
-                b.push(json!({ "type": "divider" }));
+                b.push(divider());
 
                 b
             };
-            let mut blocks: Vec<Value> = match this_page_unlocked_recipes {
+            let mut blocks: Vec<Block> = match this_page_unlocked_recipes {
                 Some(r) => r
                     .iter()
                     .cloned()
@@ -2590,45 +3272,30 @@ async fn action_endpoint(
             };
 
             if !last_page || !first_page {
-                let mut elements = vec![];
-
-                elements.push(if !first_page {
-                    json!({
-                        "style": "primary",
-                        "type": "button",
-                        "value": serde_json::to_string(&(plant_id, &steader, page - 1)).unwrap(),
-                        "text": plain_text("Previous Page"),
-                        "action_id": "crafting_back_page",
-                    })
-                } else {
-                    json!({
-                        "value": action.value,
-                        "type": "button",
-                        "text": plain_text("Previous Page"),
-                        "action_id": "crafting_back_page",
-                    })
-                });
-                elements.push(if !last_page {
-                    json!({
-                         "style": "primary",
-                         "type": "button",
-                         "value": serde_json::to_string(&(plant_id, &steader, page + 1)).unwrap(),
-                         "text": plain_text("Next Page"),
-                         "action_id": "crafting_next_page",
-                    })
-                } else {
-                    json!({
-                         "type": "button",
-                         "text": plain_text("Next Page"),
-                         "value": action.value,
-                         "action_id": "crafting_next_page",
-                    })
-                });
+                let mut elements: Vec<blocks::Element> = vec![];
 
-                blocks.push(json!({
-                    "type": "actions",
-                    "elements": elements
-                }));
+                let back = button("Previous Page", "crafting_back_page");
+                elements.push(
+                    if !first_page {
+                        back.primary()
+                            .value(serde_json::to_string(&(plant_id, &steader, page - 1)).unwrap())
+                    } else {
+                        back.value(&action.value)
+                    }
+                    .into(),
+                );
+                let next = button("Next Page", "crafting_next_page");
+                elements.push(
+                    if !last_page {
+                        next.primary()
+                            .value(serde_json::to_string(&(plant_id, &steader, page + 1)).unwrap())
+                    } else {
+                        next.value(&action.value)
+                    }
+                    .into(),
+                );
+
+                blocks.push(actions(elements));
             }
 
             const MAX_NAME_LEN: usize = 11;
@@ -2695,10 +3362,7 @@ async fn action_endpoint(
                     );
 
                     if i <= current_position {
-                        json!({
-                            "type": "section",
-                            "text": mrkdwn(text),
-                        })
+                        section(mrkdwn(text)).into()
                     } else {
                         comment(text)
                     }
@@ -2722,7 +3386,7 @@ async fn action_endpoint(
             let (user_id, plant_id): (String, uuid::Uuid) =
                 serde_json::from_str(&action.value).unwrap();
 
-            let hs = Hacksteader::from_db(&dyn_db(), user_id.to_string()).await?;
+            let hs = Hacksteader::from_db(store(), user_id.to_string()).await?;
             let plant = hs
                 .land
                 .iter()
@@ -2744,12 +3408,11 @@ async fn action_endpoint(
                 .base_yield_duration
                 .map(|x| x / sum.yield_speed_multiplier);
 
-            let mut blocks = vec![];
+            let mut blocks: Vec<Block> = vec![];
 
             if let Some(yield_farm_cycles) = yield_farm_cycles {
-                blocks.push(json!({
-                    "type": "section",
-                    "text": mrkdwn(format!(
+                blocks.push(
+                    section(mrkdwn(format!(
                         concat!(
                             "*Yield Speed*\n",
                             "Yields every: *{:.2} minutes*\n",
@@ -2757,8 +3420,9 @@ async fn action_endpoint(
                         ),
                         yield_farm_cycles / FARM_CYCLES_PER_MIN as f32,
                         sum.yield_speed_multiplier
-                    )),
-                }));
+                    )))
+                    .into(),
+                );
             }
 
             for adv in advancements.iter() {
@@ -2779,13 +3443,13 @@ async fn action_endpoint(
                 }
             }
 
-            blocks.push(json!({
-                "type": "section",
-                "text": mrkdwn(format!(
+            blocks.push(
+                section(mrkdwn(format!(
                     "*Yield Size*\n*x{:.3}* yield size multiplier",
                     sum.yield_size_multiplier,
-                ))
-            }));
+                )))
+                .into(),
+            );
             for adv in advancements.iter() {
                 match &adv.kind {
                     YieldSizeMultiplier(x) => {
@@ -2804,10 +3468,7 @@ async fn action_endpoint(
                 }
             }
 
-            blocks.push(json!({
-                "type": "section",
-                "text": mrkdwn("*Yield Items*".to_string())
-            }));
+            blocks.push(section(mrkdwn("*Yield Items*".to_string())).into());
             for y in sum.yields.iter() {
                 let arch = match CONFIG.possession_archetypes.get(y.yields) {
                     Some(arch) => arch,
@@ -2852,11 +3513,11 @@ async fn action_endpoint(
                     error!("{}", a);
                     a
                 })?;
-            let db = dyn_db();
+            let db = store();
 
             let Hacksteader {
                 inventory, land, ..
-            } = Hacksteader::from_db(&db, user_id).await.map_err(|e| {
+            } = Hacksteader::from_db(db, user_id).await.map_err(|e| {
                 error!("{}", e);
                 e
             })?;
@@ -2876,82 +3537,112 @@ async fn action_endpoint(
                 callback_id: "item_apply_modal".to_string(),
                 title: "Item + Plant = :D".to_string(),
                 private_metadata: String::new(),
-                blocks: vec![json!({
-                    "type": "input",
-                    "label": plain_text("Item Select"),
-                    "block_id": "item_apply_input",
-                    "element": {
-                        "type": "static_select",
-                        "placeholder": plain_text("Which item do you wanna use on this plant?"),
-                        "action_id": "item_apply_select",
+                blocks: vec![input(
+                    "item_apply_input",
+                    "Item Select",
+                    blocks::grouped_select(
+                        "item_apply_select",
+                        "Which item do you wanna use on this plant?",
                         // show them each item they have that can be applied
-                        "option_groups": CONFIG
+                        CONFIG
                             .possession_archetypes
                             .iter()
                             .enumerate()
                             .filter(|(_, pa)| {
-                                pa
-                                    .kind
+                                pa.kind
                                     .keepsake()
                                     .and_then(|i| {
-                                        i
-                                            .item_application
-                                            .as_ref()
-                                            .map(|item_appl| {
-                                                item_appl
-                                                    .effects
-                                                    .iter()
-                                                    .any(|e| e.keep_plants.allows(&plant.name))
-                                            })
+                                        i.item_application.as_ref().map(|item_appl| {
+                                            item_appl
+                                                .effects
+                                                .iter()
+                                                .any(|e| e.keep_plants.allows(&plant.name))
+                                        })
                                     })
                                     .unwrap_or(false)
                             })
-                            .filter_map(|(pah, pa)| { // possession archetype handle, possession archetype
+                            .filter_map(|(pah, pa)| {
+                                // possession archetype handle, possession archetype
                                 let item = inventory.iter().find(|i| i.archetype_handle == pah)?;
-                                let has_count = inventory.iter().filter(|i| i.archetype_handle == pah).count();
-                                let mut desc = pa
+                                let has_count = inventory
+                                    .iter()
+                                    .filter(|i| i.archetype_handle == pah)
+                                    .count();
+                                let desc = &pa
                                     .kind
                                     .keepsake()?
                                     .item_application
                                     .as_ref()?
-                                    .short_description
-                                    .clone();
-                                desc.truncate(75);
-                                if dbg!(desc.len()) == 75 {
-                                    desc.truncate(71);
-                                    desc.push_str("...")
-                                }
+                                    .short_description;
 
-                                Some(json!({
-                                    "label": plain_text(format!("{} {}", emojify(&pa.name), pa.name)),
-                                    "options": [{
-                                        "text": plain_text(has_count),
-                                        "description": plain_text(desc),
+                                Some(
+                                    blocks::select_option(
+                                        has_count,
                                         // this is fucky-wucky because value can only be 75 chars
-                                        "value": serde_json::to_string(&(
+                                        serde_json::to_string(&(
                                             &tile_id.to_simple().to_string(),
                                             item.id.to_simple().to_string(),
-                                        )).unwrap(),
-                                    }]
-                                }))
+                                        ))
+                                        .unwrap(),
+                                    )
+                                    .map(|o| {
+                                        blocks::option_group(
+                                            format!("{} {}", emojify(&pa.name), pa.name),
+                                            vec![o.description(desc)],
+                                        )
+                                    }),
+                                )
                             })
-                            .collect::<Vec<Value>>()
-                    }
-                })],
+                            .collect::<Result<_, _>>()?,
+                    ),
+                )],
                 submit: Some("Apply!".to_string()),
             }
             .launch()
             .await?
         }
+        "api_tokens" => {
+            Modal {
+                method: "open".to_string(),
+                trigger_id: i.trigger_id,
+                callback_id: "api_tokens_modal".to_string(),
+                title: "API Tokens".to_string(),
+                blocks: api_token_blocks(&i.user.id, None).await?,
+                ..Default::default()
+            }
+            .launch()
+            .await?
+        }
+        "api_token_mint" | "api_token_revoke" => {
+            let minted = if route == "api_token_mint" {
+                Some(api::tokens::mint(store(), &i.user.id).await?)
+            } else {
+                api::tokens::revoke(store(), &i.user.id, &action.value).await?;
+                None
+            };
+
+            ModalUpdate {
+                trigger_id: i.trigger_id,
+                callback_id: "api_tokens_modal".to_string(),
+                title: "API Tokens".to_string(),
+                view_id: i.view.ok_or("no view!".to_string())?.root_view_id,
+                blocks: api_token_blocks(&i.user.id, minted).await?,
+                ..Default::default()
+            }
+            .launch()
+            .await?
+        }
         "gotchi_hatch" => {
             info!("hatching egg!");
 
-            to_farming
-                .send(FarmingInputEvent::HatchEgg(
+            farm::queue(
+                &to_farming,
+                FarmingInputEvent::HatchEgg(
                     serde_json::from_str(&dbg!(action.value)).unwrap(),
                     i.user.id.clone(),
-                ))
-                .unwrap();
+                ),
+            )
+            .await?;
 
             json!({})
         }
@@ -2962,31 +3653,94 @@ async fn action_endpoint(
                 callback_id: "gotchi_nickname_modal".to_string(),
                 title: "Nickname Gotchi".to_string(),
                 private_metadata: i.view.ok_or("no view!".to_string())?.private_metadata,
-                blocks: vec![json!({
-                    "type": "input",
-                    "block_id": "gotchi_nickname_block",
-                    "label": plain_text("Nickname Gotchi"),
-                    "element": {
-                        "type": "plain_text_input",
-                        "action_id": "gotchi_nickname_input",
-                        "placeholder": plain_text("Nickname Gotchi"),
-                        "initial_value": action.value,
-                        "min_length": 1,
-                        "max_length": 25,
-                    }
-                })],
+                blocks: vec![input(
+                    "gotchi_nickname_block",
+                    "Nickname Gotchi",
+                    blocks::text_input("gotchi_nickname_input")
+                        .placeholder("Nickname Gotchi")
+                        .initial_value(action.value)
+                        .length(1, 25),
+                )],
                 submit: Some("Change it!".to_string()),
                 ..Default::default()
             }
             .launch()
             .await?
         }
+        "market_price_history" => {
+            let archetype_handle: config::ArchetypeHandle = action.value.parse().map_err(|e| {
+                let a = format!("couldn't parse archetype {}: {}", action.value, e);
+                error!("{}", a);
+                a
+            })?;
+            let name = &CONFIG
+                .possession_archetypes
+                .get(archetype_handle)
+                .ok_or_else(|| format!("no archetype {}", archetype_handle))?
+                .name;
+
+            Modal {
+                method: "push".to_string(),
+                trigger_id: i.trigger_id,
+                callback_id: "market_price_history_modal".to_string(),
+                title: "Price History".to_string(),
+                blocks: price_history_blocks(archetype_handle, name).await?,
+                ..Default::default()
+            }
+            .launch()
+            .await?
+        }
+        "market_bulk_buy" => {
+            let archetype_handle: config::ArchetypeHandle = action.value.parse().map_err(|e| {
+                let a = format!("couldn't parse archetype {}: {}", action.value, e);
+                error!("{}", a);
+                a
+            })?;
+            let name = market::archetype_name(archetype_handle);
+            let listings =
+                market::cheapest_listings(store(), archetype_handle, &i.user.id, market::MAX_BATCH)
+                    .await?;
+
+            Modal {
+                method: "push".to_string(),
+                trigger_id: i.trigger_id,
+                callback_id: "market_bulk_buy_modal".to_string(),
+                title: "Buy in Bulk".to_string(),
+                private_metadata: archetype_handle.to_string(),
+                blocks: vec![
+                    section(mrkdwn(format!(
+                        "{} _{}_: *{}* for sale, starting at *{}hn*.",
+                        emojify(name),
+                        name,
+                        listings.len(),
+                        listings.first().map_or(0, |l| l.price),
+                    )))
+                    .into(),
+                    input(
+                        "market_bulk_buy_quantity_block",
+                        "How many",
+                        blocks::text_input("market_bulk_buy_quantity_input")
+                            .initial_value(listings.len().min(10)),
+                    ),
+                    divider(),
+                    comment(
+                        "You'll get one invoice for the cheapest ones on the market. \
+                        If any of them sell to someone else before you pay, \
+                        you'll get back what you paid for those.",
+                    ),
+                ],
+                submit: Some("Buy!".to_string()),
+                ..Default::default()
+            }
+            .launch()
+            .await?
+        }
         "possession_market_overview_page" => {
             let page_json = &action.value;
             let (item_name, cat): (String, Category) = serde_json::from_str(page_json).unwrap();
 
             let page = PossessionOverviewPage {
-                credentials: if hacksteader::exists(&dyn_db(), i.user.id.clone()).await {
+                credentials: if hacksteader::exists(store(), i.user.id.clone()).await {
                     Credentials::Hacksteader
                 } else {
                     Credentials::None
@@ -3031,7 +3785,7 @@ async fn action_endpoint(
 
             page.modal(i.trigger_id, "push").await?.launch().await?
         }
-        _ => mrkdwn("huh?"),
+        _ => json!(mrkdwn("huh?")),
     };
 
     Ok(ActionResponse::Json(Json(output_json)))
@@ -3039,54 +3793,96 @@ async fn action_endpoint(
 
 #[rocket::get("/steadercount")]
 async fn steadercount() -> Result<String, String> {
-    hcor::Profile::fetch_all(&dyn_db())
+    store()
+        .profiles()
         .await
         .map(|profiles| profiles.len().to_string())
 }
 
+/// Takes a seed out of its owner's inventory and has the farm plant it on a tile,
+/// giving the seed back if the farm can't be told to.
+pub async fn plant_seed(
+    to_farming: &Sender<FarmingInputEvent>,
+    tile_id: uuid::Uuid,
+    seed_id: uuid::Uuid,
+) -> Result<(), String> {
+    let db = store();
+    let seed_possession = Hacksteader::take(db, Key::misc(seed_id))
+        .await
+        .map_err(|e| {
+            let a = format!("couldn't delete seed: {}", e);
+            error!("{}", a);
+            a
+        })?;
+    let seed = seed_possession.clone().try_into().map_err(|e| {
+        let a = format!("seed_id wrong type: {}", e);
+        error!("{}", a);
+        a
+    })?;
+
+    let queued = farm::queue(
+        to_farming,
+        FarmingInputEvent::PlantSeed(tile_id, hacksteader::Plant::from_seed(seed)),
+    )
+    .await;
+    if let Err(e) = queued {
+        // the seed won't get planted, so they should have it back
+        db.write(store::Batch {
+            possessions: vec![seed_possession],
+            ..Default::default()
+        })
+        .await
+        .map_err(|e| error!("couldn't give back unplanted seed: {}", e))
+        .ok();
+        error!("{}", e);
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
 pub enum FarmingInputEvent {
     ActivateUser(String),
     RedeemLandCert(uuid::Uuid, String),
     HatchEgg(uuid::Uuid, String),
     ApplyItem(ItemApplication, String),
-    PlantSeed(uuid::Uuid, hacksteader::Plant),
+    PlantSeed(
+        uuid::Uuid,
+        #[serde(with = "hacksteader::plant_av")] hacksteader::Plant,
+    ),
     BeginCraft {
         tile_id: uuid::Uuid,
         recipe_archetype_handle: usize,
     },
 }
 
-pub fn format_yield(items: Vec<Possession>, user: String) -> Vec<Value> {
+pub fn format_yield(items: Vec<Possession>, user: String) -> Vec<Block> {
     if items.len() < 8 {
         items
             .iter()
             .map(|p| {
-                json!({
-                    "type": "section",
-                    "text": mrkdwn(format!(
-                        "*<@{}>'s new* {} _{}_!",
-                        user,
-                        emojify(&p.name),
-                        p.name,
-                    )),
-                    "accessory": {
-                        "type": "image",
-                        "image_url": format!(
-                            "http://{}/gotchi/img/{}/{}.png",
-                            *URL,
-                            format!(
-                                "{:?}",
-                                p.kind.category()
-                            ).to_lowercase(),
-                            filify(&p.name)
-                        ),
-                        "alt_text": "happy shiny give u stuffs",
-                    }
-                })
+                section(mrkdwn(format!(
+                    "*{}'s new* {} _{}_!",
+                    mention(&user),
+                    emojify(&p.name),
+                    p.name,
+                )))
+                .accessory(image(
+                    format!(
+                        "http://{}/gotchi/img/{}/{}.png",
+                        *URL,
+                        format!("{:?}", p.kind.category()).to_lowercase(),
+                        filify(&p.name)
+                    ),
+                    "happy shiny give u stuffs",
+                ))
+                .into()
             })
             .collect::<Vec<_>>()
     } else {
-        let mut occurrences: HashMap<_, usize> = Default::default();
+        // sorted, so that the same yield always comes out the same way
+        let mut occurrences: std::collections::BTreeMap<_, usize> = Default::default();
 
         for p in &items {
             *occurrences
@@ -3100,31 +3896,29 @@ pub fn format_yield(items: Vec<Possession>, user: String) -> Vec<Value> {
         occurrences
             .iter()
             .map(|((name, category), count)| {
-                json!({
-                    "type": "section",
-                    "text": mrkdwn(format!(
-                        "_<@{}>'s_ *{}* new {} _{}_!",
-                        user,
-                        count,
-                        emojify(&name),
-                        name
-                    )),
-                    "accessory": {
-                        "type": "image",
-                        "image_url": format!(
-                            "http://{}/gotchi/img/{}/{}.png",
-                            *URL,
-                            category,
-                            filify(&name)
-                        ),
-                        "alt_text": "happy shiny egg give u stuffs",
-                    }
-                })
+                section(mrkdwn(format!(
+                    "_{}'s_ *{}* new {} _{}_!",
+                    mention(&user),
+                    count,
+                    emojify(&name),
+                    name
+                )))
+                .accessory(image(
+                    format!(
+                        "http://{}/gotchi/img/{}/{}.png",
+                        *URL,
+                        category,
+                        filify(&name)
+                    ),
+                    "happy shiny egg give u stuffs",
+                ))
+                .into()
             })
             .collect::<Vec<_>>()
     }
 }
 
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct ItemApplication {
     tile: uuid::Uuid,
     item: uuid::Uuid,
@@ -3176,12 +3970,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
     dotenv::dotenv().ok();
     setup_logger()?;
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(result) = archive::cli(store(), &args).await {
+        return result.map_err(|e| e.into());
+    }
+
     if let Ok(_) = fs::read("restart") {
         fs::remove_file("restart")?;
         info!("Restarted!");
     }
 
     info!("starting");
+    slack_verify::check_signing_secret()?;
+    hn_webhook::check_webhook_secret()?;
+    admin::seed_operators(store(), &CONFIG.special_users).await?;
 
     let (tx, rx) = crossbeam_channel::unbounded();
 
@@ -3191,72 +3993,41 @@ async fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
 
         let mut interval = interval(Duration::from_millis(FARM_CYCLE_MILLIS));
 
-        let mut active_users: HashMap<String, bool> = HashMap::new();
-        let mut plant_queue: HashMap<uuid::Uuid, hacksteader::Plant> = HashMap::new();
-        // fix like this
-        let mut craft_queue: HashMap<uuid::Uuid, config::ArchetypeHandle> = HashMap::new();
-        let mut item_application_queue: HashMap<String, ItemApplication> = HashMap::new();
-        let mut land_cert_queue: HashMap<String, uuid::Uuid> = HashMap::new();
-        let mut hatch_egg_queue: HashMap<String, uuid::Uuid> = HashMap::new();
+        let mut farm_state = farm::FarmState::default();
+        // inputs from cycles that couldn't be saved, to be tried again
+        let mut requeued: Vec<FarmingInputEvent> = Vec::new();
+        // how many cycles we've spent trying to use up the queues before restarting
+        let mut restart_cycles = 0;
 
         async move {
             use futures::stream::{self, StreamExt, TryStreamExt};
-            use hacksteader::{Plant, Tile};
-            use hcor::Profile;
 
-            loop {
-                for (_, fresh) in active_users.iter_mut() {
-                    *fresh = false;
+            // pick up whatever was queued before the last restart
+            match farm::queued_inputs(store()).await {
+                Ok(mut queued) => {
+                    info!("replaying {} queued farming inputs", queued.len());
+                    requeued.append(&mut queued);
                 }
+                Err(e) => error!("couldn't load queued farming inputs: {}", e),
+            }
 
-                while let Ok(farming_event) = rx.try_recv() {
-                    use FarmingInputEvent::*;
-                    match farming_event {
-                        ActivateUser(name) => {
-                            debug!("activated: {}", name);
-                            active_users.insert(name, true);
-                        }
-                        ApplyItem(application, user_id) => {
-                            item_application_queue.insert(user_id, application);
-                        }
-                        PlantSeed(tile_id, plant) => {
-                            plant_queue.insert(tile_id, plant);
-                        }
-                        RedeemLandCert(cert_id, user_id) => {
-                            land_cert_queue.insert(user_id, cert_id);
-                        }
-                        HatchEgg(egg_id, user_id) => {
-                            hatch_egg_queue.insert(user_id, egg_id);
-                        }
-                        BeginCraft {
-                            tile_id,
-                            recipe_archetype_handle,
-                        } => {
-                            craft_queue.insert(tile_id, recipe_archetype_handle);
-                        }
-                    }
-                }
+            loop {
+                let collected_at = SystemTime::now();
+                let events: Vec<FarmingInputEvent> =
+                    requeued.drain(..).chain(rx.try_iter()).collect();
 
                 interval.tick().await;
                 debug!("update!");
 
-                if active_users.is_empty() {
+                let users = farm_state.users_to_load(&events);
+                if users.is_empty() {
                     info!("nobody on.");
-                    continue;
                 }
 
-                let db = dyn_db();
+                let db = store();
 
-                use rand::Rng;
-                let mut deletions = vec![];
-                let mut clear_plants = vec![];
-                let mut possessions = vec![];
-                let mut new_tiles = vec![];
-                let mut dms: Vec<(String, Vec<Value>, String)> = Vec::new();
-                let mut market_logs: Vec<(Vec<Value>, String)> = Vec::new();
-
-                let mut hacksteaders: Vec<Hacksteader> = stream::iter(active_users.clone())
-                    .map(|(id, _)| Hacksteader::from_db(&db, id))
+                let hacksteaders: Vec<Hacksteader> = stream::iter(users)
+                    .map(|id| Hacksteader::from_db(db, id))
                     .buffer_unordered(50)
                     .collect::<Vec<_>>()
                     .await
@@ -3270,704 +4041,159 @@ async fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
                     })
                     .collect();
 
-                // Give away requested land/hatch eggs
-                for hs in hacksteaders.iter_mut() {
-                    if let Some((plant, appl, item)) =
-                        item_application_queue.remove(&hs.user_id).and_then(|appl| {
-                            let i = hs.inventory.iter().find(|i| i.id == appl.item)?;
-
-                            Some((
-                                hs.land
-                                    .iter_mut()
-                                    .find(|t| t.id == appl.tile)?
-                                    .plant
-                                    .as_mut()?,
-                                i.kind.keepsake()?.item_application.as_ref()?,
-                                i,
-                            ))
-                        })
-                    {
-                        deletions.push(Key::misc(item.id));
-                        for (i, e) in appl.effects.iter().enumerate() {
-                            match &e.kind {
-                                config::ItemApplicationEffectKind::TurnsPlantInto(name) => {
-                                    plant.archetype_handle =
-                                        CONFIG.find_plant_handle(name).expect("invalid handle");
-
-                                    for (i, e) in plant
-                                        .effects
-                                        .clone()
-                                        .iter()
-                                        .filter_map(|e| {
-                                            CONFIG.get_item_application_effect(
-                                                e.item_archetype_handle,
-                                                e.effect_archetype_handle,
-                                            )
-                                        })
-                                        .enumerate()
-                                    {
-                                        if !e
-                                            .keep_plants
-                                            .lookup_handles()
-                                            .unwrap()
-                                            .allows(&plant.archetype_handle)
-                                        {
-                                            plant.effects.swap_remove(i);
-                                        }
+                let was_active: Vec<String> = farm_state.active_users.keys().cloned().collect();
+                let progress = farm_state.progress();
+                let (new_state, output) = farm::tick(
+                    farm_state,
+                    hacksteaders,
+                    events,
+                    &roll::DICE,
+                    SystemTime::now(),
+                );
+                farm_state = new_state;
+                let farm::FarmOutput { steaders } = output;
+
+                let deactivated: Vec<FarmingInputEvent> = was_active
+                    .into_iter()
+                    .filter(|u| !farm_state.active_users.contains_key(u))
+                    .map(FarmingInputEvent::ActivateUser)
+                    .collect();
+                farm::forget_inputs(db, &deactivated, collected_at).await;
+
+                // each steader's cycle is saved on its own, so that one steader's
+                // failure doesn't hold anyone else back
+                let saved = stream::iter(steaders)
+                    .map(|(who, out)| async move {
+                        if out.too_big() {
+                            info!(
+                                "{}'s farm cycle won't fit in one transaction, farming fewer cycles",
+                                who
+                            );
+                            return Err((who, out.consumed, Some(out.cycles)));
+                        }
+                        let farm::SteaderOutput {
+                            writes,
+                            dms,
+                            market_logs,
+                            consumed,
+                            rolls,
+                            events,
+                            cycles: _,
+                        } = out;
+
+                        match farm::save(db, writes).await {
+                            Ok(()) => {
+                                farm::forget_inputs(db, &consumed, collected_at).await;
+                                for roll in rolls.iter() {
+                                    if let Err(e) = records::put(db, roll).await {
+                                        error!("couldn't keep roll {}: {}", roll.what.id(), e);
                                     }
                                 }
-                                _ => {}
+                                events.into_iter().for_each(bus::publish);
+                                Ok((who, dms, market_logs))
+                            }
+                            Err(e) => {
+                                error!("couldn't save {}'s farm cycle, rolling back: {}", who, e);
+                                Err((who, consumed, None))
                             }
-
-                            plant.effects.push(hacksteader::Effect {
-                                until_finish: e.duration,
-                                item_archetype_handle: item.archetype_handle,
-                                effect_archetype_handle: i,
-                            });
-                        }
-                    }
-                    if let Some(cert_id) = land_cert_queue.remove(&hs.user_id) {
-                        if hs.inventory.iter().any(|p| {
-                            let same_id = p.id == cert_id;
-                            let actually_land_cert = p
-                                .kind
-                                .keepsake()
-                                .filter(|k| k.unlocks_land.is_some())
-                                .is_some();
-
-                            same_id && actually_land_cert
-                        }) {
-                            deletions.push(Key::misc(cert_id));
-                            let new_tile = hacksteader::Tile::new(hs.user_id.clone());
-                            hs.land.push(new_tile.clone());
-                            new_tiles.push(new_tile.clone());
                         }
-                    }
-                    if let Some(egg_id) = hatch_egg_queue.remove(&hs.user_id) {
-                        debug!("egg hatch requested!");
-
-                        if let Some((p, hatch_table)) = hs.gotchis.iter().find_map(|g| {
-                            Some(g).filter(|g| g.id == egg_id).and_then(|g| {
-                                debug!("hatching {:?}", g);
-                                Some((g, g.inner.hatch_table.as_ref()?))
-                            })
-                        }) {
-                            deletions.push(Key::gotchi(egg_id));
-
-                            let (spawn_handles, percentile) =
-                                config::spawn_with_percentile(hatch_table, &mut rand::thread_rng());
-
-                            let spawned: Vec<Possession> = spawn_handles
-                                .into_iter()
-                                .map(|h| {
-                                    Possession::new(
-                                        CONFIG.find_possession_handle(&h).unwrap(),
-                                        possess::Owner::hatcher(hs.user_id.clone()),
-                                    )
-                                })
-                                .collect();
-
-                            let mut msg = vec![
-                                json!({
-                                    "type": "section",
-                                    "text": mrkdwn(format!(
-                                        concat!(
-                                            "*<@{}> hatched a {}!*\n",
-                                            "The rarity of this loot puts it in the ",
-                                            "*{:.2}th* percentile for loot from eggs of this type.",
-                                        ),
-                                        hs.user_id,
-                                        p.name,
-                                        percentile * 100.0
-                                    )),
-                                    "accessory": {
-                                        "type": "image",
-                                        "image_url": format!(
-                                            "http://{}/gotchi/img/{}/{}.png",
-                                            *URL,
-                                            format!(
-                                                "{:?}",
-                                                p.kind.category()
-                                            ).to_lowercase(),
-                                            filify(&p.name)
-                                        ),
-                                        "alt_text": "happy shiny egg give u stuffs",
-                                    }
-                                }),
-                                comment("WAT I TAUGHT ET WAZ ROCC!?!?!!"),
-                                json!({ "type": "divider" }),
-                            ];
-
-                            possessions.extend_from_slice(&spawned);
-
-                            msg.append(&mut format_yield(spawned, hs.user_id.clone()));
-                            dms.push((
-                                hs.user_id.clone(),
-                                msg.clone(),
-                                format!("Your {} hatched!", p.name),
-                            ));
-                            market_logs
-                                .push((msg, format!("<@{}> hatched a {}!", hs.user_id, p.name)));
-                        } else {
-                            warn!("egg hatch ignored; hack attempt?")
+                    })
+                    .buffer_unordered(50)
+                    .collect::<Vec<_>>()
+                    .await;
+
+                let mut home_tabs = vec![];
+                let mut dms = vec![];
+                let mut market_logs = vec![];
+                for result in saved {
+                    match result {
+                        Ok((who, mut steader_dms, mut steader_logs)) => {
+                            home_tabs.push(who);
+                            dms.append(&mut steader_dms);
+                            market_logs.append(&mut steader_logs);
                         }
-                    }
-                }
-
-                // Launch requested crafts
-                for hs in hacksteaders.iter_mut() {
-                    let nb = hs.neighbor_bonuses();
-                    let Hacksteader {
-                        inventory, land, ..
-                    } = hs;
-
-                    let mut land_iter = land.iter_mut();
-                    while let Some(Tile {
-                        plant: Some(ref mut plant),
-                        steader,
-                        id,
-                        ..
-                    }) = land_iter.next()
-                    {
-                        let config::PlantAdvancementSum {
-                            recipes,
-                            craft_return_chance,
-                            ..
-                        } = plant.advancements_sum(
-                            nb.clone()
-                                .bonuses_for_plant(*id, plant.archetype_handle)
-                                .iter(),
-                        );
-
-                        if let Some((recipe_archetype_handle, recipe)) = craft_queue
-                            .remove(&id)
-                            .filter(|_| plant.craft.is_none())
-                            .and_then(|i| Some((i, recipes.get(i)?)))
-                        {
-                            let should_take: usize =
-                                recipe.needs.iter().map(|(n, _)| n).sum::<usize>();
-                            let used_resources = recipe
-                                .needs
-                                .clone()
-                                .into_iter()
-                                .flat_map(|(count, ah)| {
-                                    inventory
-                                        .iter()
-                                        .filter(move |p| p.archetype_handle == ah)
-                                        .take(count)
-                                })
-                                .collect::<Vec<_>>();
-
-                            if should_take == used_resources.len() {
-                                let mut rng = rand::thread_rng();
-                                deletions.append(
-                                    &mut used_resources
-                                        .into_iter()
-                                        .filter(|p| {
-                                            let keep = rng.gen_range(0.0, 1.0) < craft_return_chance;
-                                            if keep {
-                                                debug!("mommy can we keep it? YES? YESSS");
-                                                dms.push((
-                                                    steader.clone(),
-                                                    vec![
-                                                        comment("your craft return bonus just came in quite handy!"),
-                                                        comment(format!(
-                                                            "looks like you get to keep a {} from that craft!",
-                                                            &p.name,
-                                                        )),
-                                                    ],
-                                                    "What's this, a crafting bonus‽".to_string()
-                                                ));
-                                            }
-                                            !keep
-                                        })
-                                        .map(|p| p.key())
-                                        .collect()
-                                );
-
-                                debug!("submitting craft");
-                                plant.craft = Some(hacksteader::Craft {
-                                    until_finish: recipe.time,
-                                    recipe_archetype_handle,
-                                });
-                            } else {
-                                dms.push((
-                                    steader.clone(),
-                                    vec![
-                                        comment("you don't have enough resources to craft that"),
-                                        comment("nice try tho"),
-                                    ],
-                                    "You sure you have enough to craft that? Check again..."
-                                        .to_string(),
-                                ));
+                        // nothing was saved, so the next cycle will pick up from where this
+                        // one did once it has the inputs this one used up.
+                        Err((who, mut consumed, too_big)) => {
+                            farm_state.roll_back(&who, &progress);
+                            if let Some(cycles) = too_big {
+                                farm_state.farm_fewer_cycles(&who, cycles);
                             }
+                            requeued.append(&mut consumed);
                         }
                     }
                 }
 
-                // we'll be frequently looking up profiles by who owns them to award xp.
-                let mut profiles: HashMap<String, Profile> = hacksteaders
-                    .iter()
-                    .map(|hs| (hs.user_id.clone(), hs.profile.clone()))
-                    .collect();
-
-                // we only want to update the time on someone's profile once
-                // even though they might have several plants, any of which
-                // might be boosted, so we give them a "plant token" for
-                // each of their plants, and move them forward when they
-                // run out of tokens
-                let mut plant_tokens: HashMap<String, usize> = hacksteaders
-                    .iter()
-                    .map(|hs| {
-                        (
-                            hs.user_id.clone(),
-                            hs.land.iter().filter_map(|t| t.plant.as_ref()).count(),
-                        )
-                    })
-                    .collect();
-
-                // same goes with the neighbor bonuses for each hackstead
-                let neighbor_bonuses: HashMap<String, _> = hacksteaders
-                    .iter()
-                    .map(|hs| (hs.user_id.clone(), hs.neighbor_bonuses()))
-                    .collect();
-
-                // we can only farm on tiles with plants,
-                let mut tiles: Vec<(Plant, Tile)> = hacksteaders
-                    .into_iter()
-                    .flat_map(|hs| hs.land.into_iter())
-                    .filter_map(|mut t| {
-                        Some((
-                            t.plant.take().or_else(|| {
-                                plant_queue.remove(&t.id).map(|plant| {
-                                    profiles
-                                        .get_mut(&t.steader)
-                                        .expect("tile has no owner")
-                                        .last_farm = SystemTime::now();
-                                    plant
-                                })
-                            })?,
-                            t,
-                        ))
-                    })
-                    .collect();
+                let _ = futures::try_join!(
+                    stream::iter(home_tabs)
+                        .map(|x| Ok(x))
+                        .try_for_each_concurrent(None, |who| update_user_home_tab(who)),
+                    farm::announce(dms, market_logs),
+                )
+                .map_err(|e| error!("farm cycle async err: {}", e));
 
-                // remove inactive users
-                for ((_, profile), (user, fresh)) in
-                    profiles.iter_mut().zip(active_users.clone().into_iter())
-                {
-                    let now = std::time::SystemTime::now();
-                    if fresh {
-                        profile.last_active = now;
-                    } else {
-                        const ACTIVE_DURATION_SECS: u64 = 60 * 5;
-                        if now
-                            .duration_since(profile.last_active)
-                            .ok()
-                            .filter(|r| r.as_secs() >= ACTIVE_DURATION_SECS)
-                            .is_some()
-                        {
-                            active_users.remove(&user);
+                // everything queued is saved, but it's nicer to use it up before restarting
+                if let Ok(_) = fs::read("restart") {
+                    let drained =
+                        farm_state.queues.is_empty() && requeued.is_empty() && rx.is_empty();
+                    if drained || restart_cycles >= MAX_RESTART_DRAIN_CYCLES {
+                        if !drained {
+                            info!(
+                                "restarting with farming inputs still queued; they'll be replayed"
+                            );
                         }
+                        std::process::exit(0);
                     }
+                    restart_cycles += 1;
                 }
+            }
+        }
+    });
 
-                // game tick loop:
-                // this is where we go through and we increment each xp/craft/yield
-                for (plant, tile) in tiles.iter_mut() {
-                    let profile = match profiles.get_mut(&tile.steader) {
-                        Some(profile) => profile,
-                        None => {
-                            error!(
-                                concat!(
-                                    "ignoring 1 active user: ",
-                                    "couldn't get tile[{}]'s steader[{}]'s profile",
-                                ),
-                                tile.id, tile.steader
-                            );
-                            continue;
-                        }
-                    };
+    rocket::tokio::task::spawn(async {
+        use rocket::tokio::time::interval;
 
-                    let neighbor_bonuses = match neighbor_bonuses.get(&tile.steader) {
-                        Some(bonuses) => bonuses,
-                        None => {
-                            error!(
-                                concat!(
-                                    "ignoring 1 active user: ",
-                                    "couldn't get tile[{}]'s steader[{}]'s neighbor bonuses",
-                                ),
-                                tile.id, tile.steader
-                            );
-                            continue;
-                        }
-                    };
-                    let neighbor_bonuses = neighbor_bonuses
-                        .clone()
-                        .bonuses_for_plant(tile.id, plant.archetype_handle);
-
-                    // amount of farm cycles since the last farm, rounded down
-                    let elapsed = SystemTime::now()
-                        .duration_since(profile.last_farm)
-                        .unwrap_or_default()
-                        .as_millis()
-                        / (FARM_CYCLE_MILLIS as u128);
-
-                    // increment their profile's "last farm" time so we can calculate
-                    // an accurate "elapsed" during the next update.
-                    if elapsed > 0 {
-                        if let Some(tokens) = plant_tokens.get_mut(&profile.id) {
-                            *tokens = *tokens - 1;
-                            if *tokens == 0 {
-                                debug!("all plants finished for {}", profile.id);
-                                // we don't want to add the boosted_elapsed here, then your item effects
-                                // would have to be "paid for" later (your farm wouldn't work for however
-                                // much time the effect gave you).
-                                profile.last_farm += Duration::from_millis(
-                                    (FARM_CYCLE_MILLIS as u128 * elapsed)
-                                        .try_into()
-                                        .unwrap_or_else(|e| {
-                                            error!(
-                                                "too many farm cycle millis * elapsed[{}]: {}",
-                                                elapsed, e
-                                            );
-                                            0
-                                        }),
-                                );
-                            }
-                        }
-                    }
+        let mut interval = interval(std::time::Duration::from_secs(AUCTION_SETTLE_SECS));
+        loop {
+            interval.tick().await;
+            if let Err(e) = auction::settle_ended(store()).await {
+                error!("couldn't settle auctions: {}", e);
+            }
+        }
+    });
 
-                    debug!("elapsing {} cycles for {}", elapsed, profile.id);
-
-                    for _ in 0..elapsed {
-                        plant.effects = plant
-                            .effects
-                            .iter_mut()
-                            .filter_map(|e| {
-                                if let Some(uf) = e.until_finish.as_mut() {
-                                    // decrement counter, remove if 0
-                                    *uf = (*uf - 1.0).max(0.0);
-                                    if *uf == 0.0 {
-                                        debug!("removing effect: {:?}", e);
-                                        return None;
-                                    }
-                                }
+    rocket::tokio::task::spawn(async {
+        use rocket::tokio::time::interval;
 
-                                Some(*e)
-                            })
-                            .collect::<Vec<_>>();
-
-                        // you want to recalculate this every update because it's dependent
-                        // on what effects are active, especially the `total_extra_time_ticks`.
-                        let plant_sum = plant.advancements_sum(neighbor_bonuses.iter());
-
-                        let ticks = plant_sum.total_extra_time_ticks + 1;
-                        let mut rng = rand::thread_rng();
-                        debug!("triggering {} ticks for {}'s cycle", ticks, profile.id);
-                        for _ in 0..ticks {
-                            plant.craft = match plant
-                                .current_recipe_raw()
-                                .and_then(|r| Some((r, plant.craft.take()?)))
-                            {
-                                Some((recipe, mut craft)) => {
-                                    if craft.until_finish > plant_sum.crafting_speed_multiplier {
-                                        craft.until_finish -= plant_sum.crafting_speed_multiplier;
-                                        Some(craft)
-                                    } else {
-                                        let earned_xp = {
-                                            let (lo, hi) = recipe.xp;
-                                            rng.gen_range(lo, hi)
-                                        };
-                                        plant.queued_xp_bonus += earned_xp;
-
-                                        let mut output: Vec<Possession> = recipe
-                                            .makes
-                                            .clone()
-                                            .output()
-                                            .into_iter()
-                                            .map(|ah| {
-                                                Possession::new(
-                                                    ah,
-                                                    possess::Owner::crafter(tile.steader.clone()),
-                                                )
-                                            })
-                                            .collect();
-
-                                        if rng.gen_range(0.0, 1.0)
-                                            < plant_sum.double_craft_yield_chance
-                                        {
-                                            debug!("cloning recipe output! {:?}", output);
-                                            output.append(&mut output.clone());
-                                            debug!("after clone: {:?}", output);
-                                        }
-                                        possessions.extend_from_slice(&output);
-
-                                        let mut msg = vec![
-                                            json!({
-                                                "type": "section",
-                                                "text": mrkdwn(format!(
-                                                    concat!(
-                                                        "Your *{}* has finished crafting *{}* for you!\n",
-                                                        "This earned it {} xp!",
-                                                    ),
-                                                    plant.name,
-                                                    recipe.clone().lookup_handles().unwrap().title(),
-                                                    earned_xp,
-                                                )),
-                                                "accessory": {
-                                                    "type": "image",
-                                                    "image_url": format!(
-                                                        "http://{}/gotchi/img/plant/{}.gif",
-                                                        *URL,
-                                                        filify(&plant.current_advancement().art)
-                                                    ),
-                                                    "alt_text": "happy shiny plant give u stuffs",
-                                                }
-                                            }),
-                                            comment("YAY FREE STUFFZ 'CEPT LIKE IT'S NOT FREE"),
-                                            json!({ "type": "divider" }),
-                                        ];
-
-                                        msg.append(&mut format_yield(output, tile.steader.clone()));
-                                        dms.push((
-                                            tile.steader.clone(),
-                                            msg,
-                                            format!(
-                                                "What's this, a new {}?",
-                                                recipe.clone().lookup_handles().unwrap().title()
-                                            ),
-                                        ));
-
-                                        if recipe.destroys_plant {
-                                            clear_plants.push(tile.id.clone());
-                                        }
-
-                                        None
-                                    }
-                                }
-                                None => None,
-                            };
-
-                            plant.until_yield = match plant.until_yield
-                                - plant_sum.yield_speed_multiplier
-                            {
-                                n if n > 0.0 => n,
-                                _ if plant.base_yield_duration.is_some() => {
-                                    let owner = &tile.steader;
-                                    let (yielded, xp_bonuses): (Vec<_>, Vec<_>) =
-                                        config::spawn(&plant_sum.yields, &mut rand::thread_rng())
-                                            .map(|(ah, xp)| {
-                                                (
-                                                    Possession::new(
-                                                        ah,
-                                                        possess::Owner::farmer(owner.clone()),
-                                                    ),
-                                                    xp,
-                                                )
-                                            })
-                                            .unzip();
-                                    let earned_xp = xp_bonuses.into_iter().sum::<usize>() as u64;
-
-                                    plant.queued_xp_bonus += earned_xp;
-
-                                    let mut msg = vec![
-                                        json!({
-                                            "type": "section",
-                                            "text": mrkdwn(format!(
-                                                concat!(
-                                                    "Your *{}* has produced a crop yield for you!\n",
-                                                    "This earned it {} xp!"
-                                                ),
-                                                plant.name,
-                                                earned_xp,
-                                            )),
-                                            "accessory": {
-                                                "type": "image",
-                                                "image_url": format!(
-                                                    "http://{}/gotchi/img/plant/{}.gif",
-                                                    *URL,
-                                                    filify(&plant.current_advancement().art)
-                                                ),
-                                                "alt_text": "happy shiny plant give u stuffs",
-                                            }
-                                        }),
-                                        comment("FREE STUFF FROM CUTE THING"),
-                                        json!({ "type": "divider" }),
-                                    ];
-
-                                    possessions.extend_from_slice(&yielded);
-
-                                    msg.append(&mut format_yield(yielded, tile.steader.clone()));
-
-                                    dms.push((
-                                        tile.steader.clone(),
-                                        msg,
-                                        "FREE STUFF FROM CUTE THING".to_string(),
-                                    ));
-
-                                    plant.base_yield_duration.unwrap_or(0.0)
-                                }
-                                n => n,
-                            };
-
-                            if let Some(advancement) = plant.increase_xp(plant_sum.xp_multiplier) {
-                                dms.push((tile.steader.clone(), vec![
-                                    json!({
-                                        "type": "section",
-                                        "text": mrkdwn(format!(
-                                            concat!(
-                                                ":tada: Your _{}_ is now a *{}*!\n\n",
-                                                "*{}* Achieved:\n _{}_\n\n",
-                                                ":stonks: Total XP: *{}xp*",
-                                            ),
-                                            plant.name,
-                                            advancement.achiever_title,
-                                            advancement.title,
-                                            advancement.description,
-                                            advancement.xp
-                                        )),
-                                        "accessory": {
-                                            "type": "image",
-                                            "image_url": format!("http://{}/gotchi/img/plant/{}.gif", *URL, filify(&advancement.art)),
-                                            "alt_text": "happy shiny better plant",
-                                        }
-                                    }),
-                                    comment("EXCITING LEVELING UP NOISES"),
-                                ],
-                                format!(
-                                    "Your {} is now a {}!",
-                                    plant.name,
-                                    advancement.achiever_title
-                                )
-                            ))
-                            }
-                            let profile_sum =
-                                profile.advancements.sum(profile.xp, std::iter::empty());
-                            if let Some(advancement) = profile.increase_xp(plant_sum.xp_multiplier)
-                            {
-                                dms.push((tile.steader.clone(), vec![
-                                    json!({
-                                        "type": "section",
-                                        "text": mrkdwn(format!(
-                                            concat!(
-                                                ":tada: Your _Hackstead_ is now a *{}*!\n\n",
-                                                "*{}* Achieved:\n_{}_\n\n",
-                                                ":stonks: Total XP: *{}xp*\n",
-                                                ":mountain: Land Available: *{} pieces* _(+{} pieces)_"
-                                            ),
-                                            advancement.achiever_title,
-                                            advancement.title,
-                                            advancement.description,
-                                            advancement.xp,
-                                            profile_sum.land,
-                                            match advancement.kind {
-                                                config::HacksteadAdvancementKind::Land { pieces } => pieces,
-                                            }
-                                        )),
-                                        "accessory": {
-                                            "type": "image",
-                                            "image_url": format!("http://{}/gotchi/img/icon/seedlet.png", *URL),
-                                            "alt_text": "happy shiny better hackstead",
-                                        }
-                                    }),
-                                    comment("SUPER EXCITING LEVELING UP NOISES"),
-                                ],
-                                format!("Your Hackstead is now a {}!", advancement.achiever_title)
-                            ));
-                            }
-                        }
-                    }
-                }
+        let mut interval = interval(std::time::Duration::from_secs(LISTING_SWEEP_SECS));
+        loop {
+            interval.tick().await;
+            if let Err(e) = market::sweep_expired_listings(store()).await {
+                error!("couldn't sweep expired listings: {}", e);
+            }
+        }
+    });
 
-                let _ = stream::iter(
-                    tiles
-                        .into_iter()
-                        .map(|(plant, mut tile)| rusoto_dynamodb::WriteRequest {
-                            put_request: Some(rusoto_dynamodb::PutRequest {
-                                item: {
-                                    tile.plant = if clear_plants.iter().any(|id| *id == tile.id) {
-                                        None
-                                    } else {
-                                        Some(plant)
-                                    };
-
-                                    tile.into_av().m.expect("tile attribute should be map")
-                                },
-                            }),
-                            ..Default::default()
-                        })
-                        .chain(
-                            new_tiles
-                                .into_iter()
-                                .map(|t| rusoto_dynamodb::WriteRequest {
-                                    put_request: Some(rusoto_dynamodb::PutRequest {
-                                        item: t.into_av().m.expect("tile attribute should be map"),
-                                    }),
-                                    ..Default::default()
-                                }),
-                        )
-                        .chain(profiles.iter().map(|(_, p)| rusoto_dynamodb::WriteRequest {
-                            put_request: Some(rusoto_dynamodb::PutRequest { item: p.item() }),
-                            ..Default::default()
-                        }))
-                        .chain(possessions.iter().map(|p| rusoto_dynamodb::WriteRequest {
-                            put_request: Some(rusoto_dynamodb::PutRequest { item: p.item() }),
-                            ..Default::default()
-                        }))
-                        .chain(
-                            deletions
-                                .into_iter()
-                                .map(|key| rusoto_dynamodb::WriteRequest {
-                                    delete_request: Some(rusoto_dynamodb::DeleteRequest {
-                                        key: key.into_item(),
-                                    }),
-                                    ..Default::default()
-                                }),
-                        )
-                        .collect::<Vec<_>>()
-                        .chunks(25)
-                        .map(|items| {
-                            db.batch_write_item(rusoto_dynamodb::BatchWriteItemInput {
-                                request_items: [(hcor::TABLE_NAME.to_string(), items.to_vec())]
-                                    .iter()
-                                    .cloned()
-                                    .collect(),
-                                ..Default::default()
-                            })
-                        }),
-                )
-                .map(|x| Ok(x))
-                .try_for_each_concurrent(None, |r| async move {
-                    match r.await {
-                        Ok(_) => Ok(()),
-                        Err(e) => Err(format!("error updating db after farm cycle: {}", e)),
-                    }
-                })
-                .await
-                .map_err(|e| error!("farm cycle async err: {}", e));
+    rocket::tokio::task::spawn(async {
+        use rocket::tokio::time::interval;
 
-                let _ = futures::try_join!(
-                    stream::iter(profiles.clone())
-                        .map(|x| Ok(x))
-                        .try_for_each_concurrent(None, |(who, _)| { update_user_home_tab(who) }),
-                    stream::iter(dms).map(|x| Ok(x)).try_for_each_concurrent(
-                        None,
-                        |(who, blocks, craft_type)| {
-                            dm_blocks(who.clone(), craft_type.clone(), blocks.to_vec())
-                        }
-                    ),
-                    stream::iter(market_logs)
-                        .map(|x| Ok(x))
-                        .try_for_each_concurrent(None, |(blocks, notif_type)| {
-                            market::log_blocks(notif_type, blocks)
-                        }),
-                )
-                .map_err(|e| error!("farm cycle async err: {}", e));
+        let mut interval = interval(std::time::Duration::from_secs(TRADE_SWEEP_SECS));
+        loop {
+            interval.tick().await;
+            if let Err(e) = trade::sweep_expired(store()).await {
+                error!("couldn't sweep expired trade offers: {}", e);
+            }
+        }
+    });
 
-                if let Ok(_) = fs::read("restart") {
-                    std::process::exit(0);
-                }
+    rocket::tokio::task::spawn(async {
+        use rocket::tokio::time::interval;
+
+        let mut interval = interval(std::time::Duration::from_secs(OWED_SWEEP_SECS));
+        loop {
+            interval.tick().await;
+            if let Err(e) = banker::sweep_owed(store()).await {
+                error!("couldn't sweep owed payments: {}", e);
             }
         }
     });
@@ -3986,9 +4212,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
                 hgive,
                 event::event,
                 stateofsteading,
-                steadercount
+                steadercount,
+                render::hackstead_png
             ],
         )
+        .mount("/gotchi/api/v1", api::routes())
         .mount("/gotchi/img", StaticFiles::from("./img"))
         .launch()
         .await
@@ -3996,3 +4224,180 @@ async fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use blocks::assert_snapshot;
+    use chat::{recorded, Sent};
+    use config::ArchetypeHandle;
+    use hacksteader::{Plant, Tile};
+    use hcor::Profile;
+    use serde_json::to_value;
+    use std::env::set_var;
+
+    const STEADER: &str = "U1";
+
+    fn setup() {
+        set_var("URL", "localhost");
+        set_var("APP_ID", "A1");
+        set_var("CHAT", "recording");
+        set_var("STORE", "memory");
+    }
+
+    fn archetype(gotchi: bool) -> ArchetypeHandle {
+        CONFIG
+            .possession_archetypes
+            .iter()
+            .position(|a| a.kind.gotchi().is_some() == gotchi)
+            .expect("no archetype of that kind")
+    }
+
+    /// A possession with an id that's the same every time, so that it can be snapshotted.
+    fn possession(archetype_handle: ArchetypeHandle, id: u128) -> Possession {
+        let mut p = Possession::new(
+            archetype_handle,
+            possess::Owner::farmer(STEADER.to_string()),
+        );
+        p.id = uuid::Uuid::from_u128(id);
+        p
+    }
+
+    fn tile(id: u128, plant: Option<Plant>) -> Tile {
+        let mut tile = Tile::new(STEADER.to_string());
+        tile.id = uuid::Uuid::from_u128(id);
+        tile.plant = plant;
+        tile
+    }
+
+    fn a_hackstead() -> Hacksteader {
+        Hacksteader {
+            user_id: STEADER.to_string(),
+            profile: Profile::new(STEADER.to_string()),
+            land: vec![
+                tile(
+                    1,
+                    Some(Plant {
+                        archetype_handle: 0,
+                        ..Default::default()
+                    }),
+                ),
+                tile(2, None),
+            ],
+            inventory: vec![possession(archetype(false), 3)],
+            gotchis: vec![Possessed::from_possession(possession(archetype(true), 4)).unwrap()],
+        }
+    }
+
+    #[test]
+    fn hackstead_page() {
+        setup();
+        let mut json = to_value(hackstead_blocks(
+            a_hackstead(),
+            Interactivity::Write,
+            Credentials::Owner,
+        ))
+        .unwrap();
+
+        // how long ago it was founded depends on how long the test took to get here
+        for block in json.as_array_mut().unwrap() {
+            if let Some(text) = block
+                .pointer_mut("/elements/0/text")
+                .filter(|t| t.as_str().map_or(false, |t| t.starts_with("founded ")))
+            {
+                *text = Value::from("founded just now");
+            }
+        }
+        assert_snapshot("hackstead", &json);
+    }
+
+    #[rocket::async_test]
+    async fn hackmarket_page() {
+        setup();
+        let ah = archetype(false);
+        let p = possession(ah, 5);
+        store()
+            .write(store::Batch {
+                possessions: vec![p.clone()],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        store()
+            .place_on_market(p.key(), 25, p.name.clone())
+            .await
+            .unwrap();
+        records::put(
+            store(),
+            &market::LastTrade {
+                archetype_handle: ah,
+                price: 20,
+                at: std::time::SystemTime::now(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let blocks = hackmarket_blocks(market::archetype_category(ah), "U2".to_string()).await;
+        assert_snapshot("hackmarket", &to_value(blocks).unwrap());
+    }
+
+    #[test]
+    fn a_few_yields() {
+        setup();
+        let items = vec![
+            possession(archetype(false), 6),
+            possession(archetype(true), 7),
+        ];
+        let blocks = format_yield(items, STEADER.to_string());
+        assert_snapshot("yield_few", &to_value(blocks).unwrap());
+    }
+
+    #[test]
+    fn lots_of_yields() {
+        setup();
+        let items = (0..10)
+            .map(|i| possession(archetype(i % 2 == 0), i))
+            .collect();
+        let blocks = format_yield(items, STEADER.to_string());
+        assert_snapshot("yield_lots", &to_value(blocks).unwrap());
+    }
+
+    #[rocket::async_test]
+    async fn gift() {
+        setup();
+        let p = possession(archetype(true), 8);
+        let (sent, recording) =
+            recorded(gift_dm("U2", STEADER, &p, "a gift!".to_string(), 1)).await;
+        sent.unwrap();
+
+        match recording.as_slice() {
+            [Sent::Dm {
+                user_id, blocks, ..
+            }] if user_id == STEADER => assert_snapshot("gift", &to_value(blocks).unwrap()),
+            other => panic!("expected one gift DM, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn possession_page_for_its_owner() {
+        setup();
+        let page = PossessionPage {
+            possession: possession(archetype(true), 9),
+            interactivity: Interactivity::Write,
+            credentials: Credentials::Owner,
+        };
+        assert_snapshot("possession_owner", &to_value(page.blocks()).unwrap());
+    }
+
+    #[test]
+    fn possession_page_on_the_market() {
+        setup();
+        let page = PossessionPage {
+            possession: possession(archetype(false), 10),
+            interactivity: Interactivity::Buy,
+            credentials: Credentials::None,
+        };
+        assert_snapshot("possession_market", &to_value(page.blocks()).unwrap());
+    }
+}
@@ -1,121 +1,1399 @@
-use hcor::{market::Sale, Category, Key, Possession};
-use rusoto_dynamodb::{AttributeValue, DynamoDb, DynamoDbClient};
+use crate::blocks::{comment, image, mrkdwn, section, Block};
+use crate::bus::{self, GameEvent};
+use crate::chat::{chat, mention, Channel};
+use crate::hacksteader::Hacksteader;
+use crate::records::{self, Record};
+use crate::store::{Store, Transfer};
+use crate::{banker, dm_blocks, filify, URL};
+use config::{ArchetypeHandle, CONFIG};
+use hcor::{config, market::Sale, possess, Category, Key, Possession};
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, SystemTime};
+
+pub async fn log_blocks(notif_msg: String, blocks: Vec<Block>) -> Result<(), String> {
+    chat().log(Channel::Market, notif_msg, blocks).await
+}
+
+pub async fn market_search(
+    db: &dyn Store,
+    cat: Category,
+) -> Result<Vec<(Sale, Possession)>, String> {
+    db.market_listings(cat).await
+}
 
 use std::env::var;
 lazy_static::lazy_static! {
+    /// How much of their listing fee, in percent, someone gets back
+    /// for taking something off of the market themselves.
+    pub static ref WITHDRAWAL_REFUND_PERCENT: u64 = var("MARKET_WITHDRAWAL_REFUND_PERCENT")
+        .ok()
+        .and_then(|p| p.parse::<u64>().ok())
+        .map_or(0, |p| p.min(100));
+}
 
-    pub static ref HACKMARKET_LOG_CHAT: String = var("HACKMARKET_LOG_CHAT").unwrap();
+/// How long something can be put up for sale for, in hours; `None` means until it's taken down.
+pub const LISTING_DURATIONS: &[(&str, Option<u64>)] = &[
+    ("1 day", Some(24)),
+    ("3 days", Some(3 * 24)),
+    ("1 week", Some(7 * 24)),
+    ("Until I take it down", None),
+];
+
+/// When something went up for sale, and when it comes back down.
+/// Kept alongside the sale itself, by the id of the possession being sold.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Listing {
+    pub key: Key,
+    pub seller: String,
+    pub price: u64,
+    pub placed: SystemTime,
+    pub expires: Option<SystemTime>,
 }
+impl Record for Listing {
+    const KIND: &'static str = "listing";
 
-pub async fn log_blocks(notif_msg: String, blocks: Vec<serde_json::Value>) -> Result<(), String> {
-    let o = serde_json::json!({
-        "channel": *HACKMARKET_LOG_CHAT,
-        "token": *super::TOKEN,
-        "blocks": blocks,
-        "text": notif_msg
-    });
+    fn id(&self) -> String {
+        self.key.id.to_simple().to_string()
+    }
+}
+impl Listing {
+    pub fn expired(&self) -> bool {
+        self.expires.map_or(false, |e| SystemTime::now() >= e)
+    }
+}
+
+pub async fn place_on_market(
+    db: &dyn Store,
+    key: Key,
+    seller: String,
+    price: u64,
+    name: String,
+    duration: Option<Duration>,
+) -> Result<(), String> {
+    println!("putting {} on the market", key.id);
 
-    log::debug!("{}", serde_json::to_string_pretty(&o).unwrap());
+    db.place_on_market(key, price, name)
+        .await
+        .map_err(|e| dbg!(format!("Couldn't place {} on market: {}", key.id, e)))?;
+
+    let placed = SystemTime::now();
+    records::put(
+        db,
+        &Listing {
+            key,
+            seller,
+            price,
+            placed,
+            expires: duration.map(|d| placed + d),
+        },
+    )
+    .await
+}
 
-    // TODO: use response
-    let client = reqwest::Client::new();
-    client
-        .post("https://slack.com/api/chat.postMessage")
-        .bearer_auth(&*super::TOKEN)
-        .json(&o)
-        .send()
+pub async fn take_off_market(db: &dyn Store, key: Key) -> Result<(), String> {
+    println!("taking {} off the market", key.id);
+
+    db.take_off_market(key)
         .await
-        .map_err(|e| format!("couldn't log blocks: {}", e))?;
+        .map_err(|e| dbg!(format!("Couldn't remove {} from market: {}", key.id, e)))?;
+    records::remove::<Listing>(db, &key.id.to_simple().to_string())
+        .await
+        .map(|_| ())
+}
+
+/// When something that's for sale was put up, and when it'll come down.
+pub async fn listing(db: &dyn Store, key: Key) -> Result<Option<Listing>, String> {
+    records::get(db, &key.id.to_simple().to_string()).await
+}
+
+/// Takes something off the market for its seller, giving them back
+/// `WITHDRAWAL_REFUND_PERCENT` of its listing fee if it hadn't expired yet.
+pub async fn withdraw_listing(db: &dyn Store, key: Key, user: &str) -> Result<(), String> {
+    let possession = db.possession(key).await?;
+    let sale = match possession.sale.as_ref() {
+        Some(sale) if possession.steader == user => sale,
+        Some(_) => {
+            return Err(format!(
+                "{} can't take someone else's {} off the market",
+                mention(user),
+                possession.name
+            ))
+        }
+        None => return Ok(()),
+    };
+    let expired = listing(db, key).await?.map_or(false, |l| l.expired());
+
+    take_off_market(db, key).await?;
+
+    let refund = sale.price / 20 * *WITHDRAWAL_REFUND_PERCENT / 100;
+    if refund > 0 && !expired {
+        banker::pay(
+            possession.steader.clone(),
+            refund,
+            format!("taking your {} off the market early", possession.name),
+        )
+        .await?;
+    }
 
     Ok(())
 }
 
-pub async fn market_search(
-    db: &DynamoDbClient,
-    cat: Category,
-) -> Result<Vec<(Sale, Possession)>, String> {
-    let query = db
-        .query(rusoto_dynamodb::QueryInput {
-            table_name: hcor::TABLE_NAME.to_string(),
-            index_name: Some("cat_price_index".to_string()),
-            key_condition_expression: Some("cat = :sale_cat".to_string()),
-            expression_attribute_values: Some(
-                [(":sale_cat".to_string(), cat.into_av())]
-                    .iter()
-                    .cloned()
-                    .collect(),
+/// Takes down everything that's been up for sale for as long as its seller wanted,
+/// letting them know. Listings for things that were sold or taken down some other way are forgotten.
+/// One listing that can't be swept doesn't hold up the rest; it's logged and tried again next time.
+pub async fn sweep_expired_listings(db: &dyn Store) -> Result<(), String> {
+    for listing in records::all::<Listing>(db).await? {
+        if let Err(e) = sweep_listing(db, &listing).await {
+            warn!("couldn't sweep listing {}: {}", listing.id(), e);
+        }
+    }
+
+    Ok(())
+}
+
+async fn sweep_listing(db: &dyn Store, listing: &Listing) -> Result<(), String> {
+    let possession = match db.possession(listing.key).await {
+        Ok(possession) => possession,
+        Err(e) => {
+            warn!("forgetting listing {}: {}", listing.id(), e);
+            records::remove::<Listing>(db, &listing.id()).await?;
+            return Ok(());
+        }
+    };
+    let still_up = possession.steader == listing.seller
+        && possession
+            .sale
+            .as_ref()
+            .map_or(false, |s| s.price == listing.price);
+    if !still_up {
+        records::remove::<Listing>(db, &listing.id()).await?;
+        return Ok(());
+    }
+    if !listing.expired() {
+        return Ok(());
+    }
+
+    // the listing is gone before the seller hears about it, so a DM that doesn't
+    // make it won't have the next sweep telling them twice.
+    db.take_off_market(listing.key)
+        .await
+        .map_err(|e| format!("Couldn't remove {} from market: {}", listing.key.id, e))?;
+    if records::remove::<Listing>(db, &listing.id())
+        .await?
+        .is_none()
+    {
+        return Ok(());
+    }
+    dm_blocks(
+        listing.seller.clone(),
+        format!(
+            "Your {} didn't sell, so it's off the market.",
+            possession.name
+        ),
+        vec![
+            section(mrkdwn(format!(
+                "Nobody bought your *{}* for *{} HN* in the time you gave it, \
+                so I've taken it off the market. You can always put it back up!",
+                possession.name, listing.price
+            )))
+            .into(),
+            comment("NO BYERS? NO PROBLEM"),
+        ],
+    )
+    .await
+}
+
+/// Someone's standing offer to buy a number of possessions of one archetype.
+/// The banker holds onto `max_price` for each one they still want.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BuyOrder {
+    pub id: uuid::Uuid,
+    pub buyer: String,
+    pub archetype_handle: ArchetypeHandle,
+    pub max_price: u64,
+    /// How many more they want.
+    pub quantity: u64,
+    pub placed: SystemTime,
+}
+impl Record for BuyOrder {
+    const KIND: &'static str = "buy_order";
+
+    fn id(&self) -> String {
+        self.id.to_simple().to_string()
+    }
+}
+impl BuyOrder {
+    pub fn name(&self) -> &'static str {
+        archetype_name(self.archetype_handle)
+    }
+}
+
+pub fn archetype_name(ah: ArchetypeHandle) -> &'static str {
+    CONFIG
+        .possession_archetypes
+        .get(ah)
+        .map(|a| a.name.as_str())
+        .unwrap_or("unknown item")
+}
+
+/// Bills someone the fee for putting a possession up for sale.
+/// It goes on the market once they've paid, returning the id of the invoice.
+pub async fn invoice_listing_fee(
+    user_id: &str,
+    possession: &Possession,
+    price: u64,
+    duration: Option<Duration>,
+) -> Result<String, String> {
+    banker::invoice(
+        user_id,
+        price / 20_u64,
+        &format!(
+            "hackmarket fees for selling {} at {}hn",
+            possession.name, price
+        ),
+        banker::InvoiceIntent::MarketFee {
+            key: possession.key(),
+            price,
+            market_name: possession.name.clone(),
+            duration,
+        },
+    )
+    .await
+}
+
+/// Which part of the market possessions of this archetype are sold in.
+pub fn archetype_category(ah: ArchetypeHandle) -> Category {
+    match CONFIG.possession_archetypes.get(ah) {
+        Some(a) if a.kind.gotchi().is_some() => Category::Gotchi,
+        _ => Category::Misc,
+    }
+}
+
+/// Open buy orders, optionally only those for one archetype,
+/// with whoever's willing to pay the most first, and then whoever's waited the longest.
+pub async fn buy_orders(
+    db: &dyn Store,
+    archetype_handle: Option<ArchetypeHandle>,
+) -> Result<Vec<BuyOrder>, String> {
+    let mut orders: Vec<BuyOrder> = records::all::<BuyOrder>(db)
+        .await?
+        .into_iter()
+        .filter(|o| archetype_handle.map_or(true, |ah| o.archetype_handle == ah))
+        .collect();
+    orders.sort_by(|a, b| b.max_price.cmp(&a.max_price).then(a.placed.cmp(&b.placed)));
+    Ok(orders)
+}
+
+/// Puts up a buy order once its escrow has been paid,
+/// filling as much of it as it can from what's already on the market.
+pub async fn open_buy_order(db: &dyn Store, mut order: BuyOrder) -> Result<(), String> {
+    // it's saved before anything's bought with it, so that whatever happens,
+    // all of the escrow is either spent or still there to be cancelled and refunded
+    records::put(db, &order).await?;
+
+    let listings = db
+        .market_listings(archetype_category(order.archetype_handle))
+        .await?
+        .into_iter()
+        .filter(|(sale, p)| {
+            p.archetype_handle == order.archetype_handle
+                && sale.price <= order.max_price
+                && p.steader != order.buyer
+        });
+
+    for (sale, possession) in listings {
+        if order.quantity == 0 {
+            return Ok(());
+        }
+
+        order = match fill(db, &order, &possession, Some(sale.price), sale.price).await? {
+            Some(rest) => rest,
+            // either that listing's gone, or the order's been filled from elsewhere
+            None => match records::get::<BuyOrder>(db, &order.id()).await? {
+                Some(order) => order,
+                None => return Ok(()),
+            },
+        };
+    }
+
+    if order.quantity > 0 {
+        log_buy_order(&order).await?;
+    }
+
+    Ok(())
+}
+
+/// Sells a possession to a buy order for `price`, as long as the order hasn't changed,
+/// and the possession's still with the same steader and on the market for `listed_at`
+/// (or not on the market, if that's None). Returns what's left of the order if it sold.
+///
+/// The seller's owed their HN, and the buyer whatever they held aside over `price`,
+/// in the same write that hands the possession over.
+async fn fill(
+    db: &dyn Store,
+    order: &BuyOrder,
+    possession: &Possession,
+    listed_at: Option<u64>,
+    price: u64,
+) -> Result<Option<BuyOrder>, String> {
+    let rest = BuyOrder {
+        quantity: order.quantity.saturating_sub(1),
+        ..order.clone()
+    };
+    let transfer = Transfer {
+        key: possession.key(),
+        from: possession.steader.clone(),
+        price: listed_at,
+        to: order.buyer.clone(),
+        acquisition: possess::Acquisition::Purchase { price },
+    };
+    let mut owed = vec![banker::OwedPayment::new(
+        &possession.steader,
+        price,
+        format!("sale of your {} to a buy order", possession.name),
+    )];
+    if order.max_price > price {
+        owed.push(banker::OwedPayment::new(
+            &order.buyer,
+            order.max_price - price,
+            format!("getting a {} for less than you offered", possession.name),
+        ));
+    }
+    let mut swaps = vec![records::swap(
+        order,
+        Some(&rest).filter(|r| r.quantity > 0),
+    )?];
+    for o in &owed {
+        swaps.push(records::create(o)?);
+    }
+
+    if !db
+        .transfer_if(vec![transfer], swaps)
+        .await
+        .map_err(|e| format!("couldn't fill buy order {}: {}", order.id(), e))?
+    {
+        return Ok(None);
+    }
+
+    banker::pay_owed(db, owed).await;
+    settle_fill(db, &rest, possession, price).await;
+    Ok(Some(rest))
+}
+
+async fn log_buy_order(order: &BuyOrder) -> Result<(), String> {
+    log_blocks(
+        format!(
+            "{} wants {} {} for up to {} HN each!",
+            mention(&order.buyer),
+            order.quantity,
+            order.name(),
+            order.max_price
+        ),
+        vec![
+            section(mrkdwn(format!(
+                "{} is looking to buy *{}* *{}* for up to *{} HN* each!",
+                mention(&order.buyer),
+                order.quantity,
+                order.name(),
+                order.max_price
+            )))
+            .into(),
+            comment("SELL SELL SELL"),
+        ],
+    )
+    .await
+}
+
+/// Sells a possession that's about to go up on the market for `price`
+/// to the best buy order that'll pay at least that much, if there is one.
+/// Returns whether it was sold.
+pub async fn fill_buy_order(
+    db: &dyn Store,
+    possession: &Possession,
+    price: u64,
+) -> Result<bool, String> {
+    for order in buy_orders(db, Some(possession.archetype_handle)).await? {
+        if order.max_price < price || order.buyer == possession.steader {
+            continue;
+        }
+
+        // if someone else got to the order first, try the next one
+        if fill(db, &order, possession, None, price).await?.is_some() {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Lets everyone know a buy order's been filled, and keeps track of what it went for.
+async fn settle_fill(db: &dyn Store, order: &BuyOrder, possession: &Possession, price: u64) {
+    let name = &possession.name;
+    let seller = possession.steader.clone();
+    let image_url = format!(
+        "http://{}/gotchi/img/{}/{}.png",
+        *URL,
+        possession.kind.category(),
+        filify(name)
+    );
+
+    if let Err(e) = futures::try_join!(
+        log_blocks(
+            format!(
+                "{} filled {}'s buy order for a {} at {} HN!",
+                mention(&seller),
+                mention(&order.buyer),
+                name,
+                price
             ),
-            ..Default::default()
-        })
-        .await;
+            vec![section(mrkdwn(format!(
+                "A buy order has been filled! {} sold a *{}* to {} for *{} HN*!",
+                mention(&seller),
+                name,
+                mention(&order.buyer),
+                price
+            )))
+            .accessory(image(image_url, "Hackpheus sitting on bags of money!"))
+            .into()],
+        ),
+        dm_blocks(
+            order.buyer.clone(),
+            format!("Your buy order got you a {}!", name),
+            vec![
+                section(mrkdwn(format!(
+                    "{} sold you a *{}* for *{} HN* through your buy order! \
+                    You still want *{}* more.",
+                    mention(&seller),
+                    name,
+                    price,
+                    order.quantity
+                )))
+                .into(),
+                comment("BYE ORDR GO BRRR"),
+            ],
+        ),
+    ) {
+        warn!(
+            "couldn't announce buy order {} was filled: {}",
+            order.id(),
+            e
+        );
+    }
 
-    Ok(query
-        .map_err(|e| dbg!(format!("Couldn't search market: {}", e)))?
-        .items
-        .ok_or_else(|| format!("market search query returned no items"))?
-        .iter_mut()
-        .filter_map(|i| match Possession::from_item(i) {
-            Ok(mut pos) => Some((pos.sale.take()?, pos)),
-            Err(e) => {
-                println!("error parsing possession: {}", e);
-                None
+    record_trade(db, possession, &order.buyer, price).await;
+    bus::publish(GameEvent::Sale {
+        buyer: order.buyer.clone(),
+        seller,
+        item: name.clone(),
+        price,
+    });
+}
+
+/// Takes down someone's buy order, giving them back what's left of its escrow.
+pub async fn cancel_buy_order(db: &dyn Store, id: &str, user: &str) -> Result<(), String> {
+    loop {
+        let order = match records::get::<BuyOrder>(db, id).await? {
+            Some(order) if order.buyer == user => order,
+            Some(_) => {
+                return Err(format!(
+                    "{} can't cancel someone else's buy order",
+                    mention(user)
+                ))
             }
+            None => return Err(format!("no buy order {} to cancel", id)),
+        };
+
+        let refund = banker::OwedPayment::new(
+            &order.buyer,
+            order.max_price * order.quantity,
+            format!(
+                "cancelling your buy order for {}",
+                count_of(order.quantity as usize, order.name())
+            ),
+        );
+        let swaps = vec![records::swap(&order, None)?, records::create(&refund)?];
+        if db.transfer_if(vec![], swaps).await? {
+            banker::pay_owed(db, vec![refund]).await;
+            return Ok(());
+        }
+        // some of it was filled while it was being cancelled, so refund what's left now
+    }
+}
+
+/// A sale that went through, kept so that prices can be looked back on.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Trade {
+    pub id: uuid::Uuid,
+    pub archetype_handle: ArchetypeHandle,
+    pub name: String,
+    pub price: u64,
+    pub buyer: String,
+    pub seller: String,
+    pub at: SystemTime,
+}
+impl Record for Trade {
+    const KIND: &'static str = "trade";
+
+    fn id(&self) -> String {
+        self.id.to_simple().to_string()
+    }
+}
+
+/// The latest trade of each archetype, kept on its own so that the market
+/// can show what things last went for without going through every trade.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LastTrade {
+    pub archetype_handle: ArchetypeHandle,
+    pub price: u64,
+    pub at: SystemTime,
+}
+impl Record for LastTrade {
+    const KIND: &'static str = "last_trade";
+
+    fn id(&self) -> String {
+        self.archetype_handle.to_string()
+    }
+}
+
+/// Notes down that `possession` was sold to `buyer` for `price`.
+/// The sale's already gone through by now, so this only logs if it can't.
+pub async fn record_trade(db: &dyn Store, possession: &Possession, buyer: &str, price: u64) {
+    let trade = Trade {
+        id: uuid::Uuid::new_v4(),
+        archetype_handle: possession.archetype_handle,
+        name: possession.name.clone(),
+        price,
+        buyer: buyer.to_string(),
+        seller: possession.steader.clone(),
+        at: SystemTime::now(),
+    };
+    if let Err(e) = records::put(db, &trade).await {
+        error!("couldn't record trade of {}: {}", possession.id, e);
+    }
+    let last = LastTrade {
+        archetype_handle: trade.archetype_handle,
+        price,
+        at: trade.at,
+    };
+    if let Err(e) = records::put(db, &last).await {
+        error!("couldn't record last price of {}: {}", possession.name, e);
+    }
+}
+
+/// What each archetype that's been traded last went for.
+pub async fn last_prices(db: &dyn Store) -> Result<HashMap<ArchetypeHandle, u64>, String> {
+    Ok(records::all::<LastTrade>(db)
+        .await?
+        .into_iter()
+        .map(|t| (t.archetype_handle, t.price))
+        .collect())
+}
+
+/// Every recorded trade, optionally only those for one archetype, oldest first.
+pub async fn trades(
+    db: &dyn Store,
+    archetype_handle: Option<ArchetypeHandle>,
+) -> Result<Vec<Trade>, String> {
+    let mut trades: Vec<Trade> = records::all::<Trade>(db)
+        .await?
+        .into_iter()
+        .filter(|t| archetype_handle.map_or(true, |ah| t.archetype_handle == ah))
+        .collect();
+    trades.sort_by_key(|t| t.at);
+    Ok(trades)
+}
+
+/// How many of the most recent trades a sparkline shows.
+pub const SPARKLINE_LEN: usize = 20;
+
+/// What the trades of one archetype say about what it's worth.
+#[derive(Debug, Clone)]
+pub struct PriceHistory {
+    pub last_price: u64,
+    pub median_price: u64,
+    /// How many were sold in the last week.
+    pub week_volume: usize,
+    /// The prices of the last few trades, drawn out with block characters.
+    pub sparkline: String,
+}
+
+/// Sums up trades, which should all be of one archetype and oldest first.
+pub fn price_history(trades: &[Trade]) -> Option<PriceHistory> {
+    let last_price = trades.last()?.price;
+
+    let mut prices: Vec<u64> = trades.iter().map(|t| t.price).collect();
+    prices.sort_unstable();
+    let mid = prices.len() / 2;
+    let median_price = if prices.len() % 2 == 0 {
+        (prices[mid - 1] + prices[mid]) / 2
+    } else {
+        prices[mid]
+    };
+
+    let week = std::time::Duration::from_secs(7 * 24 * 60 * 60);
+    let week_volume = trades
+        .iter()
+        .filter(|t| t.at.elapsed().map_or(true, |e| e <= week))
+        .count();
+
+    let recent: Vec<u64> = trades
+        .iter()
+        .rev()
+        .take(SPARKLINE_LEN)
+        .rev()
+        .map(|t| t.price)
+        .collect();
+
+    Some(PriceHistory {
+        last_price,
+        median_price,
+        week_volume,
+        sparkline: sparkline(&recent),
+    })
+}
+
+fn sparkline(prices: &[u64]) -> String {
+    const BARS: &[char] = &['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let low = prices.iter().copied().min().unwrap_or(0);
+    let high = prices.iter().copied().max().unwrap_or(0);
+    prices
+        .iter()
+        .map(|&p| match high - low {
+            0 => BARS[BARS.len() / 2],
+            range => BARS[((p - low) * (BARS.len() as u64 - 1) / range) as usize],
+        })
+        .collect()
+}
+
+/// The most of one thing that can be sold or bought in a single batch.
+pub const MAX_BATCH: usize = 100;
+
+/// Whichever of someone's possessions of an archetype they could put up for sale right now.
+pub async fn unlisted(
+    db: &dyn Store,
+    user_id: &str,
+    archetype_handle: ArchetypeHandle,
+) -> Result<Vec<Possession>, String> {
+    let auctioned: HashSet<uuid::Uuid> = crate::auction::auctions(db)
+        .await?
+        .into_iter()
+        .map(|a| a.key.id)
+        .collect();
+    Ok(Hacksteader::from_db(db, user_id.to_string())
+        .await?
+        .inventory
+        .into_iter()
+        .filter(|p| {
+            p.archetype_handle == archetype_handle && p.sale.is_none() && !auctioned.contains(&p.id)
         })
         .collect())
 }
 
-pub async fn place_on_market(
-    db: &DynamoDbClient,
-    key: Key,
+/// Bills someone a single fee for putting a batch of the same `Misc` item up for sale
+/// at `price` each. They go on the market once it's paid, returning the id of the invoice.
+pub async fn invoice_batch_listing_fee(
+    user_id: &str,
+    possessions: &[Possession],
     price: u64,
-    name: String,
-) -> Result<(), String> {
-    println!("putting {} on the market", key.id);
+    duration: Option<Duration>,
+) -> Result<String, String> {
+    let first = possessions
+        .first()
+        .ok_or_else(|| "there's nothing to sell".to_string())?;
+    if possessions.len() > MAX_BATCH {
+        return Err(format!("you can only sell {} at a time", MAX_BATCH));
+    }
+    if archetype_category(first.archetype_handle) != Category::Misc {
+        return Err(format!("{}s have to be sold one at a time", first.name));
+    }
+    if let Some(p) = possessions.iter().find(|p| {
+        p.archetype_handle != first.archetype_handle || p.steader != user_id || p.sale.is_some()
+    }) {
+        return Err(format!("{} can't be sold with the rest", p.name));
+    }
 
-    db.update_item(rusoto_dynamodb::UpdateItemInput {
-        key: key.clone().into_item(),
-        expression_attribute_values: Some(
-            [
-                (
-                    ":sale_price".to_string(),
-                    AttributeValue {
-                        n: Some(price.to_string()),
-                        ..Default::default()
-                    },
-                ),
-                (
-                    ":new_name".to_string(),
-                    AttributeValue {
-                        s: Some(name),
-                        ..Default::default()
-                    },
-                ),
-            ]
-            .iter()
-            .cloned()
-            .collect(),
+    let count = possessions.len() as u64;
+    banker::invoice(
+        user_id,
+        price / 20 * count,
+        &format!(
+            "hackmarket fees for selling {} {} at {}hn each",
+            count, first.name, price
         ),
-        update_expression: Some("SET price = :sale_price, market_name = :new_name".to_string()),
-        table_name: hcor::TABLE_NAME.to_string(),
-        ..Default::default()
-    })
+        banker::InvoiceIntent::BatchMarketFee {
+            keys: possessions.iter().map(|p| p.key()).collect(),
+            price,
+            market_name: first.name.clone(),
+            duration,
+        },
+    )
     .await
-    .map_err(|e| dbg!(format!("Couldn't place {} on market: {}", key.id, e)))?;
+}
+
+/// Puts a batch up for sale once its fee's been paid. Anything that's sold, given away
+/// or put up some other way in the meantime is left out, and its part of the fee given back.
+pub async fn list_batch(
+    db: &dyn Store,
+    seller: String,
+    keys: Vec<Key>,
+    price: u64,
+    name: String,
+    duration: Option<Duration>,
+) -> Result<(), String> {
+    let auctioned: HashSet<uuid::Uuid> = crate::auction::auctions(db)
+        .await?
+        .into_iter()
+        .map(|a| a.key.id)
+        .collect();
+
+    let (mut listed, mut filled, mut left_out) = (0, 0, 0);
+    for key in keys {
+        let possession = match db.possession(key).await {
+            Ok(p) if p.steader == seller && p.sale.is_none() && !auctioned.contains(&p.id) => p,
+            Ok(_) => {
+                left_out += 1;
+                continue;
+            }
+            Err(e) => {
+                warn!("leaving {} out of a batch listing: {}", key.id, e);
+                left_out += 1;
+                continue;
+            }
+        };
+
+        // if someone's already looking to buy it, it goes straight to them
+        if fill_buy_order(db, &possession, price).await? {
+            filled += 1;
+            continue;
+        }
+        place_on_market(db, key, seller.clone(), price, name.clone(), duration).await?;
+        listed += 1;
+        bus::publish(GameEvent::Listing {
+            seller: seller.clone(),
+            item: name.clone(),
+            price,
+        });
+    }
+
+    if left_out > 0 && price >= 20 {
+        banker::pay(
+            seller.clone(),
+            price / 20 * left_out,
+            format!(
+                "{} of the {} you tried to sell no longer being yours to sell",
+                left_out, name
+            ),
+        )
+        .await?;
+    }
 
+    if listed > 0 {
+        log_blocks(
+            format!(
+                "{} {}s have gone up for sale for {} HN each!",
+                listed, name, price
+            ),
+            vec![
+                section(mrkdwn(format!(
+                    "*{}* *{}s* have gone up for sale! \
+                    {} is selling them on the hackmarket for *{} HN* each!",
+                    listed,
+                    name,
+                    mention(&seller),
+                    price
+                )))
+                .accessory(image(
+                    format!(
+                        "http://{}/gotchi/img/{}/{}.png",
+                        *URL,
+                        Category::Misc,
+                        filify(&name)
+                    ),
+                    "Hackpheus sitting on bags of money!",
+                ))
+                .into(),
+                comment("BYE EM BY DA BUSHEL"),
+            ],
+        )
+        .await?;
+    }
+
+    info!(
+        "batch listing of {} for {}: {} listed, {} sold to buy orders, {} left out",
+        name, seller, listed, filled, left_out
+    );
     Ok(())
 }
 
-pub async fn take_off_market(db: &DynamoDbClient, key: Key) -> Result<(), String> {
-    println!("taking {} off the market", key.id);
+/// `name`, or more than one of them.
+fn plural(count: usize, name: &str) -> String {
+    match count {
+        1 => name.to_string(),
+        _ => format!("{}s", name),
+    }
+}
 
-    db.update_item(rusoto_dynamodb::UpdateItemInput {
-        key: key.clone().into_item(),
-        update_expression: Some("REMOVE price, market_name".to_string()),
-        table_name: hcor::TABLE_NAME.to_string(),
-        ..Default::default()
-    })
-    .await
-    .map_err(|e| dbg!(format!("Couldn't remove {} from market: {}", key.id, e)))?;
+/// How many of something there are, like "1 apple" or "3 apples".
+fn count_of(count: usize, name: &str) -> String {
+    format!("{} {}", count, plural(count, name))
+}
+
+/// One thing someone's buying as part of a batch, as it was listed when they were invoiced for it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchItem {
+    pub key: Key,
+    pub price: u64,
+    pub seller: String,
+}
+
+/// The `quantity` cheapest of an archetype on the market, leaving out whatever `buyer` is selling.
+pub async fn cheapest_listings(
+    db: &dyn Store,
+    archetype_handle: ArchetypeHandle,
+    buyer: &str,
+    quantity: usize,
+) -> Result<Vec<BatchItem>, String> {
+    let mut listings: Vec<(Sale, Possession)> = db
+        .market_listings(archetype_category(archetype_handle))
+        .await?
+        .into_iter()
+        .filter(|(_, p)| p.archetype_handle == archetype_handle && p.steader != buyer)
+        .collect();
+    // the price index gives them back cheapest first already, but not every store has one
+    listings.sort_by_key(|(sale, _)| sale.price);
+
+    Ok(listings
+        .into_iter()
+        .take(quantity)
+        .map(|(sale, p)| BatchItem {
+            key: p.key(),
+            price: sale.price,
+            seller: p.steader,
+        })
+        .collect())
+}
+
+/// Bills someone for the `quantity` cheapest of an archetype on the market, all at once.
+/// Returns the id of the invoice and how many it's for, which may be fewer than they asked.
+pub async fn invoice_batch_purchase(
+    db: &dyn Store,
+    buyer: &str,
+    archetype_handle: ArchetypeHandle,
+    quantity: usize,
+) -> Result<(String, usize), String> {
+    let name = archetype_name(archetype_handle);
+    if quantity == 0 || quantity > MAX_BATCH {
+        return Err(format!("you can buy 1 to {} at a time", MAX_BATCH));
+    }
+    let items = cheapest_listings(db, archetype_handle, buyer, quantity).await?;
+    if items.is_empty() {
+        return Err(format!("nobody else is selling any {}s", name));
+    }
+
+    let count = items.len();
+    let total: u64 = items.iter().map(|i| i.price).sum();
+    let invoice = banker::invoice(
+        buyer,
+        total,
+        &format!(
+            "hackmarket purchase buying {} {} for {}hn in total",
+            count, name, total
+        ),
+        banker::InvoiceIntent::BatchPurchase {
+            archetype_handle,
+            items,
+        },
+    )
+    .await?;
+    Ok((invoice, count))
+}
+
+/// Hands over everything in a batch that was paid for. Anything that's been sold or
+/// taken down since is given back to the buyer instead, so they only pay for what they get.
+pub async fn buy_batch(
+    db: &dyn Store,
+    buyer: String,
+    archetype_handle: ArchetypeHandle,
+    items: Vec<BatchItem>,
+) -> Result<(), String> {
+    let name = archetype_name(archetype_handle);
+    let wanted = items.len();
+
+    let mut bought: Vec<(Possession, u64)> = Vec::new();
+    let mut payouts: Vec<banker::OwedPayment> = Vec::new();
+    let mut refund = 0;
+    for item in items {
+        let still_listed = match db.possession(item.key).await {
+            Ok(p) => {
+                let listed = p.steader == item.seller
+                    && p.sale.as_ref().map_or(false, |s| s.price == item.price);
+                Some(p).filter(|_| listed)
+            }
+            Err(e) => {
+                warn!("leaving {} out of a batch purchase: {}", item.key.id, e);
+                None
+            }
+        };
+        let possession = match still_listed {
+            Some(p) => p,
+            None => {
+                refund += item.price;
+                continue;
+            }
+        };
+
+        // it only changes hands if nobody's bought or relisted it since we looked,
+        // and the seller's owed for it in the same write
+        let owed =
+            banker::OwedPayment::new(&item.seller, item.price, format!("sale of your {}s", name));
+        match db
+            .transfer_if(
+                vec![Transfer {
+                    key: item.key,
+                    from: item.seller.clone(),
+                    price: Some(item.price),
+                    to: buyer.clone(),
+                    acquisition: possess::Acquisition::Purchase { price: item.price },
+                }],
+                vec![records::create(&owed)?],
+            )
+            .await
+        {
+            Ok(true) => {
+                bought.push((possession, item.price));
+                payouts.push(owed);
+            }
+            Ok(false) => refund += item.price,
+            Err(e) => {
+                error!(
+                    "couldn't hand over {} in a batch purchase: {}",
+                    item.key.id, e
+                );
+                refund += item.price;
+            }
+        }
+    }
+
+    // everyone who sold some gets paid once for all of theirs
+    banker::pay_owed(db, payouts).await;
+    if refund > 0 {
+        banker::owe(
+            db,
+            &buyer,
+            refund,
+            format!(
+                "{} you tried to buy being sold to someone else first",
+                count_of(wanted - bought.len(), name)
+            ),
+        )
+        .await?;
+    }
+
+    let spent: u64 = bought.iter().map(|(_, price)| price).sum();
+    for (possession, price) in &bought {
+        record_trade(db, possession, &buyer, *price).await;
+        bus::publish(GameEvent::Sale {
+            buyer: buyer.clone(),
+            seller: possession.steader.clone(),
+            item: name.to_string(),
+            price: *price,
+        });
+    }
+
+    if bought.is_empty() {
+        return dm_blocks(
+            buyer,
+            "Sorry, you couldn't buy those! Your HN has been refunded.".to_string(),
+            vec![section(mrkdwn(format!(
+                "All of the {}s you tried to buy were sold to someone else first, \
+                so your HN has been refunded.",
+                name
+            )))
+            .into()],
+        )
+        .await;
+    }
+
+    futures::try_join!(
+        log_blocks(
+            format!(
+                "{} purchased {} on hackmarket for {} HN!",
+                mention(&buyer),
+                count_of(bought.len(), name),
+                spent
+            ),
+            vec![
+                section(mrkdwn(format!(
+                    "{} bought *{}* *{}* on hackmarket for *{} HN* in total!",
+                    mention(&buyer),
+                    bought.len(),
+                    plural(bought.len(), name),
+                    spent
+                )))
+                .into(),
+                comment("U NO GET 2 BYE DEM"),
+            ],
+        ),
+        dm_blocks(
+            buyer.clone(),
+            format!("You bought {}!", count_of(bought.len(), name)),
+            vec![section(mrkdwn(format!(
+                "You bought *{}* of the *{}* *{}* you wanted for *{} HN*.{}",
+                bought.len(),
+                wanted,
+                plural(wanted, name),
+                spent,
+                if refund > 0 {
+                    format!(
+                        " The rest sold before you could get them, \
+                        so you've been refunded *{} HN*.",
+                        refund
+                    )
+                } else {
+                    String::new()
+                }
+            )))
+            .into()],
+        ),
+    )?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::banker::{paying_through, OwedPayment, Payment, RecordingPayer};
+    use crate::chat::{recorded, Sent};
+    use crate::store::{Batch, MemoryStore};
+
+    const SELLER: &str = "U1";
+    const BUYER: &str = "U2";
+
+    /// Puts something up for sale by `seller` for `price`, as a buyer would've seen it.
+    async fn listed(db: &dyn Store, seller: &str, price: u64) -> BatchItem {
+        let p = Possession::new(0, possess::Owner::farmer(seller.to_string()));
+        db.write(Batch {
+            possessions: vec![p.clone()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        db.place_on_market(p.key(), price, p.name.clone())
+            .await
+            .unwrap();
+        BatchItem {
+            key: p.key(),
+            price,
+            seller: seller.to_string(),
+        }
+    }
+
+    /// Has the buyer buy `items`, returning what was paid and sent.
+    async fn buy(
+        db: &dyn Store,
+        items: Vec<BatchItem>,
+        payer: RecordingPayer,
+    ) -> (Vec<Payment>, Vec<Sent>) {
+        let payer = payer.leak();
+        let (bought, sent) = recorded(paying_through(
+            payer,
+            buy_batch(db, BUYER.to_string(), 0, items),
+        ))
+        .await;
+        bought.unwrap();
+        (payer.paid(), sent)
+    }
+
+    async fn owner(db: &dyn Store, item: &BatchItem) -> String {
+        db.possession(item.key).await.unwrap().steader
+    }
+
+    fn payment(to: &str, amount: u64, reason: String) -> Payment {
+        Payment {
+            to: to.to_string(),
+            amount,
+            reason,
+        }
+    }
+
+    #[rocket::async_test]
+    async fn sales_are_sent_to_the_seller_the_buyer_and_the_market() {
+        let db = MemoryStore::default();
+        let item = listed(&db, SELLER, 10).await;
+        let name = archetype_name(0);
+
+        let (paid, sent) = buy(&db, vec![item.clone()], RecordingPayer::default()).await;
+
+        assert_eq!(
+            paid,
+            vec![payment(SELLER, 10, format!("sale of your {}s", name))]
+        );
+        assert_eq!(owner(&db, &item).await, BUYER);
+        assert_eq!(sent.len(), 3);
+        assert!(sent.iter().any(|s| matches!(
+            s,
+            Sent::Dm { user_id, notif_msg, .. }
+                if user_id == SELLER && notif_msg.starts_with("I've just sent you 10 HN")
+        )));
+        assert!(sent.iter().any(|s| matches!(
+            s,
+            Sent::Log { channel: Channel::Market, notif_msg, .. }
+                if *notif_msg == format!("@U2 purchased 1 {} on hackmarket for 10 HN!", name)
+        )));
+        assert!(sent.iter().any(|s| matches!(
+            s,
+            Sent::Dm { user_id, notif_msg, .. }
+                if user_id == BUYER && *notif_msg == format!("You bought 1 {}!", name)
+        )));
+        assert!(records::all::<OwedPayment>(&db).await.unwrap().is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn whatever_sold_first_is_refunded() {
+        let db = MemoryStore::default();
+        let name = archetype_name(0);
+        let (a, b) = (listed(&db, SELLER, 10).await, listed(&db, SELLER, 10).await);
+        let gone = listed(&db, "U3", 15).await;
+        db.transfer_possession(
+            "U4".to_string(),
+            possess::Acquisition::Purchase { price: 15 },
+            gone.key,
+            true,
+        )
+        .await
+        .unwrap();
+
+        let (paid, sent) = buy(
+            &db,
+            vec![a.clone(), b.clone(), gone.clone()],
+            RecordingPayer::default(),
+        )
+        .await;
+
+        // the seller's paid once for both, and the buyer gets back what the third cost
+        assert_eq!(
+            paid,
+            vec![
+                payment(SELLER, 20, format!("sale of your {}s", name)),
+                payment(
+                    BUYER,
+                    15,
+                    format!(
+                        "1 {} you tried to buy being sold to someone else first",
+                        name
+                    )
+                ),
+            ]
+        );
+        assert_eq!(owner(&db, &a).await, BUYER);
+        assert_eq!(owner(&db, &b).await, BUYER);
+        assert_eq!(owner(&db, &gone).await, "U4");
+        assert!(sent.iter().any(|s| matches!(
+            s,
+            Sent::Dm { user_id, notif_msg, .. }
+                if user_id == BUYER && *notif_msg == format!("You bought 2 {}s!", name)
+        )));
+        assert!(records::all::<OwedPayment>(&db).await.unwrap().is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn losing_every_race_refunds_everything() {
+        let db = MemoryStore::default();
+        let item = listed(&db, SELLER, 10).await;
+        // relisted for more after the buyer was invoiced
+        db.place_on_market(item.key, 12, archetype_name(0).to_string())
+            .await
+            .unwrap();
+
+        let (paid, sent) = buy(&db, vec![item.clone()], RecordingPayer::default()).await;
+
+        assert_eq!(paid.len(), 1);
+        assert_eq!((paid[0].to.as_str(), paid[0].amount), (BUYER, 10));
+        assert_eq!(owner(&db, &item).await, SELLER);
+        assert!(sent.iter().any(|s| matches!(
+            s,
+            Sent::Dm { user_id, notif_msg, .. }
+                if user_id == BUYER && notif_msg.starts_with("Sorry, you couldn't buy those!")
+        )));
+    }
+
+    #[rocket::async_test]
+    async fn payouts_hn_turns_down_are_kept_until_they_go_through() {
+        let db = MemoryStore::default();
+        let item = listed(&db, SELLER, 10).await;
+
+        let (paid, _) = buy(
+            &db,
+            vec![item.clone()],
+            RecordingPayer {
+                refusing: vec![SELLER.to_string()],
+                ..Default::default()
+            },
+        )
+        .await;
+        assert!(paid.is_empty());
+        assert_eq!(owner(&db, &item).await, BUYER);
+
+        let owed = records::all::<OwedPayment>(&db).await.unwrap();
+        assert_eq!(owed.len(), 1);
+        assert_eq!((owed[0].to.as_str(), owed[0].amount), (SELLER, 10));
+
+        let payer = RecordingPayer::default().leak();
+        recorded(paying_through(payer, banker::pay_owed(&db, owed))).await;
+        assert_eq!(payer.paid().len(), 1);
+        assert!(records::all::<OwedPayment>(&db).await.unwrap().is_empty());
+    }
+
+    /// Has the buyer open a buy order, returning it as it was saved and who was paid what.
+    async fn ordered(
+        db: &dyn Store,
+        quantity: u64,
+        max_price: u64,
+    ) -> (BuyOrder, Vec<(String, u64)>, Vec<Sent>) {
+        std::env::set_var("URL", "localhost");
+        let order = BuyOrder {
+            id: uuid::Uuid::new_v4(),
+            buyer: BUYER.to_string(),
+            archetype_handle: 0,
+            max_price,
+            quantity,
+            placed: SystemTime::now(),
+        };
+        let payer = RecordingPayer::default().leak();
+        let (res, sent) = recorded(paying_through(payer, open_buy_order(db, order.clone()))).await;
+        res.unwrap();
+        let paid = payer.paid().into_iter().map(|p| (p.to, p.amount)).collect();
+        (order, paid, sent)
+    }
+
+    /// Something of the seller's that isn't on the market.
+    async fn made(db: &dyn Store) -> Possession {
+        let p = Possession::new(0, possess::Owner::farmer(SELLER.to_string()));
+        db.write(Batch {
+            possessions: vec![p.clone()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        p
+    }
+
+    async fn remaining(db: &dyn Store, order: &BuyOrder) -> Option<u64> {
+        records::get::<BuyOrder>(db, &order.id())
+            .await
+            .unwrap()
+            .map(|o| o.quantity)
+    }
+
+    #[rocket::async_test]
+    async fn buy_orders_are_filled_from_the_market_as_theyre_opened() {
+        let db = MemoryStore::default();
+        let item = listed(&db, SELLER, 8).await;
+
+        let (order, paid, sent) = ordered(&db, 2, 10).await;
+
+        assert_eq!(owner(&db, &item).await, BUYER);
+        assert_eq!(paid, vec![(SELLER.to_string(), 8), (BUYER.to_string(), 2)]);
+        assert_eq!(remaining(&db, &order).await, Some(1));
+        assert!(sent.iter().any(|s| matches!(
+            s,
+            Sent::Log { channel: Channel::Market, notif_msg, .. }
+                if notif_msg.contains("wants 1")
+        )));
+        assert!(records::all::<OwedPayment>(&db).await.unwrap().is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn buy_orders_take_one_at_a_time_until_theyre_filled() {
+        let db = MemoryStore::default();
+        let (order, paid, _) = ordered(&db, 2, 10).await;
+        assert!(paid.is_empty());
+        assert_eq!(remaining(&db, &order).await, Some(2));
+
+        let payer = RecordingPayer::default().leak();
+        for left in &[Some(1), None] {
+            let p = made(&db).await;
+            let (sold, _) = recorded(paying_through(payer, fill_buy_order(&db, &p, 10))).await;
+            assert!(sold.unwrap());
+            assert_eq!(db.possession(p.key()).await.unwrap().steader, BUYER);
+            assert_eq!(remaining(&db, &order).await, *left);
+        }
+        let p = made(&db).await;
+        let (sold, _) = recorded(paying_through(payer, fill_buy_order(&db, &p, 10))).await;
+        assert!(!sold.unwrap());
+
+        let paid: Vec<_> = payer.paid().into_iter().map(|p| (p.to, p.amount)).collect();
+        assert_eq!(
+            paid,
+            vec![(SELLER.to_string(), 10), (SELLER.to_string(), 10)]
+        );
+    }
+
+    #[rocket::async_test]
+    async fn cancelling_a_buy_order_refunds_what_is_left_of_it_once() {
+        let db = MemoryStore::default();
+        let (order, _, _) = ordered(&db, 3, 10).await;
+
+        let payer = RecordingPayer::default().leak();
+        let (res, _) = recorded(paying_through(payer, async {
+            assert!(cancel_buy_order(&db, &order.id(), "U3").await.is_err());
+            cancel_buy_order(&db, &order.id(), BUYER).await?;
+            assert!(cancel_buy_order(&db, &order.id(), BUYER).await.is_err());
+            Ok::<(), String>(())
+        }))
+        .await;
+        res.unwrap();
+
+        let paid: Vec<_> = payer.paid().into_iter().map(|p| (p.to, p.amount)).collect();
+        assert_eq!(paid, vec![(BUYER.to_string(), 30)]);
+        assert_eq!(remaining(&db, &order).await, None);
+        assert!(records::all::<OwedPayment>(&db).await.unwrap().is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn expired_listings_come_down_once_even_past_ones_that_cant() {
+        let db = MemoryStore::default();
+        let gone = Possession::new(0, possess::Owner::farmer(SELLER.to_string()));
+        records::put(
+            &db,
+            &Listing {
+                key: gone.key(),
+                seller: SELLER.to_string(),
+                price: 10,
+                placed: SystemTime::now(),
+                expires: Some(SystemTime::now()),
+            },
+        )
+        .await
+        .unwrap();
+        let item = listed(&db, SELLER, 10).await;
+        records::put(
+            &db,
+            &Listing {
+                key: item.key,
+                seller: SELLER.to_string(),
+                price: 10,
+                placed: SystemTime::now(),
+                expires: Some(SystemTime::now()),
+            },
+        )
+        .await
+        .unwrap();
+
+        let (swept, sent) = recorded(sweep_expired_listings(&db)).await;
+        swept.unwrap();
+        assert!(db.possession(item.key).await.unwrap().sale.is_none());
+        assert!(records::all::<Listing>(&db).await.unwrap().is_empty());
+        assert!(matches!(
+            &sent[..],
+            [Sent::Dm { user_id, .. }] if user_id == SELLER
+        ));
+
+        let (swept, sent) = recorded(sweep_expired_listings(&db)).await;
+        swept.unwrap();
+        assert!(sent.is_empty());
+    }
+}
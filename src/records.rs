@@ -0,0 +1,91 @@
+//! Bookkeeping that doesn't fit into any of hcor's item categories
+//! is kept by the store as JSON, sorted by its `kind` and `id`.
+use crate::store::{RecordSwap, Store};
+use log::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Something we keep track of alongside the game's items.
+pub trait Record: Serialize + DeserializeOwned {
+    /// Records of different kinds never share ids.
+    const KIND: &'static str;
+
+    fn id(&self) -> String;
+}
+
+fn to_data<R: Record>(record: &R) -> Result<String, String> {
+    serde_json::to_string(record)
+        .map_err(|e| format!("couldn't serialize {} record: {}", R::KIND, e))
+}
+
+fn parse_record<R: Record>(data: &str) -> Result<R, String> {
+    serde_json::from_str(data).map_err(|e| format!("couldn't parse {} record: {}", R::KIND, e))
+}
+
+/// Writes a record only if no record of the same kind has its id yet.
+/// Returns false if one was already there, in which case nothing is written.
+pub async fn insert<R: Record>(db: &dyn Store, record: &R) -> Result<bool, String> {
+    db.insert_record(R::KIND, &record.id(), to_data(record)?)
+        .await
+}
+
+/// Writes a record, replacing any record of the same kind with the same id.
+pub async fn put<R: Record>(db: &dyn Store, record: &R) -> Result<(), String> {
+    db.put_record(R::KIND, &record.id(), to_data(record)?).await
+}
+
+pub async fn get<R: Record>(db: &dyn Store, id: &str) -> Result<Option<R>, String> {
+    db.get_record(R::KIND, id)
+        .await?
+        .map(|data| parse_record(&data))
+        .transpose()
+}
+
+/// Deletes a record, returning what was there if anything was.
+///
+/// Only one of any number of callers racing to remove the same record gets it back,
+/// so whoever removes a record is the one who acts on it: a refund or payout that's
+/// only made after getting a record back from here can't be made twice.
+pub async fn remove<R: Record>(db: &dyn Store, id: &str) -> Result<Option<R>, String> {
+    db.remove_record(R::KIND, id)
+        .await?
+        .map(|data| parse_record(&data))
+        .transpose()
+}
+
+/// A change to a record that should only be made if it's still `old`,
+/// replacing it with `new` or removing it if that's None. See `Store::transfer_if`.
+pub fn swap<R: Record>(old: &R, new: Option<&R>) -> Result<RecordSwap, String> {
+    Ok(RecordSwap {
+        kind: R::KIND.to_string(),
+        id: old.id(),
+        old: Some(to_data(old)?),
+        new: new.map(to_data).transpose()?,
+    })
+}
+
+/// A change that writes a record as long as there isn't one of its kind with its id yet,
+/// so that it can be written in the same `Store::transfer_if` as whatever it's about.
+pub fn create<R: Record>(new: &R) -> Result<RecordSwap, String> {
+    Ok(RecordSwap {
+        kind: R::KIND.to_string(),
+        id: new.id(),
+        old: None,
+        new: Some(to_data(new)?),
+    })
+}
+
+/// Every record of a given kind.
+pub async fn all<R: Record>(db: &dyn Store) -> Result<Vec<R>, String> {
+    Ok(db
+        .records(R::KIND)
+        .await?
+        .iter()
+        .filter_map(|data| match parse_record(data) {
+            Ok(r) => Some(r),
+            Err(e) => {
+                error!("{}", e);
+                None
+            }
+        })
+        .collect())
+}
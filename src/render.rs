@@ -0,0 +1,324 @@
+//! A picture of someone's whole hackstead, pieced together out of the art in `img/`.
+//!
+//! Each tile gets its plant's current art over a patch of dirt, with bars for how far
+//! along its yield and craft are and the icons of whatever effects are on it.
+//! The steader's gotchis stand around the edge of the land, and if there are more of them
+//! than fit, the last spot says how many more there are instead.
+use crate::filify;
+use crate::hacksteader::{Hacksteader, Plant, Tile};
+use crate::store::store;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, ImageOutputFormat, Rgba, RgbaImage};
+use log::*;
+use rocket::get;
+use rocket::http::ContentType;
+use rocket::response::content::Content;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+const TILE: u32 = 128;
+const PAD: u32 = 8;
+const BAR: u32 = 8;
+const ICON: u32 = 24;
+const GOTCHI: u32 = 48;
+const COLUMNS: usize = 4;
+
+const CELL_WIDTH: u32 = TILE + 2 * PAD;
+const CELL_HEIGHT: u32 = PAD + TILE + 2 * (PAD + BAR) + PAD + ICON + PAD;
+/// The border the gotchis stand in.
+const MARGIN: u32 = GOTCHI + 2 * PAD;
+
+const GRASS: Rgba<u8> = Rgba([110, 170, 90, 255]);
+const BAR_EMPTY: Rgba<u8> = Rgba([60, 45, 35, 255]);
+const YIELD_BAR: Rgba<u8> = Rgba([240, 200, 60, 255]);
+const CRAFT_BAR: Rgba<u8> = Rgba([90, 160, 230, 255]);
+const MARKER: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+/// Just enough of a font to say how many gotchis didn't fit: 3x5 glyphs,
+/// one row to a byte, from the left in the lowest three bits.
+const GLYPH_WIDTH: u32 = 3;
+const GLYPH_HEIGHT: u32 = 5;
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT as usize] {
+    match c {
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        _ => [0; GLYPH_HEIGHT as usize],
+    }
+}
+
+lazy_static::lazy_static! {
+    /// Art that's already been read off of the disk and scaled, by path and size.
+    static ref ART: Mutex<HashMap<(String, u32), Option<RgbaImage>>> = Default::default();
+}
+
+/// The art at `./img/{folder}/{name}`, scaled to a `size` pixel square.
+/// Missing art is logged and left out of the picture rather than failing the whole thing.
+fn art(folder: &str, name: &str, size: u32) -> Option<RgbaImage> {
+    let path = format!("./img/{}/{}", folder, name);
+    ART.lock()
+        .unwrap()
+        .entry((path.clone(), size))
+        .or_insert_with(|| match image::open(&path) {
+            Ok(img) => Some(imageops::resize(
+                &img.to_rgba8(),
+                size,
+                size,
+                FilterType::Nearest,
+            )),
+            Err(e) => {
+                warn!("couldn't load {} for a render: {}", path, e);
+                None
+            }
+        })
+        .clone()
+}
+
+fn fill(canvas: &mut RgbaImage, x: u32, y: u32, w: u32, h: u32, color: Rgba<u8>) {
+    for px in x..(x + w).min(canvas.width()) {
+        for py in y..(y + h).min(canvas.height()) {
+            canvas.put_pixel(px, py, color);
+        }
+    }
+}
+
+fn progress_bar(canvas: &mut RgbaImage, x: u32, y: u32, progress: f32, color: Rgba<u8>) {
+    fill(canvas, x, y, TILE, BAR, BAR_EMPTY);
+    let done = (TILE as f32 * progress.max(0.0).min(1.0)) as u32;
+    fill(canvas, x, y, done, BAR, color);
+}
+
+/// How far along this plant's yield and craft are, if it has either going.
+fn progress(plant: &Plant, tile: &Tile, hs: &Hacksteader) -> (Option<f32>, Option<f32>) {
+    let neighbor_bonuses = hs
+        .neighbor_bonuses()
+        .bonuses_for_plant(tile.id, plant.archetype_handle);
+    let sum = plant.advancements_sum(neighbor_bonuses.iter());
+
+    let yielding = match (sum.yields.is_empty(), plant.base_yield_duration) {
+        (false, Some(base_yield_duration)) => Some(1.0 - plant.until_yield / base_yield_duration),
+        _ => None,
+    };
+    let crafting = match (plant.craft.as_ref(), plant.current_recipe()) {
+        (Some(craft), Some(recipe)) => Some(1.0 - craft.until_finish / recipe.time),
+        _ => None,
+    };
+    (yielding, crafting)
+}
+
+fn draw_tile(canvas: &mut RgbaImage, x: u32, y: u32, tile: &Tile, hs: &Hacksteader) {
+    let (x, y) = (x + PAD, y + PAD);
+    if let Some(dirt) = art("icon", "dirt.png", TILE) {
+        imageops::overlay(canvas, &dirt, x, y);
+    }
+
+    let plant = match &tile.plant {
+        Some(plant) => plant,
+        None => return,
+    };
+    let ca = plant.current_advancement();
+    if let Some(plant_art) = art("plant", &format!("{}.gif", filify(&ca.art)), TILE) {
+        imageops::overlay(canvas, &plant_art, x, y);
+    }
+
+    let (yielding, crafting) = progress(plant, tile, hs);
+    let bars_y = y + TILE + PAD;
+    if let Some(p) = yielding {
+        progress_bar(canvas, x, bars_y, p, YIELD_BAR);
+    }
+    if let Some(p) = crafting {
+        progress_bar(canvas, x, bars_y + BAR + PAD, p, CRAFT_BAR);
+    }
+
+    let icons_y = bars_y + 2 * (BAR + PAD);
+    for (i, effect) in plant
+        .effects
+        .iter()
+        .take((TILE / ICON) as usize)
+        .enumerate()
+    {
+        if let Some(icon) = art("misc", &format!("{}.png", filify(&effect.name)), ICON) {
+            imageops::overlay(canvas, &icon, x + i as u32 * ICON, icons_y);
+        }
+    }
+}
+
+/// Writes `text` as big as it'll go across a `GOTCHI` pixel square at `x`, `y`.
+fn marker(canvas: &mut RgbaImage, x: u32, y: u32, text: &str) {
+    fill(canvas, x, y, GOTCHI, GOTCHI, BAR_EMPTY);
+
+    let chars = text.chars().count() as u32;
+    let scale = (GOTCHI / (chars * (GLYPH_WIDTH + 1))).max(1);
+    let (width, height) = (
+        chars * (GLYPH_WIDTH + 1) * scale - scale,
+        GLYPH_HEIGHT * scale,
+    );
+    let left = x + GOTCHI.saturating_sub(width) / 2;
+    let top = y + GOTCHI.saturating_sub(height) / 2;
+    for (i, c) in text.chars().enumerate() {
+        let glyph_x = left + i as u32 * (GLYPH_WIDTH + 1) * scale;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - col)) != 0 {
+                    let (px, py) = (glyph_x + col * scale, top + row as u32 * scale);
+                    fill(canvas, px, py, scale, scale, MARKER);
+                }
+            }
+        }
+    }
+}
+
+/// Where gotchis can stand: clockwise around the border, starting in the top left corner.
+fn gotchi_spots(width: u32, height: u32) -> Vec<(u32, u32)> {
+    let step = GOTCHI + PAD;
+    let across = (width - PAD) / step;
+    let down = (height - PAD) / step;
+    let far_x = width - step;
+    let far_y = height - step;
+
+    // each side stops one short of the next corner, which the next side starts on
+    let top = (0..across - 1).map(|i| (PAD + i * step, PAD));
+    let right = (0..down - 1).map(|i| (far_x, PAD + i * step));
+    let bottom = (0..across - 1).map(|i| (far_x - i * step, far_y));
+    let left = (0..down - 1).map(|i| (PAD, far_y - i * step));
+    top.chain(right).chain(bottom).chain(left).collect()
+}
+
+/// Draws the hackstead as a PNG.
+pub fn render(hs: &Hacksteader) -> Result<Vec<u8>, String> {
+    let columns = hs.land.len().min(COLUMNS).max(1);
+    let rows = ((hs.land.len() + columns - 1) / columns).max(1);
+    let width = 2 * MARGIN + columns as u32 * CELL_WIDTH;
+    let height = 2 * MARGIN + rows as u32 * CELL_HEIGHT;
+
+    let mut canvas = RgbaImage::from_pixel(width, height, GRASS);
+    for (i, tile) in hs.land.iter().enumerate() {
+        let (col, row) = ((i % columns) as u32, (i / columns) as u32);
+        draw_tile(
+            &mut canvas,
+            MARGIN + col * CELL_WIDTH,
+            MARGIN + row * CELL_HEIGHT,
+            tile,
+            hs,
+        );
+    }
+
+    let mut spots = gotchi_spots(width, height);
+    let more = if hs.gotchis.len() > spots.len() {
+        spots
+            .pop()
+            .map(|spot| (spot, hs.gotchis.len() - spots.len()))
+    } else {
+        None
+    };
+    for (gotchi, (x, y)) in hs.gotchis.iter().zip(spots) {
+        if let Some(gotchi_art) = art("gotchi", &format!("{}.png", filify(&gotchi.name)), GOTCHI) {
+            imageops::overlay(&mut canvas, &gotchi_art, x, y);
+        }
+    }
+    if let Some(((x, y), more)) = more {
+        marker(&mut canvas, x, y, &format!("+{}", more));
+    }
+
+    let mut png = Vec::new();
+    DynamicImage::ImageRgba8(canvas)
+        .write_to(&mut png, ImageOutputFormat::Png)
+        .map_err(|e| format!("couldn't encode render: {}", e))?;
+    Ok(png)
+}
+
+/// Serves `/render/<user_id>.png`.
+#[get("/render/<file>")]
+pub async fn hackstead_png(file: String) -> Result<Option<Content<Vec<u8>>>, String> {
+    let user_id = match file.strip_suffix(".png") {
+        Some(user_id) => user_id.to_string(),
+        None => return Ok(None),
+    };
+    if !store().hacksteader_exists(&user_id).await? {
+        return Ok(None);
+    }
+
+    let hs = Hacksteader::from_db(store(), user_id).await?;
+    let png = rocket::tokio::task::spawn_blocking(move || render(&hs))
+        .await
+        .map_err(|e| format!("render panicked: {}", e))??;
+    Ok(Some(Content(ContentType::PNG, png)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hcor::{config::CONFIG, possess, Possession, Profile};
+    use possess::Possessed;
+
+    const STEADER: &str = "U1";
+
+    fn hackstead(tiles: usize, gotchis: usize) -> Hacksteader {
+        let gotchi = (0..CONFIG.possession_archetypes.len())
+            .find_map(|ah| {
+                Possessed::<possess::Gotchi>::from_possession(Possession::new(
+                    ah,
+                    possess::Owner::farmer(STEADER.to_string()),
+                ))
+            })
+            .expect("no gotchis");
+        Hacksteader {
+            user_id: STEADER.to_string(),
+            profile: Profile::new(STEADER.to_string()),
+            land: (0..tiles).map(|_| Tile::new(STEADER.to_string())).collect(),
+            inventory: vec![],
+            gotchis: vec![gotchi; gotchis],
+        }
+    }
+
+    fn rendered(hs: &Hacksteader) -> RgbaImage {
+        image::load_from_memory(&render(hs).unwrap())
+            .unwrap()
+            .to_rgba8()
+    }
+
+    #[test]
+    fn land_is_laid_out_a_row_of_columns_at_a_time() {
+        let img = rendered(&hackstead(5, 0));
+        assert_eq!(img.width(), 2 * MARGIN + COLUMNS as u32 * CELL_WIDTH);
+        assert_eq!(img.height(), 2 * MARGIN + 2 * CELL_HEIGHT);
+
+        let img = rendered(&hackstead(2, 0));
+        assert_eq!(img.width(), 2 * MARGIN + 2 * CELL_WIDTH);
+        assert_eq!(img.height(), 2 * MARGIN + CELL_HEIGHT);
+    }
+
+    #[test]
+    fn empty_land_still_gets_a_picture() {
+        let img = rendered(&hackstead(0, 0));
+        assert_eq!(img.width(), 2 * MARGIN + CELL_WIDTH);
+        assert_eq!(img.height(), 2 * MARGIN + CELL_HEIGHT);
+        assert_eq!(*img.get_pixel(0, 0), GRASS);
+    }
+
+    #[test]
+    fn gotchis_that_dont_fit_are_counted_in_the_last_spot() {
+        let hs = hackstead(1, 0);
+        let img = rendered(&hs);
+        let spots = gotchi_spots(img.width(), img.height());
+        let (x, y) = *spots.last().unwrap();
+
+        let everyone_fits = rendered(&hackstead(1, spots.len()));
+        assert_ne!(*everyone_fits.get_pixel(x, y), BAR_EMPTY);
+
+        let crowded = rendered(&hackstead(1, spots.len() + 3));
+        assert_eq!(*crowded.get_pixel(x, y), BAR_EMPTY);
+        let mut expected = RgbaImage::from_pixel(GOTCHI, GOTCHI, GRASS);
+        marker(&mut expected, 0, 0, "+4");
+        let drawn = imageops::crop_imm(&crowded, x, y, GOTCHI, GOTCHI).to_image();
+        assert_eq!(drawn, expected);
+    }
+}
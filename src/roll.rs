@@ -0,0 +1,206 @@
+//! Every random thing the farm does gets its own rng, seeded from what it's being
+//! rolled for, so that any roll can be worked out again afterwards.
+//!
+//! A seed is the HMAC of the kind of roll, what it's for (usually a tile or possession),
+//! and the farm cycle and tick it happened on, keyed with `ROLL_SECRET`.
+//! Nobody without the secret can predict their rolls, but anyone with it can explain them.
+//!
+//! The rngs are ChaCha20, which, unlike `StdRng`, is promised to turn a seed into the same
+//! numbers in every version of `rand_chacha`. Along with the `Odds` each roll keeps,
+//! that's what lets `farm::reroll` make any roll over again and check that it comes out the same.
+use crate::records::Record;
+use hmac::{Hmac, Mac, NewMac};
+use log::*;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+lazy_static::lazy_static! {
+    pub static ref DICE: Dice = Dice::new(std::env::var("ROLL_SECRET").unwrap());
+}
+
+/// Which farm cycle it was at a given time; cycles are counted from the Unix epoch.
+pub fn cycle_at(time: SystemTime) -> u64 {
+    (time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+        / crate::FARM_CYCLE_MILLIS as u128) as u64
+}
+
+/// What a roll was for. Two rolls for the same thing always come out the same.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RollFor {
+    /// What sort of roll this is, like `yield` or `hatch`.
+    pub kind: String,
+    /// What it was rolled for, usually the id of a tile or possession.
+    pub subject: String,
+    pub cycle: u64,
+    /// Plants can get several ticks in a single cycle.
+    pub tick: u64,
+}
+impl RollFor {
+    pub fn new(kind: &str, subject: impl ToString, cycle: u64, tick: u64) -> Self {
+        Self {
+            kind: kind.to_string(),
+            subject: subject.to_string(),
+            cycle,
+            tick,
+        }
+    }
+
+    pub fn id(&self) -> String {
+        format!(
+            "{}:{}:{}:{}",
+            self.kind, self.subject, self.cycle, self.tick
+        )
+    }
+}
+
+/// What a roll was made against, with everything it takes to make it again.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Odds {
+    /// A number from 0 up to 1 is drawn, and has to come in under this.
+    Chance(f32),
+    /// A whole number from the first up to the second.
+    Between(u64, u64),
+    /// What a plant could yield, as JSON.
+    Yields(serde_json::Value),
+    /// What an egg could hatch into, as JSON.
+    Hatch(serde_json::Value),
+}
+
+/// A roll, and what came of it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Roll {
+    pub what: RollFor,
+    /// What it was rolled against, e.g. a chance or a loot table.
+    pub odds: String,
+    pub outcome: String,
+    /// None for rolls kept before odds were.
+    #[serde(default)]
+    pub against: Option<Odds>,
+}
+impl Record for Roll {
+    const KIND: &'static str = "roll";
+
+    fn id(&self) -> String {
+        self.what.id()
+    }
+}
+
+pub struct Dice {
+    secret: Vec<u8>,
+}
+impl Dice {
+    /// Dice with a fixed secret roll the same way every time, which is handy for tests.
+    pub fn new(secret: impl Into<Vec<u8>>) -> Self {
+        Self {
+            secret: secret.into(),
+        }
+    }
+
+    pub fn seed(&self, what: &RollFor) -> [u8; 32] {
+        let mut mac =
+            Hmac::<Sha256>::new_varkey(&self.secret).expect("HMAC takes keys of any size");
+        mac.update(what.id().as_bytes());
+
+        let mut seed = [0; 32];
+        seed.copy_from_slice(&mac.finalize().into_bytes());
+        seed
+    }
+
+    pub fn rng(&self, what: &RollFor) -> ChaCha20Rng {
+        ChaCha20Rng::from_seed(self.seed(what))
+    }
+
+    /// Rolls for something, returning what came of it along with a `Roll` that should be kept.
+    pub fn roll<T>(
+        &self,
+        what: RollFor,
+        odds: String,
+        against: Odds,
+        f: impl FnOnce(&mut ChaCha20Rng) -> T,
+        describe: impl FnOnce(&T) -> String,
+    ) -> (T, Roll) {
+        let value = f(&mut self.rng(&what));
+        let roll = Roll {
+            outcome: describe(&value),
+            odds,
+            what,
+            against: Some(against),
+        };
+        info!(
+            "rolled {} against {}: {}",
+            roll.what.id(),
+            roll.odds,
+            roll.outcome
+        );
+        (value, roll)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::RngCore;
+
+    fn dice() -> Dice {
+        Dice::new("not very secret")
+    }
+
+    #[test]
+    fn seeds_are_the_hmac_of_what_was_rolled_for() {
+        assert_eq!(
+            hex::encode(dice().seed(&RollFor::new("yield", "tile", 1, 0))),
+            "46d574038be03f2914ab234b20753368df2af28ff7c76ba6caf7dc06236a5f13"
+        );
+    }
+
+    #[test]
+    fn fixed_seeds_roll_the_same_numbers() {
+        let mut rng = dice().rng(&RollFor::new("yield", "tile", 1, 0));
+        assert_eq!(rng.next_u32(), 2414140703);
+        assert_eq!(rng.next_u32(), 121759913);
+    }
+
+    #[test]
+    fn anything_different_rolls_differently() {
+        let what = RollFor::new("yield", "tile", 1, 0);
+        let seed = dice().seed(&what);
+        for other in vec![
+            RollFor::new("hatch", "tile", 1, 0),
+            RollFor::new("yield", "other tile", 1, 0),
+            RollFor::new("yield", "tile", 2, 0),
+            RollFor::new("yield", "tile", 1, 1),
+        ] {
+            assert_ne!(
+                dice().seed(&other),
+                seed,
+                "{} rolled like {}",
+                other.id(),
+                what.id()
+            );
+        }
+        assert_ne!(Dice::new("another secret").seed(&what), seed);
+    }
+
+    #[test]
+    fn rolls_keep_what_came_of_them() {
+        let what = RollFor::new("craft_return", "item", 1, 0);
+        let (drawn, roll) = dice().roll(
+            what.clone(),
+            "a 0.5 chance".to_string(),
+            Odds::Chance(0.5),
+            |rng| rng.next_u32(),
+            |drawn| format!("drew {}", drawn),
+        );
+        assert_eq!(drawn, dice().rng(&what).next_u32());
+        assert_eq!(roll.what, what);
+        assert_eq!(roll.outcome, format!("drew {}", drawn));
+        assert_eq!(roll.against, Some(Odds::Chance(0.5)));
+    }
+}
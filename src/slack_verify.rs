@@ -0,0 +1,315 @@
+//! Data guards that check Slack's request signature before anything else
+//! gets a look at what was posted to us.
+//!
+//! Slack signs every request with `X-Slack-Signature`, an HMAC-SHA256 of
+//! `v0:{X-Slack-Request-Timestamp}:{raw body}` keyed by the app's signing secret.
+//! Since the signature covers the raw body, these guards have to read the body
+//! themselves and only then parse it into a form or JSON.
+use hmac::{Hmac, Mac, NewMac};
+use log::*;
+use rocket::data::{self, Data, FromData, ToByteUnit};
+use rocket::http::Status;
+use rocket::Request;
+use serde::de::DeserializeOwned;
+use sha2::Sha256;
+use std::ops::Deref;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use std::env::var;
+lazy_static::lazy_static! {
+    pub static ref SIGNING_SECRET: String = var("SLACK_SIGNING_SECRET").unwrap();
+}
+
+/// Makes sure there's a secret to check requests against, so that a server
+/// started without one doesn't come up only to turn away everything Slack sends.
+pub fn check_signing_secret() -> Result<(), String> {
+    match var("SLACK_SIGNING_SECRET") {
+        Ok(secret) if !secret.is_empty() => {
+            lazy_static::initialize(&SIGNING_SECRET);
+            Ok(())
+        }
+        _ => Err("SLACK_SIGNING_SECRET has to be set to check requests from slack".to_string()),
+    }
+}
+
+/// Requests whose timestamp is further than this from our clock are refused,
+/// so that a captured request can't be replayed later.
+const MAX_REQUEST_AGE_SECS: u64 = 60 * 5;
+
+/// Checks a Slack signature against the raw body of a request.
+pub fn verify(
+    secret: &str,
+    timestamp: &str,
+    signature: &str,
+    body: &str,
+    now: SystemTime,
+) -> Result<(), String> {
+    let sent_at: u64 = timestamp
+        .parse()
+        .map_err(|e| format!("invalid slack request timestamp {}: {}", timestamp, e))?;
+    let now = now
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| format!("clock is before unix epoch: {}", e))?
+        .as_secs();
+    if (now as i128 - sent_at as i128).abs() > MAX_REQUEST_AGE_SECS as i128 {
+        return Err(format!(
+            "slack request timestamp {} is too far from now ({})",
+            sent_at, now
+        ));
+    }
+
+    let sent_mac = signature
+        .strip_prefix("v0=")
+        .ok_or_else(|| format!("unknown slack signature version: {}", signature))
+        .and_then(|hex_mac| {
+            hex::decode(hex_mac).map_err(|e| format!("slack signature isn't hex: {}", e))
+        })?;
+
+    let mut mac = Hmac::<Sha256>::new_varkey(secret.as_bytes())
+        .map_err(|e| format!("invalid slack signing secret: {}", e))?;
+    mac.update(format!("v0:{}:", timestamp).as_bytes());
+    mac.update(body.as_bytes());
+    mac.verify(&sent_mac)
+        .map_err(|_| "slack signature mismatch".to_string())
+}
+
+async fn verified_body<'r>(req: &'r Request<'_>, data: Data) -> Result<String, String> {
+    let headers = req.headers();
+    let timestamp = headers
+        .get_one("X-Slack-Request-Timestamp")
+        .ok_or_else(|| "no X-Slack-Request-Timestamp header".to_string())?;
+    let signature = headers
+        .get_one("X-Slack-Signature")
+        .ok_or_else(|| "no X-Slack-Signature header".to_string())?;
+
+    let body = data
+        .open(1.mebibytes())
+        .into_string()
+        .await
+        .map_err(|e| format!("couldn't read slack request body: {}", e))?;
+
+    verify(
+        &SIGNING_SECRET,
+        timestamp,
+        signature,
+        &body,
+        SystemTime::now(),
+    )?;
+
+    Ok(body)
+}
+
+/// A url-encoded form that Slack has been verified to have sent.
+/// Unknown fields are ignored, like with `LenientForm`.
+#[derive(Debug)]
+pub struct SlackForm<T>(pub T);
+
+impl<T> Deref for SlackForm<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, T: DeserializeOwned + Send> FromData<'r> for SlackForm<T> {
+    type Error = String;
+
+    async fn from_data(req: &'r Request<'_>, data: Data) -> data::Outcome<Self, String> {
+        let body = match verified_body(req, data).await {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("refusing slack form: {}", e);
+                return data::Outcome::Failure((Status::Unauthorized, e));
+            }
+        };
+
+        match serde_urlencoded::from_str(&body) {
+            Ok(form) => data::Outcome::Success(SlackForm(form)),
+            Err(e) => {
+                let a = format!("couldn't parse slack form: {}", e);
+                error!("{}", a);
+                data::Outcome::Failure((Status::UnprocessableEntity, a))
+            }
+        }
+    }
+}
+
+/// A JSON body that Slack has been verified to have sent.
+#[derive(Debug)]
+pub struct SlackJson<T>(pub T);
+
+impl<T> Deref for SlackJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, T: DeserializeOwned + Send> FromData<'r> for SlackJson<T> {
+    type Error = String;
+
+    async fn from_data(req: &'r Request<'_>, data: Data) -> data::Outcome<Self, String> {
+        let body = match verified_body(req, data).await {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("refusing slack json: {}", e);
+                return data::Outcome::Failure((Status::Unauthorized, e));
+            }
+        };
+
+        match serde_json::from_str(&body) {
+            Ok(json) => data::Outcome::Success(SlackJson(json)),
+            Err(e) => {
+                let a = format!("couldn't parse slack json: {}", e);
+                error!("{}", a);
+                data::Outcome::Failure((Status::UnprocessableEntity, a))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rocket::http::{ContentType, Header};
+    use rocket::local::blocking::Client;
+    use rocket::{post, routes};
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    const SECRET: &str = "8f742231b10e8888abcd99yyyzzz85a5";
+
+    fn sign(secret: &str, timestamp: &str, body: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_varkey(secret.as_bytes()).unwrap();
+        mac.update(format!("v0:{}:{}", timestamp, body).as_bytes());
+        format!("v0={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn now_secs() -> String {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            .to_string()
+    }
+
+    #[test]
+    fn accepts_a_good_signature() {
+        let body = "token=xyz&command=%2Fhackstead";
+        let signature = sign(SECRET, "1531420618", body);
+        assert_eq!(
+            verify(SECRET, "1531420618", &signature, body, at(1531420618)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn refuses_a_bad_signature() {
+        let body = "token=xyz&command=%2Fhackstead";
+        let signature = sign("some other secret", "1531420618", body);
+        assert!(verify(SECRET, "1531420618", &signature, body, at(1531420618)).is_err());
+
+        let signature = sign(SECRET, "1531420618", body);
+        let tampered = "token=xyz&command=%2Fhackmarket";
+        assert!(verify(SECRET, "1531420618", &signature, tampered, at(1531420618)).is_err());
+    }
+
+    #[test]
+    fn refuses_a_stale_timestamp() {
+        let body = "token=xyz";
+        let signature = sign(SECRET, "1531420618", body);
+        let later = 1531420618 + MAX_REQUEST_AGE_SECS + 1;
+        assert!(verify(SECRET, "1531420618", &signature, body, at(later)).is_err());
+        assert!(verify(SECRET, "1531420618", &signature, body, at(later - 2)).is_ok());
+    }
+
+    #[test]
+    fn refuses_other_signature_versions() {
+        let body = "token=xyz";
+        let signature = sign(SECRET, "1531420618", body).replacen("v0=", "v1=", 1);
+        assert!(verify(SECRET, "1531420618", &signature, body, at(1531420618)).is_err());
+    }
+
+    #[post("/form", data = "<form>")]
+    fn form(form: SlackForm<HashMap<String, String>>) -> String {
+        form.get("command").cloned().unwrap_or_default()
+    }
+
+    #[post("/json", data = "<json>")]
+    fn json(json: SlackJson<serde_json::Value>) -> String {
+        json.0["type"].as_str().unwrap_or_default().to_string()
+    }
+
+    fn client() -> Client {
+        std::env::set_var("SLACK_SIGNING_SECRET", SECRET);
+        Client::tracked(rocket::ignite().mount("/", routes![form, json])).unwrap()
+    }
+
+    #[test]
+    fn guards_let_signed_requests_through() {
+        let client = client();
+        let timestamp = now_secs();
+
+        let body = "command=%2Fhackstead&text=";
+        let response = client
+            .post("/form")
+            .header(ContentType::Form)
+            .header(Header::new("X-Slack-Request-Timestamp", timestamp.clone()))
+            .header(Header::new(
+                "X-Slack-Signature",
+                sign(SECRET, &timestamp, body),
+            ))
+            .body(body)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_string().as_deref(), Some("/hackstead"));
+
+        let body = r#"{"type":"url_verification"}"#;
+        let response = client
+            .post("/json")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Slack-Request-Timestamp", timestamp.clone()))
+            .header(Header::new(
+                "X-Slack-Signature",
+                sign(SECRET, &timestamp, body),
+            ))
+            .body(body)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_string().as_deref(), Some("url_verification"));
+    }
+
+    #[test]
+    fn guards_turn_away_unsigned_requests() {
+        let client = client();
+        let timestamp = now_secs();
+
+        let body = "command=%2Fhackstead&text=";
+        let response = client
+            .post("/form")
+            .header(ContentType::Form)
+            .header(Header::new("X-Slack-Request-Timestamp", timestamp.clone()))
+            .header(Header::new(
+                "X-Slack-Signature",
+                sign("some other secret", &timestamp, body),
+            ))
+            .body(body)
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+
+        let response = client
+            .post("/json")
+            .header(ContentType::JSON)
+            .body(r#"{"type":"url_verification"}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+    }
+}
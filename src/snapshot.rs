@@ -0,0 +1,194 @@
+//! Copies of items taken right before something drastic happens to them,
+//! so that it can be undone by writing them back.
+//!
+//! Items are kept as their DynamoDB attributes, split up into parts so that
+//! no one record gets too big for DynamoDB to hold.
+use crate::hacksteader::Tile;
+use crate::records::{self, Record};
+use crate::store::{Batch, Store};
+use hcor::{Item, Possession, Profile};
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+const ITEMS_PER_PART: usize = 100;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Kind {
+    Profile,
+    Tile,
+    Possession,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Snapshot {
+    pub id: uuid::Uuid,
+    pub taken: SystemTime,
+    /// What was about to happen to these items.
+    pub reason: String,
+    pub items: usize,
+    parts: usize,
+    /// When it was put back, if it has been. A snapshot can only be put back once.
+    #[serde(default)]
+    pub restored: Option<SystemTime>,
+}
+impl Record for Snapshot {
+    const KIND: &'static str = "snapshot";
+
+    fn id(&self) -> String {
+        self.id.to_simple().to_string()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Part {
+    snapshot: uuid::Uuid,
+    part: usize,
+    items: Vec<(Kind, Item)>,
+}
+impl Record for Part {
+    const KIND: &'static str = "snapshot_part";
+
+    fn id(&self) -> String {
+        part_id(self.snapshot, self.part)
+    }
+}
+
+fn part_id(snapshot: uuid::Uuid, part: usize) -> String {
+    format!("{}-{}", snapshot.to_simple(), part)
+}
+
+/// Keeps a copy of everything a batch is about to overwrite, returning the snapshot's id.
+/// `batch` should hold the items as they are now, not as they're about to be.
+pub async fn take(db: &dyn Store, reason: String, batch: &Batch) -> Result<String, String> {
+    let items: Vec<(Kind, Item)> = batch
+        .profiles
+        .iter()
+        .map(|p| (Kind::Profile, p.item()))
+        .chain(batch.tiles.iter().map(|t| {
+            (
+                Kind::Tile,
+                t.clone().into_av().m.expect("tile attribute should be map"),
+            )
+        }))
+        .chain(
+            batch
+                .possessions
+                .iter()
+                .map(|p| (Kind::Possession, p.item())),
+        )
+        .collect();
+
+    let snapshot = Snapshot {
+        id: uuid::Uuid::new_v4(),
+        taken: SystemTime::now(),
+        reason,
+        items: items.len(),
+        parts: (items.len() + ITEMS_PER_PART - 1) / ITEMS_PER_PART,
+        restored: None,
+    };
+    for (part, chunk) in items.chunks(ITEMS_PER_PART).enumerate() {
+        records::put(
+            db,
+            &Part {
+                snapshot: snapshot.id,
+                part,
+                items: chunk.to_vec(),
+            },
+        )
+        .await?;
+    }
+    // the snapshot only shows up once all of its parts are there
+    records::put(db, &snapshot).await?;
+
+    info!(
+        "took snapshot {} of {} items before {}",
+        snapshot.id, snapshot.items, snapshot.reason
+    );
+    Ok(snapshot.id())
+}
+
+pub async fn get(db: &dyn Store, id: &str) -> Result<Option<Snapshot>, String> {
+    records::get(db, id).await
+}
+
+/// Every snapshot, newest first.
+pub async fn all(db: &dyn Store) -> Result<Vec<Snapshot>, String> {
+    let mut snapshots: Vec<Snapshot> = records::all(db).await?;
+    snapshots.sort_by(|a, b| b.taken.cmp(&a.taken));
+    Ok(snapshots)
+}
+
+/// Everything that was in a snapshot, ready to be written back.
+pub async fn contents(db: &dyn Store, snapshot: &Snapshot) -> Result<Batch, String> {
+    let mut batch = Batch::default();
+    for part in 0..snapshot.parts {
+        let part = records::get::<Part>(db, &part_id(snapshot.id, part))
+            .await?
+            .ok_or_else(|| format!("snapshot {} is missing part {}", snapshot.id, part))?;
+        for (kind, item) in part.items {
+            let e = |e: hcor::AttributeParseError| {
+                format!(
+                    "couldn't parse {:?} in snapshot {}: {}",
+                    kind, snapshot.id, e
+                )
+            };
+            match kind {
+                Kind::Profile => batch.profiles.push(Profile::from_item(&item).map_err(e)?),
+                Kind::Tile => batch.tiles.push(Tile::from_item(&item).map_err(e)?),
+                Kind::Possession => batch
+                    .possessions
+                    .push(Possession::from_item(&item).map_err(e)?),
+            }
+        }
+    }
+    Ok(batch)
+}
+
+/// Puts everything in a snapshot back the way it was when the snapshot was taken.
+/// Once that's been done, it can't be done again.
+pub async fn restore(db: &dyn Store, id: &str) -> Result<Snapshot, String> {
+    let snapshot = get(db, id)
+        .await?
+        .ok_or_else(|| format!("no snapshot {}", id))?;
+    if let Some(restored) = snapshot.restored {
+        return Err(format!(
+            "snapshot {} was already restored at {}",
+            id,
+            humantime::format_rfc3339_seconds(restored)
+        ));
+    }
+
+    let batch = contents(db, &snapshot).await?;
+
+    // it's marked as restored before it is, so that nobody else can restore it at the same time
+    let restoring = Snapshot {
+        restored: Some(SystemTime::now()),
+        ..snapshot.clone()
+    };
+    if !db
+        .transfer_if(vec![], vec![records::swap(&snapshot, Some(&restoring))?])
+        .await?
+    {
+        return Err(format!("snapshot {} is already being restored", id));
+    }
+
+    // every item in a snapshot is put back on its own, so restoring it
+    // a transaction at a time is fine, and doing it again finishes the job
+    let mut result = Ok(());
+    for chunk in batch.chunks() {
+        result = db.transact(chunk).await;
+        if result.is_err() {
+            break;
+        }
+    }
+    if let Err(e) = result {
+        // so that it can be tried again
+        records::put(db, &snapshot).await?;
+        return Err(format!("couldn't restore snapshot {}: {}", id, e));
+    }
+
+    info!("restored snapshot {} ({})", snapshot.id, snapshot.reason);
+    Ok(restoring)
+}
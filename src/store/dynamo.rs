@@ -0,0 +1,998 @@
+//! The store we run on in production: everything hcor knows how to turn into an item
+//! lives in `hcor::TABLE_NAME`, and records get a table of their own.
+//!
+//! DynamoDB hands back at most 1 MB of items per query or scan, so every one of them
+//! goes through `fetch_all`, which keeps asking for more until it has everything.
+//!
+//! Tests that need the real thing run against a DynamoDB Local at `DYNAMODB_LOCAL_ENDPOINT`,
+//! and are skipped when that isn't set.
+use super::{Batch, RecordSwap, Store, Transfer, TRANSACT_LIMIT};
+use crate::hacksteader::{Hacksteader, Tile};
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+use futures::Future;
+use hcor::{market::Sale, possess, Category, Item, Key, Possession, Profile, TABLE_NAME};
+use log::*;
+use rusoto_core::RusotoError;
+use rusoto_dynamodb::{
+    AttributeValue, DynamoDb, DynamoDbClient, PutItemError, QueryInput, ScanInput,
+    TransactWriteItemsError,
+};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use std::env::var;
+lazy_static::lazy_static! {
+    pub static ref RECORDS_TABLE_NAME: String =
+        var("RECORDS_TABLE_NAME").unwrap_or_else(|_| "hackagotchi_records".to_string());
+}
+
+pub struct DynamoStore {
+    db: DynamoDbClient,
+}
+impl DynamoStore {
+    pub fn new() -> Self {
+        if *crate::LOCAL_DB {
+            return Self::local("http://dynamodb-local:8000");
+        }
+        Self {
+            db: DynamoDbClient::new(rusoto_core::Region::UsWest2),
+        }
+    }
+
+    /// A store on a DynamoDB Local running at `endpoint`.
+    pub fn local(endpoint: &str) -> Self {
+        Self {
+            db: DynamoDbClient::new(rusoto_core::Region::Custom {
+                name: "local".to_string(),
+                endpoint: endpoint.to_string(),
+            }),
+        }
+    }
+
+    /// The DynamoDB Local at `DYNAMODB_LOCAL_ENDPOINT`, if tests should be run against one.
+    /// Its tables should already be set up, like `docker-compose up` leaves them.
+    #[cfg(test)]
+    pub fn for_tests() -> Option<Self> {
+        var("DYNAMODB_LOCAL_ENDPOINT")
+            .ok()
+            .map(|endpoint| Self::local(&endpoint))
+    }
+
+    /// Every item a query or scan matches, however many pages it takes to get them.
+    async fn fetch_all<R: Paged>(&self, input: R) -> Result<Vec<Item>, String> {
+        let db = &self.db;
+        pages(move |start| input.clone().starting_at(start).fetch(db))
+            .try_concat()
+            .await
+    }
+
+    async fn get_item(&self, key: Key) -> Result<Option<Item>, String> {
+        Ok(self
+            .db
+            .get_item(rusoto_dynamodb::GetItemInput {
+                key: key.into_item(),
+                table_name: TABLE_NAME.to_string(),
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| format!("couldn't read {:?} from db: {}", key, e))?
+            .item)
+    }
+
+    /// Writes up to 25 requests, retrying whatever DynamoDB leaves unprocessed.
+    async fn batch_write(
+        &self,
+        requests: Vec<rusoto_dynamodb::WriteRequest>,
+    ) -> Result<(), String> {
+        let mut pending = requests;
+        for attempt in 0..MAX_WRITE_ATTEMPTS {
+            if attempt > 0 {
+                backoff(attempt).await;
+            }
+
+            pending = self
+                .db
+                .batch_write_item(rusoto_dynamodb::BatchWriteItemInput {
+                    request_items: [(TABLE_NAME.to_string(), pending)]
+                        .iter()
+                        .cloned()
+                        .collect(),
+                    ..Default::default()
+                })
+                .await
+                .map_err(|e| format!("couldn't write batch to db: {}", e))?
+                .unprocessed_items
+                .and_then(|mut unprocessed| unprocessed.remove(&TABLE_NAME.to_string()))
+                .unwrap_or_default();
+
+            if pending.is_empty() {
+                return Ok(());
+            }
+            warn!("{} writes unprocessed, retrying", pending.len());
+        }
+
+        Err(format!(
+            "{} writes still unprocessed after {} attempts",
+            pending.len(),
+            MAX_WRITE_ATTEMPTS
+        ))
+    }
+
+    /// Writes everything or nothing, retrying with the same token so that a
+    /// retry of a transaction that did go through doesn't write it twice.
+    /// Returns false if one of the items' conditions didn't hold, which isn't worth retrying.
+    async fn transact_write(
+        &self,
+        items: Vec<rusoto_dynamodb::TransactWriteItem>,
+    ) -> Result<bool, String> {
+        let token = uuid::Uuid::new_v4().to_string();
+        let mut last_err = String::new();
+        for attempt in 0..MAX_WRITE_ATTEMPTS {
+            if attempt > 0 {
+                backoff(attempt).await;
+            }
+
+            match self
+                .db
+                .transact_write_items(rusoto_dynamodb::TransactWriteItemsInput {
+                    transact_items: items.clone(),
+                    client_request_token: Some(token.clone()),
+                    ..Default::default()
+                })
+                .await
+            {
+                Ok(_) => return Ok(true),
+                Err(RusotoError::Service(TransactWriteItemsError::TransactionCanceled(why)))
+                    if why.contains("ConditionalCheckFailed") =>
+                {
+                    return Ok(false)
+                }
+                Err(e) => {
+                    warn!("transaction attempt {} failed: {}", attempt + 1, e);
+                    last_err = e.to_string();
+                }
+            }
+        }
+
+        Err(format!(
+            "transaction failed after {} attempts: {}",
+            MAX_WRITE_ATTEMPTS, last_err
+        ))
+    }
+
+    async fn category_items(&self, cat: Category) -> Result<Vec<Item>, String> {
+        self.fetch_all(QueryInput {
+            table_name: TABLE_NAME.to_string(),
+            key_condition_expression: Some("cat = :cat".to_string()),
+            expression_attribute_values: Some(
+                [(":cat".to_string(), cat.into_av())]
+                    .iter()
+                    .cloned()
+                    .collect(),
+            ),
+            ..Default::default()
+        })
+        .await
+        .map_err(|e| format!("couldn't query {} category: {}", cat, e))
+    }
+
+    async fn update(
+        &self,
+        key: Key,
+        update_expression: &str,
+        values: Vec<(&str, AttributeValue)>,
+    ) -> Result<(), String> {
+        self.db
+            .update_item(rusoto_dynamodb::UpdateItemInput {
+                table_name: TABLE_NAME.to_string(),
+                key: key.into_item(),
+                update_expression: Some(update_expression.to_string()),
+                expression_attribute_values: if values.is_empty() {
+                    None
+                } else {
+                    Some(
+                        values
+                            .into_iter()
+                            .map(|(k, v)| (k.to_string(), v))
+                            .collect(),
+                    )
+                },
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| format!("couldn't update {:?} in db: {}", key, e))?;
+
+        Ok(())
+    }
+}
+
+/// One page of items, and the key of the last one if there might be more after it.
+type Page = (Vec<Item>, Option<Item>);
+
+/// A request that DynamoDB answers a page at a time.
+#[rocket::async_trait]
+trait Paged: Clone + Send + Sync {
+    /// The same request, but for the page starting after `start`.
+    fn starting_at(self, start: Option<Item>) -> Self;
+
+    async fn fetch(self, db: &DynamoDbClient) -> Result<Page, String>;
+}
+
+#[rocket::async_trait]
+impl Paged for QueryInput {
+    fn starting_at(self, start: Option<Item>) -> Self {
+        QueryInput {
+            exclusive_start_key: start,
+            ..self
+        }
+    }
+
+    async fn fetch(self, db: &DynamoDbClient) -> Result<Page, String> {
+        let output = db.query(self).await.map_err(|e| e.to_string())?;
+        Ok((output.items.unwrap_or_default(), output.last_evaluated_key))
+    }
+}
+
+#[rocket::async_trait]
+impl Paged for ScanInput {
+    fn starting_at(self, start: Option<Item>) -> Self {
+        ScanInput {
+            exclusive_start_key: start,
+            ..self
+        }
+    }
+
+    async fn fetch(self, db: &DynamoDbClient) -> Result<Page, String> {
+        let output = db.scan(self).await.map_err(|e| e.to_string())?;
+        Ok((output.items.unwrap_or_default(), output.last_evaluated_key))
+    }
+}
+
+/// Every page of a query or scan, fetched one after the other by starting each
+/// where the last one left off, until DynamoDB says there's nothing left.
+/// `fetch` is given the key to start from, or None for the first page.
+fn pages<'a, F, Fut>(fetch: F) -> impl Stream<Item = Result<Vec<Item>, String>> + 'a
+where
+    F: FnMut(Option<Item>) -> Fut + 'a,
+    Fut: Future<Output = Result<Page, String>> + 'a,
+{
+    // the start of the next page, if there is a next page
+    let first: Option<Option<Item>> = Some(None);
+    stream::try_unfold((fetch, first), |(mut fetch, next)| async move {
+        let start = match next {
+            Some(start) => start,
+            None => return Ok(None),
+        };
+        let (items, last_evaluated_key) = fetch(start).await?;
+        Ok(Some((items, (fetch, last_evaluated_key.map(Some)))))
+    })
+}
+
+/// How many times a write is tried before we give up on it.
+const MAX_WRITE_ATTEMPTS: u32 = 5;
+
+async fn backoff(attempt: u32) {
+    rocket::tokio::time::sleep(Duration::from_millis(50 * 2_u64.pow(attempt))).await
+}
+
+fn s_av(s: &str) -> AttributeValue {
+    AttributeValue {
+        s: Some(s.to_string()),
+        ..Default::default()
+    }
+}
+
+fn record_key(kind: &str, id: &str) -> HashMap<String, AttributeValue> {
+    [
+        ("kind".to_string(), s_av(kind)),
+        ("id".to_string(), s_av(id)),
+    ]
+    .iter()
+    .cloned()
+    .collect()
+}
+
+fn record_item(kind: &str, id: &str, data: String) -> HashMap<String, AttributeValue> {
+    let mut item = record_key(kind, id);
+    item.insert("data".to_string(), s_av(&data));
+    item
+}
+
+/// Every record of one kind.
+fn records_query(kind: &str) -> QueryInput {
+    QueryInput {
+        table_name: RECORDS_TABLE_NAME.to_string(),
+        key_condition_expression: Some("#kind = :kind".to_string()),
+        expression_attribute_names: Some(
+            [("#kind".to_string(), "kind".to_string())]
+                .iter()
+                .cloned()
+                .collect(),
+        ),
+        expression_attribute_values: Some(
+            [(":kind".to_string(), s_av(kind))]
+                .iter()
+                .cloned()
+                .collect(),
+        ),
+        ..Default::default()
+    }
+}
+
+fn record_data(kind: &str, item: HashMap<String, AttributeValue>) -> Result<String, String> {
+    item.get("data")
+        .and_then(|d| d.s.clone())
+        .ok_or_else(|| format!("{} record has no data", kind))
+}
+
+#[rocket::async_trait]
+impl Store for DynamoStore {
+    async fn hacksteader_exists(&self, user_id: &str) -> Result<bool, String> {
+        Ok(self
+            .db
+            .get_item(rusoto_dynamodb::GetItemInput {
+                key: Profile::key_item(user_id.to_string()),
+                table_name: TABLE_NAME.to_string(),
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| format!("couldn't see if hacksteader exists: {}", e))?
+            .item
+            .is_some())
+    }
+
+    async fn hacksteader(&self, user_id: &str) -> Result<Hacksteader, String> {
+        let items = self
+            .fetch_all(QueryInput {
+                table_name: TABLE_NAME.to_string(),
+                key_condition_expression: Some("steader = :steader_id".to_string()),
+                index_name: Some("steader_index".to_string()),
+                expression_attribute_values: Some(
+                    [(":steader_id".to_string(), s_av(user_id))]
+                        .iter()
+                        .cloned()
+                        .collect(),
+                ),
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| format!("couldn't profile query: {}", e))?;
+
+        Hacksteader::from_items(user_id.to_string(), &items)
+    }
+
+    async fn profiles(&self) -> Result<Vec<Profile>, String> {
+        Ok(self
+            .category_items(Category::Profile)
+            .await?
+            .iter()
+            .filter_map(|i| match Profile::from_item(i) {
+                Ok(profile) => Some(profile),
+                Err(e) => {
+                    error!("error parsing profile: {}", e);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    async fn tile(&self, id: uuid::Uuid) -> Result<Tile, String> {
+        let item = self
+            .get_item(Key::tile(id))
+            .await?
+            .ok_or_else(|| format!("no item at {:?} to get tile for", id))?;
+        Tile::from_item(&item).map_err(|e| format!("couldn't parse tile: {}", e))
+    }
+
+    async fn tiles(&self) -> Result<Vec<Tile>, String> {
+        Ok(self
+            .category_items(Category::Land)
+            .await?
+            .iter()
+            .filter_map(|i| match Tile::from_item(i) {
+                Ok(tile) => Some(tile),
+                Err(e) => {
+                    error!("error parsing tile: {}", e);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    async fn possession(&self, key: Key) -> Result<Possession, String> {
+        let item = self
+            .get_item(key)
+            .await?
+            .ok_or_else(|| format!("no item at {:?} to get possession for", key))?;
+        Possession::from_item(&item)
+            .map_err(|e| format!("couldn't parse possession to get possession: {}", e))
+    }
+
+    async fn possessions(&self, cat: Category) -> Result<Vec<Possession>, String> {
+        Ok(self
+            .category_items(cat)
+            .await?
+            .iter()
+            .filter_map(|i| match Possession::from_item(i) {
+                Ok(p) => Some(p),
+                Err(e) => {
+                    error!("error parsing possession: {}", e);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    async fn market_listings(&self, cat: Category) -> Result<Vec<(Sale, Possession)>, String> {
+        let query = self
+            .fetch_all(QueryInput {
+                table_name: TABLE_NAME.to_string(),
+                index_name: Some("cat_price_index".to_string()),
+                key_condition_expression: Some("cat = :sale_cat".to_string()),
+                expression_attribute_values: Some(
+                    [(":sale_cat".to_string(), cat.into_av())]
+                        .iter()
+                        .cloned()
+                        .collect(),
+                ),
+                ..Default::default()
+            })
+            .await;
+
+        Ok(query
+            .map_err(|e| format!("Couldn't search market: {}", e))?
+            .iter()
+            .filter_map(|i| match Possession::from_item(i) {
+                Ok(mut pos) => Some((pos.sale.take()?, pos)),
+                Err(e) => {
+                    error!("error parsing possession: {}", e);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    async fn write(&self, batch: Batch) -> Result<(), String> {
+        let Batch {
+            profiles,
+            tiles,
+            possessions,
+            deletions,
+            profile_deletions,
+        } = batch;
+
+        let put = |item| rusoto_dynamodb::WriteRequest {
+            put_request: Some(rusoto_dynamodb::PutRequest { item }),
+            ..Default::default()
+        };
+        let requests = profiles
+            .iter()
+            .map(|p| put(p.item()))
+            .chain(
+                tiles
+                    .into_iter()
+                    .map(|t| put(t.into_av().m.expect("tile attribute should be map"))),
+            )
+            .chain(possessions.iter().map(|p| put(p.item())))
+            .chain(
+                deletions
+                    .into_iter()
+                    .map(|key| key.into_item())
+                    .chain(profile_deletions.into_iter().map(Profile::key_item))
+                    .map(|key| rusoto_dynamodb::WriteRequest {
+                        delete_request: Some(rusoto_dynamodb::DeleteRequest { key }),
+                        ..Default::default()
+                    }),
+            )
+            .collect::<Vec<_>>();
+
+        stream::iter(
+            requests
+                .chunks(25)
+                .map(|items| self.batch_write(items.to_vec())),
+        )
+        .map(|x| Ok(x))
+        .try_for_each_concurrent(None, |r| r)
+        .await
+    }
+
+    async fn transact(&self, batch: Batch) -> Result<(), String> {
+        if batch.len() > TRANSACT_LIMIT {
+            return Err(format!(
+                "can't transact {} writes, only {}",
+                batch.len(),
+                TRANSACT_LIMIT
+            ));
+        }
+        let Batch {
+            profiles,
+            tiles,
+            possessions,
+            deletions,
+            profile_deletions,
+        } = batch;
+
+        let put = |item| rusoto_dynamodb::TransactWriteItem {
+            put: Some(rusoto_dynamodb::Put {
+                item,
+                table_name: TABLE_NAME.to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let items = profiles
+            .iter()
+            .map(|p| put(p.item()))
+            .chain(
+                tiles
+                    .into_iter()
+                    .map(|t| put(t.into_av().m.expect("tile attribute should be map"))),
+            )
+            .chain(possessions.iter().map(|p| put(p.item())))
+            .chain(
+                deletions
+                    .into_iter()
+                    .map(|key| key.into_item())
+                    .chain(profile_deletions.into_iter().map(Profile::key_item))
+                    .map(|key| rusoto_dynamodb::TransactWriteItem {
+                        delete: Some(rusoto_dynamodb::Delete {
+                            key,
+                            table_name: TABLE_NAME.to_string(),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    }),
+            )
+            .collect::<Vec<_>>();
+        if items.is_empty() {
+            return Ok(());
+        }
+
+        if self.transact_write(items).await? {
+            Ok(())
+        } else {
+            Err("transaction was refused".to_string())
+        }
+    }
+
+    async fn take(&self, key: Key) -> Result<Possession, String> {
+        match self
+            .db
+            .delete_item(rusoto_dynamodb::DeleteItemInput {
+                key: key.into_item(),
+                table_name: TABLE_NAME.to_string(),
+                return_values: Some("ALL_OLD".to_string()),
+                ..Default::default()
+            })
+            .await
+        {
+            Ok(rusoto_dynamodb::DeleteItemOutput {
+                attributes: Some(item),
+                ..
+            }) => Possession::from_item(&item)
+                .map_err(|e| format!("couldn't parse value returned from delete: {}", e)),
+            Err(e) => Err(format!("couldn't delete in db: {}", e)),
+            _ => Err(format!("no attributes returned!")),
+        }
+    }
+
+    async fn place_on_market(&self, key: Key, price: u64, name: String) -> Result<(), String> {
+        self.update(
+            key,
+            "SET price = :sale_price, market_name = :new_name",
+            vec![
+                (
+                    ":sale_price",
+                    AttributeValue {
+                        n: Some(price.to_string()),
+                        ..Default::default()
+                    },
+                ),
+                (":new_name", s_av(&name)),
+            ],
+        )
+        .await
+    }
+
+    async fn take_off_market(&self, key: Key) -> Result<(), String> {
+        self.update(key, "REMOVE price, market_name", vec![]).await
+    }
+
+    async fn transfer_possession(
+        &self,
+        new_owner: String,
+        acquisition: possess::Acquisition,
+        key: Key,
+        delist: bool,
+    ) -> Result<(), String> {
+        self.update(
+            key,
+            &format!(
+                "{}SET steader = :new_owner, {}",
+                if delist {
+                    "REMOVE price, market_name "
+                } else {
+                    ""
+                },
+                "ownership_log = list_append(ownership_log, :ownership_entry)"
+            ),
+            vec![
+                (":new_owner", s_av(&new_owner)),
+                (
+                    ":ownership_entry",
+                    AttributeValue {
+                        l: Some(vec![possess::Owner {
+                            id: new_owner.clone(),
+                            acquisition,
+                        }
+                        .into()]),
+                        ..Default::default()
+                    },
+                ),
+            ],
+        )
+        .await
+    }
+
+    async fn transfer_if(
+        &self,
+        transfers: Vec<Transfer>,
+        records: Vec<RecordSwap>,
+    ) -> Result<bool, String> {
+        let items = transfers
+            .into_iter()
+            .map(|t| {
+                let mut values: HashMap<String, AttributeValue> = [
+                    (":old_owner".to_string(), s_av(&t.from)),
+                    (":new_owner".to_string(), s_av(&t.to)),
+                    (
+                        ":ownership_entry".to_string(),
+                        AttributeValue {
+                            l: Some(vec![possess::Owner {
+                                id: t.to.clone(),
+                                acquisition: t.acquisition,
+                            }
+                            .into()]),
+                            ..Default::default()
+                        },
+                    ),
+                ]
+                .iter()
+                .cloned()
+                .collect();
+                let condition = match t.price {
+                    Some(price) => {
+                        values.insert(
+                            ":price".to_string(),
+                            AttributeValue {
+                                n: Some(price.to_string()),
+                                ..Default::default()
+                            },
+                        );
+                        "steader = :old_owner AND price = :price"
+                    }
+                    None => "steader = :old_owner AND attribute_not_exists(price)",
+                };
+
+                rusoto_dynamodb::TransactWriteItem {
+                    update: Some(rusoto_dynamodb::Update {
+                        table_name: TABLE_NAME.to_string(),
+                        key: t.key.into_item(),
+                        update_expression: "REMOVE price, market_name \
+                            SET steader = :new_owner, \
+                            ownership_log = list_append(ownership_log, :ownership_entry)"
+                            .to_string(),
+                        condition_expression: Some(condition.to_string()),
+                        expression_attribute_values: Some(values),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }
+            })
+            .chain(records.into_iter().map(|r| {
+                // "data" is one of DynamoDB's reserved words
+                let names = Some(
+                    [("#data".to_string(), "data".to_string())]
+                        .iter()
+                        .cloned()
+                        .collect(),
+                );
+                let (condition, values) = match &r.old {
+                    Some(old) => (
+                        "#data = :old",
+                        Some([(":old".to_string(), s_av(old))].iter().cloned().collect()),
+                    ),
+                    None => ("attribute_not_exists(#data)", None),
+                };
+                let condition = Some(condition.to_string());
+                match r.new {
+                    Some(new) => rusoto_dynamodb::TransactWriteItem {
+                        put: Some(rusoto_dynamodb::Put {
+                            table_name: RECORDS_TABLE_NAME.to_string(),
+                            item: record_item(&r.kind, &r.id, new),
+                            condition_expression: condition,
+                            expression_attribute_names: names,
+                            expression_attribute_values: values,
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    },
+                    None => rusoto_dynamodb::TransactWriteItem {
+                        delete: Some(rusoto_dynamodb::Delete {
+                            table_name: RECORDS_TABLE_NAME.to_string(),
+                            key: record_key(&r.kind, &r.id),
+                            condition_expression: condition,
+                            expression_attribute_names: names,
+                            expression_attribute_values: values,
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    },
+                }
+            }))
+            .collect::<Vec<_>>();
+
+        if items.len() > TRANSACT_LIMIT {
+            return Err(format!(
+                "can't make {} changes in one transaction, only {}",
+                items.len(),
+                TRANSACT_LIMIT
+            ));
+        }
+        if items.is_empty() {
+            return Ok(true);
+        }
+        self.transact_write(items).await
+    }
+
+    async fn rename_gotchi(&self, key: Key, nickname: String) -> Result<(), String> {
+        self.update(
+            key,
+            "SET nickname = :new_name",
+            vec![(":new_name", s_av(&nickname))],
+        )
+        .await
+    }
+
+    async fn record_harvest(&self, key: Key, steader: String, amount: u64) -> Result<(), String> {
+        let harvest_log = self
+            .possession(key)
+            .await?
+            .kind
+            .gotchi()
+            .ok_or_else(|| format!("{:?} isn't a gotchi, can't record harvest", key))?
+            .harvest_log
+            .clone();
+
+        match harvest_log.last().filter(|x| x.id == steader) {
+            Some(_) => {
+                self.update(
+                    key,
+                    &format!("ADD harvest_log[{}].harvested :harv", harvest_log.len() - 1),
+                    vec![(
+                        ":harv",
+                        AttributeValue {
+                            n: Some(amount.to_string()),
+                            ..Default::default()
+                        },
+                    )],
+                )
+                .await
+            }
+            None => {
+                self.update(
+                    key,
+                    "SET harvest_log = list_append(harvest_log, :harv)",
+                    vec![(
+                        ":harv",
+                        AttributeValue {
+                            l: Some(vec![possess::gotchi::GotchiHarvestOwner {
+                                id: steader,
+                                harvested: amount,
+                            }
+                            .into()]),
+                            ..Default::default()
+                        },
+                    )],
+                )
+                .await
+            }
+        }
+    }
+
+    async fn insert_record(&self, kind: &str, id: &str, data: String) -> Result<bool, String> {
+        match self
+            .db
+            .put_item(rusoto_dynamodb::PutItemInput {
+                table_name: RECORDS_TABLE_NAME.to_string(),
+                item: record_item(kind, id, data),
+                condition_expression: Some("attribute_not_exists(id)".to_string()),
+                ..Default::default()
+            })
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(RusotoError::Service(PutItemError::ConditionalCheckFailed(_))) => Ok(false),
+            Err(e) => Err(format!("couldn't insert {} record {}: {}", kind, id, e)),
+        }
+    }
+
+    async fn put_record(&self, kind: &str, id: &str, data: String) -> Result<(), String> {
+        self.db
+            .put_item(rusoto_dynamodb::PutItemInput {
+                table_name: RECORDS_TABLE_NAME.to_string(),
+                item: record_item(kind, id, data),
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| format!("couldn't put {} record {}: {}", kind, id, e))?;
+
+        Ok(())
+    }
+
+    async fn get_record(&self, kind: &str, id: &str) -> Result<Option<String>, String> {
+        self.db
+            .get_item(rusoto_dynamodb::GetItemInput {
+                table_name: RECORDS_TABLE_NAME.to_string(),
+                key: record_key(kind, id),
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| format!("couldn't get {} record {}: {}", kind, id, e))?
+            .item
+            .map(|item| record_data(kind, item))
+            .transpose()
+    }
+
+    async fn remove_record(&self, kind: &str, id: &str) -> Result<Option<String>, String> {
+        self.db
+            .delete_item(rusoto_dynamodb::DeleteItemInput {
+                table_name: RECORDS_TABLE_NAME.to_string(),
+                key: record_key(kind, id),
+                return_values: Some("ALL_OLD".to_string()),
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| format!("couldn't remove {} record {}: {}", kind, id, e))?
+            .attributes
+            .map(|item| record_data(kind, item))
+            .transpose()
+    }
+
+    async fn records(&self, kind: &str) -> Result<Vec<String>, String> {
+        self.fetch_all(records_query(kind))
+            .await
+            .map_err(|e| format!("couldn't query {} records: {}", kind, e))?
+            .into_iter()
+            .map(|item| record_data(kind, item))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Stands in for DynamoDB, answering with at most `page_size` items at a time.
+    struct FakeTable {
+        items: Vec<Item>,
+        page_size: usize,
+    }
+    impl FakeTable {
+        fn new(len: usize, page_size: usize) -> Self {
+            Self {
+                items: (0..len)
+                    .map(|n| {
+                        [("id".to_string(), s_av(&n.to_string()))]
+                            .iter()
+                            .cloned()
+                            .collect()
+                    })
+                    .collect(),
+                page_size,
+            }
+        }
+
+        async fn fetch(&self, start: Option<Item>) -> Result<Page, String> {
+            let from = match start {
+                Some(key) => self.items.iter().position(|i| *i == key).unwrap() + 1,
+                None => 0,
+            };
+            let to = (from + self.page_size).min(self.items.len());
+            let last_evaluated_key = if to < self.items.len() {
+                Some(self.items[to - 1].clone())
+            } else {
+                None
+            };
+            Ok((self.items[from..to].to_vec(), last_evaluated_key))
+        }
+    }
+
+    #[rocket::async_test]
+    async fn follows_every_page() {
+        let table = FakeTable::new(7, 3);
+        let mut fetches = 0;
+        let items: Vec<Item> = pages(|start| {
+            fetches += 1;
+            table.fetch(start)
+        })
+        .try_concat()
+        .await
+        .unwrap();
+        assert_eq!(items, table.items);
+        assert_eq!(fetches, 3);
+    }
+
+    #[rocket::async_test]
+    async fn stops_at_an_empty_table() {
+        let table = FakeTable::new(0, 3);
+        let items: Vec<Item> = pages(|start| table.fetch(start))
+            .try_concat()
+            .await
+            .unwrap();
+        assert!(items.is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn stops_at_the_first_error() {
+        let table = FakeTable::new(7, 3);
+        let mut fetches = 0;
+        let result: Result<Vec<Item>, String> = pages(|start| {
+            fetches += 1;
+            let failed = fetches == 2;
+            let page = table.fetch(start);
+            async move {
+                if failed {
+                    Err("throttled".to_string())
+                } else {
+                    page.await
+                }
+            }
+        })
+        .try_concat()
+        .await;
+        assert_eq!(result, Err("throttled".to_string()));
+        assert_eq!(fetches, 2);
+    }
+
+    #[rocket::async_test]
+    async fn follows_every_page_dynamodb_local_hands_back() {
+        let db = match DynamoStore::for_tests() {
+            Some(db) => db,
+            None => return,
+        };
+        let kind = format!("pager_test_{}", uuid::Uuid::new_v4().to_simple());
+        for n in 0..25 {
+            db.put_record(&kind, &format!("{:02}", n), "{}".to_string())
+                .await
+                .unwrap();
+        }
+
+        // at most 10 at a time, so there's a LastEvaluatedKey to follow twice
+        let query = QueryInput {
+            limit: Some(10),
+            ..records_query(&kind)
+        };
+        let sizes: Vec<usize> = pages(|start| query.clone().starting_at(start).fetch(&db.db))
+            .map_ok(|page| page.len())
+            .try_collect()
+            .await
+            .unwrap();
+        let everything = db.fetch_all(query.clone()).await.unwrap();
+
+        for n in 0..25 {
+            db.remove_record(&kind, &format!("{:02}", n)).await.unwrap();
+        }
+        assert_eq!(sizes, vec![10, 10, 5]);
+        assert_eq!(everything.len(), 25);
+    }
+
+    #[test]
+    fn pages_start_where_the_last_left_off() {
+        let key: Item = [("id".to_string(), s_av("2"))].iter().cloned().collect();
+        let query = QueryInput::default().starting_at(Some(key.clone()));
+        assert_eq!(query.exclusive_start_key, Some(key.clone()));
+        let scan = ScanInput::default().starting_at(Some(key.clone()));
+        assert_eq!(scan.exclusive_start_key, Some(key));
+    }
+}
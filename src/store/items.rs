@@ -0,0 +1,455 @@
+//! Stores that just need to hold onto items and records, like `MemoryStore` and
+//! `SqliteStore`, implement `ItemTable` and get a `Store` for free.
+//!
+//! The items are the same ones hcor reads and writes to DynamoDB, so the updates
+//! below mirror the update expressions `DynamoStore` sends.
+//!
+//! Tables wait on locks and disks, so all of their work is done on tokio's blocking threads.
+use super::{Batch, RecordSwap, Store, Transfer, TRANSACT_LIMIT};
+use crate::hacksteader::{Hacksteader, Tile};
+use hcor::{market::Sale, possess, Category, Item, Key, Possession, Profile};
+use log::*;
+use rusoto_dynamodb::AttributeValue;
+
+/// The category and id of an item, as they're written in the item.
+pub type ItemKey = (String, String);
+
+pub enum ItemFilter<'a> {
+    /// Items of the category written like this.
+    Category(&'a str),
+    /// Items belonging to this steader.
+    Steader(&'a str),
+}
+impl ItemFilter<'_> {
+    pub fn matches(&self, item: &Item) -> bool {
+        match self {
+            ItemFilter::Category(cat) => item
+                .get("cat")
+                .and_then(av_str)
+                .map_or(false, |c| c == *cat),
+            ItemFilter::Steader(steader) => item
+                .get("steader")
+                .and_then(|s| s.s.as_ref())
+                .map_or(false, |s| s == steader),
+        }
+    }
+}
+
+fn av_str(av: &AttributeValue) -> Option<&String> {
+    av.n.as_ref().or_else(|| av.s.as_ref())
+}
+
+pub fn cat_str(cat: Category) -> String {
+    av_str(&cat.into_av()).cloned().unwrap_or_default()
+}
+
+pub fn item_key(item: &Item) -> Result<ItemKey, String> {
+    Ok((
+        item.get("cat")
+            .and_then(av_str)
+            .ok_or_else(|| "item has no cat".to_string())?
+            .clone(),
+        item.get("id")
+            .and_then(av_str)
+            .ok_or_else(|| "item has no id".to_string())?
+            .clone(),
+    ))
+}
+
+fn key_of(key: Key) -> Result<ItemKey, String> {
+    item_key(&key.into_item())
+}
+
+fn s_av(s: String) -> AttributeValue {
+    AttributeValue {
+        s: Some(s),
+        ..Default::default()
+    }
+}
+
+fn n_av(n: u64) -> AttributeValue {
+    AttributeValue {
+        n: Some(n.to_string()),
+        ..Default::default()
+    }
+}
+
+fn list_push(item: &mut Item, field: &str, entry: AttributeValue) {
+    item.entry(field.to_string())
+        .or_insert_with(|| AttributeValue {
+            l: Some(vec![]),
+            ..Default::default()
+        })
+        .l
+        .get_or_insert_with(Vec::new)
+        .push(entry);
+}
+
+fn price_of(item: &Item) -> Option<u64> {
+    item.get("price")?.n.as_ref()?.parse().ok()
+}
+
+/// Gives an item to someone else, noting it in its ownership log.
+fn hand_over(item: &mut Item, new_owner: &str, acquisition: &possess::Acquisition, delist: bool) {
+    if delist {
+        item.remove("price");
+        item.remove("market_name");
+    }
+    item.insert("steader".to_string(), s_av(new_owner.to_string()));
+    list_push(
+        item,
+        "ownership_log",
+        possess::Owner {
+            id: new_owner.to_string(),
+            acquisition: acquisition.clone(),
+        }
+        .into(),
+    );
+}
+
+pub trait ItemTable: Send + Sync + Clone + 'static {
+    fn get(&self, key: &ItemKey) -> Result<Option<Item>, String>;
+
+    fn items(&self, filter: &ItemFilter) -> Result<Vec<Item>, String>;
+
+    /// Puts and deletes items all at once; if any of it fails, none of it should happen.
+    fn write_items(&self, puts: Vec<Item>, deletes: Vec<ItemKey>) -> Result<(), String>;
+
+    fn remove(&self, key: &ItemKey) -> Result<Option<Item>, String>;
+
+    /// Changes an item in place, without anything else writing to it in the meantime.
+    fn modify(
+        &self,
+        key: &ItemKey,
+        f: &mut dyn FnMut(&mut Item) -> Result<(), String>,
+    ) -> Result<(), String>;
+
+    /// Reads some items and the data of some records, lets `f` change them, and writes them back,
+    /// without anything else writing to them in the meantime. Whatever isn't there is None,
+    /// and whatever `f` sets to None is removed. If `f` returns false or fails, nothing is written.
+    fn modify_all(
+        &self,
+        items: &[ItemKey],
+        records: &[(String, String)],
+        f: &mut dyn FnMut(&mut [Option<Item>], &mut [Option<String>]) -> Result<bool, String>,
+    ) -> Result<bool, String>;
+
+    fn insert_record(&self, kind: &str, id: &str, data: String) -> Result<bool, String>;
+
+    fn put_record(&self, kind: &str, id: &str, data: String) -> Result<(), String>;
+
+    fn get_record(&self, kind: &str, id: &str) -> Result<Option<String>, String>;
+
+    fn remove_record(&self, kind: &str, id: &str) -> Result<Option<String>, String>;
+
+    fn records(&self, kind: &str) -> Result<Vec<String>, String>;
+}
+
+/// Runs `f` on one of tokio's blocking threads, so it can't hold up the rest of the game.
+async fn blocking<T, R, F>(table: &T, f: F) -> Result<R, String>
+where
+    T: ItemTable,
+    R: Send + 'static,
+    F: FnOnce(T) -> Result<R, String> + Send + 'static,
+{
+    let table = table.clone();
+    rocket::tokio::task::spawn_blocking(move || f(table))
+        .await
+        .map_err(|e| format!("store task failed: {}", e))?
+}
+
+fn parse_all<T>(
+    items: Vec<Item>,
+    what: &str,
+    parse: impl Fn(&Item) -> Result<T, hcor::AttributeParseError>,
+) -> Vec<T> {
+    items
+        .iter()
+        .filter_map(|i| match parse(i) {
+            Ok(t) => Some(t),
+            Err(e) => {
+                error!("error parsing {}: {}", what, e);
+                None
+            }
+        })
+        .collect()
+}
+
+#[rocket::async_trait]
+impl<T: ItemTable> Store for T {
+    async fn hacksteader_exists(&self, user_id: &str) -> Result<bool, String> {
+        let key = item_key(&Profile::key_item(user_id.to_string()))?;
+        blocking(self, move |t| Ok(t.get(&key)?.is_some())).await
+    }
+
+    async fn hacksteader(&self, user_id: &str) -> Result<Hacksteader, String> {
+        let user_id = user_id.to_string();
+        blocking(self, move |t| {
+            let items = t.items(&ItemFilter::Steader(&user_id))?;
+            Hacksteader::from_items(user_id, &items)
+        })
+        .await
+    }
+
+    async fn profiles(&self) -> Result<Vec<Profile>, String> {
+        let items = blocking(self, |t| {
+            t.items(&ItemFilter::Category(&cat_str(Category::Profile)))
+        })
+        .await?;
+        Ok(parse_all(items, "profile", Profile::from_item))
+    }
+
+    async fn tile(&self, id: uuid::Uuid) -> Result<Tile, String> {
+        let key = key_of(Key::tile(id))?;
+        let item = blocking(self, move |t| t.get(&key))
+            .await?
+            .ok_or_else(|| format!("no item at {:?} to get tile for", id))?;
+        Tile::from_item(&item).map_err(|e| format!("couldn't parse tile: {}", e))
+    }
+
+    async fn tiles(&self) -> Result<Vec<Tile>, String> {
+        let items = blocking(self, |t| {
+            t.items(&ItemFilter::Category(&cat_str(Category::Land)))
+        })
+        .await?;
+        Ok(parse_all(items, "tile", Tile::from_item))
+    }
+
+    async fn possession(&self, key: Key) -> Result<Possession, String> {
+        let item_key = key_of(key)?;
+        let item = blocking(self, move |t| t.get(&item_key))
+            .await?
+            .ok_or_else(|| format!("no item at {:?} to get possession for", key))?;
+        Possession::from_item(&item)
+            .map_err(|e| format!("couldn't parse possession to get possession: {}", e))
+    }
+
+    async fn possessions(&self, cat: Category) -> Result<Vec<Possession>, String> {
+        let items = blocking(self, move |t| t.items(&ItemFilter::Category(&cat_str(cat)))).await?;
+        Ok(parse_all(items, "possession", Possession::from_item))
+    }
+
+    async fn market_listings(&self, cat: Category) -> Result<Vec<(Sale, Possession)>, String> {
+        let mut listings: Vec<(Sale, Possession)> = self
+            .possessions(cat)
+            .await?
+            .into_iter()
+            .filter_map(|mut p| Some((p.sale.take()?, p)))
+            .collect();
+        listings.sort_by_key(|(sale, _)| sale.price);
+        Ok(listings)
+    }
+
+    async fn write(&self, batch: Batch) -> Result<(), String> {
+        let Batch {
+            profiles,
+            tiles,
+            possessions,
+            deletions,
+            profile_deletions,
+        } = batch;
+
+        let puts = profiles
+            .iter()
+            .map(|p| p.item())
+            .chain(
+                tiles
+                    .into_iter()
+                    .map(|t| t.into_av().m.expect("tile attribute should be map")),
+            )
+            .chain(possessions.iter().map(|p| p.item()))
+            .collect();
+        let deletes = deletions
+            .into_iter()
+            .map(key_of)
+            .chain(
+                profile_deletions
+                    .into_iter()
+                    .map(|id| item_key(&Profile::key_item(id))),
+            )
+            .collect::<Result<_, _>>()?;
+        blocking(self, move |t| t.write_items(puts, deletes)).await
+    }
+
+    async fn transact(&self, batch: Batch) -> Result<(), String> {
+        if batch.len() > TRANSACT_LIMIT {
+            return Err(format!(
+                "can't transact {} writes, only {}",
+                batch.len(),
+                TRANSACT_LIMIT
+            ));
+        }
+        // write_items is already all or nothing
+        self.write(batch).await
+    }
+
+    async fn take(&self, key: Key) -> Result<Possession, String> {
+        let item_key = key_of(key)?;
+        let item = blocking(self, move |t| t.remove(&item_key))
+            .await?
+            .ok_or_else(|| format!("no {:?} to take", key))?;
+        Possession::from_item(&item)
+            .map_err(|e| format!("couldn't parse value returned from delete: {}", e))
+    }
+
+    async fn place_on_market(&self, key: Key, price: u64, name: String) -> Result<(), String> {
+        let key = key_of(key)?;
+        blocking(self, move |t| {
+            t.modify(&key, &mut |item| {
+                item.insert("price".to_string(), n_av(price));
+                item.insert("market_name".to_string(), s_av(name.clone()));
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn take_off_market(&self, key: Key) -> Result<(), String> {
+        let key = key_of(key)?;
+        blocking(self, move |t| {
+            t.modify(&key, &mut |item| {
+                item.remove("price");
+                item.remove("market_name");
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn transfer_possession(
+        &self,
+        new_owner: String,
+        acquisition: possess::Acquisition,
+        key: Key,
+        delist: bool,
+    ) -> Result<(), String> {
+        let key = key_of(key)?;
+        blocking(self, move |t| {
+            t.modify(&key, &mut |item| {
+                hand_over(item, &new_owner, &acquisition, delist);
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn transfer_if(
+        &self,
+        transfers: Vec<Transfer>,
+        records: Vec<RecordSwap>,
+    ) -> Result<bool, String> {
+        if transfers.len() + records.len() > TRANSACT_LIMIT {
+            return Err(format!(
+                "can't make {} changes in one transaction, only {}",
+                transfers.len() + records.len(),
+                TRANSACT_LIMIT
+            ));
+        }
+        let item_keys = transfers
+            .iter()
+            .map(|t| key_of(t.key))
+            .collect::<Result<Vec<_>, _>>()?;
+        let record_keys: Vec<(String, String)> = records
+            .iter()
+            .map(|r| (r.kind.clone(), r.id.clone()))
+            .collect();
+
+        blocking(self, move |t| {
+            t.modify_all(&item_keys, &record_keys, &mut |items, datas| {
+                for (item, transfer) in items.iter_mut().zip(&transfers) {
+                    let item = match item {
+                        Some(item) => item,
+                        None => return Ok(false),
+                    };
+                    let owner = item.get("steader").and_then(|s| s.s.as_ref());
+                    if owner != Some(&transfer.from) || price_of(item) != transfer.price {
+                        return Ok(false);
+                    }
+                    hand_over(item, &transfer.to, &transfer.acquisition, true);
+                }
+                for (data, swap) in datas.iter_mut().zip(&records) {
+                    if *data != swap.old {
+                        return Ok(false);
+                    }
+                    *data = swap.new.clone();
+                }
+                Ok(true)
+            })
+        })
+        .await
+    }
+
+    async fn rename_gotchi(&self, key: Key, nickname: String) -> Result<(), String> {
+        let key = key_of(key)?;
+        blocking(self, move |t| {
+            t.modify(&key, &mut |item| {
+                item.insert("nickname".to_string(), s_av(nickname.clone()));
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn record_harvest(&self, key: Key, steader: String, amount: u64) -> Result<(), String> {
+        let item_key = key_of(key)?;
+        blocking(self, move |t| {
+            t.modify(&item_key, &mut |item| {
+                let log = item
+                    .get_mut("harvest_log")
+                    .and_then(|l| l.l.as_mut())
+                    .ok_or_else(|| format!("{:?} has no harvest log", key))?;
+                let steader_in_harvest_log = log
+                    .last()
+                    .and_then(|h| h.m.as_ref()?.get("id")?.s.as_ref())
+                    .map_or(false, |id| *id == steader);
+
+                if steader_in_harvest_log {
+                    let last = log.last_mut().and_then(|h| h.m.as_mut()).unwrap();
+                    let harvested = last
+                        .get("harvested")
+                        .and_then(|h| h.n.as_ref()?.parse::<u64>().ok())
+                        .unwrap_or(0);
+                    last.insert("harvested".to_string(), n_av(harvested + amount));
+                } else {
+                    log.push(
+                        possess::gotchi::GotchiHarvestOwner {
+                            id: steader.clone(),
+                            harvested: amount,
+                        }
+                        .into(),
+                    );
+                }
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn insert_record(&self, kind: &str, id: &str, data: String) -> Result<bool, String> {
+        let (kind, id) = (kind.to_string(), id.to_string());
+        blocking(self, move |t| {
+            ItemTable::insert_record(&t, &kind, &id, data)
+        })
+        .await
+    }
+
+    async fn put_record(&self, kind: &str, id: &str, data: String) -> Result<(), String> {
+        let (kind, id) = (kind.to_string(), id.to_string());
+        blocking(self, move |t| ItemTable::put_record(&t, &kind, &id, data)).await
+    }
+
+    async fn get_record(&self, kind: &str, id: &str) -> Result<Option<String>, String> {
+        let (kind, id) = (kind.to_string(), id.to_string());
+        blocking(self, move |t| ItemTable::get_record(&t, &kind, &id)).await
+    }
+
+    async fn remove_record(&self, kind: &str, id: &str) -> Result<Option<String>, String> {
+        let (kind, id) = (kind.to_string(), id.to_string());
+        blocking(self, move |t| ItemTable::remove_record(&t, &kind, &id)).await
+    }
+
+    async fn records(&self, kind: &str) -> Result<Vec<String>, String> {
+        let kind = kind.to_string();
+        blocking(self, move |t| ItemTable::records(&t, &kind)).await
+    }
+}
@@ -0,0 +1,145 @@
+//! Keeps everything in memory, for running locally without any database at all.
+//! Nothing survives a restart.
+use super::items::{item_key, ItemFilter, ItemKey, ItemTable};
+use hcor::Item;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// Clones share the same items and records.
+#[derive(Default, Clone)]
+pub struct MemoryStore {
+    items: Arc<Mutex<BTreeMap<ItemKey, Item>>>,
+    records: Arc<Mutex<BTreeMap<(String, String), String>>>,
+}
+
+impl ItemTable for MemoryStore {
+    fn get(&self, key: &ItemKey) -> Result<Option<Item>, String> {
+        Ok(self.items.lock().unwrap().get(key).cloned())
+    }
+
+    fn items(&self, filter: &ItemFilter) -> Result<Vec<Item>, String> {
+        Ok(self
+            .items
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|i| filter.matches(i))
+            .cloned()
+            .collect())
+    }
+
+    fn write_items(&self, puts: Vec<Item>, deletes: Vec<ItemKey>) -> Result<(), String> {
+        let puts = puts
+            .into_iter()
+            .map(|i| Ok((item_key(&i)?, i)))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let mut items = self.items.lock().unwrap();
+        items.extend(puts);
+        for key in deletes {
+            items.remove(&key);
+        }
+        Ok(())
+    }
+
+    fn remove(&self, key: &ItemKey) -> Result<Option<Item>, String> {
+        Ok(self.items.lock().unwrap().remove(key))
+    }
+
+    fn modify(
+        &self,
+        key: &ItemKey,
+        f: &mut dyn FnMut(&mut Item) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let mut items = self.items.lock().unwrap();
+        let item = items
+            .get_mut(key)
+            .ok_or_else(|| format!("no item at {:?} to modify", key))?;
+
+        // work on a copy so a failed change doesn't leave anything half done
+        let mut changed = item.clone();
+        f(&mut changed)?;
+        *item = changed;
+        Ok(())
+    }
+
+    fn modify_all(
+        &self,
+        keys: &[ItemKey],
+        record_keys: &[(String, String)],
+        f: &mut dyn FnMut(&mut [Option<Item>], &mut [Option<String>]) -> Result<bool, String>,
+    ) -> Result<bool, String> {
+        // always items, then records, so two of these can't each hold the lock the other wants
+        let mut items = self.items.lock().unwrap();
+        let mut records = self.records.lock().unwrap();
+
+        let mut found: Vec<Option<Item>> = keys.iter().map(|k| items.get(k).cloned()).collect();
+        let mut data: Vec<Option<String>> = record_keys
+            .iter()
+            .map(|k| records.get(k).cloned())
+            .collect();
+        if !f(&mut found, &mut data)? {
+            return Ok(false);
+        }
+
+        for (key, item) in keys.iter().zip(found) {
+            match item {
+                Some(item) => items.insert(key.clone(), item),
+                None => items.remove(key),
+            };
+        }
+        for (key, data) in record_keys.iter().zip(data) {
+            match data {
+                Some(data) => records.insert(key.clone(), data),
+                None => records.remove(key),
+            };
+        }
+        Ok(true)
+    }
+
+    fn insert_record(&self, kind: &str, id: &str, data: String) -> Result<bool, String> {
+        let mut records = self.records.lock().unwrap();
+        let key = (kind.to_string(), id.to_string());
+        if records.contains_key(&key) {
+            return Ok(false);
+        }
+        records.insert(key, data);
+        Ok(true)
+    }
+
+    fn put_record(&self, kind: &str, id: &str, data: String) -> Result<(), String> {
+        self.records
+            .lock()
+            .unwrap()
+            .insert((kind.to_string(), id.to_string()), data);
+        Ok(())
+    }
+
+    fn get_record(&self, kind: &str, id: &str) -> Result<Option<String>, String> {
+        Ok(self
+            .records
+            .lock()
+            .unwrap()
+            .get(&(kind.to_string(), id.to_string()))
+            .cloned())
+    }
+
+    fn remove_record(&self, kind: &str, id: &str) -> Result<Option<String>, String> {
+        Ok(self
+            .records
+            .lock()
+            .unwrap()
+            .remove(&(kind.to_string(), id.to_string())))
+    }
+
+    fn records(&self, kind: &str) -> Result<Vec<String>, String> {
+        Ok(self
+            .records
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|((k, _), _)| k == kind)
+            .map(|(_, data)| data.clone())
+            .collect())
+    }
+}
@@ -0,0 +1,202 @@
+//! Everything that gets saved goes through a `Store`, so that the game doesn't
+//! have to know whether it's talking to DynamoDB, a SQLite file, or a HashMap.
+//!
+//! Which one is used is picked with the `STORE` environment variable:
+//! `dynamodb` (the default), `sqlite` (at `SQLITE_PATH`) or `memory`.
+use crate::hacksteader::{Hacksteader, Tile};
+use hcor::{market::Sale, possess, Category, Key, Possession, Profile};
+
+mod dynamo;
+mod items;
+mod memory;
+mod sqlite;
+
+pub use dynamo::DynamoStore;
+pub use items::ItemTable;
+pub use memory::MemoryStore;
+pub use sqlite::SqliteStore;
+
+use std::env::var;
+lazy_static::lazy_static! {
+    static ref STORE: Box<dyn Store> = match var("STORE").as_ref().map(|s| s.as_str()) {
+        Ok("memory") => Box::new(MemoryStore::default()),
+        Ok("sqlite") => Box::new(
+            SqliteStore::open(
+                &var("SQLITE_PATH").unwrap_or_else(|_| "hackagotchi.sqlite".to_string())
+            )
+            .unwrap()
+        ),
+        Ok("dynamodb") | Err(_) => Box::new(DynamoStore::new()),
+        Ok(other) => panic!("unknown STORE: {}", other),
+    };
+}
+
+/// The store picked by the environment for this process.
+pub fn store() -> &'static dyn Store {
+    &**STORE
+}
+
+/// The most writes one transaction can make. That's all DynamoDB allows,
+/// and every store holds to it so that nothing only works locally.
+pub const TRANSACT_LIMIT: usize = 100;
+
+/// A bunch of writes that should go to the store together.
+#[derive(Debug, Clone, Default)]
+pub struct Batch {
+    pub profiles: Vec<Profile>,
+    pub tiles: Vec<Tile>,
+    pub possessions: Vec<Possession>,
+    pub deletions: Vec<Key>,
+    /// The steaders whose profiles should be removed.
+    pub profile_deletions: Vec<String>,
+}
+impl Batch {
+    pub fn is_empty(&self) -> bool {
+        self.profiles.is_empty()
+            && self.tiles.is_empty()
+            && self.possessions.is_empty()
+            && self.deletions.is_empty()
+            && self.profile_deletions.is_empty()
+    }
+
+    /// How many writes this batch makes.
+    pub fn len(&self) -> usize {
+        self.profiles.len()
+            + self.tiles.len()
+            + self.possessions.len()
+            + self.deletions.len()
+            + self.profile_deletions.len()
+    }
+
+    /// Splits the batch up into batches small enough to be transacted.
+    /// Only for batches whose writes don't depend on each other, since
+    /// some of the chunks can be saved when others aren't.
+    pub fn chunks(self) -> Vec<Batch> {
+        let Batch {
+            profiles,
+            tiles,
+            possessions,
+            deletions,
+            profile_deletions,
+        } = self;
+
+        // the chunk with room for another write
+        fn next(chunks: &mut Vec<Batch>) -> &mut Batch {
+            if chunks.last().map_or(true, |c| c.len() >= TRANSACT_LIMIT) {
+                chunks.push(Batch::default());
+            }
+            chunks.last_mut().unwrap()
+        }
+
+        let mut chunks = vec![];
+        for p in profiles {
+            next(&mut chunks).profiles.push(p);
+        }
+        for t in tiles {
+            next(&mut chunks).tiles.push(t);
+        }
+        for p in possessions {
+            next(&mut chunks).possessions.push(p);
+        }
+        for k in deletions {
+            next(&mut chunks).deletions.push(k);
+        }
+        for id in profile_deletions {
+            next(&mut chunks).profile_deletions.push(id);
+        }
+        chunks
+    }
+}
+
+/// A possession changing hands, which should only happen if it's still where it was
+/// when someone decided to hand it over.
+#[derive(Debug, Clone)]
+pub struct Transfer {
+    pub key: Key,
+    /// Who it has to still belong to.
+    pub from: String,
+    /// What it has to still be on the market for, or None if it mustn't be on the market.
+    pub price: Option<u64>,
+    pub to: String,
+    pub acquisition: possess::Acquisition,
+}
+
+/// A record to be replaced with `new`, or removed if that's None, as long as it's still `old`,
+/// or as long as there still isn't one if `old` is None.
+#[derive(Debug, Clone)]
+pub struct RecordSwap {
+    pub kind: String,
+    pub id: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+#[rocket::async_trait]
+pub trait Store: Send + Sync {
+    async fn hacksteader_exists(&self, user_id: &str) -> Result<bool, String>;
+
+    /// Everything a steader owns, along with their profile.
+    async fn hacksteader(&self, user_id: &str) -> Result<Hacksteader, String>;
+
+    async fn profiles(&self) -> Result<Vec<Profile>, String>;
+
+    async fn tile(&self, id: uuid::Uuid) -> Result<Tile, String>;
+
+    async fn tiles(&self) -> Result<Vec<Tile>, String>;
+
+    async fn possession(&self, key: Key) -> Result<Possession, String>;
+
+    async fn possessions(&self, cat: Category) -> Result<Vec<Possession>, String>;
+
+    /// Possessions of a category that are up for sale, cheapest first.
+    async fn market_listings(&self, cat: Category) -> Result<Vec<(Sale, Possession)>, String>;
+
+    async fn write(&self, batch: Batch) -> Result<(), String>;
+
+    /// Like `write`, but if it fails, none of the batch should have been written.
+    /// Batches of more than `TRANSACT_LIMIT` writes are refused.
+    async fn transact(&self, batch: Batch) -> Result<(), String>;
+
+    /// Deletes a possession, returning what was deleted.
+    async fn take(&self, key: Key) -> Result<Possession, String>;
+
+    async fn place_on_market(&self, key: Key, price: u64, name: String) -> Result<(), String>;
+
+    async fn take_off_market(&self, key: Key) -> Result<(), String>;
+
+    /// Gives a possession to someone else, noting it in its ownership log.
+    /// If `delist` is set, it's also taken off the market in the same write.
+    async fn transfer_possession(
+        &self,
+        new_owner: String,
+        acquisition: possess::Acquisition,
+        key: Key,
+        delist: bool,
+    ) -> Result<(), String>;
+
+    /// Makes every transfer, taking what's transferred off the market, and every record swap,
+    /// all at once and only if all of them still hold.
+    /// Returns false, having written nothing, if any of them didn't.
+    async fn transfer_if(
+        &self,
+        transfers: Vec<Transfer>,
+        records: Vec<RecordSwap>,
+    ) -> Result<bool, String>;
+
+    async fn rename_gotchi(&self, key: Key, nickname: String) -> Result<(), String>;
+
+    /// Notes in a gotchi's harvest log that its current steader got some HN out of it.
+    async fn record_harvest(&self, key: Key, steader: String, amount: u64) -> Result<(), String>;
+
+    /// Writes a record as JSON if there isn't one of the same kind with that id yet,
+    /// returning whether it was written.
+    async fn insert_record(&self, kind: &str, id: &str, data: String) -> Result<bool, String>;
+
+    async fn put_record(&self, kind: &str, id: &str, data: String) -> Result<(), String>;
+
+    async fn get_record(&self, kind: &str, id: &str) -> Result<Option<String>, String>;
+
+    async fn remove_record(&self, kind: &str, id: &str) -> Result<Option<String>, String>;
+
+    async fn records(&self, kind: &str) -> Result<Vec<String>, String>;
+}
@@ -0,0 +1,253 @@
+//! Keeps everything in a single SQLite file, for running locally without DynamoDB.
+//!
+//! Items are stored as the JSON of their DynamoDB attributes, with the few
+//! attributes we look them up by pulled out into columns.
+use super::items::{item_key, ItemFilter, ItemKey, ItemTable};
+use hcor::Item;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::sync::{Arc, Mutex};
+
+/// Clones share the same connection.
+#[derive(Clone)]
+pub struct SqliteStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS items (
+        cat TEXT NOT NULL,
+        id TEXT NOT NULL,
+        steader TEXT,
+        item TEXT NOT NULL,
+        PRIMARY KEY (cat, id)
+    );
+    CREATE INDEX IF NOT EXISTS items_steader ON items (steader);
+    CREATE TABLE IF NOT EXISTS records (
+        kind TEXT NOT NULL,
+        id TEXT NOT NULL,
+        data TEXT NOT NULL,
+        PRIMARY KEY (kind, id)
+    );
+";
+
+fn sql_err(e: rusqlite::Error) -> String {
+    format!("sqlite error: {}", e)
+}
+
+fn parse_item(json: String) -> Result<Item, String> {
+    serde_json::from_str(&json).map_err(|e| format!("couldn't parse stored item: {}", e))
+}
+
+fn put_item(conn: &Connection, item: &Item) -> Result<(), String> {
+    let (cat, id) = item_key(item)?;
+    conn.execute(
+        "INSERT OR REPLACE INTO items (cat, id, steader, item) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            cat,
+            id,
+            item.get("steader").and_then(|s| s.s.clone()),
+            serde_json::to_string(item).map_err(|e| format!("couldn't serialize item: {}", e))?
+        ],
+    )
+    .map_err(sql_err)?;
+    Ok(())
+}
+
+fn delete_item(conn: &Connection, (cat, id): &ItemKey) -> Result<(), String> {
+    conn.execute(
+        "DELETE FROM items WHERE cat = ?1 AND id = ?2",
+        params![cat, id],
+    )
+    .map_err(sql_err)?;
+    Ok(())
+}
+
+fn get_record(conn: &Connection, kind: &str, id: &str) -> Result<Option<String>, String> {
+    conn.query_row(
+        "SELECT data FROM records WHERE kind = ?1 AND id = ?2",
+        params![kind, id],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(sql_err)
+}
+
+fn get_item(conn: &Connection, (cat, id): &ItemKey) -> Result<Option<Item>, String> {
+    conn.query_row(
+        "SELECT item FROM items WHERE cat = ?1 AND id = ?2",
+        params![cat, id],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(sql_err)?
+    .map(parse_item)
+    .transpose()
+}
+
+impl SqliteStore {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let conn = Connection::open(path)
+            .map_err(|e| format!("couldn't open sqlite store at {}: {}", path, e))?;
+        conn.execute_batch(SCHEMA).map_err(sql_err)?;
+
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+}
+
+impl ItemTable for SqliteStore {
+    fn get(&self, key: &ItemKey) -> Result<Option<Item>, String> {
+        get_item(&self.conn.lock().unwrap(), key)
+    }
+
+    fn items(&self, filter: &ItemFilter) -> Result<Vec<Item>, String> {
+        let conn = self.conn.lock().unwrap();
+        let (sql, value) = match filter {
+            ItemFilter::Category(cat) => ("SELECT item FROM items WHERE cat = ?1", *cat),
+            ItemFilter::Steader(steader) => ("SELECT item FROM items WHERE steader = ?1", *steader),
+        };
+
+        let mut statement = conn.prepare(sql).map_err(sql_err)?;
+        let rows = statement
+            .query_map(params![value], |row| row.get::<_, String>(0))
+            .map_err(sql_err)?;
+        rows.map(|json| parse_item(json.map_err(sql_err)?))
+            .collect()
+    }
+
+    fn write_items(&self, puts: Vec<Item>, deletes: Vec<ItemKey>) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(sql_err)?;
+        for item in puts.iter() {
+            put_item(&tx, item)?;
+        }
+        for key in deletes.iter() {
+            delete_item(&tx, key)?;
+        }
+        tx.commit().map_err(sql_err)
+    }
+
+    fn remove(&self, key: &ItemKey) -> Result<Option<Item>, String> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(sql_err)?;
+        let item = get_item(&tx, key)?;
+        delete_item(&tx, key)?;
+        tx.commit().map_err(sql_err)?;
+        Ok(item)
+    }
+
+    fn modify(
+        &self,
+        key: &ItemKey,
+        f: &mut dyn FnMut(&mut Item) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(sql_err)?;
+        let mut item =
+            get_item(&tx, key)?.ok_or_else(|| format!("no item at {:?} to modify", key))?;
+        f(&mut item)?;
+        put_item(&tx, &item)?;
+        tx.commit().map_err(sql_err)
+    }
+
+    fn modify_all(
+        &self,
+        keys: &[ItemKey],
+        record_keys: &[(String, String)],
+        f: &mut dyn FnMut(&mut [Option<Item>], &mut [Option<String>]) -> Result<bool, String>,
+    ) -> Result<bool, String> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(sql_err)?;
+
+        let mut items = keys
+            .iter()
+            .map(|key| get_item(&tx, key))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut data = record_keys
+            .iter()
+            .map(|(kind, id)| get_record(&tx, kind, id))
+            .collect::<Result<Vec<_>, _>>()?;
+        if !f(&mut items, &mut data)? {
+            return Ok(false);
+        }
+
+        for (key, item) in keys.iter().zip(items) {
+            match item {
+                Some(item) => put_item(&tx, &item)?,
+                None => delete_item(&tx, key)?,
+            }
+        }
+        for ((kind, id), data) in record_keys.iter().zip(data) {
+            match data {
+                Some(data) => tx
+                    .execute(
+                        "INSERT OR REPLACE INTO records (kind, id, data) VALUES (?1, ?2, ?3)",
+                        params![kind, id, data],
+                    )
+                    .map_err(sql_err)?,
+                None => tx
+                    .execute(
+                        "DELETE FROM records WHERE kind = ?1 AND id = ?2",
+                        params![kind, id],
+                    )
+                    .map_err(sql_err)?,
+            };
+        }
+        tx.commit().map_err(sql_err)?;
+        Ok(true)
+    }
+
+    fn insert_record(&self, kind: &str, id: &str, data: String) -> Result<bool, String> {
+        let inserted = self
+            .conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT OR IGNORE INTO records (kind, id, data) VALUES (?1, ?2, ?3)",
+                params![kind, id, data],
+            )
+            .map_err(sql_err)?;
+        Ok(inserted > 0)
+    }
+
+    fn put_record(&self, kind: &str, id: &str, data: String) -> Result<(), String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT OR REPLACE INTO records (kind, id, data) VALUES (?1, ?2, ?3)",
+                params![kind, id, data],
+            )
+            .map_err(sql_err)?;
+        Ok(())
+    }
+
+    fn get_record(&self, kind: &str, id: &str) -> Result<Option<String>, String> {
+        get_record(&self.conn.lock().unwrap(), kind, id)
+    }
+
+    fn remove_record(&self, kind: &str, id: &str) -> Result<Option<String>, String> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(sql_err)?;
+        let data = get_record(&tx, kind, id)?;
+        tx.execute(
+            "DELETE FROM records WHERE kind = ?1 AND id = ?2",
+            params![kind, id],
+        )
+        .map_err(sql_err)?;
+        tx.commit().map_err(sql_err)?;
+        Ok(data)
+    }
+
+    fn records(&self, kind: &str) -> Result<Vec<String>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn
+            .prepare("SELECT data FROM records WHERE kind = ?1 ORDER BY id")
+            .map_err(sql_err)?;
+        let rows = statement
+            .query_map(params![kind], |row| row.get(0))
+            .map_err(sql_err)?;
+        rows.map(|data| data.map_err(sql_err)).collect()
+    }
+}
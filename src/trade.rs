@@ -0,0 +1,775 @@
+//! Trades between two players, where each puts up some of their things and HN.
+//!
+//! Someone proposes a trade by putting up their side, and whoever they proposed it to fills
+//! in theirs. Whenever one side changes what they're putting up, the other has to accept it
+//! again. Once both have accepted, any HN in the trade is invoiced and held by the banker,
+//! and once that's all paid, everything changes hands at once. Either side can call it off
+//! until then, and offers that sit around for too long expire.
+use crate::blocks::{actions, button, comment, mrkdwn, section, Block, Style};
+use crate::chat::mention;
+use crate::hacksteader::{self, Hacksteader};
+use crate::records::{self, Record};
+use crate::store::{Store, Transfer};
+use crate::{banker, dm_blocks};
+use hcor::{possess, Key, Possession};
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime};
+
+/// How long an offer stays open without going through.
+pub const OFFER_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
+/// The most things either side can put up.
+pub const MAX_ITEMS: usize = 20;
+
+lazy_static::lazy_static! {
+    /// Offers are changed one at a time, so that nothing changes hands twice.
+    static ref TRADING: rocket::tokio::sync::Mutex<()> = Default::default();
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TradeItem {
+    pub key: Key,
+    pub name: String,
+}
+impl From<&Possession> for TradeItem {
+    fn from(p: &Possession) -> Self {
+        TradeItem {
+            key: p.key(),
+            name: p.nickname().to_string(),
+        }
+    }
+}
+
+/// What one player's putting up in a trade.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Side {
+    pub steader: String,
+    pub items: Vec<TradeItem>,
+    pub hn: u64,
+    pub accepted: bool,
+    /// Whether the banker's holding onto their HN yet.
+    pub paid: bool,
+}
+impl Side {
+    fn new(steader: String) -> Self {
+        Side {
+            steader,
+            items: vec![],
+            hn: 0,
+            accepted: false,
+            paid: false,
+        }
+    }
+
+    /// Whether they've paid in everything they need to for the trade to go through.
+    fn settled(&self) -> bool {
+        self.hn == 0 || self.paid
+    }
+
+    pub fn describe(&self) -> String {
+        let mut things: Vec<String> = self
+            .items
+            .iter()
+            .map(|i| format!("a *{}*", i.name))
+            .collect();
+        if self.hn > 0 {
+            things.push(format!("*{} HN*", self.hn));
+        }
+        match things.len() {
+            0 => "nothing".to_string(),
+            1 => things.remove(0),
+            n => format!("{} and {}", things[..n - 1].join(", "), things[n - 1]),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Offer {
+    pub id: uuid::Uuid,
+    pub proposer: Side,
+    pub recipient: Side,
+    /// Whatever the proposer had to say about what they want.
+    pub note: String,
+    pub proposed: SystemTime,
+    pub expires: SystemTime,
+}
+impl Record for Offer {
+    const KIND: &'static str = "trade_offer";
+
+    fn id(&self) -> String {
+        self.id.to_simple().to_string()
+    }
+}
+impl Offer {
+    pub fn expired(&self) -> bool {
+        SystemTime::now() >= self.expires
+    }
+
+    /// Both sides have accepted, so nothing can change but calling it off.
+    pub fn locked(&self) -> bool {
+        self.proposer.accepted && self.recipient.accepted
+    }
+
+    pub fn involves(&self, user_id: &str) -> bool {
+        self.proposer.steader == user_id || self.recipient.steader == user_id
+    }
+
+    /// The side `user_id` is on, and then the other one.
+    pub fn sides_of(&self, user_id: &str) -> (&Side, &Side) {
+        if self.proposer.steader == user_id {
+            (&self.proposer, &self.recipient)
+        } else {
+            (&self.recipient, &self.proposer)
+        }
+    }
+
+    fn sides_of_mut(&mut self, user_id: &str) -> (&mut Side, &mut Side) {
+        if self.proposer.steader == user_id {
+            (&mut self.proposer, &mut self.recipient)
+        } else {
+            (&mut self.recipient, &mut self.proposer)
+        }
+    }
+
+    /// What the trade looks like to someone in it, with buttons to answer it.
+    pub fn blocks(&self, viewer: &str) -> Vec<Block> {
+        let (yours, theirs) = self.sides_of(viewer);
+        let mut blocks = vec![section(mrkdwn(format!(
+            "*{}* would give you {}.\n*You* would give {} {}.",
+            mention(&theirs.steader),
+            theirs.describe(),
+            mention(&theirs.steader),
+            yours.describe()
+        )))
+        .into()];
+        if !self.note.is_empty() {
+            blocks.push(comment(format!(
+                "{} says: {}",
+                mention(&self.proposer.steader),
+                self.note
+            )));
+        }
+        blocks.push(comment(format!(
+            "{} {}. This offer expires in {}.",
+            mention(&theirs.steader),
+            if theirs.accepted {
+                "has accepted"
+            } else {
+                "hasn't accepted yet"
+            },
+            humantime::format_duration(
+                self.expires
+                    .duration_since(SystemTime::now())
+                    .map(|d| Duration::from_secs(d.as_secs()))
+                    .unwrap_or_default()
+            )
+        )));
+        blocks.push(actions(vec![
+            button("Respond", "trade_respond")
+                .primary()
+                .value(self.id())
+                .into(),
+            button("Call it off", "trade_cancel")
+                .style(Style::Danger)
+                .value(self.id())
+                .into(),
+        ]));
+        blocks
+    }
+}
+
+pub async fn get(db: &dyn Store, id: &str) -> Result<Option<Offer>, String> {
+    records::get(db, id).await
+}
+
+/// Everything someone could put up in a trade right now.
+pub async fn tradeable(db: &dyn Store, user_id: &str) -> Result<Vec<Possession>, String> {
+    let auctioned = auctioned(db).await?;
+    let hs = Hacksteader::from_db(db, user_id.to_string()).await?;
+    Ok(hs
+        .gotchis
+        .into_iter()
+        .map(|g| g.into_possession())
+        .chain(hs.inventory)
+        .filter(|p| p.sale.is_none() && !auctioned.contains(&p.id))
+        .collect())
+}
+
+async fn auctioned(db: &dyn Store) -> Result<HashSet<uuid::Uuid>, String> {
+    Ok(crate::auction::auctions(db)
+        .await?
+        .into_iter()
+        .map(|a| a.key.id)
+        .collect())
+}
+
+/// Makes sure everything a side's putting up is still theirs to trade.
+async fn check_side(
+    db: &dyn Store,
+    side: &Side,
+    auctioned: &HashSet<uuid::Uuid>,
+) -> Result<(), String> {
+    if side.items.len() > MAX_ITEMS {
+        return Err(format!("only {} things can be traded at once", MAX_ITEMS));
+    }
+    let mut seen = HashSet::new();
+    if let Some(twice) = side.items.iter().find(|i| !seen.insert(i.key.id)) {
+        return Err(format!("that {} can only be put up once", twice.name));
+    }
+    for item in &side.items {
+        let p = db.possession(item.key).await?;
+        if p.steader != side.steader {
+            return Err(format!(
+                "{} doesn't have that {} anymore",
+                mention(&side.steader),
+                item.name
+            ));
+        }
+        if p.sale.is_some() || auctioned.contains(&p.id) {
+            return Err(format!(
+                "{}'s {} is up for sale",
+                mention(&side.steader),
+                item.name
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Offers `recipient` a trade for whatever `proposer` is putting up,
+/// which the recipient can then fill in their side of.
+pub async fn propose(
+    db: &dyn Store,
+    proposer: String,
+    recipient: String,
+    items: Vec<TradeItem>,
+    hn: u64,
+    note: String,
+) -> Result<(), String> {
+    if proposer == recipient {
+        return Err("you can't trade with yourself".to_string());
+    }
+    if !hacksteader::exists(db, recipient.clone()).await {
+        return Err(format!("{} doesn't have a hackstead", mention(&recipient)));
+    }
+
+    let now = SystemTime::now();
+    let offer = Offer {
+        id: uuid::Uuid::new_v4(),
+        proposer: Side {
+            items,
+            hn,
+            accepted: true,
+            ..Side::new(proposer)
+        },
+        recipient: Side::new(recipient),
+        note,
+        proposed: now,
+        expires: now + OFFER_LIFETIME,
+    };
+    check_side(db, &offer.proposer, &auctioned(db).await?).await?;
+    records::put(db, &offer).await?;
+
+    dm_blocks(
+        offer.recipient.steader.clone(),
+        format!(
+            "{} wants to trade with you!",
+            mention(&offer.proposer.steader)
+        ),
+        offer.blocks(&offer.recipient.steader),
+    )
+    .await
+}
+
+/// Sets what `user_id` is putting up and accepts the trade as it stands. If that's
+/// different from what they were putting up before, the other side has to accept it again.
+pub async fn respond(
+    db: &dyn Store,
+    id: &str,
+    user_id: &str,
+    items: Vec<TradeItem>,
+    hn: u64,
+) -> Result<(), String> {
+    let _trading = TRADING.lock().await;
+
+    let mut offer = match get(db, id).await? {
+        Some(o) if o.involves(user_id) && !o.expired() => o,
+        Some(o) if o.involves(user_id) => return Err("that trade offer expired".to_string()),
+        _ => return Err("that trade offer's gone".to_string()),
+    };
+    if offer.locked() {
+        return Err("you've both accepted already; it goes through once the HN's paid".to_string());
+    }
+
+    let (yours, theirs) = offer.sides_of_mut(user_id);
+    let changed = yours.hn != hn
+        || yours
+            .items
+            .iter()
+            .map(|i| i.key.id)
+            .ne(items.iter().map(|i| i.key.id));
+    yours.items = items;
+    yours.hn = hn;
+    yours.accepted = true;
+    if changed {
+        theirs.accepted = false;
+    }
+    check_side(db, yours, &auctioned(db).await?).await?;
+    let other = theirs.steader.clone();
+
+    if offer.locked() {
+        return lock_in(db, offer).await;
+    }
+
+    records::put(db, &offer).await?;
+    dm_blocks(
+        other.clone(),
+        format!(
+            "{} {} your trade!",
+            mention(user_id),
+            if changed { "changed" } else { "accepted" }
+        ),
+        offer.blocks(&other),
+    )
+    .await
+}
+
+/// Once both sides have accepted, bills each of them for the HN they're putting up,
+/// or goes ahead with the trade if there isn't any.
+async fn lock_in(db: &dyn Store, mut offer: Offer) -> Result<(), String> {
+    records::put(db, &offer).await?;
+    if offer.proposer.settled() && offer.recipient.settled() {
+        return execute(db, offer).await;
+    }
+
+    for (side, other) in &[
+        (&offer.proposer, &offer.recipient),
+        (&offer.recipient, &offer.proposer),
+    ] {
+        if side.hn == 0 {
+            continue;
+        }
+        if let Err(e) = banker::invoice(
+            &side.steader,
+            side.hn,
+            &format!(
+                "the {}hn you're putting up in your trade with {}",
+                side.hn, other.steader
+            ),
+            banker::InvoiceIntent::TradeEscrow {
+                offer: offer.id,
+                amount: side.hn,
+            },
+        )
+        .await
+        {
+            // nobody can pay into it without an invoice, so it's not locked in after all;
+            // whatever was paid against an invoice that did go out gets refunded.
+            offer.proposer.accepted = false;
+            offer.recipient.accepted = false;
+            records::put(db, &offer).await?;
+            return Err(format!(
+                "couldn't bill for the trade, so it's unlocked: {}",
+                e
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Holds onto HN that's been paid into a trade, going through with it once it's all there.
+/// If the trade's gone by now, the HN goes back to whoever paid it.
+pub async fn escrow_paid(
+    db: &dyn Store,
+    id: uuid::Uuid,
+    payer: String,
+    amount: u64,
+) -> Result<(), String> {
+    let _trading = TRADING.lock().await;
+
+    let offer = get(db, &id.to_simple().to_string()).await?;
+    let mut offer = match offer {
+        Some(o)
+            if o.locked()
+                && o.involves(&payer)
+                && !o.sides_of(&payer).0.paid
+                && o.sides_of(&payer).0.hn == amount =>
+        {
+            o
+        }
+        _ => {
+            return banker::owe(
+                db,
+                &payer,
+                amount,
+                "a trade that was called off before you paid into it".to_string(),
+            )
+            .await
+        }
+    };
+
+    offer.sides_of_mut(&payer).0.paid = true;
+    records::put(db, &offer).await?;
+    if offer.proposer.settled() && offer.recipient.settled() {
+        execute(db, offer).await
+    } else {
+        Ok(())
+    }
+}
+
+/// Hands everything over. If anything can't be, nothing is, and the trade's called off.
+///
+/// `offer` has to be just as it's stored. The items change hands, and whatever HN
+/// was escrowed is owed to the other side, in the same transaction that removes it,
+/// so nothing else can call the trade off and refund it once that's gone through.
+async fn execute(db: &dyn Store, offer: Offer) -> Result<(), String> {
+    let auctioned = auctioned(db).await?;
+    for side in &[&offer.proposer, &offer.recipient] {
+        if let Err(why) = check_side(db, side, &auctioned).await {
+            return call_off(db, offer.clone(), why).await;
+        }
+    }
+
+    let transfers: Vec<Transfer> = [
+        (&offer.proposer, &offer.recipient),
+        (&offer.recipient, &offer.proposer),
+    ]
+    .iter()
+    .flat_map(|(side, other)| {
+        side.items.iter().map(move |i| Transfer {
+            key: i.key,
+            from: side.steader.clone(),
+            price: None,
+            to: other.steader.clone(),
+            acquisition: possess::Acquisition::Trade,
+        })
+    })
+    .collect();
+    let payouts: Vec<banker::OwedPayment> = [
+        (&offer.proposer, &offer.recipient),
+        (&offer.recipient, &offer.proposer),
+    ]
+    .iter()
+    .filter(|(side, _)| side.hn > 0)
+    .map(|(side, other)| {
+        banker::OwedPayment::new(
+            &other.steader,
+            side.hn,
+            format!("your trade with {}", side.steader),
+        )
+    })
+    .collect();
+    let mut swaps = vec![records::swap(&offer, None)?];
+    for owed in &payouts {
+        swaps.push(records::create(owed)?);
+    }
+    if !db.transfer_if(transfers, swaps).await? {
+        let why = "something in it changed hands before it went through".to_string();
+        return call_off(db, offer, why).await;
+    }
+
+    banker::pay_owed(db, payouts).await;
+    for (side, other) in &[
+        (&offer.proposer, &offer.recipient),
+        (&offer.recipient, &offer.proposer),
+    ] {
+        if let Err(e) = dm_blocks(
+            side.steader.clone(),
+            format!("Your trade with {} went through!", mention(&other.steader)),
+            vec![
+                section(mrkdwn(format!(
+                    "Your trade with {} went through! You got {} for {}.",
+                    mention(&other.steader),
+                    other.describe(),
+                    side.describe()
+                )))
+                .into(),
+                comment("A FARE TRAYD"),
+            ],
+        )
+        .await
+        {
+            warn!(
+                "couldn't tell {} their trade went through: {}",
+                side.steader, e
+            );
+        }
+    }
+
+    info!(
+        "trade {} went through between {} and {}",
+        offer.id, offer.proposer.steader, offer.recipient.steader
+    );
+    Ok(())
+}
+
+/// Forgets about an offer, giving back any HN that was paid into it and letting both sides know why.
+///
+/// `offer` has to be just as it's stored; the refunds are owed in the same transaction
+/// that removes it, so if it's already gone, nothing is refunded again.
+async fn call_off(db: &dyn Store, offer: Offer, why: String) -> Result<(), String> {
+    let refunds: Vec<banker::OwedPayment> = [
+        (&offer.proposer, &offer.recipient),
+        (&offer.recipient, &offer.proposer),
+    ]
+    .iter()
+    .filter(|(side, _)| side.paid && side.hn > 0)
+    .map(|(side, other)| {
+        banker::OwedPayment::new(
+            &side.steader,
+            side.hn,
+            format!("your trade with {} being called off", other.steader),
+        )
+    })
+    .collect();
+    let mut swaps = vec![records::swap(&offer, None)?];
+    for owed in &refunds {
+        swaps.push(records::create(owed)?);
+    }
+    if !db.transfer_if(vec![], swaps).await? {
+        return Ok(());
+    }
+
+    banker::pay_owed(db, refunds).await;
+    for (side, other) in &[
+        (&offer.proposer, &offer.recipient),
+        (&offer.recipient, &offer.proposer),
+    ] {
+        if let Err(e) = dm_blocks(
+            side.steader.clone(),
+            format!("Your trade with {} is off.", mention(&other.steader)),
+            vec![section(mrkdwn(format!(
+                "Your trade with {} is off, because {}.",
+                mention(&other.steader),
+                why
+            )))
+            .into()],
+        )
+        .await
+        {
+            warn!("couldn't tell {} their trade is off: {}", side.steader, e);
+        }
+    }
+    Ok(())
+}
+
+/// Calls off a trade for either side of it.
+pub async fn cancel(db: &dyn Store, id: &str, user_id: &str) -> Result<(), String> {
+    let _trading = TRADING.lock().await;
+
+    match get(db, id).await? {
+        Some(offer) if offer.involves(user_id) => {
+            let why = format!("{} called it off", mention(user_id));
+            call_off(db, offer, why).await
+        }
+        Some(_) => Err(format!(
+            "{} can't call off someone else's trade",
+            mention(user_id)
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Calls off every offer that's been sitting around for too long.
+pub async fn sweep_expired(db: &dyn Store) -> Result<(), String> {
+    let _trading = TRADING.lock().await;
+
+    for offer in records::all::<Offer>(db).await? {
+        if offer.expired() {
+            call_off(db, offer, "it expired".to_string()).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::banker::{paying_through, OwedPayment, Payment, RecordingPayer};
+    use crate::chat::{recorded, Sent};
+    use crate::store::{Batch, MemoryStore};
+
+    const PROPOSER: &str = "U1";
+    const RECIPIENT: &str = "U2";
+
+    async fn possession(db: &dyn Store, steader: &str) -> TradeItem {
+        let p = Possession::new(0, possess::Owner::farmer(steader.to_string()));
+        db.write(Batch {
+            possessions: vec![p.clone()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        TradeItem::from(&p)
+    }
+
+    async fn owner(db: &dyn Store, item: &TradeItem) -> String {
+        db.possession(item.key).await.unwrap().steader
+    }
+
+    /// An offer both sides have accepted, each putting up `item` and `hn`.
+    async fn locked(
+        db: &dyn Store,
+        proposer: (TradeItem, u64),
+        recipient: (TradeItem, u64),
+    ) -> Offer {
+        let now = SystemTime::now();
+        let offer = Offer {
+            id: uuid::Uuid::new_v4(),
+            proposer: Side {
+                items: vec![proposer.0],
+                hn: proposer.1,
+                accepted: true,
+                ..Side::new(PROPOSER.to_string())
+            },
+            recipient: Side {
+                items: vec![recipient.0],
+                hn: recipient.1,
+                accepted: true,
+                ..Side::new(RECIPIENT.to_string())
+            },
+            note: String::new(),
+            proposed: now,
+            expires: now + OFFER_LIFETIME,
+        };
+        records::put(db, &offer).await.unwrap();
+        offer
+    }
+
+    #[rocket::async_test]
+    async fn a_locked_swap_goes_through_once_the_hn_is_in() {
+        let db = MemoryStore::default();
+        let (mine, theirs) = (
+            possession(&db, PROPOSER).await,
+            possession(&db, RECIPIENT).await,
+        );
+        let offer = locked(&db, (mine.clone(), 10), (theirs.clone(), 0)).await;
+
+        let payer = RecordingPayer::default().leak();
+        let (res, sent) = recorded(paying_through(
+            payer,
+            escrow_paid(&db, offer.id, PROPOSER.to_string(), 10),
+        ))
+        .await;
+        res.unwrap();
+
+        assert_eq!(owner(&db, &mine).await, RECIPIENT);
+        assert_eq!(owner(&db, &theirs).await, PROPOSER);
+        assert_eq!(
+            payer.paid(),
+            vec![Payment {
+                to: RECIPIENT.to_string(),
+                amount: 10,
+                reason: format!("your trade with {}", PROPOSER),
+            }]
+        );
+        assert_eq!(sent.len(), 2);
+        assert!(get(&db, &offer.id()).await.unwrap().is_none());
+        assert!(records::all::<OwedPayment>(&db).await.unwrap().is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn calling_off_a_paid_trade_refunds_it_once() {
+        let db = MemoryStore::default();
+        let (mine, theirs) = (
+            possession(&db, PROPOSER).await,
+            possession(&db, RECIPIENT).await,
+        );
+        let mut offer = locked(&db, (mine.clone(), 10), (theirs.clone(), 5)).await;
+        offer.proposer.paid = true;
+        records::put(&db, &offer).await.unwrap();
+
+        let payer = RecordingPayer::default().leak();
+        let (res, _) = recorded(paying_through(payer, async {
+            cancel(&db, &offer.id(), PROPOSER).await?;
+            cancel(&db, &offer.id(), RECIPIENT).await?;
+            // the recipient's payment landing after it's called off goes straight back
+            escrow_paid(&db, offer.id, RECIPIENT.to_string(), 5).await
+        }))
+        .await;
+        res.unwrap();
+
+        assert_eq!(
+            payer.paid(),
+            vec![
+                Payment {
+                    to: PROPOSER.to_string(),
+                    amount: 10,
+                    reason: format!("your trade with {} being called off", RECIPIENT),
+                },
+                Payment {
+                    to: RECIPIENT.to_string(),
+                    amount: 5,
+                    reason: "a trade that was called off before you paid into it".to_string(),
+                },
+            ]
+        );
+        assert_eq!(owner(&db, &mine).await, PROPOSER);
+        assert_eq!(owner(&db, &theirs).await, RECIPIENT);
+        assert!(records::all::<OwedPayment>(&db).await.unwrap().is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn a_trade_is_off_if_something_in_it_changes_hands() {
+        let db = MemoryStore::default();
+        Hacksteader::new_in_db(&db, RECIPIENT.to_string())
+            .await
+            .unwrap();
+        let mine = possession(&db, PROPOSER).await;
+
+        let payer = RecordingPayer::default().leak();
+        let (res, sent) = recorded(paying_through(payer, async {
+            propose(
+                &db,
+                PROPOSER.to_string(),
+                RECIPIENT.to_string(),
+                vec![mine.clone()],
+                0,
+                String::new(),
+            )
+            .await?;
+            db.transfer_possession(
+                "U3".to_string(),
+                possess::Acquisition::Trade,
+                mine.key,
+                false,
+            )
+            .await?;
+            let offer = records::all::<Offer>(&db).await?.remove(0);
+            respond(&db, &offer.id(), RECIPIENT, vec![], 0).await
+        }))
+        .await;
+        res.unwrap();
+
+        assert_eq!(owner(&db, &mine).await, "U3");
+        assert!(records::all::<Offer>(&db).await.unwrap().is_empty());
+        assert!(payer.paid().is_empty());
+        assert!(sent.iter().any(|s| matches!(
+            s,
+            Sent::Dm { user_id, notif_msg, .. }
+                if user_id == PROPOSER && notif_msg == "Your trade with @U2 is off."
+        )));
+    }
+
+    #[rocket::async_test]
+    async fn the_same_thing_cant_be_put_up_twice() {
+        let db = MemoryStore::default();
+        Hacksteader::new_in_db(&db, RECIPIENT.to_string())
+            .await
+            .unwrap();
+        let mine = possession(&db, PROPOSER).await;
+
+        let (res, _) = recorded(propose(
+            &db,
+            PROPOSER.to_string(),
+            RECIPIENT.to_string(),
+            vec![mine.clone(), mine],
+            0,
+            String::new(),
+        ))
+        .await;
+        assert!(res.is_err());
+        assert!(records::all::<Offer>(&db).await.unwrap().is_empty());
+    }
+}